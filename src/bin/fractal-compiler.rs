@@ -5,7 +5,13 @@ use std::path::Path;
 use std::process;
 
 use fractal::compiler::codegen;
+use fractal::compiler::condition::{self, BreakCondition};
+use fractal::compiler::diagnostic::emit_all;
+use fractal::compiler::formatter;
+use fractal::compiler::optimizer::{self, OptLevel};
+use fractal::compiler::profile;
 use fractal::compiler::semanter::analyze;
+use fractal::compiler::snapshot;
 use fractal::compiler::{lexer, parser, preprocessor};
 
 const DEBUG: bool = false;
@@ -22,17 +28,85 @@ fn section(title: &str) {
     eprintln!("\x1b[1;36m╚{}╝\x1b[0m", line);
 }
 
+// `fmt [--check] <file.fr>...`: formats each file in place, or with
+// `--check` only names the files that are not formatted. Exits with 1 if a
+// file cannot be read or lexed, or is not formatted under `--check`.
+fn run_fmt(args: &[String]) -> ! {
+    let check = args.iter().any(|a| a == "--check");
+    let files: Vec<&String> = args.iter().filter(|a| *a != "--check").collect();
+    if files.is_empty() {
+        print_error("Usage: fractal fmt [--check] <path/to/file.fr>...");
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for file in files {
+        let contents = match fs::read_to_string(file) {
+            Ok(data) => data,
+            Err(err) => {
+                print_error(&format!("could not read `{file}`: {err}"));
+                failed = true;
+                continue;
+            }
+        };
+        match formatter::try_format(&contents, file) {
+            Err(errors) => {
+                emit_all(&errors);
+                failed = true;
+            }
+            Ok(formatted) if formatted == contents => {}
+            Ok(_) if check => {
+                eprintln!("`{file}` is not formatted");
+                failed = true;
+            }
+            Ok(formatted) => match fs::write(file, formatted) {
+                Ok(_) => eprintln!("formatted `{file}`"),
+                Err(err) => {
+                    print_error(&format!("could not write `{file}`: {err}"));
+                    failed = true;
+                }
+            },
+        }
+    }
+    process::exit(failed as i32);
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    if args.get(1).is_some_and(|a| a == "fmt") {
+        run_fmt(&args[2..]);
+    }
 
-    let (debug_mode, emit_rust_only, source_file_str) = match args.as_slice() {
-        [_, f] => (false, false, f.clone()),
-        [_, flag, f] if flag == "--debug" => (true, false, f.clone()),
-        [_, sub, f] if sub == "debug" => (true, false, f.clone()),
-        [_, flag, f] if flag == "--emit-rust" => (false, true, f.clone()),
+    // -O0 / -O1 may appear anywhere; the last one wins.
+    let mut opt_level = OptLevel::O1;
+    args.retain(|a| match OptLevel::from_flag(a) {
+        Some(level) => {
+            opt_level = level;
+            false
+        }
+        None => true,
+    });
+
+    // --break-if <file>:<line>:<expr> may be repeated. `file` is empty for the
+    // main program and the module name otherwise.
+    let mut break_ifs: Vec<String> = Vec::new();
+    while let Some(i) = args.iter().position(|a| a == "--break-if") {
+        if i + 1 < args.len() {
+            break_ifs.push(args.remove(i + 1));
+        }
+        args.remove(i);
+    }
+
+    let (debug_mode, profile_mode, emit_rust_only, source_file_str) = match args.as_slice() {
+        [_, f] => (false, false, false, f.clone()),
+        [_, flag, f] if flag == "--debug" => (true, false, false, f.clone()),
+        [_, sub, f] if sub == "debug" => (true, false, false, f.clone()),
+        [_, flag, f] if flag == "--profile" => (false, true, false, f.clone()),
+        [_, flag, f] if flag == "--emit-rust" => (false, false, true, f.clone()),
         _ => {
             print_error(&format!(
-                "Usage: {} [--debug | debug | --emit-rust] <path/to/file.fr>",
+                "Usage: {} [-O0 | -O1] [--debug | debug [--break-if <cond>]... | --profile | --emit-rust] <path/to/file.fr>",
                 &args[0]
             ));
             eprintln!();
@@ -45,10 +119,26 @@ fn main() {
                 "  {}  --debug file.fr        same as above (flag form)",
                 &args[0]
             );
+            eprintln!(
+                "  {}  --profile file.fr      count and time each line and function as it runs",
+                &args[0]
+            );
             eprintln!(
                 "  {}  --emit-rust file.fr    output Rust source to stdout, skip rustc",
                 &args[0]
             );
+            eprintln!(
+                "  {}  -O0 file.fr            skip the Fractal optimisation pass (default -O1)",
+                &args[0]
+            );
+            eprintln!(
+                "  {}  fmt [--check] file.fr  format the file in place (or check that it is)",
+                &args[0]
+            );
+            eprintln!(
+                "  {}  debug --break-if :12:i == 3 file.fr   only stop at line 12 when `i == 3`",
+                &args[0]
+            );
             process::exit(1);
         }
     };
@@ -108,13 +198,69 @@ fn main() {
                 process::exit(1);
             }
 
+            let mut conditions: Vec<BreakCondition> = Vec::new();
+            for spec in &break_ifs {
+                let parsed = spec.split_once(':').and_then(|(file, rest)| {
+                    let (line, text) = rest.split_once(':')?;
+                    Some((file, line.trim().parse::<usize>().ok()?, text))
+                });
+                let Some((file, line, text)) = parsed else {
+                    print_error(&format!(
+                        "malformed `--break-if {spec}`; expected `<module>:<line>:<expression>`"
+                    ));
+                    process::exit(1);
+                };
+                if !condition::has_file(&node, file) {
+                    eprintln!(
+                        "\x1b[1;33mwarning:\x1b[0m ignoring breakpoint condition for `{file}`, \
+                         which is not imported by this program"
+                    );
+                    continue;
+                }
+                match condition::check(&node, file, line, text, true) {
+                    Ok(expr) => conditions.push(BreakCondition {
+                        file: file.to_string(),
+                        line,
+                        expr,
+                    }),
+                    Err(msg) => {
+                        let location = if file.is_empty() {
+                            format!("line {line}")
+                        } else {
+                            format!("`{file}` line {line}")
+                        };
+                        print_error(&format!(
+                            "breakpoint condition `{}` at {location}: {msg}",
+                            text.trim()
+                        ));
+                        process::exit(1);
+                    }
+                }
+            }
+
+            // Debug and profile builds keep the tree as written so that
+            // snapshots, stepping and line counts line up with the source.
+            let opt_level = if debug_mode || profile_mode {
+                OptLevel::O0
+            } else {
+                opt_level
+            };
+            let node = optimizer::optimize(&node, opt_level);
+
             let debug_jsonl_path = Path::new(source_file)
                 .with_extension("debug.jsonl")
                 .to_string_lossy()
                 .to_string();
 
+            let profile_path = profile::path_for(Path::new(source_file))
+                .to_string_lossy()
+                .to_string();
+
             let rs_code = if debug_mode {
-                codegen::generate_debug(&node, &result, &debug_jsonl_path)
+                let header = snapshot::header(source_file, &contents, &processed_program);
+                codegen::generate_debug(&node, &result, &debug_jsonl_path, &header, &conditions)
+            } else if profile_mode {
+                codegen::generate_profile(&node, &result, &profile_path)
             } else {
                 codegen::generate(&node, &result)
             };
@@ -180,13 +326,21 @@ fn main() {
                         .unwrap_or("<binary>");
                     eprintln!("\x1b[1;32m compiled:\x1b[0m `{}`", display);
                     if debug_mode {
-                        let meta_path = Path::new(source_file).with_extension("debug-meta");
-                        let _ = fs::write(&meta_path, &debug_jsonl_path);
+                        let _ = snapshot::write_meta(
+                            Path::new(source_file),
+                            Path::new(&debug_jsonl_path),
+                        );
                         eprintln!(
                             "\x1b[1;34m   debug:\x1b[0m snapshots → `{}`",
                             debug_jsonl_path
                         );
                     }
+                    if profile_mode {
+                        eprintln!(
+                            "\x1b[1;34m profile:\x1b[0m written to `{}` when the program ends",
+                            profile_path
+                        );
+                    }
                 }
                 Ok(_) => {
                     print_error("rustc reported errors - this is likely a compiler bug");
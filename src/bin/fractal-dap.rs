@@ -0,0 +1,758 @@
+use fractal::compiler::condition;
+use fractal::compiler::diagnostic::Diagnostic;
+use fractal::compiler::lexer::lex;
+use fractal::compiler::parser::parse_with_source;
+use fractal::compiler::preprocessor::try_preprocess;
+use fractal::compiler::snapshot;
+use fractal::ui::debugger::{DebugFrame, DebugSession, FractalValue};
+use fractal::ui::diagnostics::describe;
+use fractal::ui::watch;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Fractal programs are single-threaded; every request names this one.
+const THREAD_ID: u64 = 1;
+const POLL_INTERVAL_MS: u64 = 10;
+
+// The client side of stdout. Events from the output forwarding threads and
+// responses from the main loop share the sequence counter.
+#[derive(Clone)]
+struct Client {
+    seq: Arc<Mutex<u64>>,
+}
+
+impl Client {
+    fn send(&self, mut message: Value) {
+        let mut seq = self.seq.lock().unwrap();
+        *seq += 1;
+        message["seq"] = json!(*seq);
+        let body = message.to_string();
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = out.flush();
+    }
+
+    fn event(&self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn respond(&self, request: &Value, result: Result<Value, String>) {
+        let mut message = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => message["body"] = body,
+            Err(e) => message["message"] = json!(e),
+        }
+        self.send(message);
+    }
+
+    fn output(&self, category: &str, text: &str) {
+        self.event("output", json!({ "category": category, "output": text }));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Resume {
+    Entry,
+    StepIn,
+    StepOver,
+    StepOut,
+    Continue,
+}
+
+struct Breakpoint {
+    line: usize,
+    condition: Option<String>,
+}
+
+struct Debuggee {
+    child: Child,
+    forwarders: Vec<JoinHandle<()>>,
+    session: DebugSession,
+    frame: DebugFrame,
+    jsonl_path: PathBuf,
+    lock_path: PathBuf,
+    // The conditions compiled into the binary, by module key and line.
+    compiled: HashMap<(String, usize), String>,
+    resuming: Option<Resume>,
+    // Lists and structs handed to the client since the last stop. Entry `i`
+    // has variables reference `scopes.len() + 1 + i`.
+    expandable: Vec<FractalValue>,
+}
+
+struct Adapter {
+    client: Client,
+    program: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    stop_on_entry: bool,
+    configured: bool,
+    pending_launch: Option<Value>,
+    breakpoints: BTreeMap<PathBuf, Vec<Breakpoint>>,
+    debuggee: Option<Debuggee>,
+}
+
+impl Adapter {
+    fn new(client: Client) -> Self {
+        Self {
+            client,
+            program: None,
+            cwd: None,
+            stop_on_entry: false,
+            configured: false,
+            pending_launch: None,
+            breakpoints: BTreeMap::new(),
+            debuggee: None,
+        }
+    }
+
+    // Returns false once the client has disconnected.
+    fn handle(&mut self, request: Value) -> bool {
+        let args = &request["arguments"];
+        let result = match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.client.respond(
+                    &request,
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsConditionalBreakpoints": true,
+                        "supportsTerminateRequest": true,
+                    })),
+                );
+                self.client.event("initialized", json!({}));
+                return true;
+            }
+            "launch" => match self.launch(args) {
+                Ok(()) => {
+                    // Answered once the client has sent its breakpoints, as
+                    // their conditions are compiled into the binary.
+                    self.pending_launch = Some(request);
+                    self.start_if_ready();
+                    return true;
+                }
+                Err(e) => Err(e),
+            },
+            "setBreakpoints" => self.set_breakpoints(args),
+            "configurationDone" => {
+                self.configured = true;
+                self.client.respond(&request, Ok(json!({})));
+                self.start_if_ready();
+                return true;
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => self.scopes(args),
+            "variables" => self.variables(args),
+            "next" => self.resume(Resume::StepOver),
+            "stepIn" => self.resume(Resume::StepIn),
+            "stepOut" => self.resume(Resume::StepOut),
+            "continue" => self
+                .resume(Resume::Continue)
+                .map(|_| json!({ "allThreadsContinued": true })),
+            "terminate" => {
+                self.terminate();
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                self.terminate();
+                self.client.respond(&request, Ok(json!({})));
+                return false;
+            }
+            other => Err(format!("`{other}` is not supported by fractal-dap")),
+        };
+        self.client.respond(&request, result);
+        true
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<(), String> {
+        let program = args["program"]
+            .as_str()
+            .ok_or("launch needs a `program` path to a .fr file")?;
+        let program = fs::canonicalize(program)
+            .map_err(|e| format!("cannot open program `{program}`: {e}"))?;
+        self.program = Some(program);
+        self.cwd = args["cwd"].as_str().map(PathBuf::from);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        Ok(())
+    }
+
+    fn start_if_ready(&mut self) {
+        if !self.configured || self.debuggee.is_some() {
+            return;
+        }
+        let Some(request) = self.pending_launch.take() else {
+            return;
+        };
+        let started = self.start();
+        let failed = started.is_err();
+        self.client.respond(&request, started.map(|_| json!({})));
+        if failed {
+            self.client.event("terminated", json!({}));
+        }
+    }
+
+    fn start(&mut self) -> Result<(), String> {
+        let program = self.program.clone().ok_or("no program was launched")?;
+        let conditions = self.conditions(&program);
+
+        let mut cmd = Command::new(compiler_path());
+        cmd.arg("debug");
+        for ((file, line), cond) in &conditions {
+            cmd.arg("--break-if").arg(format!("{file}:{line}:{cond}"));
+        }
+        let out = cmd
+            .arg(&program)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| {
+                format!(
+                    "could not launch fractal-compiler: {e} \
+                     (is it in PATH or next to fractal-dap?)"
+                )
+            })?;
+        let stderr = strip_ansi(&String::from_utf8_lossy(&out.stderr));
+        if !out.status.success() {
+            let stdout = strip_ansi(&String::from_utf8_lossy(&out.stdout));
+            return Err(format!("{stdout}{stderr}").trim().to_string());
+        }
+        if !stderr.trim().is_empty() {
+            self.client.output("console", &stderr);
+        }
+
+        let jsonl_path = snapshot::read_meta(&program)?;
+        let lock_path = jsonl_path.with_extension("lock");
+        let _ = fs::remove_file(&lock_path);
+
+        // The source, or a module it imports, may have changed since the
+        // compiler accepted it, so a front-end error fails the launch.
+        let name = program.to_string_lossy().to_string();
+        let source = fs::read_to_string(&program).map_err(|e| e.to_string())?;
+        let (expanded, _) = try_preprocess(&source, &name).map_err(|d| first_error(&d))?;
+        let tokens = lex(&expanded, &name).map_err(|d| first_error(&d))?;
+        let root =
+            parse_with_source(tokens, &name).map_err(|e| first_error(&[e.diagnostic(&name)]))?;
+
+        let mut cmd = Command::new(program.with_extension(""));
+        if let Some(ref cwd) = self.cwd {
+            cmd.current_dir(cwd);
+        }
+        let mut child = cmd
+            .env("FRACTAL_DEBUG_LOCK", &lock_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not start the debug build of `{name}`: {e}"))?;
+
+        let mut forwarders = Vec::new();
+        if let Some(pipe) = child.stdout.take() {
+            forwarders.push(forward_output(pipe, "stdout", self.client.clone()));
+        }
+        if let Some(pipe) = child.stderr.take() {
+            forwarders.push(forward_output(pipe, "stderr", self.client.clone()));
+        }
+
+        let session = DebugSession::new(&root, jsonl_path.clone());
+        let frame = session.current_frame();
+        self.debuggee = Some(Debuggee {
+            child,
+            forwarders,
+            session,
+            frame,
+            jsonl_path,
+            lock_path,
+            compiled: conditions.into_iter().collect(),
+            resuming: Some(Resume::Entry),
+            expandable: Vec::new(),
+        });
+        self.client.event(
+            "process",
+            json!({ "name": name, "isLocalProcess": true, "startMethod": "launch" }),
+        );
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"]
+            .as_str()
+            .ok_or("setBreakpoints needs a source path")?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let key = module_key(self.program.as_ref(), &path);
+
+        let mut kept = Vec::new();
+        let mut results = Vec::new();
+        for bp in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = bp["line"].as_u64().unwrap_or(0) as usize;
+            let condition = bp["condition"]
+                .as_str()
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(String::from);
+            if let Some(Err(e)) = condition.as_deref().map(condition::parse_expr) {
+                results.push(json!({ "verified": false, "line": line, "message": e }));
+                continue;
+            }
+            let live = self
+                .debuggee
+                .as_ref()
+                .is_none_or(|d| d.compiled.get(&(key.clone(), line)) == condition.as_ref());
+            results.push(if live {
+                json!({ "verified": true, "line": line })
+            } else {
+                json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "changed conditions apply from the next launch",
+                })
+            });
+            kept.push(Breakpoint { line, condition });
+        }
+        self.breakpoints.insert(path, kept);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    // One condition per conditional breakpoint, keyed the way the debug
+    // runtime names files.
+    fn conditions(&self, program: &PathBuf) -> Vec<((String, usize), String)> {
+        let mut out = Vec::new();
+        for (path, bps) in &self.breakpoints {
+            let key = module_key(Some(program), path);
+            for bp in bps {
+                if let Some(ref cond) = bp.condition {
+                    out.push(((key.clone(), bp.line), cond.clone()));
+                }
+            }
+        }
+        out
+    }
+
+    // The breakpoints the running binary can honour: those whose condition is
+    // the one it was compiled with.
+    fn live_breakpoints(&self) -> Vec<(String, usize, Option<String>)> {
+        let Some(ref dbg) = self.debuggee else {
+            return vec![];
+        };
+        let mut out = Vec::new();
+        for (path, bps) in &self.breakpoints {
+            let key = module_key(self.program.as_ref(), path);
+            for bp in bps {
+                if dbg.compiled.get(&(key.clone(), bp.line)) == bp.condition.as_ref() {
+                    out.push((key.clone(), bp.line, bp.condition.clone()));
+                }
+            }
+        }
+        out
+    }
+
+    fn resume(&mut self, mode: Resume) -> Result<Value, String> {
+        let mut command = match self.debuggee {
+            Some(ref dbg) if dbg.resuming.is_none() => {
+                let depth = dbg.frame.call_stack.len();
+                match mode {
+                    Resume::StepIn | Resume::Entry => None,
+                    Resume::StepOver => Some(format!("continue {depth}\n")),
+                    Resume::StepOut => Some(format!("continue {}\n", depth.saturating_sub(1))),
+                    Resume::Continue => Some(String::from("continue\n")),
+                }
+            }
+            Some(_) => return Err("the program is already running".into()),
+            None => return Err("the program is not running".into()),
+        };
+        if let Some(ref mut command) = command {
+            for (file, line, _) in self.live_breakpoints() {
+                command.push_str(&format!("{file}:{line}\n"));
+            }
+        }
+
+        let dbg = self.debuggee.as_mut().unwrap();
+        let sent = match command {
+            None => fs::remove_file(&dbg.lock_path),
+            Some(command) => {
+                let tmp_path = dbg.lock_path.with_extension("lock-cmd");
+                fs::write(&tmp_path, command).and_then(|_| fs::rename(&tmp_path, &dbg.lock_path))
+            }
+        };
+        sent.map_err(|e| format!("failed to resume the program: {e}"))?;
+        dbg.resuming = Some(mode);
+        Ok(json!({}))
+    }
+
+    // Called between requests: reports the debuggee pausing or exiting.
+    fn poll(&mut self) {
+        let Some(ref mut dbg) = self.debuggee else {
+            return;
+        };
+        if let Ok(Some(status)) = dbg.child.try_wait() {
+            for forwarder in dbg.forwarders.drain(..) {
+                let _ = forwarder.join();
+            }
+            dbg.session.poll_file();
+            dbg.session.seek_latest();
+            let frame = dbg.session.current_frame();
+            if let Some(err) = frame.error {
+                self.client.output(
+                    "stderr",
+                    &format!("runtime error at line {}: {err}\n", frame.source_line),
+                );
+            }
+            self.terminate();
+            self.client
+                .event("exited", json!({ "exitCode": status.code().unwrap_or(1) }));
+            self.client.event("terminated", json!({}));
+            return;
+        }
+
+        let Some(mode) = dbg.resuming else {
+            return;
+        };
+        let paused = fs::read_to_string(&dbg.lock_path).is_ok_and(|c| c.is_empty());
+        if !paused {
+            return;
+        }
+        dbg.session.poll_file();
+        dbg.session.seek_latest();
+        dbg.frame = dbg.session.current_frame();
+        dbg.resuming = None;
+        dbg.expandable.clear();
+
+        // The binary always pauses on its first statement; run on unless the
+        // client asked to stop there or a breakpoint sits on it.
+        let reason = match mode {
+            Resume::Entry if self.stop_on_entry => "entry",
+            Resume::Entry if !self.at_breakpoint() => {
+                if let Err(e) = self.resume(Resume::Continue) {
+                    self.client.output("stderr", &format!("{e}\n"));
+                }
+                return;
+            }
+            Resume::Entry | Resume::Continue => "breakpoint",
+            _ if self.at_breakpoint() => "breakpoint",
+            Resume::StepIn | Resume::StepOver | Resume::StepOut => "step",
+        };
+        self.client.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+    }
+
+    // Whether the paused frame sits on a breakpoint whose condition holds. The
+    // binary checks conditions itself once resumed, but not before.
+    fn at_breakpoint(&self) -> bool {
+        let Some(ref dbg) = self.debuggee else {
+            return false;
+        };
+        let frame = &dbg.frame;
+        let vars = frame
+            .scopes
+            .first()
+            .map(|s| s.vars.as_slice())
+            .unwrap_or_default();
+        self.live_breakpoints()
+            .into_iter()
+            .filter(|(file, line, _)| *file == frame.source_file && *line == frame.source_line)
+            .any(|(_, _, cond)| match cond {
+                None => true,
+                Some(cond) => condition::parse_expr(&cond)
+                    .and_then(|expr| watch::eval(&expr, vars))
+                    .is_ok_and(|v| matches!(v, FractalValue::Bool(true))),
+            })
+    }
+
+    fn stopped_frame(&self) -> Result<&Debuggee, String> {
+        match self.debuggee {
+            Some(ref dbg) if dbg.resuming.is_none() => Ok(dbg),
+            Some(_) => Err("the program is running".into()),
+            None => Err("the program is not running".into()),
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let dbg = self.stopped_frame()?;
+        let stack = &dbg.frame.call_stack;
+        let frames: Vec<Value> = stack
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(depth, name)| {
+                let (file, line) = dbg.session.frame_location(depth).unwrap_or_default();
+                let mut frame = json!({
+                    "id": stack.len() - 1 - depth,
+                    "name": name,
+                    "line": line,
+                    "column": 1,
+                });
+                if let Some(path) = self.source_path(&file) {
+                    frame["source"] = json!({
+                        "name": path.file_name().map(|n| n.to_string_lossy()),
+                        "path": path,
+                    });
+                }
+                frame
+            })
+            .collect();
+        Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    // Scope `i` of a snapshot belongs to stack frame `i`, innermost first, so
+    // a frame's variables reference is simply its id plus one.
+    fn scopes(&self, args: &Value) -> Result<Value, String> {
+        let dbg = self.stopped_frame()?;
+        let id = args["frameId"].as_u64().unwrap_or(0) as usize;
+        let scope = dbg.frame.scopes.get(id).ok_or("no such stack frame")?;
+        Ok(json!({
+            "scopes": [{
+                "name": "Locals",
+                "presentationHint": "locals",
+                "variablesReference": id + 1,
+                "namedVariables": scope.vars.len(),
+                "expensive": false,
+            }]
+        }))
+    }
+
+    // References up to the number of scopes name a frame's locals; the rest
+    // name a list or struct and list its elements or fields.
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        self.stopped_frame()?;
+        let Some(ref mut dbg) = self.debuggee else {
+            return Err("the program is not running".into());
+        };
+        let reference = args["variablesReference"].as_u64().unwrap_or(0) as usize;
+        let scopes = dbg.frame.scopes.len();
+        let rows: Vec<(String, String, FractalValue)> = if (1..=scopes).contains(&reference) {
+            dbg.frame.scopes[reference - 1]
+                .vars
+                .iter()
+                .map(|row| (row.name.clone(), row.type_label.clone(), row.value.clone()))
+                .collect()
+        } else {
+            let value = reference
+                .checked_sub(scopes + 1)
+                .and_then(|i| dbg.expandable.get(i))
+                .ok_or("no such variables reference")?;
+            value
+                .children()
+                .into_iter()
+                .map(|(name, v)| (name, v.type_label().to_string(), v.clone()))
+                .collect()
+        };
+
+        let vars: Vec<Value> = rows
+            .into_iter()
+            .map(|(name, type_label, value)| {
+                let shown = watch::display(&value);
+                let count = value.children().len();
+                let reference = if count == 0 {
+                    0
+                } else {
+                    dbg.expandable.push(value);
+                    scopes + dbg.expandable.len()
+                };
+                json!({
+                    "name": name,
+                    "value": shown,
+                    "type": type_label,
+                    "variablesReference": reference,
+                    "indexedVariables": count,
+                })
+            })
+            .collect();
+        Ok(json!({ "variables": vars }))
+    }
+
+    // The file behind a module key: the launched program for the empty key,
+    // otherwise a file the client set breakpoints in or one next to the
+    // program with that name.
+    fn source_path(&self, file: &str) -> Option<PathBuf> {
+        let program = self.program.as_ref()?;
+        if file.is_empty() {
+            return Some(program.clone());
+        }
+        self.breakpoints
+            .keys()
+            .find(|p| module_key(Some(program), p) == file)
+            .cloned()
+            .or_else(|| {
+                let beside = program.parent()?.join(format!("{file}.fr"));
+                beside.exists().then_some(beside)
+            })
+    }
+
+    fn terminate(&mut self) {
+        let Some(mut dbg) = self.debuggee.take() else {
+            return;
+        };
+        let _ = dbg.child.kill();
+        let _ = dbg.child.wait();
+        let _ = fs::remove_file(&dbg.jsonl_path);
+        let _ = fs::remove_file(&dbg.lock_path);
+    }
+}
+
+// How the debug runtime names the file of `path`: empty for the program being
+// debugged and the module name for anything it imports.
+fn module_key(program: Option<&PathBuf>, path: &PathBuf) -> String {
+    if program == Some(path) {
+        String::new()
+    } else {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+// The first error in `diagnostics`, with where it is, for a failed launch.
+fn first_error(diagnostics: &[Diagnostic]) -> String {
+    let Some(d) = diagnostics.iter().find(|d| d.is_error()) else {
+        return "the program could not be read".to_string();
+    };
+    match d.line {
+        0 => describe(d),
+        line if d.path.is_empty() => format!("line {line}: {}", describe(d)),
+        line => format!("{}:{line}: {}", d.path, describe(d)),
+    }
+}
+
+fn compiler_path() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+    let compiler = exe_dir.join("fractal-compiler");
+    if compiler.exists() {
+        compiler
+    } else {
+        PathBuf::from("fractal-compiler")
+    }
+}
+
+// The compiler colours its diagnostics for a terminal; clients show plain text.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Sends whatever the program writes to `pipe` as output events, without
+// waiting for a newline so prompts show up, and without splitting a UTF-8
+// character across two events.
+fn forward_output(
+    mut pipe: impl Read + Send + 'static,
+    category: &'static str,
+    client: Client,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            let n = match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            pending.extend_from_slice(&buf[..n]);
+            let valid = match std::str::from_utf8(&pending) {
+                Ok(s) => s.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => pending.len(),
+            };
+            if valid > 0 {
+                let rest = pending.split_off(valid);
+                client.output(category, &String::from_utf8_lossy(&pending));
+                pending = rest;
+            }
+        }
+        if !pending.is_empty() {
+            client.output(category, &String::from_utf8_lossy(&pending));
+        }
+    })
+}
+
+// Reads Content-Length framed requests from stdin until the client goes away.
+fn read_requests(tx: mpsc::Sender<Value>) {
+    let mut input = BufReader::new(io::stdin().lock());
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            match input.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim();
+            if header.is_empty() {
+                if length.is_some() {
+                    break;
+                }
+            } else if let Some(n) = header.strip_prefix("Content-Length:") {
+                length = n.trim().parse::<usize>().ok();
+            }
+        }
+        let mut body = vec![0u8; length.unwrap_or(0)];
+        if input.read_exact(&mut body).is_err() {
+            return;
+        }
+        match serde_json::from_slice(&body) {
+            Ok(request) => {
+                if tx.send(request).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("fractal-dap: ignoring malformed message: {e}"),
+        }
+    }
+}
+
+fn main() {
+    if std::env::args().len() > 1 {
+        eprintln!("Usage: fractal-dap");
+        eprintln!(
+            "Speaks the Debug Adapter Protocol on stdin and stdout; start it from an editor."
+        );
+        std::process::exit(1);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || read_requests(tx));
+
+    let client = Client {
+        seq: Arc::new(Mutex::new(0)),
+    };
+    let mut adapter = Adapter::new(client);
+    loop {
+        match rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+            Ok(request) => {
+                if !adapter.handle(request) {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                adapter.terminate();
+                break;
+            }
+        }
+        adapter.poll();
+    }
+}
@@ -1,23 +1,41 @@
 use eframe::egui;
+use fractal::compiler::condition;
+use fractal::compiler::diagnostic::Severity;
+use fractal::compiler::formatter::{format_code, try_format};
+use fractal::compiler::lexer::try_tokenize;
+use fractal::compiler::parser::{parse_with_source, ParseNode};
+use fractal::compiler::profile;
+use fractal::compiler::snapshot;
+use fractal::ui::breakpoint_dialog::{BreakpointAction, BreakpointDialog};
 use fractal::ui::close_confirm::{
     CloseConfirmAction, CloseConfirmDialog, QuitConfirmAction, QuitConfirmDialog,
 };
-use fractal::ui::debugger::{DebugFrame, DebugSession};
+use fractal::ui::debugger::{DebugFrame, DebugSession, FractalValue};
+use fractal::ui::diagnostics::{ProblemTarget, ProblemsWindow};
 use fractal::ui::docs::DocsWindow;
-use fractal::ui::editor::{show_empty_state, EmptyStateAction};
+use fractal::ui::editor::{show_empty_state, EditorView, EmptyStateAction};
 use fractal::ui::file_dialog::{FileDialog, FileDialogMode};
-use fractal::ui::formatter::format_code;
+use fractal::ui::find_in_files::{FileChange, FindAction, FindInFilesWindow};
+use fractal::ui::heap_view::HeapViewWindow;
 use fractal::ui::icons::{self as ic, setup_fonts};
-use fractal::ui::menu_bar::{show_menu_bar, MenuAction, MenuBarState};
+use fractal::ui::import_graph::ImportGraphWindow;
+use fractal::ui::menu_bar::{show_menu_bar, MenuAction, MenuBarState, MenuBarView};
+use fractal::ui::outline::OutlinePanel;
+use fractal::ui::profile_view::{self, ProfileWindow};
+use fractal::ui::references::{self, FileRef, Place, ReferencesWindow, RenameAction, RenameDialog};
 use fractal::ui::search_bar::{SearchBar, SearchBarAction};
 use fractal::ui::tab::{show_tab_bar, Tab, TabBarAction};
 use fractal::ui::terminal::Terminal;
 use fractal::ui::theme::{Theme, ThemeVariant};
+use fractal::ui::timeline::{TimelineAction, TimelineBar};
 use fractal::ui::tree_view::TreeViewWindow;
 use fractal::ui::user_profile::{SettingsPanel, UserProfile};
 use fractal::ui::var_view::VarViewWindow;
+use fractal::ui::watch;
+use fractal::ui::workspace::{ExplorerAction, Workspace};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -32,6 +50,13 @@ struct SessionState {
     open_files: Vec<PathBuf>,
     active_index: usize,
     recent_files: Vec<PathBuf>,
+    #[serde(default)]
+    breakpoints: BTreeMap<PathBuf, BTreeSet<usize>>,
+    #[serde(default)]
+    breakpoint_conditions: BTreeMap<PathBuf, BTreeMap<usize, String>>,
+    /// The folder opened with Open Folder.
+    #[serde(default)]
+    workspace: Option<PathBuf>,
 }
 
 impl SessionState {
@@ -62,9 +87,26 @@ impl SessionState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum ResumeMode {
+    Continue,
+    StepOver,
+    StepOut,
+}
+
+// How `launch_compile` builds the program.
+#[derive(Clone, Copy, PartialEq)]
+enum BuildMode {
+    Run,
+    Debug,
+    Profile,
+}
+
 enum CompileResult {
     Success(PathBuf),
     DebugSuccess(PathBuf, PathBuf),
+    // The binary, and where it writes its profile.
+    ProfileSuccess(PathBuf, PathBuf),
     Error(String),
     LaunchError(String),
 }
@@ -101,15 +143,37 @@ struct FractalEditor {
     last_autosave: Instant,
 
     recent_files: Vec<PathBuf>,
+    breakpoints: BTreeMap<PathBuf, BTreeSet<usize>>,
+    breakpoint_conditions: BTreeMap<PathBuf, BTreeMap<usize, String>>,
+    breakpoint_dialog: BreakpointDialog,
 
     debug_session: Option<DebugSession>,
     debug_frame: Option<DebugFrame>,
     debug_jsonl_path: Option<PathBuf>,
     debug_lock_path: Option<PathBuf>,
     debug_binary_running: bool,
+    debug_resuming: Option<ResumeMode>,
+    debug_source: Option<PathBuf>,
+    debug_root: Option<ParseNode>,
     pending_debug_source: Option<PathBuf>,
+    // Stepping through a recording rather than a running binary.
+    debug_replay: bool,
+    // The open file dialog picks a recording rather than a source file.
+    recording_dialog: bool,
     tree_view_window: TreeViewWindow,
+    heap_view_window: HeapViewWindow,
+    profile_window: ProfileWindow,
+    // The program whose profiled run is in progress, and its profile file.
+    pending_profile: Option<(PathBuf, PathBuf)>,
+    problems_window: ProblemsWindow,
+    outline_panel: OutlinePanel,
+    workspace: Option<Workspace>,
+    import_graph: ImportGraphWindow,
+    find_in_files: FindInFilesWindow,
+    references_window: ReferencesWindow,
+    rename_dialog: RenameDialog,
     var_view_window: VarViewWindow,
+    timeline_bar: TimelineBar,
 }
 
 impl FractalEditor {
@@ -150,14 +214,33 @@ impl FractalEditor {
             active_tab: 0,
             profile,
             recent_files,
+            breakpoints: session.breakpoints.clone(),
+            breakpoint_conditions: session.breakpoint_conditions.clone(),
+            breakpoint_dialog: BreakpointDialog::default(),
             debug_session: None,
             debug_frame: None,
             debug_jsonl_path: None,
             debug_lock_path: None,
             debug_binary_running: false,
+            debug_resuming: None,
+            debug_source: None,
+            debug_root: None,
             pending_debug_source: None,
+            debug_replay: false,
+            recording_dialog: false,
             tree_view_window: TreeViewWindow::new(),
+            heap_view_window: HeapViewWindow::new(),
+            profile_window: ProfileWindow::new(),
+            pending_profile: None,
+            problems_window: ProblemsWindow::new(),
+            outline_panel: OutlinePanel::new(),
+            workspace: None,
+            import_graph: ImportGraphWindow::new(),
+            find_in_files: FindInFilesWindow::new(),
+            references_window: ReferencesWindow::new(),
+            rename_dialog: RenameDialog::default(),
             var_view_window: VarViewWindow::new(),
+            timeline_bar: TimelineBar::default(),
         };
 
         if !session.open_files.is_empty() {
@@ -175,6 +258,9 @@ impl FractalEditor {
                 editor.active_tab = saved_idx;
             }
         }
+        if let Some(root) = session.workspace.filter(|p| p.is_dir()) {
+            editor.workspace = Some(Workspace::new(root));
+        }
 
         editor
     }
@@ -217,6 +303,12 @@ impl FractalEditor {
                         .push(Tab::from_file(path.clone(), content, self.theme));
                     self.active_tab = self.tabs.len() - 1;
                 }
+                if let Some(bps) = self.breakpoints.get(path) {
+                    self.tabs[self.active_tab].breakpoints = bps.clone();
+                }
+                if let Some(conds) = self.breakpoint_conditions.get(path) {
+                    self.tabs[self.active_tab].breakpoint_conditions = conds.clone();
+                }
                 self.push_recent(path.clone());
                 self.success_message = Some(format!("Opened: {}", path.display()));
                 self.error_message = None;
@@ -226,12 +318,16 @@ impl FractalEditor {
     }
 
     fn save_file(&mut self, path: &PathBuf) {
+        let format = self.profile.format_on_save;
         let tab = &mut self.tabs[self.active_tab];
-        tab.code = format_code(&tab.code);
+        if format {
+            tab.code = format_code(&tab.code);
+        }
         match fs::write(path, &tab.code) {
             Ok(_) => {
                 tab.last_saved_code = tab.code.clone();
                 tab.current_file = Some(path.clone());
+                tab.recorded_path = None;
                 self.last_autosave = Instant::now();
                 self.push_recent(path.clone());
                 self.success_message = Some(format!("Saved: {}", path.display()));
@@ -260,8 +356,11 @@ impl FractalEditor {
 
     fn autosave(&mut self) {
         if let Some(path) = self.tabs[self.active_tab].current_file.clone() {
+            let format = self.profile.format_on_save;
             let tab = &mut self.tabs[self.active_tab];
-            tab.code = format_code(&tab.code);
+            if format {
+                tab.code = format_code(&tab.code);
+            }
             if fs::write(&path, &tab.code).is_ok() {
                 tab.last_saved_code = tab.code.clone();
             }
@@ -269,6 +368,24 @@ impl FractalEditor {
         self.last_autosave = Instant::now();
     }
 
+    fn format_document(&mut self) {
+        let tab = &mut self.tabs[self.active_tab];
+        match try_format(&tab.code, "") {
+            Ok(code) if code == tab.code => {
+                self.success_message = Some("Already formatted".to_string());
+            }
+            Ok(code) => {
+                tab.code = code;
+                self.success_message = Some("Formatted the document".to_string());
+                self.error_message = None;
+            }
+            Err(_) => {
+                self.error_message =
+                    Some("Cannot format: fix the errors in the file first".to_string());
+            }
+        }
+    }
+
     fn push_recent(&mut self, path: PathBuf) {
         self.recent_files.retain(|p| p != &path);
         self.recent_files.insert(0, path);
@@ -285,21 +402,53 @@ impl FractalEditor {
             open_files,
             active_index: self.active_tab,
             recent_files: self.recent_files.clone(),
+            breakpoints: self.all_breakpoints(),
+            breakpoint_conditions: self.all_breakpoint_conditions(),
+            workspace: self.workspace.as_ref().map(|w| w.root.clone()),
         }
         .save();
     }
 
+    fn all_breakpoints(&self) -> BTreeMap<PathBuf, BTreeSet<usize>> {
+        let mut all = self.breakpoints.clone();
+        for tab in &self.tabs {
+            if let Some(ref path) = tab.current_file {
+                all.insert(path.clone(), tab.breakpoints.clone());
+            }
+        }
+        all.retain(|_, lines| !lines.is_empty());
+        all
+    }
+
+    fn all_breakpoint_conditions(&self) -> BTreeMap<PathBuf, BTreeMap<usize, String>> {
+        let mut all = self.breakpoint_conditions.clone();
+        for tab in &self.tabs {
+            if let Some(ref path) = tab.current_file {
+                all.insert(path.clone(), tab.breakpoint_conditions.clone());
+            }
+        }
+        all.retain(|_, conds| !conds.is_empty());
+        all
+    }
+
     fn close_tab(&mut self, index: usize) {
         if self.tabs.is_empty() {
             return;
         }
-        self.tabs.remove(index);
+        let tab = self.tabs.remove(index);
+        if let Some(path) = tab.current_file {
+            self.breakpoints.insert(path.clone(), tab.breakpoints);
+            self.breakpoint_conditions
+                .insert(path, tab.breakpoint_conditions);
+        }
         if self.active_tab >= self.tabs.len() && self.active_tab > 0 {
             self.active_tab = self.tabs.len().saturating_sub(1);
         }
         if self.tabs.is_empty() {
             self.search_bar.visible = false;
         }
+        // Its references point at tabs by position.
+        self.references_window.open = false;
     }
 
     fn request_close_tab(&mut self, index: usize) {
@@ -331,39 +480,16 @@ impl FractalEditor {
         if self.tabs.is_empty() || self.search_bar.query.is_empty() {
             return;
         }
+        let Ok(matcher) = self.search_bar.matcher() else {
+            return;
+        };
         let tab = &mut self.tabs[self.active_tab];
-        let query = self.search_bar.query.clone();
-        let replacement = self.search_bar.replace_text.clone();
-        let match_case = self.search_bar.match_case;
+        let replacement = &self.search_bar.replace_text;
         let idx = self.search_bar.current_match;
-        let code = tab.code.clone();
-        let mut found = 0usize;
-        let mut byte_pos = 0usize;
-        let lower_code = if match_case {
-            code.clone()
-        } else {
-            code.to_lowercase()
-        };
-        let lower_query = if match_case {
-            query.clone()
-        } else {
-            query.to_lowercase()
-        };
-        while let Some(pos) = lower_code[byte_pos..].find(lower_query.as_str()) {
-            let abs_pos = byte_pos + pos;
-            if found == idx {
-                let mut new_code = String::with_capacity(code.len());
-                new_code.push_str(&code[..abs_pos]);
-                new_code.push_str(&replacement);
-                new_code.push_str(&code[abs_pos + query.len()..]);
-                tab.code = new_code;
-                self.search_bar.current_match = idx
-                    .saturating_sub(0)
-                    .min(self.search_bar.total_matches.saturating_sub(1));
-                return;
-            }
-            found += 1;
-            byte_pos = abs_pos + lower_query.len();
+        if let Some(&range) = matcher.find_all(&tab.code).get(idx) {
+            tab.code = matcher.replace_one(&tab.code, range, replacement);
+            self.search_bar.current_match =
+                idx.min(self.search_bar.total_matches.saturating_sub(1));
         }
     }
 
@@ -371,35 +497,11 @@ impl FractalEditor {
         if self.tabs.is_empty() || self.search_bar.query.is_empty() {
             return;
         }
-        let tab = &mut self.tabs[self.active_tab];
-        let query = self.search_bar.query.clone();
-        let replacement = self.search_bar.replace_text.clone();
-        let new_code = if self.search_bar.match_case {
-            tab.code.replace(query.as_str(), &replacement)
-        } else {
-            let lower_code = tab.code.to_lowercase();
-            let lower_query = query.to_lowercase();
-            let mut result = String::with_capacity(tab.code.len());
-            let mut last = 0usize;
-            let mut search_from = 0usize;
-            while let Some(pos) = lower_code[search_from..].find(lower_query.as_str()) {
-                let abs = search_from + pos;
-                result.push_str(&tab.code[last..abs]);
-                result.push_str(&replacement);
-                last = abs + query.len();
-                search_from = last;
-            }
-            result.push_str(&tab.code[last..]);
-            result
-        };
-        let count = if self.search_bar.match_case {
-            tab.code.matches(query.as_str()).count()
-        } else {
-            tab.code
-                .to_lowercase()
-                .matches(query.to_lowercase().as_str())
-                .count()
+        let Ok(matcher) = self.search_bar.matcher() else {
+            return;
         };
+        let tab = &mut self.tabs[self.active_tab];
+        let (new_code, count) = matcher.replace_all(&tab.code, &self.search_bar.replace_text);
         tab.code = new_code;
         self.search_bar.current_match = 0;
         self.success_message = Some(format!("Replaced {count} occurrence(s)."));
@@ -421,10 +523,27 @@ impl FractalEditor {
         tab.is_running = true;
 
         self.terminal.minimized = false;
-        self.launch_compile(source_path, false, ctx);
+        self.launch_compile(source_path, BuildMode::Run, ctx);
+    }
+
+    fn run_profile(&mut self, ctx: &egui::Context) {
+        let Some(source_path) = self
+            .tabs
+            .get(self.active_tab)
+            .and_then(|t| t.current_file.clone())
+        else {
+            self.error_message = Some("Save the file before profiling it.".into());
+            return;
+        };
+        let tab = &mut self.tabs[self.active_tab];
+        let _ = fs::write(&source_path, &tab.code);
+        tab.is_running = true;
+
+        self.terminal.minimized = false;
+        self.launch_compile(source_path, BuildMode::Profile, ctx);
     }
 
-    fn launch_compile(&mut self, source_path: PathBuf, debug: bool, ctx: &egui::Context) {
+    fn launch_compile(&mut self, source_path: PathBuf, mode: BuildMode, ctx: &egui::Context) {
         let result_buf: Arc<Mutex<Option<CompileResult>>> = Arc::new(Mutex::new(None));
         let result_buf2 = result_buf.clone();
         let ctx2 = ctx.clone();
@@ -442,41 +561,60 @@ impl FractalEditor {
             PathBuf::from("fractal-compiler")
         };
 
+        let break_ifs = if mode == BuildMode::Debug {
+            self.break_if_args(&source_path)
+        } else {
+            Vec::new()
+        };
+
         let compiler_str = compiler_path.to_string_lossy();
         let bin_str = bin_path.to_string_lossy();
-        let shell_cmd = if debug {
-            format!("{compiler_str} debug {path_str} 2>&1\n")
-        } else {
-            format!("{compiler_str} {path_str} 2>&1 && {bin_str}\n")
+        let shell_cmd = match mode {
+            BuildMode::Debug => {
+                let conds: String = break_ifs
+                    .iter()
+                    .map(|c| format!(" --break-if '{c}'"))
+                    .collect();
+                format!("{compiler_str} debug{conds} {path_str} 2>&1\n")
+            }
+            BuildMode::Profile => {
+                format!("{compiler_str} --profile {path_str} 2>&1 && {bin_str}\n")
+            }
+            BuildMode::Run => format!("{compiler_str} {path_str} 2>&1 && {bin_str}\n"),
         };
         self.terminal.append(&shell_cmd);
         self.terminal.minimized = false;
 
         thread::spawn(move || {
             let mut cmd = Command::new(&compiler_path);
-            if debug {
-                cmd.arg("debug");
+            match mode {
+                BuildMode::Debug => {
+                    cmd.arg("debug");
+                    for cond in &break_ifs {
+                        cmd.arg("--break-if").arg(cond);
+                    }
+                }
+                BuildMode::Profile => {
+                    cmd.arg("--profile");
+                }
+                BuildMode::Run => {}
             }
             cmd.arg(&path_str)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped());
 
             let result = match cmd.output() {
-                Ok(out) if out.status.success() => {
-                    if debug {
-                        let meta_path = PathBuf::from(&path_str).with_extension("debug-meta");
-                        if let Ok(jsonl_str) = std::fs::read_to_string(&meta_path) {
-                            let jp = PathBuf::from(jsonl_str.trim());
-                            CompileResult::DebugSuccess(bin_path, jp)
-                        } else {
-                            CompileResult::Error(
-                                "Debug compile succeeded but could not read .debug-meta sidecar file".into(),
-                            )
-                        }
-                    } else {
-                        CompileResult::Success(bin_path)
+                Ok(out) if out.status.success() => match mode {
+                    BuildMode::Debug => match snapshot::read_meta(Path::new(&path_str)) {
+                        Ok(jp) => CompileResult::DebugSuccess(bin_path, jp),
+                        Err(e) => CompileResult::Error(format!("Cannot debug: {e}")),
+                    },
+                    BuildMode::Profile => {
+                        let profile_path = profile::path_for(Path::new(&path_str));
+                        CompileResult::ProfileSuccess(bin_path, profile_path)
                     }
-                }
+                    BuildMode::Run => CompileResult::Success(bin_path),
+                },
                 Ok(_) => CompileResult::Error(String::new()),
                 Err(e) => CompileResult::LaunchError(format!(
                     "Could not launch fractal-compiler: {e}\n\
@@ -518,6 +656,14 @@ impl FractalEditor {
 
             match result {
                 CompileResult::Success(_bin_path) => {}
+                CompileResult::ProfileSuccess(bin_path, profile_path) => {
+                    // A profile left by an earlier run would be read as this one's.
+                    let _ = fs::remove_file(&profile_path);
+                    let source = bin_path.with_extension("fr");
+                    self.pending_profile = Some((source, profile_path));
+                    self.terminal.run_binary(&bin_path);
+                    self.terminal.minimized = false;
+                }
                 CompileResult::DebugSuccess(bin_path, jsonl_path) => {
                     self.start_debug_session_from_source(&jsonl_path, ctx);
                     let lock_path = jsonl_path.with_extension("lock");
@@ -526,8 +672,10 @@ impl FractalEditor {
                     self.terminal.minimized = false;
                     self.debug_binary_running = true;
                     self.debug_jsonl_path = Some(jsonl_path);
-                    self.success_message =
-                        Some("Debug session started - F5 to step, Shift+F5 to stop.".into());
+                    self.success_message = Some(
+                        "Debug session started - F5 to step, F10 over, F8 to continue, F6 to stop."
+                            .into(),
+                    );
                 }
                 CompileResult::Error(msg) => {
                     if !msg.trim().is_empty() {
@@ -541,6 +689,39 @@ impl FractalEditor {
         }
     }
 
+    // Picks up the profile once the profiled program has ended, and paints it
+    // onto the tabs of the program and its modules.
+    fn poll_profile(&mut self, ctx: &egui::Context) {
+        let Some((_, ref profile_path)) = self.pending_profile else {
+            return;
+        };
+        if !profile_path.exists() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(200));
+            return;
+        }
+        let (source, profile_path) = self.pending_profile.take().unwrap();
+        match profile::read(&profile_path) {
+            Ok(p) => {
+                for tab in &mut self.tabs {
+                    if let Some(path) = tab.current_file.as_ref() {
+                        let key = module_key(Some(&source), path);
+                        tab.line_heat = profile_view::line_heat(&p, &key);
+                    }
+                }
+                self.success_message = Some(format!(
+                    "Profiled {} in {}.",
+                    source
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    profile::format_nanos(p.total_nanos())
+                ));
+                self.profile_window.add_run(source, p);
+            }
+            Err(e) => self.error_message = Some(format!("Cannot read the profile: {e}")),
+        }
+    }
+
     fn run_debug(&mut self) {
         if self.tabs.is_empty() {
             return;
@@ -607,6 +788,11 @@ impl FractalEditor {
         }
 
         let mut session = DebugSession::new(&root, jsonl_path.clone());
+        self.debug_root = Some(root);
+        self.debug_source = self
+            .tabs
+            .get(self.active_tab)
+            .and_then(|t| t.current_file.clone());
 
         session.poll_file();
 
@@ -621,6 +807,16 @@ impl FractalEditor {
     }
 
     fn step_debug(&mut self) {
+        if self.debug_resuming.is_some() {
+            return;
+        }
+        if let Some(ref mut session) = self.debug_session {
+            if session.is_rewound() {
+                let frame = session.step_forward_recorded();
+                self.show_recorded_frame(frame);
+                return;
+            }
+        }
         if let Some(ref mut session) = self.debug_session {
             session.poll_file();
         }
@@ -630,6 +826,9 @@ impl FractalEditor {
         };
 
         match session.step() {
+            None if self.debug_replay => {
+                self.success_message = Some("End of the recording.".into());
+            }
             None => {
                 self.success_message =
                     Some("Waiting for program output… (is input expected?)".into());
@@ -656,7 +855,11 @@ impl FractalEditor {
                     self.debug_frame = Some(frame);
                     let msg = err_msg.unwrap_or_else(|| "Runtime error".into());
                     self.error_message = Some(format!("Debug fault: {msg}"));
-                    self.debug_session = None;
+                    // A recording stays open so the steps before the fault
+                    // can still be inspected.
+                    if !self.debug_replay {
+                        self.debug_session = None;
+                    }
                     self.debug_binary_running = false;
                     if let Some(ref path) = self.debug_lock_path {
                         let _ = std::fs::remove_file(path);
@@ -670,9 +873,311 @@ impl FractalEditor {
         }
     }
 
+    // The module name the debug runtime gives the active tab's file.
+    fn active_stem(&self) -> String {
+        self.tabs
+            .get(self.active_tab)
+            .and_then(|t| t.source_path())
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    fn remember_call_line(&mut self) {
+        let active_stem = self.active_stem();
+        if let Some(ref frame) = self.debug_frame {
+            let currently_in_own_file =
+                frame.source_file.is_empty() || frame.source_file == active_stem;
+            if currently_in_own_file && frame.source_line > 0 {
+                self.cross_file_call_line = Some(frame.source_line);
+            }
+        }
+    }
+
+    fn resume_debug(&mut self, mode: ResumeMode) {
+        if self.debug_resuming.is_some() {
+            return;
+        }
+        if self.debug_replay {
+            self.replay_resume(mode);
+            return;
+        }
+        let Some(lock_path) = self.debug_lock_path.clone() else {
+            return;
+        };
+        let Some(ref mut session) = self.debug_session else {
+            return;
+        };
+        session.return_to_live();
+        session.poll_file();
+        if session.steps_available() == 0 {
+            self.success_message = Some("Waiting for program output… (is input expected?)".into());
+            return;
+        }
+        let current = session.current_frame();
+        if current.finished {
+            self.step_debug();
+            return;
+        }
+        if let Some(frame) = session.step() {
+            self.var_view_window.push_output(&frame.buffered_output);
+        }
+
+        let depth = current.call_stack.len();
+        let max_depth = match mode {
+            ResumeMode::Continue => None,
+            ResumeMode::StepOver => Some(depth),
+            ResumeMode::StepOut => Some(depth.saturating_sub(1)),
+        };
+        let tmp_path = lock_path.with_extension("lock-cmd");
+        let command = self.resume_command(max_depth);
+        let sent = fs::write(&tmp_path, command).and_then(|_| fs::rename(&tmp_path, &lock_path));
+        if let Err(e) = sent {
+            self.error_message = Some(format!("Failed to resume debug session: {e}"));
+            return;
+        }
+        if mode == ResumeMode::Continue {
+            self.cross_file_call_line = None;
+            self.success_message = Some("Running to next breakpoint…".into());
+        } else {
+            self.remember_call_line();
+            self.success_message = Some("Running…".into());
+        }
+        self.debug_resuming = Some(mode);
+    }
+
+    // Step over, step out and continue through a recording, which has no
+    // binary to resume: the recorded snapshots are searched for the next
+    // place the binary would have paused.
+    fn replay_resume(&mut self, mode: ResumeMode) {
+        let Some(depth) = self.debug_frame.as_ref().map(|f| f.call_stack.len()) else {
+            return;
+        };
+        let mut breakpoints = Vec::new();
+        for tab in &self.tabs {
+            let Some(ref path) = tab.recorded_path else {
+                continue;
+            };
+            let file = module_key(self.debug_source.as_ref(), path);
+            for line in &tab.breakpoints {
+                let cond = tab
+                    .breakpoint_conditions
+                    .get(line)
+                    .and_then(|c| condition::parse_expr(c).ok());
+                breakpoints.push((file.clone(), *line, cond));
+            }
+        }
+        let stop = |frame: &DebugFrame| {
+            let at_depth = match mode {
+                ResumeMode::Continue => false,
+                ResumeMode::StepOver => frame.call_stack.len() <= depth,
+                ResumeMode::StepOut => frame.call_stack.len() < depth,
+            };
+            let vars = frame
+                .scopes
+                .first()
+                .map(|s| s.vars.as_slice())
+                .unwrap_or_default();
+            at_depth
+                || breakpoints.iter().any(|(file, line, cond)| {
+                    *file == frame.source_file
+                        && *line == frame.source_line
+                        && cond.as_ref().is_none_or(|c| {
+                            matches!(watch::eval(c, vars), Ok(FractalValue::Bool(true)))
+                        })
+                })
+        };
+
+        if mode == ResumeMode::Continue {
+            self.cross_file_call_line = None;
+        } else {
+            self.remember_call_line();
+        }
+        let Some(ref mut session) = self.debug_session else {
+            return;
+        };
+        session.return_to_live();
+        let Some(frame) = session.step_until(stop) else {
+            self.success_message = Some("End of the recording.".into());
+            return;
+        };
+        self.var_view_window.push_output(&frame.buffered_output);
+        if let Some(ref err) = frame.error {
+            self.error_message = Some(format!("Debug fault: {err}"));
+        } else if frame.finished {
+            self.success_message = Some("Debug: program finished.".into());
+        } else {
+            self.success_message = Some(format!("Paused at line {}.", frame.source_line));
+        }
+        self.debug_frame = Some(frame);
+    }
+
+    fn resume_command(&self, max_depth: Option<usize>) -> String {
+        let mut command = match max_depth {
+            Some(depth) => format!("continue {depth}\n"),
+            None => String::from("continue\n"),
+        };
+        for (path, lines) in self.all_breakpoints() {
+            let file = module_key(self.debug_source.as_ref(), &path);
+            for line in lines {
+                command.push_str(&format!("{file}:{line}\n"));
+            }
+        }
+        command
+    }
+
+    // One `--break-if` argument per conditional breakpoint, keyed the same
+    // way as in `resume_command`.
+    fn break_if_args(&self, source: &PathBuf) -> Vec<String> {
+        let breakpoints = self.all_breakpoints();
+        let mut args = Vec::new();
+        for (path, conds) in self.all_breakpoint_conditions() {
+            let file = module_key(Some(source), &path);
+            for (line, cond) in conds {
+                if breakpoints.get(&path).is_some_and(|l| l.contains(&line)) {
+                    args.push(format!("{file}:{line}:{cond}"));
+                }
+            }
+        }
+        args
+    }
+
+    // Conditions are type-checked against the running debug session's program
+    // when this file is part of it; otherwise only the syntax is checked here
+    // and the compiler checks the rest when the next debug run starts.
+    fn check_breakpoint_condition(&self, line: usize, text: &str) -> Result<(), String> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let file = self
+            .tabs
+            .get(self.active_tab)
+            .and_then(|t| t.source_path())
+            .map(|p| module_key(self.debug_source.as_ref(), p));
+        match (&self.debug_root, file) {
+            (Some(root), Some(file)) if condition::has_file(root, &file) => {
+                condition::check(root, &file, line, text, true).map(|_| ())
+            }
+            _ => condition::parse_expr(text).map(|_| ()),
+        }
+    }
+
+    fn handle_breakpoint_dialog(&mut self, ctx: &egui::Context) {
+        match self.breakpoint_dialog.show(ctx, &self.theme) {
+            BreakpointAction::Pending => {}
+            BreakpointAction::Remove(line) => {
+                if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                    tab.breakpoints.remove(&line);
+                    tab.breakpoint_conditions.remove(&line);
+                }
+                self.breakpoint_dialog.visible = false;
+            }
+            BreakpointAction::Apply(line, text) => {
+                if let Err(e) = self.check_breakpoint_condition(line, &text) {
+                    self.breakpoint_dialog.set_error(e);
+                    return;
+                }
+                let Some(tab) = self.tabs.get_mut(self.active_tab) else {
+                    return;
+                };
+                tab.breakpoints.insert(line);
+                if text.is_empty() {
+                    tab.breakpoint_conditions.remove(&line);
+                } else {
+                    tab.breakpoint_conditions.insert(line, text);
+                }
+                self.breakpoint_dialog.visible = false;
+                if self.debug_session.is_some() && !self.debug_replay {
+                    self.success_message = Some(
+                        "Breakpoint condition saved - it applies from the next debug run.".into(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn poll_resume(&mut self) {
+        let paused = self
+            .debug_lock_path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .is_some_and(|c| c.is_empty());
+        let Some(ref mut session) = self.debug_session else {
+            self.debug_resuming = None;
+            return;
+        };
+        session.poll_file();
+        if !paused && !session.finished {
+            return;
+        }
+
+        let mode = self.debug_resuming.take();
+        let skipped = session.seek_latest();
+        let frame = session.current_frame();
+        self.var_view_window.push_output(&skipped);
+        if frame.finished || frame.error.is_some() {
+            self.step_debug();
+        } else {
+            self.success_message = Some(if mode == Some(ResumeMode::Continue) {
+                format!("Paused at breakpoint - line {}.", frame.source_line)
+            } else {
+                format!("Paused at line {}.", frame.source_line)
+            });
+            self.debug_frame = Some(frame);
+        }
+    }
+
+    fn time_travel(&mut self, action: TimelineAction) {
+        let Some(ref mut session) = self.debug_session else {
+            return;
+        };
+        let frame = match action {
+            TimelineAction::None => return,
+            TimelineAction::StepBack => session.step_back(),
+            TimelineAction::StepForward => session.step_forward_recorded(),
+            TimelineAction::JumpTo(index) => session.jump_to(index),
+            TimelineAction::ReturnToLive => {
+                session.return_to_live();
+                Some(session.current_frame())
+            }
+            TimelineAction::PreviousWrite(var) => {
+                let found = session.previous_write(&var);
+                if found.is_none() {
+                    self.success_message = Some(format!("No earlier write of `{var}` recorded."));
+                    return;
+                }
+                found
+            }
+        };
+        self.show_recorded_frame(frame);
+    }
+
+    fn show_recorded_frame(&mut self, frame: Option<DebugFrame>) {
+        let Some(frame) = frame else {
+            return;
+        };
+        self.cross_file_call_line = None;
+        if let Some(ref session) = self.debug_session {
+            self.success_message = Some(if session.is_rewound() {
+                format!(
+                    "Viewing recorded step {} - line {}.",
+                    session.position() + 1,
+                    frame.source_line
+                )
+            } else {
+                "Back at the live position.".into()
+            });
+        }
+        self.debug_frame = Some(frame);
+    }
+
     fn stop_debug_session(&mut self) {
-        if let Some(ref path) = self.debug_jsonl_path {
-            let _ = fs::remove_file(path);
+        // A replayed recording belongs to the user; only our own traces go.
+        match self.debug_jsonl_path {
+            Some(ref path) if !self.debug_replay => {
+                let _ = fs::remove_file(path);
+            }
+            _ => {}
         }
 
         if let Some(ref path) = self.debug_lock_path {
@@ -684,10 +1189,110 @@ impl FractalEditor {
         self.debug_jsonl_path = None;
         self.debug_lock_path = None;
         self.debug_binary_running = false;
+        self.debug_resuming = None;
+        self.debug_source = None;
+        self.debug_root = None;
+        self.debug_replay = false;
         self.var_view_window.clear_output();
         self.success_message = Some("Debug session stopped.".into());
     }
 
+    // Replays a `.debug.jsonl` file with the sources recorded in it, which
+    // open as tabs of their own so the files on disk are left alone.
+    fn open_recording(&mut self, path: &Path) {
+        let header = match snapshot::read_header(path) {
+            Ok(header) => header,
+            Err(e) => {
+                self.error_message = Some(format!("Cannot open recording: {e}"));
+                return;
+            }
+        };
+        let Some(main) = header.sources.first() else {
+            self.error_message = Some("Cannot open recording: it has no sources".into());
+            return;
+        };
+        let name = main.path.to_string_lossy().to_string();
+        let root = try_tokenize(&header.program, &name)
+            .ok_or_else(|| "invalid character in the recorded program".to_string())
+            .and_then(|tokens| parse_with_source(tokens, &name).map_err(|e| e.message));
+        let root = match root {
+            Ok(root) => root,
+            Err(e) => {
+                self.error_message = Some(format!("Cannot open recording: {e}"));
+                return;
+            }
+        };
+        let mut session = DebugSession::new(&root, path.to_path_buf());
+        session.poll_file();
+        if let Some(e) = session.format_error() {
+            self.error_message = Some(format!("Cannot open recording: {e}"));
+            return;
+        }
+
+        if self.debug_session.is_some() {
+            self.stop_debug_session();
+        }
+        let mut main_tab = None;
+        for source in &header.sources {
+            let existing = self
+                .tabs
+                .iter()
+                .position(|t| t.recorded_path.as_ref() == Some(&source.path));
+            let index = match existing {
+                Some(i) => {
+                    self.tabs[i].code = source.text.clone();
+                    self.tabs[i].last_saved_code = source.text.clone();
+                    i
+                }
+                None if self.tabs.len() == 1 && self.tabs[0].is_pristine_new() => {
+                    self.tabs[0] =
+                        Tab::from_recording(source.path.clone(), source.text.clone(), self.theme);
+                    0
+                }
+                None => {
+                    self.tabs.push(Tab::from_recording(
+                        source.path.clone(),
+                        source.text.clone(),
+                        self.theme,
+                    ));
+                    self.tabs.len() - 1
+                }
+            };
+            main_tab.get_or_insert(index);
+        }
+        self.active_tab = main_tab.unwrap_or(0);
+        self.docs_window.open = false;
+
+        self.debug_frame = Some(session.current_frame());
+        self.debug_session = Some(session);
+        self.debug_source = Some(main.path.clone());
+        self.debug_root = Some(root);
+        self.debug_jsonl_path = Some(path.to_path_buf());
+        self.debug_replay = true;
+        self.cross_file_call_line = None;
+        self.tree_view_window.open = true;
+        self.var_view_window.open = true;
+        self.var_view_window.clear_output();
+        self.error_message = None;
+        self.success_message = Some(
+            "Replaying recording - F5 to step, F7 back, F10 over, F8 to continue, F6 to close."
+                .into(),
+        );
+    }
+
+    fn save_recording(&mut self, dest: &Path) {
+        let Some(ref src) = self.debug_jsonl_path else {
+            return;
+        };
+        if src == dest {
+            return;
+        }
+        match fs::copy(src, dest) {
+            Ok(_) => self.success_message = Some(format!("Saved recording: {}", dest.display())),
+            Err(e) => self.error_message = Some(format!("Failed to save recording: {e}")),
+        }
+    }
+
     fn handle_close_confirm(&mut self, ctx: &egui::Context) {
         match self.close_confirm.show(ctx, &self.theme) {
             CloseConfirmAction::Cancel => {}
@@ -721,6 +1326,213 @@ impl FractalEditor {
         }
     }
 
+    /// Shows where a problem picked in the problems panel is: in the tab it
+    /// was found in, or in the imported module it points into.
+    fn open_folder(&mut self, root: PathBuf) {
+        self.success_message = Some(format!("Opened folder: {}", root.display()));
+        self.error_message = None;
+        self.workspace = Some(Workspace::new(root));
+    }
+
+    fn handle_explorer(&mut self, action: ExplorerAction) {
+        match action {
+            ExplorerAction::None => {}
+            ExplorerAction::Open(path) => {
+                if path.is_file() {
+                    self.open_file(&path);
+                    self.docs_window.open = false;
+                } else {
+                    self.error_message = Some(format!("No file at {}", path.display()));
+                }
+            }
+            ExplorerAction::Renamed(from, to) => {
+                let moved = |path: &Path| path.strip_prefix(&from).ok().map(|rest| to.join(rest));
+                for tab in &mut self.tabs {
+                    if let Some(path) = tab.current_file.as_deref().and_then(moved) {
+                        tab.current_file = Some(path);
+                    }
+                }
+                for path in &mut self.recent_files {
+                    if let Some(new) = moved(path) {
+                        *path = new;
+                    }
+                }
+                self.breakpoints = std::mem::take(&mut self.breakpoints)
+                    .into_iter()
+                    .map(|(path, lines)| (moved(&path).unwrap_or(path), lines))
+                    .collect();
+                self.breakpoint_conditions = std::mem::take(&mut self.breakpoint_conditions)
+                    .into_iter()
+                    .map(|(path, conds)| (moved(&path).unwrap_or(path), conds))
+                    .collect();
+                self.success_message = Some(format!("Renamed to {}", to.display()));
+            }
+            ExplorerAction::Deleted(path) => {
+                // Open tabs keep their text, unsaved, so nothing is lost by
+                // accident.
+                for tab in &mut self.tabs {
+                    if tab
+                        .current_file
+                        .as_ref()
+                        .is_some_and(|f| f.starts_with(&path))
+                    {
+                        tab.current_file = None;
+                        tab.last_saved_code.clear();
+                    }
+                }
+                self.recent_files.retain(|f| !f.starts_with(&path));
+                self.success_message = Some(format!("Deleted {}", path.display()));
+            }
+            ExplorerAction::ToggleGraph => self.import_graph.open = !self.import_graph.open,
+            ExplorerAction::Close => self.workspace = None,
+        }
+    }
+
+    fn go_to_problem(&mut self, target: ProblemTarget) {
+        let d = target.diagnostic;
+        let mut index = target.tab;
+        if !d.file.is_empty() {
+            let source = self.tabs.get(index).and_then(|t| t.source_path()).cloned();
+            let open = self.tabs.iter().position(|t| {
+                t.source_path()
+                    .is_some_and(|p| module_key(source.as_ref(), p) == d.file)
+            });
+            match open {
+                Some(i) => index = i,
+                None if Path::new(&d.path).is_file() => {
+                    self.open_file(&PathBuf::from(&d.path));
+                    index = self.active_tab;
+                }
+                None => {
+                    self.error_message =
+                        Some(format!("Open module `{}` to see this problem.", d.file));
+                    return;
+                }
+            }
+        }
+        if let Some(tab) = self.tabs.get_mut(index) {
+            self.active_tab = index;
+            self.docs_window.open = false;
+            if d.line > 0 {
+                tab.editor.jump_to_line(d.line);
+            }
+        }
+    }
+
+    // The active tab and where its cursor is, for the symbol commands.
+    fn symbol_cursor(&self) -> Option<(usize, usize)> {
+        let tab = self.tabs.get(self.active_tab)?;
+        Some((self.active_tab, tab.editor.cursor()?))
+    }
+
+    fn go_to_definition(&mut self, index: usize, cursor: usize) {
+        match references::definition(&self.tabs, index, cursor) {
+            Some((place, name)) => self.go_to_place(&place, &name),
+            None => {
+                self.error_message =
+                    Some("No definition found for the name under the cursor.".into())
+            }
+        }
+    }
+
+    // Shows `line` of `place`, opening its file when it is not open, with the
+    // cursor on `name`: at its column when the place has one.
+    fn go_to_place(&mut self, place: &Place, name: &str) {
+        match &place.file {
+            FileRef::Tab(i) if *i < self.tabs.len() => self.active_tab = *i,
+            FileRef::Tab(_) => return,
+            FileRef::Path(path) => {
+                self.open_file(path);
+                if self.error_message.is_some() {
+                    return;
+                }
+            }
+        }
+        self.docs_window.open = false;
+        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+            let col = match place.col {
+                0 => references::name_column(&tab.code, place.line, name),
+                col => col - 1,
+            };
+            tab.editor.jump_to(place.line, col);
+        }
+    }
+
+    fn find_references(&mut self, index: usize, cursor: usize) {
+        match references::find(&self.tabs, index, cursor) {
+            Ok(found) => self.references_window.show_found(found),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    fn rename_symbol(&mut self, index: usize, cursor: usize) {
+        let found = references::find(&self.tabs, index, cursor)
+            .and_then(|found| references::check_rename(&self.tabs, &found).map(|_| found));
+        match found {
+            Ok(found) => self.rename_dialog.open(found),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    fn replace_in_files(&mut self, changes: Vec<FileChange>) {
+        // Like a rename, files that are not open are opened, so the replace
+        // can be reviewed and saved or undone with them.
+        let active = self.active_tab;
+        let (mut files, mut count, mut skipped) = (0, 0, Vec::new());
+        for change in changes {
+            self.open_file(&change.path);
+            let tab = self
+                .tabs
+                .iter_mut()
+                .find(|t| t.current_file.as_deref() == Some(change.path.as_path()));
+            match tab {
+                Some(tab) if tab.code == change.before => {
+                    tab.code = change.after;
+                    files += 1;
+                    count += change.count;
+                }
+                _ => skipped.push(
+                    change
+                        .path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                ),
+            }
+        }
+        self.active_tab = active;
+        self.success_message = Some(format!(
+            "Replaced {count} occurrence(s) in {files} file(s)."
+        ));
+        if !skipped.is_empty() {
+            self.error_message = Some(format!(
+                "Skipped {}: changed since the preview.",
+                skipped.join(", ")
+            ));
+        }
+    }
+
+    fn handle_rename_dialog(&mut self, ctx: &egui::Context) {
+        let RenameAction::Apply(found, new_name) = self.rename_dialog.show(ctx, &self.theme) else {
+            return;
+        };
+        // Modules that are not open are opened, so the rename can be reviewed
+        // and saved or undone with them.
+        let active = self.active_tab;
+        for o in &found.occurrences {
+            if let FileRef::Path(path) = &o.place.file {
+                self.open_file(path);
+            }
+        }
+        self.active_tab = active;
+        let count = references::rename(&mut self.tabs, &found, &new_name);
+        self.references_window.open = false;
+        self.success_message = Some(format!(
+            "Renamed `{}` to `{new_name}` in {count} place(s).",
+            found.name
+        ));
+    }
+
     fn show_status_bar(&mut self, ctx: &egui::Context) {
         let t = self.theme;
         egui::TopBottomPanel::bottom("status_bar")
@@ -824,6 +1636,35 @@ impl FractalEditor {
                             .size(11.0)
                             .color(t.status_bar_fg),
                         );
+                        let count = |severity| {
+                            self.tabs
+                                .iter()
+                                .map(|tab| tab.live_check.count(severity))
+                                .sum::<usize>()
+                        };
+                        let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+                        ui.separator();
+                        let problems = ui
+                            .add(
+                                egui::Label::new(
+                                    egui::RichText::new(format!(
+                                        "{} {errors}  {} {warnings}",
+                                        ic::ERROR,
+                                        ic::PROBLEMS
+                                    ))
+                                    .size(11.0)
+                                    .color(if errors > 0 {
+                                        t.terminal_error
+                                    } else {
+                                        t.status_bar_fg
+                                    }),
+                                )
+                                .sense(egui::Sense::click()),
+                            )
+                            .on_hover_text("Problems (Ctrl+Shift+M)");
+                        if problems.clicked() {
+                            self.problems_window.open = !self.problems_window.open;
+                        }
                         if self.search_bar.visible && !self.search_bar.query.is_empty() {
                             ui.separator();
                             ui.label(
@@ -855,14 +1696,30 @@ impl eframe::App for FractalEditor {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if let Some(path) = self.pending_debug_source.take() {
             self.tabs[self.active_tab].is_running = true;
-            self.launch_compile(path, true, ctx);
+            self.launch_compile(path, BuildMode::Debug, ctx);
         }
 
         self.poll_compiler_output(ctx);
+        self.poll_profile(ctx);
+        for tab in &mut self.tabs {
+            tab.sync_breakpoints();
+            let source_file = tab
+                .source_path()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            tab.live_check.update(&tab.code, &source_file, ctx);
+        }
 
         if self.debug_binary_running {
             if let Some(ref mut session) = self.debug_session {
                 session.poll_file();
+                if let Some(e) = session.format_error().map(str::to_string) {
+                    self.stop_debug_session();
+                    self.error_message = Some(format!("Cannot debug: {e}"));
+                }
+            }
+            if self.debug_resuming.is_some() {
+                self.poll_resume();
             }
             ctx.request_repaint_after(std::time::Duration::from_millis(100));
         }
@@ -922,33 +1779,63 @@ impl eframe::App for FractalEditor {
             .get(self.active_tab)
             .and_then(|t| t.current_file.as_ref())
             .cloned();
-        let docs_open = self.docs_window.open;
-        let is_debug_running = self.debug_session.is_some();
-        let tree_view_open = self.tree_view_window.open;
-        let var_view_open = self.var_view_window.open;
-
-        let action = show_menu_bar(
-            ctx,
-            &mut self.menu_state,
-            current_file.as_ref(),
+        let view = MenuBarView {
+            current_file: current_file.as_ref(),
+            recent_files: &self.recent_files,
             is_running,
-            is_debug_running,
-            docs_open,
-            tree_view_open,
-            var_view_open,
-            &self.theme,
-            &self.recent_files,
-            self.search_bar.visible,
-        );
+            is_debugging: self.debug_session.is_some(),
+            search_bar_visible: self.search_bar.visible,
+            docs_open: self.docs_window.open,
+            tree_view_open: self.tree_view_window.open,
+            var_view_open: self.var_view_window.open,
+            heap_view_open: self.heap_view_window.open,
+            profile_view_open: self.profile_window.open,
+            problems_open: self.problems_window.open,
+            outline_open: self.outline_panel.open,
+            explorer_open: self.workspace.as_ref().is_some_and(|w| w.open),
+            import_graph_open: self.import_graph.open,
+        };
+        let action = show_menu_bar(ctx, &mut self.menu_state, &view, &self.theme);
 
         match action {
-            MenuAction::OpenDialog => self.file_dialog.open_for_open(),
+            MenuAction::OpenDialog => {
+                self.recording_dialog = false;
+                self.file_dialog.open_for_open();
+            }
+            MenuAction::OpenFolder => {
+                self.recording_dialog = false;
+                self.file_dialog.open_for_folder();
+            }
+            MenuAction::OpenRecording => {
+                self.recording_dialog = true;
+                self.file_dialog.open_for_open();
+            }
+            MenuAction::SaveRecording => match self.debug_jsonl_path {
+                Some(ref path) => {
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "session.debug.jsonl".into());
+                    let dir = self
+                        .debug_source
+                        .as_ref()
+                        .and_then(|p| p.parent())
+                        .map(|p| p.to_path_buf());
+                    self.recording_dialog = true;
+                    self.file_dialog.open_for_save_in(&name, dir.as_deref());
+                }
+                None => {
+                    self.error_message =
+                        Some("No debug session to save - start one with F5 first.".into());
+                }
+            },
             MenuAction::SaveDialog => {
+                self.recording_dialog = false;
                 let (name, dir) = self
                     .tabs
                     .get(self.active_tab)
                     .map(|t| {
-                        if let Some(ref path) = t.current_file {
+                        if let Some(path) = t.source_path() {
                             let fname = path
                                 .file_name()
                                 .map(|n| n.to_string_lossy().to_string())
@@ -977,30 +1864,48 @@ impl eframe::App for FractalEditor {
                 self.docs_window.open = false;
             }
             MenuAction::Run => self.run_code(ctx),
+            MenuAction::Profile => self.run_profile(ctx),
             MenuAction::StepRun => {
                 if self.debug_session.is_some() {
-                    if let Some(ref frame) = self.debug_frame {
-                        let active_stem = self
-                            .tabs
-                            .get(self.active_tab)
-                            .and_then(|t| t.current_file.as_ref())
-                            .and_then(|p| p.file_stem())
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("");
-                        let currently_in_own_file =
-                            frame.source_file.is_empty() || frame.source_file == active_stem;
-                        if currently_in_own_file && frame.source_line > 0 {
-                            self.cross_file_call_line = Some(frame.source_line);
-                        }
-                    }
+                    self.remember_call_line();
                     self.step_debug();
                 } else {
                     self.run_debug();
                 }
             }
+            MenuAction::StepInto => {
+                self.remember_call_line();
+                self.step_debug();
+            }
+            MenuAction::StepBack => self.time_travel(TimelineAction::StepBack),
+            MenuAction::StepOver => self.resume_debug(ResumeMode::StepOver),
+            MenuAction::StepOut => self.resume_debug(ResumeMode::StepOut),
+            MenuAction::StepContinue => self.resume_debug(ResumeMode::Continue),
             MenuAction::StepStop => self.stop_debug_session(),
             MenuAction::ToggleTreeView => self.tree_view_window.open = !self.tree_view_window.open,
             MenuAction::ToggleVarView => self.var_view_window.open = !self.var_view_window.open,
+            MenuAction::ToggleHeapView => self.heap_view_window.open = !self.heap_view_window.open,
+            MenuAction::ToggleProfileView => self.profile_window.open = !self.profile_window.open,
+            MenuAction::ToggleProblems => self.problems_window.open = !self.problems_window.open,
+            MenuAction::ToggleOutline => self.outline_panel.toggle(),
+            MenuAction::ToggleExplorer => match self.workspace.as_mut() {
+                Some(workspace) => workspace.open = !workspace.open,
+                None => {
+                    self.recording_dialog = false;
+                    self.file_dialog.open_for_folder();
+                }
+            },
+            MenuAction::ToggleImportGraph => self.import_graph.open = !self.import_graph.open,
+            MenuAction::FoldAll => {
+                if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                    tab.editor.fold_all();
+                }
+            }
+            MenuAction::UnfoldAll => {
+                if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                    tab.editor.unfold_all();
+                }
+            }
             MenuAction::ToggleDocs => self.docs_window.open = !self.docs_window.open,
             MenuAction::OpenSettings => self.settings_panel.open(),
             MenuAction::OpenRecent(path) => {
@@ -1027,6 +1932,30 @@ impl eframe::App for FractalEditor {
                     self.search_bar.focus_replace = true;
                 }
             }
+            MenuAction::FindInFiles => {
+                let query = self.search_bar.query.clone();
+                self.find_in_files.open_with(&query);
+            }
+            MenuAction::GoToDefinition => {
+                if let Some((index, cursor)) = self.symbol_cursor() {
+                    self.go_to_definition(index, cursor);
+                }
+            }
+            MenuAction::FindReferences => {
+                if let Some((index, cursor)) = self.symbol_cursor() {
+                    self.find_references(index, cursor);
+                }
+            }
+            MenuAction::RenameSymbol => {
+                if let Some((index, cursor)) = self.symbol_cursor() {
+                    self.rename_symbol(index, cursor);
+                }
+            }
+            MenuAction::FormatDocument => {
+                if self.active_tab < self.tabs.len() {
+                    self.format_document();
+                }
+            }
             MenuAction::None => {}
         }
 
@@ -1065,6 +1994,8 @@ impl eframe::App for FractalEditor {
         }
 
         self.handle_close_confirm(ctx);
+        self.handle_breakpoint_dialog(ctx);
+        self.handle_rename_dialog(ctx);
         self.handle_quit_confirm(ctx);
 
         if let Some(ref msg) = self.recent_file_error.clone() {
@@ -1097,20 +2028,31 @@ impl eframe::App for FractalEditor {
 
         self.file_dialog.show(ctx);
         if let Some(result) = self.file_dialog.result.take() {
-            match result.mode {
-                FileDialogMode::Open => {
-                    self.open_file(&result.path);
-                    self.docs_window.open = false;
+            if std::mem::take(&mut self.recording_dialog) {
+                match result.mode {
+                    FileDialogMode::Open => self.open_recording(&result.path),
+                    FileDialogMode::Save => self.save_recording(&result.path),
+                    FileDialogMode::Folder => {}
                 }
-                FileDialogMode::Save => {
-                    let run_after = self.pending_run_after_save;
-                    self.pending_run_after_save = false;
-                    self.save_file(&result.path);
-                    if run_after {
-                        self.run_code(ctx);
+            } else {
+                match result.mode {
+                    FileDialogMode::Open => {
+                        self.open_file(&result.path);
+                        self.docs_window.open = false;
+                    }
+                    FileDialogMode::Folder => self.open_folder(result.path),
+                    FileDialogMode::Save => {
+                        let run_after = self.pending_run_after_save;
+                        self.pending_run_after_save = false;
+                        self.save_file(&result.path);
+                        if run_after {
+                            self.run_code(ctx);
+                        }
                     }
                 }
             }
+        } else if !self.file_dialog.visible {
+            self.recording_dialog = false;
         }
 
         if self.settings_panel.show(ctx, &mut self.profile) {
@@ -1121,6 +2063,18 @@ impl eframe::App for FractalEditor {
         self.show_status_bar(ctx);
         self.terminal.show(ctx);
 
+        if let (Some(session), Some(frame)) = (&self.debug_session, &self.debug_frame) {
+            let action = self.timeline_bar.show(
+                ctx,
+                frame,
+                session.position(),
+                session.live_position(),
+                session.is_rewound(),
+                &self.theme,
+            );
+            self.time_travel(action);
+        }
+
         let active_node_id = self
             .debug_frame
             .as_ref()
@@ -1156,21 +2110,87 @@ impl eframe::App for FractalEditor {
                 finished: false,
                 error: None,
                 buffered_output: String::new(),
+                previous_values: Default::default(),
             };
             let frame_ref: &DebugFrame = self.debug_frame.as_ref().unwrap_or(&placeholder);
             let theme = self.theme;
-            self.var_view_window.show(ctx, frame_ref, &theme);
+            let root = self.debug_root.as_ref();
+            let watch_line = if frame_ref.finished {
+                usize::MAX
+            } else {
+                frame_ref.source_line
+            };
+            let check_watch = |text: &str| match root {
+                Some(root) => {
+                    condition::check(root, &frame_ref.source_file, watch_line, text, false)
+                }
+                None => condition::parse_expr(text),
+            };
+            self.var_view_window
+                .show(ctx, frame_ref, &theme, &check_watch);
+        }
+
+        if self.heap_view_window.open {
+            let theme = self.theme;
+            self.heap_view_window
+                .show(ctx, self.debug_frame.as_ref(), &theme);
         }
 
-        let debug_line = if let Some(ref frame) = self.debug_frame {
-            let active_stem = self
-                .tabs
-                .get(self.active_tab)
-                .and_then(|t| t.current_file.as_ref())
-                .and_then(|p| p.file_stem())
-                .and_then(|s| s.to_str())
-                .unwrap_or("");
+        let theme = self.theme;
+        self.profile_window.show(ctx, &theme);
+        if let Some(target) = self.problems_window.show(ctx, &self.tabs, &theme) {
+            self.go_to_problem(target);
+        }
+        if let Some((place, name)) = self.references_window.show(ctx, &self.tabs, &theme) {
+            self.go_to_place(&place, &name);
+        }
+        let search_root = self.workspace.as_ref().map(|w| w.root.clone());
+        match self
+            .find_in_files
+            .show(ctx, search_root.as_deref(), &self.tabs, &theme)
+        {
+            FindAction::None => {}
+            FindAction::Open(path, line, col) => {
+                self.open_file(&path);
+                self.docs_window.open = false;
+                if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                    tab.editor.jump_to(line, col);
+                }
+            }
+            FindAction::Apply(changes) => self.replace_in_files(changes),
+        }
+
+        let active_file = self
+            .tabs
+            .get(self.active_tab)
+            .and_then(|t| t.current_file.clone());
+        let graph_root = self.workspace.as_ref().map(|w| w.root.clone());
+        if let Some(path) =
+            self.import_graph
+                .show(ctx, graph_root.as_deref(), active_file.as_deref(), &theme)
+        {
+            self.open_file(&path);
+            self.docs_window.open = false;
+        }
+        if let Some(workspace) = self.workspace.as_mut() {
+            let action =
+                workspace.show(ctx, active_file.as_deref(), self.import_graph.open, &theme);
+            self.handle_explorer(action);
+        }
+
+        let (program, code) = match self.tabs.get(self.active_tab) {
+            Some(tab) => (tab.live_check.program.as_ref(), tab.code.as_str()),
+            None => (None, ""),
+        };
+        if let Some(line) = self.outline_panel.show(ctx, program, code, &theme) {
+            if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                self.docs_window.open = false;
+                tab.editor.jump_to_line(line);
+            }
+        }
 
+        let active_stem = self.active_stem();
+        let debug_line = if let Some(ref frame) = self.debug_frame {
             let in_foreign_file = !frame.source_file.is_empty() && frame.source_file != active_stem;
 
             if in_foreign_file {
@@ -1192,6 +2212,7 @@ impl eframe::App for FractalEditor {
             self.cross_file_call_line = None;
             None
         };
+        let mut definition_click = None;
         egui::CentralPanel::default()
             .frame(egui::Frame::new().fill(self.theme.editor_bg))
             .show(ctx, |ui| {
@@ -1207,17 +2228,58 @@ impl eframe::App for FractalEditor {
                         EmptyStateAction::None => {}
                     }
                 } else if let Some(tab) = self.tabs.get_mut(self.active_tab) {
-                    let fs = self.profile.font_size;
-                    let ln = self.profile.show_line_numbers;
-                    let sel = if self.search_bar.visible {
-                        self.search_bar.current_match_byte_range
-                    } else {
-                        None
+                    let view = EditorView {
+                        tab_id: tab.id,
+                        font_size: self.profile.font_size,
+                        show_line_numbers: self.profile.show_line_numbers,
+                        select_range: if self.search_bar.visible {
+                            self.search_bar.current_match_byte_range
+                        } else {
+                            None
+                        },
+                        debug_line,
+                        conditions: &tab.breakpoint_conditions,
+                        heat: &tab.line_heat,
+                        diagnostics: &tab.live_check.diagnostics,
+                        symbols: &tab.live_check.symbols,
+                        program: tab
+                            .live_check
+                            .program
+                            .as_ref()
+                            .filter(|_| tab.live_check.is_current(&tab.code)),
                     };
-                    tab.editor
-                        .show_with_id(ui, &mut tab.code, tab.id, fs, ln, sel, debug_line);
+                    let edit_condition =
+                        tab.editor
+                            .show_with_id(ui, &mut tab.code, &mut tab.breakpoints, &view);
+                    definition_click = tab.editor.take_definition_click();
+                    if tab.is_dirty() {
+                        tab.line_heat.clear();
+                    }
+                    let breakpoints = &tab.breakpoints;
+                    tab.breakpoint_conditions
+                        .retain(|line, _| breakpoints.contains(line));
+                    if let Some(line) = edit_condition {
+                        let cond = tab.breakpoint_conditions.get(&line);
+                        self.breakpoint_dialog
+                            .open(line, cond.map(String::as_str).unwrap_or(""));
+                    }
                 }
             });
+        if let Some(cursor) = definition_click {
+            self.go_to_definition(self.active_tab, cursor);
+        }
+    }
+}
+
+// How the debug runtime names the file of `path`: empty for the program being
+// debugged and the module name for anything it imports.
+fn module_key(source: Option<&PathBuf>, path: &PathBuf) -> String {
+    if source == Some(path) {
+        String::new()
+    } else {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
     }
 }
 
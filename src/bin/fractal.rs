@@ -8,7 +8,8 @@ fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
 
     if args.is_empty() {
-        eprintln!("Usage: fractal [--debug] <path/to/file.fr>");
+        eprintln!("Usage: fractal [-O0 | -O1] [--debug] <path/to/file.fr>");
+        eprintln!("       fractal fmt [--check] <path/to/file.fr>");
         process::exit(1);
     }
 
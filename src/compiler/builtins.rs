@@ -48,6 +48,12 @@ pub static ALL_BUILTINS: &[BuiltinDef] = &[
         ret: BType::Void,
         codegen: CodegenRule::Input,
     },
+    BuiltinDef {
+        name: "eof",
+        params: &[],
+        ret: BType::Boolean,
+        codegen: CodegenRule::Template("__FRACTAL_EOF.load(std::sync::atomic::Ordering::SeqCst)"),
+    },
     BuiltinDef {
         name: "append",
         params: &[BType::Any, BType::Any],
@@ -146,4 +152,60 @@ pub static ALL_BUILTINS: &[BuiltinDef] = &[
         ret: BType::ListOfChar,
         codegen: CodegenRule::Template("{0}.to_string().chars().collect::<Vec<char>>()"),
     },
+    BuiltinDef {
+        name: "random_int",
+        params: &[BType::Int, BType::Int],
+        ret: BType::Int,
+        codegen: CodegenRule::Template("__fractal_random_int({0}, {1})"),
+    },
+    BuiltinDef {
+        name: "random_float",
+        params: &[],
+        ret: BType::Float,
+        codegen: CodegenRule::Template("__fractal_random_float()"),
+    },
+    BuiltinDef {
+        name: "seed",
+        params: &[BType::Int],
+        ret: BType::Void,
+        codegen: CodegenRule::Template("__fractal_seed({0})"),
+    },
+    BuiltinDef {
+        name: "now_ms",
+        params: &[],
+        ret: BType::Int,
+        codegen: CodegenRule::Template("__fractal_now_ms()"),
+    },
+    BuiltinDef {
+        name: "sleep_ms",
+        params: &[BType::Int],
+        ret: BType::Void,
+        codegen: CodegenRule::Template(
+            "std::thread::sleep(std::time::Duration::from_millis(({0}).max(0) as u64))",
+        ),
+    },
+    BuiltinDef {
+        name: "map",
+        params: &[BType::Any, BType::Any],
+        ret: BType::Any,
+        codegen: CodegenRule::Template(
+            "{ let __f = {1}; {0}.iter().cloned().map(|__v| (__f.0)(__v)).collect::<Vec<_>>() }",
+        ),
+    },
+    BuiltinDef {
+        name: "filter",
+        params: &[BType::Any, BType::Any],
+        ret: BType::Any,
+        codegen: CodegenRule::Template(
+            "{ let __f = {1}; {0}.iter().cloned().filter(|__v| (__f.0)(__v.clone())).collect::<Vec<_>>() }",
+        ),
+    },
+    BuiltinDef {
+        name: "reduce",
+        params: &[BType::Any, BType::Any, BType::Any],
+        ret: BType::Any,
+        codegen: CodegenRule::Template(
+            "{ let __f = {1}; {0}.iter().cloned().fold({2}, |__acc, __v| (__f.0)(__acc, __v)) }",
+        ),
+    },
 ];
@@ -1,7 +1,12 @@
+use crate::compiler::condition::BreakCondition;
+use crate::compiler::constfold::{fold, ConstValue};
+use crate::compiler::optimizer::collect_names;
 use crate::compiler::parser::{
     AccessStep, AddOp, AssignOp, CmpOp, MulOp, ParseNode, ShiftOp, UnOp,
 };
+use crate::compiler::profile::PROFILE_VERSION;
 use crate::compiler::semanter::{SemType, SemanticResult, SymbolKind};
+use crate::compiler::snapshot::{Header, SNAPSHOT_VERSION};
 use std::collections::HashMap;
 
 pub fn generate(root: &ParseNode, sem: &SemanticResult) -> String {
@@ -10,15 +15,41 @@ pub fn generate(root: &ParseNode, sem: &SemanticResult) -> String {
     cg.buf
 }
 
-pub fn generate_debug(root: &ParseNode, sem: &SemanticResult, debug_out_path: &str) -> String {
+/// Like `generate`, with a runtime that writes `header` and then a snapshot
+/// per statement to `debug_out_path`, and pauses as the editor directs.
+pub fn generate_debug(
+    root: &ParseNode,
+    sem: &SemanticResult,
+    debug_out_path: &str,
+    header: &Header,
+    conditions: &[BreakCondition],
+) -> String {
     let mut cg = CodeGen::new(sem);
     cg.debug_mode = true;
+    cg.debug_header = serde_json::to_string(header).unwrap_or_default();
+    cg.debug_conditions = conditions
+        .iter()
+        .map(|c| ((c.file.clone(), c.line), c.expr.clone()))
+        .collect();
     cg.debug_path = debug_out_path.replace('\\', "\\\\").replace('"', "\\\"");
     cg.debug_current_func = "<main>".into();
     cg.gen_root(root);
     cg.buf
 }
 
+/// Like `generate`, with the statement and call hooks of a debug build
+/// counting hits and time instead of recording snapshots. The program writes
+/// a `profile::Profile` to `profile_out_path` when it ends, without pausing.
+pub fn generate_profile(root: &ParseNode, sem: &SemanticResult, profile_out_path: &str) -> String {
+    let mut cg = CodeGen::new(sem);
+    cg.debug_mode = true;
+    cg.profile_mode = true;
+    cg.debug_path = profile_out_path.replace('\\', "\\\\").replace('"', "\\\"");
+    cg.debug_current_func = "<main>".into();
+    cg.gen_root(root);
+    cg.buf
+}
+
 struct CodeGen {
     buf: String,
     indent: usize,
@@ -36,12 +67,18 @@ struct CodeGen {
     list_params: std::collections::HashSet<String>,
     list_param_elem_types: HashMap<String, SemType>,
     func_return_types: HashMap<String, SemType>,
+    func_params: HashMap<String, Vec<SemType>>,
     hoist_buf: Vec<String>,
     hoist_counter: usize,
     local_var_types: HashMap<String, SemType>,
+    const_values: HashMap<String, ConstValue>,
 
     debug_mode: bool,
+    // A debug build whose hooks profile rather than snapshot; `debug_path`
+    // is then where the profile goes.
+    profile_mode: bool,
     debug_path: String,
+    debug_header: String,
 
     debug_visible_vars: Vec<(String, String)>,
     debug_current_func: String,
@@ -49,6 +86,8 @@ struct CodeGen {
     debug_module_prefix: String,
 
     debug_current_file: String,
+
+    debug_conditions: HashMap<(String, usize), ParseNode>,
 }
 
 impl CodeGen {
@@ -56,10 +95,11 @@ impl CodeGen {
         let mut var_types = HashMap::new();
         let mut struct_fields = HashMap::new();
         let mut func_return_types = HashMap::new();
+        let mut func_params = HashMap::new();
 
         for sym in &sem.symbol_table {
             match &sym.kind {
-                SymbolKind::Variable => {
+                SymbolKind::Variable | SymbolKind::Constant { .. } => {
                     if sym.origin.starts_with("param:") {
                         continue;
                     }
@@ -70,8 +110,9 @@ impl CodeGen {
                 SymbolKind::Struct { fields } => {
                     struct_fields.insert(sym.name.clone(), fields.clone());
                 }
-                SymbolKind::Function { .. } => {
+                SymbolKind::Function { params } => {
                     func_return_types.insert(sym.name.clone(), sym.sem_type.clone());
+                    func_params.insert(sym.name.clone(), params.clone());
                 }
             }
         }
@@ -92,16 +133,21 @@ impl CodeGen {
             array_param_elem_types: HashMap::new(),
             list_param_elem_types: HashMap::new(),
             func_return_types,
+            func_params,
             hoist_buf: Vec::new(),
             hoist_counter: 0,
             local_var_types: HashMap::new(),
+            const_values: HashMap::new(),
             debug_mode: false,
+            profile_mode: false,
             debug_path: String::new(),
+            debug_header: String::new(),
 
             debug_visible_vars: Vec::new(),
             debug_current_func: String::new(),
             debug_module_prefix: String::new(),
             debug_current_file: String::new(),
+            debug_conditions: HashMap::new(),
         }
     }
 
@@ -136,12 +182,20 @@ impl CodeGen {
             ParseNode::TypeChar(_) => "char".into(),
             ParseNode::TypeBoolean(_) => "bool".into(),
             ParseNode::TypeVoid(_) => "()".into(),
-            ParseNode::TypeArray { elem, size, .. } => match elem.as_ref() {
-                ParseNode::TypeStruct { name, .. } => {
-                    format!("[Option<Box<{}>>; {}]", escape_struct_name(name), size)
+            ParseNode::TypeArray {
+                elem,
+                size,
+                size_const,
+                ..
+            } => {
+                let size = array_size_str(*size, size_const.as_deref());
+                match elem.as_ref() {
+                    ParseNode::TypeStruct { name, .. } => {
+                        format!("[Option<Box<{}>>; {}]", escape_struct_name(name), size)
+                    }
+                    _ => format!("[{}; {}]", self.type_str(elem), size),
                 }
-                _ => format!("[{}; {}]", self.type_str(elem), size),
-            },
+            }
             ParseNode::TypeList { elem, .. } => match elem.as_ref() {
                 ParseNode::TypeStruct { name, .. } => {
                     format!("Vec<Option<Box<{}>>>", escape_struct_name(name))
@@ -149,6 +203,7 @@ impl CodeGen {
                 _ => format!("Vec<{}>", self.type_str(elem)),
             },
             ParseNode::TypeStruct { name, .. } => escape_struct_name(name),
+            ParseNode::TypeFunc { .. } => sem_type_str(&self.parse_node_to_sem_type(node)),
             _ => "/* ? */".into(),
         }
     }
@@ -178,14 +233,23 @@ impl CodeGen {
             ParseNode::TypeFloat(_) => "0.0_f64".into(),
             ParseNode::TypeChar(_) => "'\\0'".into(),
             ParseNode::TypeBoolean(_) => "false".into(),
-            ParseNode::TypeArray { elem, size, .. } => match elem.as_ref() {
+            ParseNode::TypeArray {
+                elem,
+                size,
+                size_const,
+                ..
+            } => match elem.as_ref() {
                 ParseNode::TypeStruct { name, .. } => {
                     format!(
                         "std::array::from_fn(|_| Some(Box::new({}::default())))",
                         escape_struct_name(name)
                     )
                 }
-                _ => format!("[{}; {}]", self.zero_val(elem), size),
+                _ => format!(
+                    "[{}; {}]",
+                    self.zero_val(elem),
+                    array_size_str(*size, size_const.as_deref())
+                ),
             },
             ParseNode::TypeList { .. } => "Vec::new()".into(),
             ParseNode::TypeStruct { name, .. } => {
@@ -207,7 +271,7 @@ impl CodeGen {
             }
         }
 
-        self.line("#![allow(unused_variables, unused_mut, dead_code, non_snake_case, unused_imports, unreachable_patterns)]");
+        self.line("#![allow(unused_variables, unused_mut, dead_code, non_snake_case, non_upper_case_globals, unused_imports, unreachable_patterns)]");
         self.line("use std::io::{self, BufRead, Write};");
         self.blank();
         self.line("fn __fractal_fmt_float(v: f64) -> String {");
@@ -218,12 +282,31 @@ impl CodeGen {
         self.line("    }");
         self.line("}");
         self.blank();
-        if self.debug_mode {
+        self.emit_func_runtime();
+        self.blank();
+        self.emit_input_runtime();
+        self.blank();
+        self.emit_random_runtime();
+        self.blank();
+        if self.profile_mode {
+            self.emit_profile_runtime();
+            self.blank();
+        } else if self.debug_mode {
             self.emit_debug_runtime();
             self.blank();
         }
 
-        let (defs, stmts): (Vec<_>, Vec<_>) = items.iter().partition(|n| {
+        let (consts, items): (Vec<_>, Vec<_>) = items
+            .iter()
+            .partition(|n| matches!(n, ParseNode::ConstDecl { .. }));
+        for c in &consts {
+            self.gen_const_decl(c, false);
+        }
+        if !consts.is_empty() {
+            self.blank();
+        }
+
+        let (defs, stmts): (Vec<_>, Vec<_>) = items.into_iter().partition(|n| {
             matches!(
                 n,
                 ParseNode::FuncDef { .. } | ParseNode::StructDef { .. } | ParseNode::Module { .. }
@@ -237,7 +320,9 @@ impl CodeGen {
 
         self.line("fn main() {");
         self.indent();
-        if self.debug_mode {
+        if self.profile_mode {
+            self.line("let __fractal_frame = __fractal_profile_enter(\"<main>\");");
+        } else if self.debug_mode {
             self.line("__fractal_debug_init();");
             self.line("let __fractal_lock_path = std::env::var(\"FRACTAL_DEBUG_LOCK\").unwrap_or_default();");
             self.line("if !__fractal_lock_path.is_empty() { __FRACTAL_DBG_LOCK.set(__fractal_lock_path).ok(); }");
@@ -258,7 +343,7 @@ impl CodeGen {
                 }
             }
         }
-        if self.debug_mode {
+        if self.debug_mode && !self.profile_mode {
             let func = self.debug_current_func.clone();
             let vars_code = self.build_vars_json_code();
             let finished_line = format!(
@@ -306,6 +391,33 @@ impl CodeGen {
         }
         self.dedent();
         self.line("}");
+
+        if self.debug_mode && !self.profile_mode {
+            self.line(&format!(
+                "impl __FractalDebugValue for {} {{",
+                escape_struct_name(name)
+            ));
+            self.indent();
+            self.line("fn __dbg_json(&self, o: &mut String) {");
+            self.indent();
+            self.line(&format!(
+                r#"o.push_str("{{\"struct\":\"{}{}\",\"fields\":[");"#,
+                self.debug_module_prefix, name
+            ));
+            for (i, f) in fields.iter().enumerate() {
+                if let ParseNode::Field { name: fname, .. } = f {
+                    let sep = if i > 0 { "," } else { "" };
+                    self.line(&format!(
+                        r#"o.push_str("{sep}[\"{fname}\","); self.{fname}.__dbg_json(o); o.push(']');"#
+                    ));
+                }
+            }
+            self.line(r#"o.push_str("]}");"#);
+            self.dedent();
+            self.line("}");
+            self.dedent();
+            self.line("}");
+        }
     }
 
     fn gen_funcdef(
@@ -335,6 +447,7 @@ impl CodeGen {
             if let ParseNode::Param {
                 data_type,
                 name: pname,
+                ..
             } = p
             {
                 if let ParseNode::TypeStruct { name: sname, .. } = data_type.as_ref() {
@@ -358,6 +471,10 @@ impl CodeGen {
                         self.list_param_elem_types.insert(pname.clone(), elem_sem);
                     }
                 }
+                if matches!(data_type.as_ref(), ParseNode::TypeFunc { .. }) {
+                    let sem = self.parse_node_to_sem_type(data_type);
+                    self.local_var_types.insert(pname.clone(), sem);
+                }
             }
         }
 
@@ -367,21 +484,30 @@ impl CodeGen {
                 ParseNode::Param {
                     data_type,
                     name: pname,
+                    ..
                 } => {
                     let ty = match data_type.as_ref() {
                         ParseNode::TypeStruct { name: sname, .. } => {
                             format!("&mut Option<Box<{}>>", escape_struct_name(sname))
                         }
-                        ParseNode::TypeArray { elem, size, .. } => match elem.as_ref() {
-                            ParseNode::TypeStruct { name: sname, .. } => {
-                                format!(
-                                    "&mut [Option<Box<{}>>; {}]",
-                                    escape_struct_name(sname),
-                                    size
-                                )
+                        ParseNode::TypeArray {
+                            elem,
+                            size,
+                            size_const,
+                            ..
+                        } => {
+                            let size = array_size_str(*size, size_const.as_deref());
+                            match elem.as_ref() {
+                                ParseNode::TypeStruct { name: sname, .. } => {
+                                    format!(
+                                        "&mut [Option<Box<{}>>; {}]",
+                                        escape_struct_name(sname),
+                                        size
+                                    )
+                                }
+                                _ => format!("&mut [{}; {}]", self.type_str(elem), size),
                             }
-                            _ => format!("&mut [{}; {}]", self.type_str(elem), size),
-                        },
+                        }
                         ParseNode::TypeList { elem, .. } => match elem.as_ref() {
                             ParseNode::TypeStruct { name: sname, .. } => {
                                 format!("&mut Vec<Option<Box<{}>>>", escape_struct_name(sname))
@@ -415,6 +541,7 @@ impl CodeGen {
                 if let ParseNode::Param {
                     data_type,
                     name: pname,
+                    ..
                 } = p
                 {
                     let tl = parse_node_type_label(data_type);
@@ -423,7 +550,12 @@ impl CodeGen {
             }
         }
 
-        if self.debug_mode {
+        if self.profile_mode {
+            self.line(&format!(
+                "let __fractal_frame = __fractal_profile_enter(\"{}{}\");",
+                self.debug_module_prefix, name
+            ));
+        } else if self.debug_mode {
             self.line("__fractal_debug_init();");
 
             let caller_vars_code = self.build_vars_json_code();
@@ -469,11 +601,16 @@ impl CodeGen {
             let init_vars: Vec<String> = params
                 .iter()
                 .filter_map(|p| {
-                    if let ParseNode::Param { data_type, name: pname } = p {
+                    if let ParseNode::Param {
+                        data_type,
+                        name: pname,
+                        ..
+                    } = p
+                    {
                         let tl = parse_node_type_label(data_type);
                         let ident = escape_ident(pname);
                         Some(format!(
-                            "__fractal_debug_var(\"{ident}\", \"{tl}\", &{{ let __v = &{ident}; format!(\"{{:?}}\", __v) }})",
+                            "__fractal_debug_var(\"{ident}\", \"{tl}\", &__fractal_debug_value(&{ident}))",
                             ident = ident,
                             tl = tl,
                         ))
@@ -496,6 +633,7 @@ impl CodeGen {
                 "__FRACTAL_CALL_STACK.with(|__s| __s.borrow_mut().push(({}, {})));",
                 display_name, init_vars_code
             ));
+            self.line("let __fractal_frame = __FractalFrameGuard;");
         }
 
         for s in body {
@@ -515,10 +653,6 @@ impl CodeGen {
             }
         }
 
-        if self.debug_mode {
-            self.line("__FRACTAL_CALL_STACK.with(|__s| { __s.borrow_mut().pop(); });");
-        }
-
         self.debug_current_func = prev_dbg_func;
         self.debug_visible_vars = prev_dbg_vars;
         self.dedent();
@@ -549,7 +683,17 @@ impl CodeGen {
         let prev_module_file = self.debug_current_file.clone();
         self.debug_current_file = name.to_string();
 
-        let (defs, stmts): (Vec<_>, Vec<_>) = items.iter().partition(|n| {
+        let (consts, items): (Vec<_>, Vec<_>) = items
+            .iter()
+            .partition(|n| matches!(n, ParseNode::ConstDecl { .. }));
+        for c in &consts {
+            self.gen_const_decl(c, true);
+        }
+        if !consts.is_empty() {
+            self.blank();
+        }
+
+        let (defs, stmts): (Vec<_>, Vec<_>) = items.into_iter().partition(|n| {
             matches!(
                 n,
                 ParseNode::FuncDef { .. } | ParseNode::StructDef { .. } | ParseNode::Module { .. }
@@ -662,6 +806,8 @@ impl CodeGen {
                 ..
             } => self.gen_struct_decl(struct_name, var_name, init.as_deref()),
 
+            ParseNode::ConstDecl { .. } => self.gen_const_decl(node, false),
+
             ParseNode::Assign {
                 lvalue, op, expr, ..
             } => self.gen_assign(lvalue, op, expr),
@@ -681,6 +827,7 @@ impl CodeGen {
                 step,
                 body,
                 line,
+                ..
             } => self.gen_for(var_type, var_name, start, stop, step, body, *line),
 
             ParseNode::While {
@@ -778,6 +925,9 @@ impl CodeGen {
 
             ParseNode::Exit { expr, .. } => {
                 let e = self.gen_expr(expr);
+                if self.profile_mode {
+                    self.line("__fractal_profile_write();");
+                }
                 self.line(&format!("std::process::exit({} as i32);", e));
             }
 
@@ -812,14 +962,9 @@ impl CodeGen {
             self.local_var_types
                 .insert(name.to_string(), SemType::Boolean);
         }
-        if let ParseNode::TypeArray { elem, size, .. } = data_type {
-            self.local_var_types.insert(
-                name.to_string(),
-                SemType::Array {
-                    elem: Box::new(self.parse_node_to_sem_type(elem)),
-                    size: *size,
-                },
-            );
+        if let ParseNode::TypeArray { .. } = data_type {
+            let sem = self.parse_node_to_sem_type(data_type);
+            self.local_var_types.insert(name.to_string(), sem);
         }
         if let ParseNode::TypeList { elem, .. } = data_type {
             self.local_var_types.insert(
@@ -829,6 +974,10 @@ impl CodeGen {
                 },
             );
         }
+        if let ParseNode::TypeFunc { .. } = data_type {
+            let sem = self.parse_node_to_sem_type(data_type);
+            self.local_var_types.insert(name.to_string(), sem);
+        }
 
         let ty = self.type_str(data_type);
         let rhs = match (data_type, init) {
@@ -910,6 +1059,54 @@ impl CodeGen {
         }
     }
 
+    fn gen_const_decl(&mut self, node: &ParseNode, public: bool) {
+        let ParseNode::ConstDecl {
+            data_type,
+            name,
+            init,
+            ..
+        } = node
+        else {
+            return;
+        };
+        let sem = self.parse_node_to_sem_type(data_type);
+        self.local_var_types.insert(name.clone(), sem);
+
+        let folded = fold(init, &|n| self.const_values.get(n).cloned());
+        let rhs = match &folded {
+            Some(ConstValue::Int(n)) => format!("{}_i64", n),
+            Some(ConstValue::Float(f)) => format!("{:?}_f64", f),
+            Some(ConstValue::Char(c)) => format!("'{}'", escape_char(*c)),
+            Some(ConstValue::Boolean(b)) => b.to_string(),
+            None => self.gen_expr(init),
+        };
+        if let Some(v) = folded {
+            let key = if self.debug_module_prefix.is_empty() || !public {
+                name.clone()
+            } else {
+                format!("{}{}", self.debug_module_prefix, name)
+            };
+            self.const_values.insert(key, v.clone());
+            if public {
+                self.const_values.insert(name.clone(), v);
+            }
+        }
+        self.line(&format!(
+            "{}const {}: {} = {};",
+            if public { "pub " } else { "" },
+            escape_ident(name),
+            self.type_str(data_type),
+            rhs
+        ));
+    }
+
+    fn resolved_array_size(&self, size: i64, size_const: Option<&str>) -> i64 {
+        match size_const.and_then(|n| self.const_values.get(n)) {
+            Some(ConstValue::Int(n)) => *n,
+            _ => size,
+        }
+    }
+
     fn gen_struct_decl(&mut self, struct_name: &str, var_name: &str, init: Option<&ParseNode>) {
         self.local_var_types.insert(
             var_name.to_string(),
@@ -933,6 +1130,7 @@ impl CodeGen {
                                 SemType::Array { .. } => "None".to_string(),
                                 SemType::List { .. } => "Some(Vec::new())".to_string(),
                                 SemType::Struct(_) => "None".to_string(),
+                                SemType::Func { .. } => "None".to_string(),
                                 SemType::Unknown => "None".to_string(),
                             };
                             format!("{}: {}", fname, default_val)
@@ -1042,7 +1240,7 @@ impl CodeGen {
 
     fn gen_assign(&mut self, lvalue: &ParseNode, op: &AssignOp, expr: &ParseNode) {
         if let ParseNode::AccessChain { base, steps, .. } = lvalue {
-            if let Some(AccessStep::Field(fname)) = steps.last() {
+            if let Some(AccessStep::Field(fname, _)) = steps.last() {
                 let fname = fname.clone();
                 let is_null = matches!(expr, ParseNode::Null(_));
                 let prefix = self.emit_struct_field_prefix(base, steps);
@@ -1138,7 +1336,7 @@ impl CodeGen {
 
         for (i, step) in prefix_steps.iter().enumerate() {
             match step {
-                AccessStep::Field(f) => {
+                AccessStep::Field(f, _) => {
                     out = format!("{}.{}.as_mut().unwrap()", out, f);
                 }
                 AccessStep::Index(e) => {
@@ -1146,7 +1344,7 @@ impl CodeGen {
                     out = format!("{}[{} as usize]", out, idx);
                     let next_is_field = prefix_steps
                         .get(i + 1)
-                        .map_or(true, |s| matches!(s, AccessStep::Field(_)));
+                        .map_or(true, |s| matches!(s, AccessStep::Field(..)));
                     if next_is_field {
                         out = format!("{}.as_mut().unwrap()", out);
                     }
@@ -1173,7 +1371,7 @@ impl CodeGen {
             });
         for step in &steps[..steps.len().saturating_sub(1)] {
             cur = match (cur, step) {
-                (Some(SemType::Struct(sname)), AccessStep::Field(f)) => self
+                (Some(SemType::Struct(sname)), AccessStep::Field(f, _)) => self
                     .struct_fields
                     .get(&sname)
                     .and_then(|fs| fs.iter().find(|(n, _)| n == f))
@@ -1204,7 +1402,7 @@ impl CodeGen {
                 let lv = self.emit_access_chain_mut(base, steps);
                 let is_field_end = steps
                     .last()
-                    .map_or(false, |s| matches!(s, AccessStep::Field(_)));
+                    .map_or(false, |s| matches!(s, AccessStep::Field(..)));
                 if is_field_end {
                     format!("{}.as_mut().unwrap()", lv)
                 } else {
@@ -1252,7 +1450,7 @@ impl CodeGen {
         for (i, step) in steps.iter().enumerate() {
             let is_last = i == steps.len() - 1;
             match step {
-                AccessStep::Field(fname) => {
+                AccessStep::Field(fname, _) => {
                     let field_t = if let Some(SemType::Struct(sname)) = &cur_type {
                         self.struct_fields
                             .get(sname)
@@ -1415,6 +1613,9 @@ impl CodeGen {
                 else_block,
                 ..
             } => {
+                if self.debug_mode {
+                    self.emit_snapshot(node);
+                }
                 self.gen_if_for_body(
                     condition,
                     then_block,
@@ -1431,21 +1632,10 @@ impl CodeGen {
                 step,
                 body,
                 line,
+                ..
             } => {
                 self.gen_for(var_type, inner_vn, start, stop, step, body, *line);
             }
-            ParseNode::While {
-                condition, body, ..
-            } => {
-                let c = self.gen_expr(condition);
-                self.line(&format!("while {} {{", c));
-                self.indent();
-                for s in body {
-                    self.gen_stmt(s);
-                }
-                self.dedent();
-                self.line("}");
-            }
             other => {
                 self.gen_stmt(other);
             }
@@ -1465,6 +1655,19 @@ impl CodeGen {
         self.indent();
         for s in then_blk {
             self.gen_stmt_for_body(s, var_name, step_expr);
+            if self.debug_mode
+                && !matches!(
+                    s,
+                    ParseNode::Return { .. }
+                        | ParseNode::Break { .. }
+                        | ParseNode::Continue { .. }
+                        | ParseNode::Exit { .. }
+                        | ParseNode::If { .. }
+                        | ParseNode::While { .. }
+                )
+            {
+                self.emit_snapshot(s);
+            }
         }
         self.dedent();
         self.close_if_chain_for_body(else_blk, var_name, step_expr);
@@ -1492,6 +1695,19 @@ impl CodeGen {
                     self.indent();
                     for s in et {
                         self.gen_stmt_for_body(s, var_name, step_expr);
+                        if self.debug_mode
+                            && !matches!(
+                                s,
+                                ParseNode::Return { .. }
+                                    | ParseNode::Break { .. }
+                                    | ParseNode::Continue { .. }
+                                    | ParseNode::Exit { .. }
+                                    | ParseNode::If { .. }
+                                    | ParseNode::While { .. }
+                            )
+                        {
+                            self.emit_snapshot(s);
+                        }
                     }
                     self.dedent();
                     self.close_if_chain_for_body(ee.as_deref(), var_name, step_expr);
@@ -1501,6 +1717,19 @@ impl CodeGen {
                     self.indent();
                     for s in eb {
                         self.gen_stmt_for_body(s, var_name, step_expr);
+                        if self.debug_mode
+                            && !matches!(
+                                s,
+                                ParseNode::Return { .. }
+                                    | ParseNode::Break { .. }
+                                    | ParseNode::Continue { .. }
+                                    | ParseNode::Exit { .. }
+                                    | ParseNode::If { .. }
+                                    | ParseNode::While { .. }
+                            )
+                        {
+                            self.emit_snapshot(s);
+                        }
                     }
                     self.dedent();
                     self.line("}");
@@ -1548,7 +1777,13 @@ impl CodeGen {
             self.debug_visible_vars.push((vn.clone(), for_ty_label));
         }
 
-        if self.debug_mode {
+        if self.profile_mode {
+            let file = self.debug_current_file.replace('"', "'");
+            self.line(&format!(
+                "__fractal_profile_line({}, \"{}\");",
+                for_line, file
+            ));
+        } else if self.debug_mode {
             let for_label = format!("For {}", var_name);
             let func = self.debug_current_func.clone();
             let file = self.debug_current_file.replace('"', "'");
@@ -1562,7 +1797,11 @@ impl CodeGen {
                 vrs = vars_code,
             );
             self.line(&snap);
-            self.line("__fractal_debug_wait();");
+            let cond = self.debug_condition_code(for_line);
+            self.line(&format!(
+                "__fractal_debug_wait({}, \"{}\", {});",
+                for_line, file, cond
+            ));
         }
 
         self.line(&format!("while {} {} {} {{", vn, cmp_op, st_s));
@@ -1599,6 +1838,11 @@ impl CodeGen {
     fn gen_call_stmt(&mut self, node: &ParseNode) {
         let (func_base, call_args) = match node {
             ParseNode::AccessChain { base, steps, .. } => {
+                if matches!(self.value_type_of(base), Some(SemType::Func { .. })) {
+                    let s = self.gen_expr(node);
+                    self.line(&format!("{};", s));
+                    return;
+                }
                 if steps.len() == 1 {
                     if let AccessStep::Call(args) = &steps[0] {
                         (base.as_str(), args.as_slice())
@@ -1746,7 +1990,7 @@ impl CodeGen {
                 let call_returns_struct =
                     if !is_struct_result && matches!(steps.last(), Some(AccessStep::Call(_))) {
                         let func_name = steps.iter().rev().skip(1).find_map(|s| {
-                            if let AccessStep::Field(f) = s {
+                            if let AccessStep::Field(f, _) = s {
                                 Some(f.as_str())
                             } else {
                                 None
@@ -1819,7 +2063,7 @@ impl CodeGen {
                     for (i, step) in steps.iter().enumerate() {
                         let is_last = i == steps.len() - 1;
                         match step {
-                            AccessStep::Field(f) => {
+                            AccessStep::Field(f, _) => {
                                 if is_last {
                                     return format!("&mut {}.{}", out, escape_ident(f));
                                 } else {
@@ -1883,19 +2127,35 @@ impl CodeGen {
             ParseNode::TypeChar(_) => SemType::Char,
             ParseNode::TypeBoolean(_) => SemType::Boolean,
             ParseNode::TypeVoid(_) => SemType::Void,
-            ParseNode::TypeArray { elem, size, .. } => SemType::Array {
+            ParseNode::TypeArray {
+                elem,
+                size,
+                size_const,
+                ..
+            } => SemType::Array {
                 elem: Box::new(self.parse_node_to_sem_type(elem)),
-                size: *size,
+                size: self.resolved_array_size(*size, size_const.as_deref()),
             },
             ParseNode::TypeList { elem, .. } => SemType::List {
                 elem: Box::new(self.parse_node_to_sem_type(elem)),
             },
             ParseNode::TypeStruct { name, .. } => SemType::Struct(name.clone()),
+            ParseNode::TypeFunc { params, ret, .. } => SemType::Func {
+                params: params
+                    .iter()
+                    .map(|p| self.parse_node_to_sem_type(p))
+                    .collect(),
+                ret: Box::new(self.parse_node_to_sem_type(ret)),
+            },
             _ => SemType::Unknown,
         }
     }
 
     fn access_chain_is_struct(&self, base: &str, steps: &[AccessStep]) -> bool {
+        matches!(self.access_chain_type(base, steps), Some(SemType::Struct(_)))
+    }
+
+    fn access_chain_type(&self, base: &str, steps: &[AccessStep]) -> Option<SemType> {
         let mut cur = if let Some(sname) = self.struct_param_types.get(base) {
             Some(SemType::Struct(sname.clone()))
         } else if let Some(elem) = self.array_param_elem_types.get(base) {
@@ -1914,12 +2174,9 @@ impl CodeGen {
                 .or_else(|| self.local_var_types.get(base).cloned())
         };
 
-        if steps.is_empty() {
-            return matches!(cur, Some(SemType::Struct(_)));
-        }
         for step in steps {
             cur = match (cur, step) {
-                (Some(SemType::Struct(sname)), AccessStep::Field(f)) => self
+                (Some(SemType::Struct(sname)), AccessStep::Field(f, _)) => self
                     .struct_fields
                     .get(&sname)
                     .and_then(|fs| fs.iter().find(|(n, _)| n == f))
@@ -1929,12 +2186,16 @@ impl CodeGen {
                 _ => None,
             };
         }
-        matches!(cur, Some(SemType::Struct(_)))
+        cur
     }
 
     fn gen_expr(&mut self, node: &ParseNode) -> String {
         match node {
+            // Negative literals (from the parser or from folding) are wrapped so
+            // that a following method call such as `.abs()` binds correctly.
+            ParseNode::IntLit(v, _) if *v < 0 => format!("({}_i64)", v),
             ParseNode::IntLit(v, _) => format!("{}_i64", v),
+            ParseNode::FloatLit(v, _) if v.is_sign_negative() => format!("({:?}_f64)", v),
             ParseNode::FloatLit(v, _) => format!("{:?}_f64", v),
             ParseNode::CharLit(c, _) => format!("'{}'", escape_char(*c)),
             ParseNode::StringLit(s, _) => {
@@ -1950,6 +2211,12 @@ impl CodeGen {
             }
             ParseNode::Null(_) => "None".into(),
             ParseNode::AccessChain { base, steps, .. } => self.emit_access_chain(base, steps),
+            ParseNode::Lambda {
+                params,
+                return_type,
+                body,
+                ..
+            } => self.gen_lambda(params, return_type, body),
             ParseNode::ArrayLit(elems, _) => {
                 if elems.is_empty() {
                     "Vec::new()".into()
@@ -2127,11 +2394,15 @@ impl CodeGen {
             }
         }
 
+        if let Some(adapter) = self.gen_func_value(base, steps) {
+            return adapter;
+        }
+
         let base_escaped = escape_ident(base);
         let base_is_param_struct = self.struct_params.contains(base);
 
         if steps.len() == 1 {
-            if let AccessStep::Field(fname) = &steps[0] {
+            if let AccessStep::Field(fname, _) = &steps[0] {
                 if !self.var_types.contains_key(base)
                     && !self.local_var_types.contains_key(base)
                     && !self.struct_params.contains(base)
@@ -2145,7 +2416,7 @@ impl CodeGen {
         }
 
         if steps.len() >= 2 {
-            if let AccessStep::Field(qualified_name) = &steps[0] {
+            if let AccessStep::Field(qualified_name, _) = &steps[0] {
                 let full = format!("{}::{}", base_escaped, escape_ident(qualified_name));
                 if !self.var_types.contains_key(base)
                     && !self.local_var_types.contains_key(base)
@@ -2156,7 +2427,7 @@ impl CodeGen {
                         for (i, step) in steps.iter().enumerate() {
                             let is_last = i == steps.len() - 1;
                             match step {
-                                AccessStep::Field(f) => {
+                                AccessStep::Field(f, _) => {
                                     out = if is_last {
                                         format!("{}.{}.unwrap()", out, escape_ident(f))
                                     } else {
@@ -2177,8 +2448,13 @@ impl CodeGen {
                         return out;
                     }
                     let mut out = full;
+                    let mut called = false;
                     for step in &steps[1..] {
                         match step {
+                            AccessStep::Call(args) if called => {
+                                let av = self.gen_value_call_args(args);
+                                out = format!("({}.0)({})", out, av.join(", "));
+                            }
                             AccessStep::Call(args) => {
                                 let av: Vec<_> =
                                     args.iter().map(|a| self.gen_call_arg(a)).collect();
@@ -2186,8 +2462,11 @@ impl CodeGen {
                                     return format!("{}::{}", base_escaped, s);
                                 }
                                 out = format!("{}({})", out, av.join(", "));
+                                called = true;
+                            }
+                            AccessStep::Field(f, _) => {
+                                out = format!("{}::{}", out, escape_ident(f))
                             }
-                            AccessStep::Field(f) => out = format!("{}::{}", out, escape_ident(f)),
                             AccessStep::Index(e) => {
                                 let idx = self.gen_expr(e);
                                 out = format!("{}[{} as usize]", out, idx);
@@ -2235,7 +2514,7 @@ impl CodeGen {
         for (i, step) in steps.iter().enumerate() {
             let is_last = i == steps.len() - 1;
             match step {
-                AccessStep::Field(fname) => {
+                AccessStep::Field(fname, _) => {
                     let field_t = if let Some(SemType::Struct(sname)) = &cur_type {
                         self.struct_fields
                             .get(sname)
@@ -2277,12 +2556,22 @@ impl CodeGen {
                 }
 
                 AccessStep::Call(args) => {
+                    if let Some(SemType::Func { ret, .. }) = &cur_type {
+                        let ret = ret.as_ref().clone();
+                        let av = self.gen_value_call_args(args);
+                        out = format!("({}.0)({})", out, av.join(", "));
+                        if matches!(ret, SemType::Struct(_)) && !is_last {
+                            out = format!("{}.as_ref().unwrap()", out);
+                        }
+                        cur_type = Some(ret);
+                        continue;
+                    }
                     let av: Vec<_> = args.iter().map(|a| self.gen_call_arg(a)).collect();
                     out = format!("{}({})", out, av.join(", "));
 
                     let call_ret: Option<SemType> = {
                         let fname = if i > 0 {
-                            if let Some(AccessStep::Field(f)) = steps.get(i - 1) {
+                            if let Some(AccessStep::Field(f, _)) = steps.get(i - 1) {
                                 let q = format!("{}::{}", base, f);
                                 self.func_return_types
                                     .get(f.as_str())
@@ -2309,118 +2598,372 @@ impl CodeGen {
             }
         }
 
+        if matches!(cur_type, Some(SemType::Func { .. }))
+            && !matches!(steps.last(), Some(AccessStep::Call(_)))
+        {
+            out = format!("{}.clone()", out);
+        }
         out
     }
 
-    fn try_builtin(&mut self, name: &str, args: &[ParseNode]) -> Option<String> {
-        let n = args.len();
-        let a: Vec<String> = args.iter().map(|x| self.gen_expr(x)).collect();
+    /// A named function used as a value (`apply(double, 3)`, `m::double`) is
+    /// wrapped in a closure that owns its arguments and lends the composite
+    /// ones to the function, which takes them by `&mut`.
+    fn gen_func_value(&self, base: &str, steps: &[AccessStep]) -> Option<String> {
+        if self.var_types.contains_key(base)
+            || self.local_var_types.contains_key(base)
+            || self.struct_params.contains(base)
+            || self.array_params.contains(base)
+            || self.list_params.contains(base)
+        {
+            return None;
+        }
+        let (key, path) = match steps {
+            [] => (base.to_string(), escape_ident(base)),
+            [AccessStep::Field(f, _)] if self.module_names.contains(base) => (
+                format!("{}::{}", base, f),
+                format!("{}::{}", escape_ident(base), escape_ident(f)),
+            ),
+            _ => return None,
+        };
+        let params = self.func_params.get(&key)?;
+        let ret = self.func_return_types.get(&key)?;
+        let mut decls = Vec::new();
+        let mut args = Vec::new();
+        for (i, p) in params.iter().enumerate() {
+            decls.push(format!("mut __a{}: {}", i, sem_type_str(p)));
+            if matches!(
+                p,
+                SemType::Struct(_) | SemType::Array { .. } | SemType::List { .. }
+            ) {
+                args.push(format!("&mut __a{}", i));
+            } else {
+                args.push(format!("__a{}", i));
+            }
+        }
+        Some(format!(
+            "__FractalFn::<{}>(std::rc::Rc::new(move |{}| {}({})))",
+            dyn_fn_str(params, ret),
+            decls.join(", "),
+            path,
+            args.join(", ")
+        ))
+    }
 
-        match (name, n) {
-            ("print", 0) => Some("{ print!(); io::stdout().flush().unwrap(); }".into()),
-            ("print", 1) => {
-                if let ParseNode::StringLit(s, _) = &args[0] {
-                    let escaped: String = s.chars().map(|c| escape_char(c)).collect();
-                    Some(format!(
-                        "{{ print!(\"{escaped}\"); io::stdout().flush().unwrap(); }}"
-                    ))
-                } else {
-                    let is_float = self.expr_is_float(&args[0]);
-                    if is_float {
-                        Some(format!(
-                            "{{ print!(\"{{}}\", __fractal_fmt_float({})); io::stdout().flush().unwrap(); }}",
-                            a[0]
-                        ))
-                    } else {
-                        Some(format!(
-                            "{{ print!(\"{{}}\", {}); io::stdout().flush().unwrap(); }}",
-                            a[0]
-                        ))
+    /// Arguments to a function value are passed by value, so anything that is
+    /// not `Copy` is cloned rather than moved out of its variable.
+    fn gen_value_call_args(&mut self, args: &[ParseNode]) -> Vec<String> {
+        args.iter()
+            .map(|a| {
+                let v = self.gen_expr(a);
+                match a {
+                    ParseNode::AccessChain { base, steps, .. }
+                        if matches!(
+                            self.access_chain_type(base, steps),
+                            Some(SemType::Struct(_) | SemType::List { .. } | SemType::Array { .. })
+                        ) =>
+                    {
+                        format!("{}.clone()", v)
                     }
+                    _ => v,
                 }
+            })
+            .collect()
+    }
+
+    fn gen_lambda(
+        &mut self,
+        params: &[ParseNode],
+        return_type: &ParseNode,
+        body: &[ParseNode],
+    ) -> String {
+        let param_list: Vec<(String, SemType, String)> = params
+            .iter()
+            .filter_map(|p| match p {
+                ParseNode::Param {
+                    data_type, name, ..
+                } => Some((
+                    name.clone(),
+                    self.parse_node_to_sem_type(data_type),
+                    parse_node_type_label(data_type),
+                )),
+                _ => None,
+            })
+            .collect();
+        let ret = self.parse_node_to_sem_type(return_type);
+        let param_sems: Vec<SemType> = param_list.iter().map(|(_, t, _)| t.clone()).collect();
+        let fn_ty = dyn_fn_str(&param_sems, &ret);
+
+        // Scalars are copied into the `move` closure; everything else is
+        // cloned first so the outer variable stays usable, and cloned again
+        // inside so the body can lend it out by `&mut`.
+        let mut names = Vec::new();
+        for stmt in body {
+            collect_names(stmt, &mut names);
+        }
+        let mut captures: Vec<(String, SemType)> = Vec::new();
+        for n in names {
+            if param_list.iter().any(|(p, _, _)| *p == n) || captures.iter().any(|(c, _)| *c == n) {
+                continue;
             }
-            ("print", _) => {
-                let fmt_lit = if let ParseNode::StringLit(s, _) = &args[0] {
-                    let escaped: String = s.chars().map(|c| escape_char(c)).collect();
-                    format!("\"{}\"", escaped)
-                } else {
-                    a[0].clone()
-                };
-                let rest_args: Vec<String> = args[1..]
-                    .iter()
-                    .map(|arg| {
-                        let val = self.gen_expr(arg);
-                        if self.expr_is_float(arg) {
-                            format!("__fractal_fmt_float({})", val)
-                        } else {
-                            val
-                        }
-                    })
-                    .collect();
-                let rest = rest_args.join(", ");
-                Some(format!(
-                    "{{ print!({}, {}); io::stdout().flush().unwrap(); }}",
-                    fmt_lit, rest
-                ))
-            }
-            ("input", _) => {
-                let prompt = &a[0];
-                let vars = &args[1..];
-                if vars.is_empty() {
-                    return Some(format!(
-                        "{{ print!(\"{{}}\", {prompt}); io::stdout().flush().unwrap(); \
-                         let mut __ln = String::new(); \
-                         io::stdin().lock().read_line(&mut __ln).unwrap(); \
-                         panic!(\"input() requires at least one variable to fill\"); }}"
-                    ));
+            if let Some(t) = self.value_type_of(&n) {
+                if matches!(
+                    t,
+                    SemType::Struct(_)
+                        | SemType::Array { .. }
+                        | SemType::List { .. }
+                        | SemType::Func { .. }
+                ) {
+                    captures.push((n, t));
                 }
-                let mut stmts = format!(
-                    "{{ print!(\"{{}}\", {prompt}); io::stdout().flush().unwrap(); \
-                     let mut __ln = String::new(); \
-                     io::stdin().lock().read_line(&mut __ln).unwrap(); \
-                     let mut __toks = __ln.trim().split_whitespace(); ",
-                );
-                for var_node in vars {
-                    let (var_name, var_type) =
-                        if let ParseNode::AccessChain { base, steps, .. } = var_node {
-                            if steps.is_empty() {
-                                let ty = self
-                                    .var_types
-                                    .get(base.as_str())
-                                    .cloned()
-                                    .or_else(|| self.local_var_types.get(base.as_str()).cloned())
-                                    .unwrap_or(SemType::Unknown);
-                                (escape_ident(base), ty)
-                            } else {
-                                let lv = self.emit_access_chain_mut(base, steps);
-                                (lv, SemType::Int)
-                            }
-                        } else {
-                            continue;
-                        };
+            }
+        }
 
-                    let parse_expr = match var_type {
-                        SemType::Int => {
-                            "{\n                                let __raw = __toks.next().unwrap_or(\"\");\n                                match __raw.parse::<i64>() {\n                                    Ok(v) => v,\n                                    Err(e) => panic!(\"invalid input for :int: '{}' is not a valid integer\", __raw),\n                                }\n                            }"
-                        }
-                        SemType::Float => {
-                            "{\n                                let __raw = __toks.next().unwrap_or(\"\");\n                                match __raw.parse::<f64>() {\n                                    Ok(v) => v,\n                                    Err(e) => panic!(\"invalid input for :float: '{}' is not a valid number\", __raw),\n                                }\n                            }"
-                        }
-                        SemType::Char => {
-                            "__toks.next().unwrap_or(\"\").chars().next().unwrap_or('\\0')"
+        let saved_buf = std::mem::take(&mut self.buf);
+        let saved_indent = self.indent;
+        let saved_hoists = std::mem::take(&mut self.hoist_buf);
+        let prev_ret = self.current_return_struct.take();
+        let prev_void = self.current_return_void;
+        if let ParseNode::TypeStruct { name: sname, .. } = return_type {
+            self.current_return_struct = Some(sname.clone());
+        }
+        self.current_return_void = matches!(return_type, ParseNode::TypeVoid(_));
+        let prev_params = std::mem::take(&mut self.struct_params);
+        let prev_param_types = std::mem::take(&mut self.struct_param_types);
+        let prev_bool_params = std::mem::take(&mut self.bool_params);
+        let prev_array_params = std::mem::take(&mut self.array_params);
+        let prev_list_params = std::mem::take(&mut self.list_params);
+        let prev_array_param_elem_types = std::mem::take(&mut self.array_param_elem_types);
+        let prev_list_param_elem_types = std::mem::take(&mut self.list_param_elem_types);
+        let prev_local_vars = self.local_var_types.clone();
+        let prev_dbg_vars = std::mem::take(&mut self.debug_visible_vars);
+
+        for (n, t) in &captures {
+            self.local_var_types.insert(n.clone(), t.clone());
+            self.debug_visible_vars
+                .push((escape_ident(n), sem_type_label(t)));
+        }
+        for (n, t, label) in &param_list {
+            self.local_var_types.insert(n.clone(), t.clone());
+            self.debug_visible_vars
+                .push((escape_ident(n), label.clone()));
+        }
+
+        self.indent = saved_indent + if captures.is_empty() { 1 } else { 2 };
+        for (n, _) in &captures {
+            let e = escape_ident(n);
+            self.line(&format!("let mut {} = {}.clone();", e, e));
+        }
+        if self.profile_mode {
+            self.line("let __fractal_frame = __fractal_profile_enter(\"<lambda>\");");
+        } else if self.debug_mode {
+            self.line("__FRACTAL_CALL_STACK.with(|__s| __s.borrow_mut().push((\"<lambda>\".to_string(), \"[]\".to_string())));");
+            self.line("let __fractal_frame = __FractalFrameGuard;");
+        }
+        for s in body {
+            self.gen_stmt(s);
+            if self.debug_mode
+                && !matches!(
+                    s,
+                    ParseNode::Return { .. }
+                        | ParseNode::Break { .. }
+                        | ParseNode::Continue { .. }
+                        | ParseNode::Exit { .. }
+                        | ParseNode::If { .. }
+                        | ParseNode::While { .. }
+                )
+            {
+                self.emit_snapshot(s);
+            }
+        }
+        let body_code = std::mem::replace(&mut self.buf, saved_buf);
+
+        self.indent = saved_indent;
+        self.hoist_buf = saved_hoists;
+        self.current_return_struct = prev_ret;
+        self.current_return_void = prev_void;
+        self.struct_params = prev_params;
+        self.struct_param_types = prev_param_types;
+        self.bool_params = prev_bool_params;
+        self.array_params = prev_array_params;
+        self.list_params = prev_list_params;
+        self.array_param_elem_types = prev_array_param_elem_types;
+        self.list_param_elem_types = prev_list_param_elem_types;
+        self.local_var_types = prev_local_vars;
+        self.debug_visible_vars = prev_dbg_vars;
+
+        let pad = "    ".repeat(saved_indent);
+        let inner_pad = if captures.is_empty() {
+            pad.clone()
+        } else {
+            "    ".repeat(saved_indent + 1)
+        };
+        let ps: Vec<String> = param_list
+            .iter()
+            .map(|(n, t, _)| format!("mut {}: {}", escape_ident(n), sem_type_str(t)))
+            .collect();
+        let ret_str = match ret {
+            SemType::Void => String::new(),
+            ref r => format!(" -> {}", sem_type_str(r)),
+        };
+        let closure = format!(
+            "__FractalFn::<{}>(std::rc::Rc::new(move |{}|{} {{\n{}{}}}))",
+            fn_ty,
+            ps.join(", "),
+            ret_str,
+            body_code,
+            inner_pad
+        );
+        if captures.is_empty() {
+            return closure;
+        }
+        let mut out = String::from("{\n");
+        for (n, _) in &captures {
+            let e = escape_ident(n);
+            out.push_str(&format!("{}let {} = {}.clone();\n", inner_pad, e, e));
+        }
+        out.push_str(&format!("{}{}\n{}}}", inner_pad, closure, pad));
+        out
+    }
+
+    fn value_type_of(&self, name: &str) -> Option<SemType> {
+        if let Some(sname) = self.struct_param_types.get(name) {
+            return Some(SemType::Struct(sname.clone()));
+        }
+        if let Some(elem) = self.array_param_elem_types.get(name) {
+            return Some(SemType::Array {
+                elem: Box::new(elem.clone()),
+                size: 0,
+            });
+        }
+        if let Some(elem) = self.list_param_elem_types.get(name) {
+            return Some(SemType::List {
+                elem: Box::new(elem.clone()),
+            });
+        }
+        self.local_var_types
+            .get(name)
+            .or_else(|| self.var_types.get(name))
+            .cloned()
+    }
+
+    fn try_builtin(&mut self, name: &str, args: &[ParseNode]) -> Option<String> {
+        let n = args.len();
+        let a: Vec<String> = args.iter().map(|x| self.gen_expr(x)).collect();
+
+        match (name, n) {
+            ("print", 0) => Some("{ print!(); io::stdout().flush().unwrap(); }".into()),
+            ("print", 1) => {
+                if let ParseNode::StringLit(s, _) = &args[0] {
+                    let escaped: String = s.chars().map(|c| escape_char(c)).collect();
+                    Some(format!(
+                        "{{ print!(\"{escaped}\"); io::stdout().flush().unwrap(); }}"
+                    ))
+                } else {
+                    let is_float = self.expr_is_float(&args[0]);
+                    if is_float {
+                        Some(format!(
+                            "{{ print!(\"{{}}\", __fractal_fmt_float({})); io::stdout().flush().unwrap(); }}",
+                            a[0]
+                        ))
+                    } else {
+                        Some(format!(
+                            "{{ print!(\"{{}}\", {}); io::stdout().flush().unwrap(); }}",
+                            a[0]
+                        ))
+                    }
+                }
+            }
+            ("print", _) => {
+                let fmt_lit = if let ParseNode::StringLit(s, _) = &args[0] {
+                    let escaped: String = s.chars().map(|c| escape_char(c)).collect();
+                    format!("\"{}\"", escaped)
+                } else {
+                    a[0].clone()
+                };
+                let rest_args: Vec<String> = args[1..]
+                    .iter()
+                    .map(|arg| {
+                        let val = self.gen_expr(arg);
+                        if self.expr_is_float(arg) {
+                            format!("__fractal_fmt_float({})", val)
+                        } else {
+                            val
+                        }
+                    })
+                    .collect();
+                let rest = rest_args.join(", ");
+                Some(format!(
+                    "{{ print!({}, {}); io::stdout().flush().unwrap(); }}",
+                    fmt_lit, rest
+                ))
+            }
+            ("input", _) => {
+                // The semanter only accepts a string literal as the prompt.
+                let Some(ParseNode::StringLit(s, _)) = args.first() else {
+                    return None;
+                };
+                let escaped: String = s.chars().map(escape_char).collect();
+                let prompt = format!("\"{}\"", escaped);
+                let vars = &args[1..];
+                let mut reads = String::new();
+                let mut stores = String::new();
+                for (i, var_node) in vars.iter().enumerate() {
+                    let ParseNode::AccessChain { base, steps, .. } = var_node else {
+                        continue;
+                    };
+                    let var_type = self
+                        .access_chain_type(base, steps)
+                        .unwrap_or(SemType::Unknown);
+                    let lv = self.emit_access_chain_mut(base, steps);
+                    let is_field = matches!(steps.last(), Some(AccessStep::Field(..)));
+                    let tmp = format!("__in_{}", i);
+
+                    let reader = match &var_type {
+                        SemType::Float => "float()",
+                        SemType::Char => "char()",
+                        SemType::Boolean => "boolean()",
+                        SemType::List { elem } | SemType::Array { elem, .. }
+                            if matches!(elem.as_ref(), SemType::Char) =>
+                        {
+                            "rest()"
                         }
-                        SemType::Boolean => {
-                            "{\n                            let __raw = __toks.next().unwrap_or(\"\");\n                            match __raw {\n                                \"true\" | \"1\" => true,\n                                \"false\" | \"0\" => false,\n                                _ => panic!(\"invalid input for :boolean: expected true/false/1/0, got '{}'\", __raw),\n                            }\n                        }"
+                        _ => "int()",
+                    };
+                    reads.push_str(&format!(
+                        "let {tmp} = match __in.{reader} {{ Ok(v) => v, Err(e) => {{ \
+                         eprintln!(\"invalid input: {{}}; please try again\", e); continue; }} }}; "
+                    ));
+
+                    let store = match (&var_type, is_field) {
+                        (SemType::Array { .. }, true) => format!(
+                            "if let Some(__arr) = {lv}.as_mut() {{ __fractal_fill_chars(__arr, &{tmp}); }} "
+                        ),
+                        (SemType::Array { .. }, false) => {
+                            format!("__fractal_fill_chars(&mut {lv}, &{tmp}); ")
                         }
-                        _ => {
-                            "{\n                                let __raw = __toks.next().unwrap_or(\"\");\n                                match __raw.parse::<i64>() {\n                                    Ok(v) => v,\n                                    Err(e) => panic!(\"invalid input for :int: '{}' is not a valid integer\", __raw),\n                                }\n                            }"
+                        (SemType::List { .. }, false) => {
+                            format!("{lv}.clear(); {lv}.extend({tmp}); ")
                         }
+                        (_, true) => format!("{lv} = Some({tmp}); "),
+                        (_, false) => format!("{lv} = {tmp}; "),
                     };
-                    stmts.push_str(&format!("{} = {}; ", var_name, parse_expr));
+                    stores.push_str(&store);
                 }
-                stmts.push('}');
-                Some(stmts)
+                Some(format!(
+                    "loop {{ let Some(__ln) = __fractal_read_line({prompt}) else {{ break; }}; \
+                     let mut __in = __FractalInput::new(&__ln); {reads}{stores}break; }}"
+                ))
             }
+            ("random_int", 2) => Some(format!("__fractal_random_int({}, {})", a[0], a[1])),
+            ("random_float", 0) => Some("__fractal_random_float()".into()),
+            ("seed", 1) => Some(format!("__fractal_seed({})", a[0])),
+            ("now_ms", 0) => Some("__fractal_now_ms()".into()),
+            ("sleep_ms", 1) => Some(format!(
+                "std::thread::sleep(std::time::Duration::from_millis(({}).max(0) as u64))",
+                a[0]
+            )),
+            ("eof", 0) => Some("__FRACTAL_EOF.load(std::sync::atomic::Ordering::SeqCst)".into()),
             ("append", 2) => {
                 let container = self.gen_list_container(&args[0]);
                 let val = self.gen_expr(&args[1]);
@@ -2464,13 +3007,37 @@ impl CodeGen {
                 "{}.to_string().chars().collect::<Vec<char>>()",
                 a[0]
             )),
+            ("map", 2) => Some(format!(
+                "{{ let __f = {}; {}.iter().cloned().map(|__v| (__f.0)(__v)).collect::<Vec<_>>() }}",
+                a[1], a[0]
+            )),
+            ("filter", 2) => Some(format!(
+                "{{ let __f = {}; {}.iter().cloned().filter(|__v| (__f.0)(__v.clone())).collect::<Vec<_>>() }}",
+                a[1], a[0]
+            )),
+            ("reduce", 3) => Some(format!(
+                "{{ let __f = {}; {}.iter().cloned().fold({}, |__acc, __v| (__f.0)(__acc, __v)) }}",
+                a[1], a[0], a[2]
+            )),
             _ => None,
         }
     }
 
     fn emit_snapshot(&mut self, stmt: &ParseNode) {
-        let label = stmt_debug_label(stmt);
         let source_line = stmt_source_line(stmt);
+        if self.profile_mode {
+            // A `!for` counts its line once as the loop starts.
+            if matches!(stmt, ParseNode::For { .. }) {
+                return;
+            }
+            let file = self.debug_current_file.replace('"', "'");
+            self.line(&format!(
+                "__fractal_profile_line({}, \"{}\");",
+                source_line, file
+            ));
+            return;
+        }
+        let label = stmt_debug_label(stmt);
         let func = self.debug_current_func.clone();
         let file = self.debug_current_file.replace('"', "'");
         let vars_code = self.build_vars_json_code();
@@ -2483,7 +3050,28 @@ impl CodeGen {
             vrs = vars_code,
         );
         self.line(&line);
-        self.line("__fractal_debug_wait();");
+        let cond = self.debug_condition_code(source_line);
+        self.line(&format!(
+            "__fractal_debug_wait({}, \"{}\", {});",
+            source_line, file, cond
+        ));
+    }
+
+    // The condition of a breakpoint on `line` of the current file, as the
+    // closure `__fractal_debug_wait` calls when it reaches that breakpoint.
+    fn debug_condition_code(&mut self, line: usize) -> String {
+        let key = (self.debug_current_file.clone(), line);
+        let Some(cond) = self.debug_conditions.get(&key).cloned() else {
+            return "|| true".into();
+        };
+        let saved_hoists = std::mem::take(&mut self.hoist_buf);
+        let expr = self.gen_expr(&cond);
+        let hoists = std::mem::replace(&mut self.hoist_buf, saved_hoists);
+        if hoists.is_empty() {
+            format!("|| {}", expr)
+        } else {
+            format!("|| {{ {} {} }}", hoists.join(" "), expr)
+        }
     }
 
     fn build_vars_json_code(&self) -> String {
@@ -2491,7 +3079,7 @@ impl CodeGen {
             .iter()
             .map(|(ident, type_label)| {
                 format!(
-                    "__fractal_debug_var(\"{name}\", \"{tl}\", &{{ let __v = &{ident}; format!(\"{{:?}}\", __v) }})",
+                    "__fractal_debug_var(\"{name}\", \"{tl}\", &__fractal_debug_value(&{ident}))",
                     name = ident,
                     tl = type_label,
                     ident = ident,
@@ -2501,6 +3089,226 @@ impl CodeGen {
             .join(", ")
     }
 
+    fn emit_func_runtime(&mut self) {
+        self.line("struct __FractalFn<F: ?Sized>(std::rc::Rc<F>);");
+        self.line("impl<F: ?Sized> Clone for __FractalFn<F> {");
+        self.line("    fn clone(&self) -> Self { __FractalFn(self.0.clone()) }");
+        self.line("}");
+        self.line("impl<F: ?Sized> std::fmt::Debug for __FractalFn<F> {");
+        self.line("    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, \"<func>\") }");
+        self.line("}");
+    }
+
+    fn emit_input_runtime(&mut self) {
+        self.line("static __FRACTAL_EOF: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);");
+        self.blank();
+
+        self.line("fn __fractal_read_line(prompt: &str) -> Option<String> {");
+        self.indent();
+        self.line("print!(\"{}\", prompt);");
+        self.line("io::stdout().flush().unwrap();");
+        self.line("let mut ln = String::new();");
+        self.line("match io::stdin().lock().read_line(&mut ln) {");
+        self.indent();
+        self.line("Ok(0) | Err(_) => {");
+        self.line("    __FRACTAL_EOF.store(true, std::sync::atomic::Ordering::SeqCst);");
+        self.line("    None");
+        self.line("}");
+        self.line("Ok(_) => Some(ln.trim_end_matches(['\\n', '\\r']).to_string()),");
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_fill_chars<A: AsMut<[char]>>(dst: &mut A, src: &[char]) {");
+        self.line("    for (i, c) in dst.as_mut().iter_mut().enumerate() {");
+        self.line("        *c = src.get(i).copied().unwrap_or('\\0');");
+        self.line("    }");
+        self.line("}");
+        self.blank();
+
+        self.line("struct __FractalInput {");
+        self.line("    chars: Vec<char>,");
+        self.line("    pos: usize,");
+        self.line("}");
+        self.blank();
+        self.line("impl __FractalInput {");
+        self.indent();
+        self.line("fn new(line: &str) -> Self {");
+        self.line("    __FractalInput { chars: line.chars().collect(), pos: 0 }");
+        self.line("}");
+        self.line("fn skip_ws(&mut self) {");
+        self.line("    while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {");
+        self.line("        self.pos += 1;");
+        self.line("    }");
+        self.line("}");
+        self.line("fn token(&mut self, ty: &str) -> Result<String, String> {");
+        self.line("    self.skip_ws();");
+        self.line("    let start = self.pos;");
+        self.line("    while self.pos < self.chars.len() && !self.chars[self.pos].is_whitespace() {");
+        self.line("        self.pos += 1;");
+        self.line("    }");
+        self.line("    if start == self.pos {");
+        self.line("        return Err(format!(\"expected a {} value but the line ended\", ty));");
+        self.line("    }");
+        self.line("    Ok(self.chars[start..self.pos].iter().collect())");
+        self.line("}");
+        self.line("fn int(&mut self) -> Result<i64, String> {");
+        self.line("    let raw = self.token(\":int\")?;");
+        self.line("    raw.parse::<i64>().map_err(|_| format!(\"'{}' is not a valid :int\", raw))");
+        self.line("}");
+        self.line("fn float(&mut self) -> Result<f64, String> {");
+        self.line("    let raw = self.token(\":float\")?;");
+        self.line("    raw.parse::<f64>().map_err(|_| format!(\"'{}' is not a valid :float\", raw))");
+        self.line("}");
+        self.line("fn char(&mut self) -> Result<char, String> {");
+        self.line("    let raw = self.token(\":char\")?;");
+        self.line("    let mut it = raw.chars();");
+        self.line("    match (it.next(), it.next()) {");
+        self.line("        (Some(c), None) => Ok(c),");
+        self.line("        _ => Err(format!(\"'{}' is not a single :char\", raw)),");
+        self.line("    }");
+        self.line("}");
+        self.line("fn boolean(&mut self) -> Result<bool, String> {");
+        self.line("    let raw = self.token(\":boolean\")?;");
+        self.line("    match raw.as_str() {");
+        self.line("        \"true\" | \"1\" => Ok(true),");
+        self.line("        \"false\" | \"0\" => Ok(false),");
+        self.line("        _ => Err(format!(\"'{}' is not a valid :boolean (expected true, false, 1 or 0)\", raw)),");
+        self.line("    }");
+        self.line("}");
+        self.line("fn rest(&mut self) -> Result<Vec<char>, String> {");
+        self.line("    self.skip_ws();");
+        self.line("    let mut end = self.chars.len();");
+        self.line("    while end > self.pos && self.chars[end - 1].is_whitespace() {");
+        self.line("        end -= 1;");
+        self.line("    }");
+        self.line("    let out = self.chars[self.pos..end].to_vec();");
+        self.line("    self.pos = self.chars.len();");
+        self.line("    Ok(out)");
+        self.line("}");
+        self.dedent();
+        self.line("}");
+    }
+
+    fn emit_random_runtime(&mut self) {
+        self.line("static __FRACTAL_RNG: std::sync::Mutex<Option<u64>> = std::sync::Mutex::new(None);");
+        self.blank();
+
+        self.line("fn __fractal_seed(n: i64) {");
+        self.line("    *__FRACTAL_RNG.lock().unwrap() = Some(n as u64);");
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_next_u64() -> u64 {");
+        self.indent();
+        self.line("let mut rng = __FRACTAL_RNG.lock().unwrap();");
+        self.line("let state = rng.get_or_insert_with(|| {");
+        self.line("    std::time::SystemTime::now()");
+        self.line("        .duration_since(std::time::UNIX_EPOCH)");
+        self.line("        .map(|d| d.as_nanos() as u64)");
+        self.line("        .unwrap_or(0)");
+        self.line("});");
+        self.line("*state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);");
+        self.line("let mut z = *state;");
+        self.line("z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);");
+        self.line("z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);");
+        self.line("z ^ (z >> 31)");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_random_int(lo: i64, hi: i64) -> i64 {");
+        self.indent();
+        self.line("let (lo, hi) = if lo <= hi { (lo, hi) } else { (hi, lo) };");
+        self.line("let span = (hi as u64).wrapping_sub(lo as u64).wrapping_add(1);");
+        self.line("if span == 0 {");
+        self.line("    return __fractal_next_u64() as i64;");
+        self.line("}");
+        self.line("lo.wrapping_add((__fractal_next_u64() % span) as i64)");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_random_float() -> f64 {");
+        self.line("    (__fractal_next_u64() >> 11) as f64 / (1_u64 << 53) as f64");
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_now_ms() -> i64 {");
+        self.line("    std::time::SystemTime::now()");
+        self.line("        .duration_since(std::time::UNIX_EPOCH)");
+        self.line("        .map(|d| d.as_millis() as i64)");
+        self.line("        .unwrap_or(0)");
+        self.line("}");
+    }
+
+    // Variables are recorded as typed JSON: ints and floats as JSON numbers
+    // (a float always has a fraction or exponent), chars as one-character
+    // strings, bools as themselves, `null` for a null struct, and tagged
+    // objects for everything else. `gen_structdef` adds the impl for each
+    // struct.
+    fn emit_debug_value_runtime(&mut self) {
+        self.line("trait __FractalDebugValue {");
+        self.line("    fn __dbg_json(&self, o: &mut String);");
+        self.line("}");
+        for (ty, body) in [
+            ("i64", "o.push_str(&self.to_string());"),
+            (
+                "f64",
+                r#"if self.is_finite() { o.push_str(&format!("{:?}", self)); } else { o.push_str(&format!("{{\"float\":\"{}\"}}", self)); }"#,
+            ),
+            (
+                "char",
+                r#"o.push('"'); o.push_str(&__fractal_debug_json_escape(&self.to_string())); o.push('"');"#,
+            ),
+            ("bool", "o.push_str(if *self { \"true\" } else { \"false\" });"),
+        ] {
+            self.line(&format!(
+                "impl __FractalDebugValue for {ty} {{ fn __dbg_json(&self, o: &mut String) {{ {body} }} }}"
+            ));
+        }
+        self.line("impl<T: __FractalDebugValue> __FractalDebugValue for Vec<T> {");
+        self.line(r#"    fn __dbg_json(&self, o: &mut String) { o.push_str("{\"list\":"); __fractal_debug_items(self, o); o.push('}'); }"#);
+        self.line("}");
+        self.line("impl<T: __FractalDebugValue, const N: usize> __FractalDebugValue for [T; N] {");
+        self.line(r#"    fn __dbg_json(&self, o: &mut String) { o.push_str("{\"array\":"); __fractal_debug_items(self, o); o.push('}'); }"#);
+        self.line("}");
+        self.line("impl<T: __FractalDebugValue> __FractalDebugValue for Option<T> {");
+        self.line(r#"    fn __dbg_json(&self, o: &mut String) { match self { Some(v) => v.__dbg_json(o), None => o.push_str("null") } }"#);
+        self.line("}");
+        // Parameters arrive by reference, so references forward as boxes do.
+        for ptr in ["Box<T>", "&T", "&mut T"] {
+            self.line(&format!(
+                "impl<T: __FractalDebugValue + ?Sized> __FractalDebugValue for {ptr} {{"
+            ));
+            self.line("    fn __dbg_json(&self, o: &mut String) { (**self).__dbg_json(o) }");
+            self.line("}");
+        }
+        self.line("impl<F: ?Sized> __FractalDebugValue for __FractalFn<F> {");
+        self.line(r#"    fn __dbg_json(&self, o: &mut String) { o.push_str("{\"func\":null}"); }"#);
+        self.line("}");
+        self.line(
+            "fn __fractal_debug_items<T: __FractalDebugValue>(items: &[T], o: &mut String) {",
+        );
+        self.indent();
+        self.line("o.push('[');");
+        self.line("for (i, v) in items.iter().enumerate() {");
+        self.line("    if i > 0 { o.push(','); }");
+        self.line("    v.__dbg_json(o);");
+        self.line("}");
+        self.line("o.push(']');");
+        self.dedent();
+        self.line("}");
+        self.line("fn __fractal_debug_value<T: __FractalDebugValue>(v: &T) -> String {");
+        self.line("    let mut o = String::new();");
+        self.line("    v.__dbg_json(&mut o);");
+        self.line("    o");
+        self.line("}");
+        self.blank();
+    }
+
     fn emit_debug_runtime(&mut self) {
         let path = self.debug_path.clone();
 
@@ -2509,6 +3317,10 @@ impl CodeGen {
         self.line("use std::io::{BufWriter as __DbgBufWriter, Write as __DbgWrite};");
         self.blank();
 
+        self.line(&format!(
+            "const __FRACTAL_DBG_HEADER: &str = {:?};",
+            self.debug_header
+        ));
         self.line("static __FRACTAL_DBG_INIT: Once = Once::new();");
         self.line("#[allow(clippy::type_complexity)]");
         self.line("static __FRACTAL_DBG_FILE: Mutex<Option<__DbgBufWriter<__DbgFile>>> = Mutex::new(None);");
@@ -2517,6 +3329,8 @@ impl CodeGen {
         self.line(
             "static __FRACTAL_DBG_LOCK: std::sync::OnceLock<String> = std::sync::OnceLock::new();",
         );
+        self.line("#[allow(clippy::type_complexity)]");
+        self.line("static __FRACTAL_DBG_RESUME: Mutex<Option<(Vec<(String, usize)>, Option<usize>)>> = Mutex::new(None);");
         self.blank();
 
         self.line("thread_local! {");
@@ -2527,6 +3341,18 @@ impl CodeGen {
         self.line("}");
         self.blank();
 
+        self.line("struct __FractalFrameGuard;");
+        self.line("impl Drop for __FractalFrameGuard {");
+        self.indent();
+        self.line("fn drop(&mut self) {");
+        self.indent();
+        self.line("__FRACTAL_CALL_STACK.with(|__s| { __s.borrow_mut().pop(); });");
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
         self.line("fn __fractal_debug_init() {");
         self.indent();
         self.line("__FRACTAL_DBG_INIT.call_once(|| {");
@@ -2536,21 +3362,73 @@ impl CodeGen {
              .open(\"{path}\").expect(\"cannot open fractal debug file\");",
             path = path
         ));
-        self.line("*__FRACTAL_DBG_FILE.lock().unwrap() = Some(__DbgBufWriter::new(__f));");
+        self.line("let mut __w = __DbgBufWriter::new(__f);");
+        self.line("let _ = writeln!(__w, \"{}\", __FRACTAL_DBG_HEADER);");
+        self.line("*__FRACTAL_DBG_FILE.lock().unwrap() = Some(__w);");
         self.dedent();
         self.line("});");
         self.dedent();
         self.line("}");
         self.blank();
 
-        self.line("fn __fractal_debug_wait() {");
+        // The editor releases a paused binary either by deleting the lock file
+        // (single step) or by replacing it with a `continue [depth]` command
+        // followed by one `file:line` breakpoint per line. While resumed, only
+        // statements at most `depth` calls deep and breakpoints whose
+        // condition holds pause again. The condition runs after the resume
+        // state is unlocked, as it may call back into Fractal functions.
+        self.line(
+            "fn __fractal_debug_wait(line: usize, file: &str, cond: impl FnOnce() -> bool) {",
+        );
         self.indent();
         self.line("if let Some(path) = __FRACTAL_DBG_LOCK.get() {");
         self.indent();
+        self.line(
+            "let resumed = __FRACTAL_DBG_RESUME.lock().unwrap().as_ref().map(|(bps, max_depth)| {",
+        );
+        self.indent();
+        self.line("let depth = __FRACTAL_CALL_STACK.with(|__s| __s.borrow().len());");
+        self.line("let at_depth = matches!(max_depth, Some(d) if depth <= *d);");
+        self.line("(at_depth, bps.iter().any(|(f, l)| *l == line && f == file))");
+        self.dedent();
+        self.line("});");
+        self.line("if let Some((at_depth, at_breakpoint)) = resumed {");
+        self.indent();
+        self.line("if !at_depth && !(at_breakpoint && cond()) {");
+        self.indent();
+        self.line("return;");
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("}");
         self.line("let _ = std::fs::write(path, \"\");");
-        self.line("while std::fs::metadata(path).is_ok() {");
+        self.line("loop {");
         self.indent();
-        self.line("std::thread::sleep(std::time::Duration::from_millis(10));");
+        self.line("match std::fs::read_to_string(path) {");
+        self.indent();
+        self.line("Err(_) => {");
+        self.indent();
+        self.line("*__FRACTAL_DBG_RESUME.lock().unwrap() = None;");
+        self.line("break;");
+        self.dedent();
+        self.line("}");
+        self.line("Ok(cmd) if cmd.starts_with(\"continue\") => {");
+        self.indent();
+        self.line("let bps: Vec<(String, usize)> = cmd.lines().skip(1).filter_map(|l| {");
+        self.indent();
+        self.line("let (f, n) = l.rsplit_once(':')?;");
+        self.line("Some((f.to_string(), n.trim().parse().ok()?))");
+        self.dedent();
+        self.line("}).collect();");
+        self.line("let max_depth = cmd.lines().next().and_then(|l| l[8..].trim().parse().ok());");
+        self.line("*__FRACTAL_DBG_RESUME.lock().unwrap() = Some((bps, max_depth));");
+        self.line("let _ = std::fs::remove_file(path);");
+        self.line("break;");
+        self.dedent();
+        self.line("}");
+        self.line("_ => std::thread::sleep(std::time::Duration::from_millis(10)),");
+        self.dedent();
+        self.line("}");
         self.dedent();
         self.line("}");
         self.dedent();
@@ -2571,6 +3449,7 @@ impl CodeGen {
         self.line(r#"'\n' => o.push_str("\\n"),"#);
         self.line(r#"'\t' => o.push_str("\\t"),"#);
         self.line(r#"'\r' => o.push_str("\\r"),"#);
+        self.line(r#"c if (c as u32) < 0x20 => o.push_str(&format!("\\u{:04x}", c as u32)),"#);
         self.line("c    => o.push(c),");
         self.dedent();
         self.line("}");
@@ -2581,6 +3460,8 @@ impl CodeGen {
         self.line("}");
         self.blank();
 
+        self.emit_debug_value_runtime();
+
         self.line("fn __fractal_debug_var(name: &str, type_label: &str, value: &str) -> String {");
         self.indent();
         self.line("let changed = {");
@@ -2597,13 +3478,14 @@ impl CodeGen {
         self.line(r#"let mut s = String::from("{");"#);
         self.line(r#"s.push_str("\"name\":\""); s.push_str(&__fractal_debug_json_escape(name)); s.push_str("\",");"#);
         self.line(r#"s.push_str("\"type\":\""); s.push_str(&__fractal_debug_json_escape(type_label)); s.push_str("\",");"#);
-        self.line(r#"s.push_str("\"value\":\""); s.push_str(&__fractal_debug_json_escape(value)); s.push_str("\",");"#);
+        self.line(r#"s.push_str("\"value\":"); s.push_str(value); s.push(',');"#);
         self.line(r#"s.push_str("\"changed\":"); s.push_str(if changed { "true" } else { "false" }); s.push('}');"#);
         self.line("s");
         self.dedent();
         self.line("}");
         self.blank();
 
+        // Writes one `snapshot::Snapshot` per line; keep the two in step.
         self.raw("macro_rules! __fractal_debug_snapshot {\n");
         self.raw("    ($label:expr, $func:expr, $line:expr, $file:expr, [$($var_str:expr),* $(,)?], $finished:expr, $error:expr) => {{\n");
         self.raw("        let __dbg_step = __FRACTAL_DBG_STEP.fetch_add(1, std::sync::atomic::Ordering::SeqCst);\n");
@@ -2639,7 +3521,9 @@ impl CodeGen {
         self.raw("                Some(__e) => { let mut __es = String::from(\"\\\"\"); __es.push_str(&__fractal_debug_json_escape(__e)); __es.push('\"'); __es },\n");
         self.raw("            };\n");
         self.raw("            let __dbg_line = {\n");
-        self.raw("                let mut __ln = String::from(\"{\\\"step\\\":\");\n");
+        self.raw(&format!(
+            "                let mut __ln = String::from(\"{{\\\"version\\\":{SNAPSHOT_VERSION},\\\"step\\\":\");\n"
+        ));
         self.raw("                __ln.push_str(&__dbg_step.to_string());\n");
         self.raw("                __ln.push_str(\",\\\"label\\\":\\\"\");\n");
         self.raw("                __ln.push_str(&__fractal_debug_json_escape($label));\n");
@@ -2667,16 +3551,196 @@ impl CodeGen {
         self.raw("    }};\n");
         self.raw("}\n");
     }
+
+    // Counts for the hooks of a profiling build. A line is charged the time
+    // since the hook before it, and a function the time until its guard
+    // drops. The guard of `<main>` writes the profile, so it is also written
+    // when a runtime error unwinds the program; `!exit` writes it itself.
+    fn emit_profile_runtime(&mut self) {
+        let path = self.debug_path.clone();
+
+        self.line("thread_local! {");
+        self.indent();
+        self.line("static __FRACTAL_PROF_LAST: std::cell::Cell<Option<std::time::Instant>> = const { std::cell::Cell::new(None) };");
+        self.line("#[allow(clippy::type_complexity)]");
+        self.line("static __FRACTAL_PROF_LINES: std::cell::RefCell<std::collections::HashMap<(&'static str, usize), (u64, std::time::Duration)>> = std::cell::RefCell::new(std::collections::HashMap::new());");
+        self.line("#[allow(clippy::type_complexity)]");
+        self.line("static __FRACTAL_PROF_FUNCS: std::cell::RefCell<std::collections::HashMap<&'static str, (u64, std::time::Duration, std::time::Duration)>> = std::cell::RefCell::new(std::collections::HashMap::new());");
+        self.line("#[allow(clippy::type_complexity)]");
+        self.line("static __FRACTAL_PROF_STACK: std::cell::RefCell<Vec<(&'static str, std::time::Instant, std::time::Duration)>> = const { std::cell::RefCell::new(Vec::new()) };");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_profile_line(line: usize, file: &'static str) {");
+        self.indent();
+        self.line("let now = std::time::Instant::now();");
+        self.line("let spent = __FRACTAL_PROF_LAST.with(|l| l.replace(Some(now))).map(|t| now - t).unwrap_or_default();");
+        self.line("__FRACTAL_PROF_LINES.with(|m| {");
+        self.indent();
+        self.line("let mut m = m.borrow_mut();");
+        self.line("let e = m.entry((file, line)).or_default();");
+        self.line("e.0 += 1;");
+        self.line("e.1 += spent;");
+        self.dedent();
+        self.line("});");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("struct __FractalProfileGuard;");
+        self.line("impl Drop for __FractalProfileGuard {");
+        self.indent();
+        self.line("fn drop(&mut self) {");
+        self.indent();
+        self.line("__fractal_profile_leave();");
+        self.line("if __FRACTAL_PROF_STACK.with(|s| s.borrow().is_empty()) {");
+        self.line("    __fractal_profile_write();");
+        self.line("}");
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        self.line("fn __fractal_profile_enter(name: &'static str) -> __FractalProfileGuard {");
+        self.indent();
+        self.line("let now = std::time::Instant::now();");
+        self.line("__FRACTAL_PROF_STACK.with(|s| s.borrow_mut().push((name, now, std::time::Duration::ZERO)));");
+        self.line("__FractalProfileGuard");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        // Time inside a recursive function is only added to its total by the
+        // outermost call, but every call adds its own body to its self time.
+        self.line("fn __fractal_profile_leave() {");
+        self.indent();
+        self.line("let Some((name, start, inner)) = __FRACTAL_PROF_STACK.with(|s| s.borrow_mut().pop()) else {");
+        self.line("    return;");
+        self.line("};");
+        self.line("let spent = start.elapsed();");
+        self.line("let recursive = __FRACTAL_PROF_STACK.with(|s| {");
+        self.indent();
+        self.line("let mut s = s.borrow_mut();");
+        self.line("if let Some(caller) = s.last_mut() {");
+        self.line("    caller.2 += spent;");
+        self.line("}");
+        self.line("s.iter().any(|f| f.0 == name)");
+        self.dedent();
+        self.line("});");
+        self.line("__FRACTAL_PROF_FUNCS.with(|m| {");
+        self.indent();
+        self.line("let mut m = m.borrow_mut();");
+        self.line("let e = m.entry(name).or_default();");
+        self.line("e.0 += 1;");
+        self.line("if !recursive {");
+        self.line("    e.1 += spent;");
+        self.line("}");
+        self.line("e.2 += spent.saturating_sub(inner);");
+        self.dedent();
+        self.line("});");
+        self.dedent();
+        self.line("}");
+        self.blank();
+
+        // Writes one `profile::Profile`; keep the two in step.
+        self.line("fn __fractal_profile_write() {");
+        self.indent();
+        self.line("while __FRACTAL_PROF_STACK.with(|s| !s.borrow().is_empty()) {");
+        self.line("    __fractal_profile_leave();");
+        self.line("}");
+        self.line(&format!(
+            "let mut o = String::from(\"{{\\\"version\\\":{PROFILE_VERSION},\\\"lines\\\":[\");"
+        ));
+        self.line("__FRACTAL_PROF_LINES.with(|m| {");
+        self.indent();
+        self.line("let mut lines: Vec<_> = m.borrow().iter().map(|(k, v)| (*k, *v)).collect();");
+        self.line("lines.sort();");
+        self.line("for (i, ((file, line), (hits, spent))) in lines.iter().enumerate() {");
+        self.indent();
+        self.line("if i > 0 { o.push(','); }");
+        self.line(r#"o.push_str(&format!("{{\"file\":{:?},\"line\":{},\"hits\":{},\"nanos\":{}}}", file, line, hits, spent.as_nanos()));"#);
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("});");
+        self.line(r#"o.push_str("],\"functions\":[");"#);
+        self.line("__FRACTAL_PROF_FUNCS.with(|m| {");
+        self.indent();
+        self.line("let mut funcs: Vec<_> = m.borrow().iter().map(|(k, v)| (*k, *v)).collect();");
+        self.line("funcs.sort();");
+        self.line("for (i, (name, (calls, total, own))) in funcs.iter().enumerate() {");
+        self.indent();
+        self.line("if i > 0 { o.push(','); }");
+        self.line(r#"o.push_str(&format!("{{\"name\":{:?},\"calls\":{},\"total_nanos\":{},\"self_nanos\":{}}}", name, calls, total.as_nanos(), own.as_nanos()));"#);
+        self.dedent();
+        self.line("}");
+        self.dedent();
+        self.line("});");
+        self.line(r#"o.push_str("]}");"#);
+        // Written aside and renamed, so that the editor never reads half a
+        // profile.
+        self.line(&format!("let _ = std::fs::write(\"{path}.tmp\", o);"));
+        self.line(&format!(
+            "let _ = std::fs::rename(\"{path}.tmp\", \"{path}\");"
+        ));
+        self.dedent();
+        self.line("}");
+    }
 }
 
 fn escape_ident(name: &str) -> String {
     format!("fractal_{}", name)
 }
 
+fn array_size_str(size: i64, size_const: Option<&str>) -> String {
+    match size_const {
+        Some(name) => {
+            let path: Vec<String> = name.split("::").map(escape_ident).collect();
+            format!("{} as usize", path.join("::"))
+        }
+        None => size.to_string(),
+    }
+}
+
 fn escape_struct_name(name: &str) -> String {
     format!("Fractal{}", name)
 }
 
+/// Rust type of a value of `ty` as it is stored in a variable or passed to a
+/// function value: structs are `Option<Box<..>>` and function values are
+/// `__FractalFn<dyn Fn(..)>`.
+fn sem_type_str(ty: &SemType) -> String {
+    match ty {
+        SemType::Int => "i64".into(),
+        SemType::Float => "f64".into(),
+        SemType::Char => "char".into(),
+        SemType::Boolean => "bool".into(),
+        SemType::Void => "()".into(),
+        SemType::Array { elem, size } => format!("[{}; {}]", sem_type_str(elem), size),
+        SemType::List { elem } => format!("Vec<{}>", sem_type_str(elem)),
+        SemType::Struct(name) => match name.rsplit_once("::") {
+            Some((module, n)) => format!(
+                "Option<Box<fractal_{}::{}>>",
+                module.replace("::", "::fractal_"),
+                escape_struct_name(n)
+            ),
+            None => format!("Option<Box<{}>>", escape_struct_name(name)),
+        },
+        SemType::Func { params, ret } => format!("__FractalFn<{}>", dyn_fn_str(params, ret)),
+        SemType::Unknown => "_".into(),
+    }
+}
+
+fn dyn_fn_str(params: &[SemType], ret: &SemType) -> String {
+    let ps: Vec<String> = params.iter().map(sem_type_str).collect();
+    match ret {
+        SemType::Void => format!("dyn Fn({})", ps.join(", ")),
+        _ => format!("dyn Fn({}) -> {}", ps.join(", "), sem_type_str(ret)),
+    }
+}
+
 fn escape_char(c: char) -> String {
     match c {
         '\n' => "\\n".into(),
@@ -2724,13 +3788,15 @@ fn stmt_debug_label(node: &ParseNode) -> String {
                     }
                 }
                 if steps.len() == 2 {
-                    if let (AccessStep::Field(fname), AccessStep::Call(_)) = (&steps[0], &steps[1])
+                    if let (AccessStep::Field(fname, _), AccessStep::Call(_)) =
+                        (&steps[0], &steps[1])
                     {
                         return format!("Call {}::{}", base, fname);
                     }
                 }
                 if let Some(AccessStep::Call(_)) = steps.last() {
-                    if let Some(AccessStep::Field(fname)) = steps.get(steps.len().saturating_sub(2))
+                    if let Some(AccessStep::Field(fname, _)) =
+                        steps.get(steps.len().saturating_sub(2))
                     {
                         return format!("Call {}::{}", base, fname);
                     }
@@ -2762,6 +3828,16 @@ fn stmt_source_line(node: &ParseNode) -> usize {
     }
 }
 
+fn sem_type_label(ty: &SemType) -> String {
+    match ty {
+        SemType::Array { .. } => ":array".into(),
+        SemType::List { .. } => ":list".into(),
+        SemType::Struct(name) => format!(":struct<{}>", name),
+        SemType::Func { .. } => ":func".into(),
+        _ => "?".into(),
+    }
+}
+
 fn parse_node_type_label(node: &ParseNode) -> String {
     match node {
         ParseNode::TypeInt(_) => ":int".into(),
@@ -2771,6 +3847,7 @@ fn parse_node_type_label(node: &ParseNode) -> String {
         ParseNode::TypeVoid(_) => ":void".into(),
         ParseNode::TypeArray { .. } => ":array".into(),
         ParseNode::TypeList { .. } => ":list".into(),
+        ParseNode::TypeFunc { .. } => ":func".into(),
         ParseNode::TypeStruct { name, .. } => format!(":struct<{}>", name),
         _ => "?".into(),
     }
@@ -0,0 +1,359 @@
+use crate::compiler::lexer::try_tokenize;
+use crate::compiler::parser::{parse_with_source, ParseNode};
+use crate::compiler::semanter::analyze;
+
+/// A breakpoint condition that has been parsed and type-checked. `file` is
+/// empty for the main program and the module name otherwise, matching the
+/// location the debug runtime reports for a snapshot.
+#[derive(Debug, Clone)]
+pub struct BreakCondition {
+    pub file: String,
+    pub line: usize,
+    pub expr: ParseNode,
+}
+
+/// Parses `text` as a single Fractal expression.
+pub fn parse_expr(text: &str) -> Result<ParseNode, String> {
+    if text.trim().is_empty() {
+        return Err("expected an expression".into());
+    }
+    let wrapped = format!("!start\n{};\n!end\n", text.trim().trim_end_matches(';'));
+    let tokens = try_tokenize(&wrapped, "<expr>").ok_or("invalid character in expression")?;
+    let program = parse_with_source(tokens, "<expr>").map_err(|e| first_line(&e.message))?;
+    match program {
+        ParseNode::Program(mut items) if items.len() == 1 => match items.pop() {
+            Some(ParseNode::ExprStmt(expr, _)) => Ok(*expr),
+            _ => Err("expected an expression, not a statement".into()),
+        },
+        _ => Err("expected a single expression".into()),
+    }
+}
+
+/// Parses `text` and type-checks it against the variables visible at `line`
+/// of `file` in `root`. With `want_bool` the expression must be a `:bool`,
+/// as a breakpoint condition is.
+pub fn check(
+    root: &ParseNode,
+    file: &str,
+    line: usize,
+    text: &str,
+    want_bool: bool,
+) -> Result<ParseNode, String> {
+    let expr = parse_expr(text)?;
+
+    let vars = visible_vars(root, file, line);
+    let stmt = if want_bool {
+        ParseNode::If {
+            condition: Box::new(expr.clone()),
+            then_block: vec![],
+            else_block: None,
+            line: 0,
+        }
+    } else {
+        ParseNode::ExprStmt(Box::new(expr.clone()), 0)
+    };
+
+    let definitions = program_with(root, file, &[]);
+    let probe = program_with(root, file, &with_decls(vars, stmt));
+
+    let known = analyze(&definitions).errors;
+    let errors: Vec<String> = analyze(&probe)
+        .errors
+        .into_iter()
+        .filter(|e| !known.iter().any(|k| k.message == e.message))
+        .map(|e| first_line(&e.message))
+        .collect();
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(expr),
+    }
+}
+
+/// Every variable in scope once the statement on `line` of `file` has run,
+/// innermost last. Inside a function that is its parameters and the locals
+/// declared before the line in the enclosing blocks; in the main program or
+/// a module body it is the declarations before the line.
+pub fn visible_vars(root: &ParseNode, file: &str, line: usize) -> Vec<(String, ParseNode)> {
+    let Some(items) = items_of(root, file) else {
+        return vec![];
+    };
+
+    let mut vars = Vec::new();
+    for item in items {
+        if let ParseNode::FuncDef { params, body, .. } = item {
+            if block_contains(body, line) {
+                for p in params {
+                    if let ParseNode::Param {
+                        data_type, name, ..
+                    } = p
+                    {
+                        vars.push((name.clone(), (**data_type).clone()));
+                    }
+                }
+                collect_block(body, line, &mut vars);
+                return dedup(vars);
+            }
+        }
+    }
+
+    let body: Vec<ParseNode> = items
+        .iter()
+        .filter(|i| !is_definition(i))
+        .cloned()
+        .collect();
+    collect_block(&body, line, &mut vars);
+    dedup(vars)
+}
+
+/// Whether `file` names the main program (empty) or a module of `root`.
+pub fn has_file(root: &ParseNode, file: &str) -> bool {
+    items_of(root, file).is_some()
+}
+
+fn items_of<'a>(node: &'a ParseNode, file: &str) -> Option<&'a [ParseNode]> {
+    match node {
+        ParseNode::Program(items) if file.is_empty() => Some(items),
+        ParseNode::Module { name, items } if name == file => Some(items),
+        ParseNode::Program(items) | ParseNode::Module { items, .. } => {
+            items.iter().find_map(|i| items_of(i, file))
+        }
+        _ => None,
+    }
+}
+
+fn is_definition(node: &ParseNode) -> bool {
+    matches!(
+        node,
+        ParseNode::FuncDef { .. }
+            | ParseNode::StructDef { .. }
+            | ParseNode::Module { .. }
+            | ParseNode::ConstDecl { .. }
+    )
+}
+
+fn collect_block(stmts: &[ParseNode], line: usize, vars: &mut Vec<(String, ParseNode)>) {
+    for stmt in stmts {
+        match stmt {
+            ParseNode::Decl {
+                data_type,
+                name,
+                line: l,
+                ..
+            } if *l <= line => vars.push((name.clone(), (**data_type).clone())),
+            ParseNode::ConstDecl {
+                data_type,
+                name,
+                line: l,
+                ..
+            } if *l <= line => vars.push((name.clone(), (**data_type).clone())),
+            ParseNode::StructDecl {
+                struct_name,
+                var_name,
+                line: l,
+                ..
+            } if *l <= line => vars.push((
+                var_name.clone(),
+                ParseNode::TypeStruct {
+                    name: struct_name.clone(),
+                    line: *l,
+                    col: 0,
+                },
+            )),
+            ParseNode::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                if block_contains(then_block, line) {
+                    collect_block(then_block, line, vars);
+                } else if let Some(eb) = else_block.as_ref().filter(|b| block_contains(b, line)) {
+                    collect_block(eb, line, vars);
+                }
+            }
+            ParseNode::For {
+                var_type,
+                var_name,
+                body,
+                line: l,
+                ..
+            } if *l == line || block_contains(body, line) => {
+                if !matches!(**var_type, ParseNode::TypeVoid(_)) {
+                    vars.push((var_name.clone(), (**var_type).clone()));
+                }
+                collect_block(body, line, vars);
+            }
+            ParseNode::While { body, .. } if block_contains(body, line) => {
+                collect_block(body, line, vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn block_contains(stmts: &[ParseNode], line: usize) -> bool {
+    stmts.iter().any(|s| {
+        stmt_line(s) == line
+            || match s {
+                ParseNode::If {
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    block_contains(then_block, line)
+                        || else_block.as_ref().is_some_and(|b| block_contains(b, line))
+                }
+                ParseNode::For { body, .. } | ParseNode::While { body, .. } => {
+                    block_contains(body, line)
+                }
+                _ => false,
+            }
+    })
+}
+
+fn stmt_line(node: &ParseNode) -> usize {
+    match node {
+        ParseNode::Decl { line, .. }
+        | ParseNode::ConstDecl { line, .. }
+        | ParseNode::StructDecl { line, .. }
+        | ParseNode::Assign { line, .. }
+        | ParseNode::If { line, .. }
+        | ParseNode::For { line, .. }
+        | ParseNode::While { line, .. }
+        | ParseNode::Return { line, .. }
+        | ParseNode::Exit { line, .. }
+        | ParseNode::Break { line }
+        | ParseNode::Continue { line }
+        | ParseNode::ExprStmt(_, line) => *line,
+        _ => 0,
+    }
+}
+
+// A later declaration of the same name shadows the earlier one; declaring
+// both in one synthetic scope would be rejected by the semanter.
+fn dedup(vars: Vec<(String, ParseNode)>) -> Vec<(String, ParseNode)> {
+    let mut out: Vec<(String, ParseNode)> = Vec::new();
+    for (name, ty) in vars {
+        out.retain(|(n, _)| *n != name);
+        out.push((name, ty));
+    }
+    out
+}
+
+fn with_decls(vars: Vec<(String, ParseNode)>, stmt: ParseNode) -> Vec<ParseNode> {
+    let mut body: Vec<ParseNode> = vars
+        .into_iter()
+        .map(|(name, ty)| match ty {
+            ParseNode::TypeStruct { name: sname, .. } => ParseNode::StructDecl {
+                struct_name: sname,
+                var_name: name,
+                init: None,
+                line: 0,
+                struct_col: 0,
+                col: 0,
+            },
+            ty => ParseNode::Decl {
+                init: placeholder(&ty).map(Box::new),
+                data_type: Box::new(ty),
+                name,
+                line: 0,
+                col: 0,
+            },
+        })
+        .collect();
+    body.push(stmt);
+    body
+}
+
+// A function variable must be initialised, so it is given a lambda of its
+// type that never returns; only the type matters to the check.
+fn placeholder(ty: &ParseNode) -> Option<ParseNode> {
+    let ParseNode::TypeFunc { params, ret, .. } = ty else {
+        return None;
+    };
+    Some(ParseNode::Lambda {
+        params: params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| ParseNode::Param {
+                data_type: Box::new(p.clone()),
+                name: format!("_{i}"),
+                line: 0,
+                col: 0,
+            })
+            .collect(),
+        return_type: ret.clone(),
+        body: vec![ParseNode::Exit {
+            expr: Box::new(ParseNode::IntLit(0, 0)),
+            line: 0,
+        }],
+        line: 0,
+    })
+}
+
+// The definitions of `root` with the top-level statements of `file` replaced
+// by `body`; the statements of every other file are dropped.
+fn program_with(root: &ParseNode, file: &str, body: &[ParseNode]) -> ParseNode {
+    fn rebuild(items: &[ParseNode], here: bool, file: &str, body: &[ParseNode]) -> Vec<ParseNode> {
+        let mut out: Vec<ParseNode> = items
+            .iter()
+            .filter(|i| is_definition(i))
+            .map(|i| match i {
+                ParseNode::Module { name, items } => ParseNode::Module {
+                    name: name.clone(),
+                    items: rebuild(items, name == file, file, body),
+                },
+                other => other.clone(),
+            })
+            .collect();
+        if here {
+            out.extend(body.iter().cloned());
+        }
+        out
+    }
+    match root {
+        ParseNode::Program(items) => {
+            ParseNode::Program(rebuild(items, file.is_empty(), file, body))
+        }
+        other => other.clone(),
+    }
+}
+
+fn first_line(message: &str) -> String {
+    message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(src: &str) -> ParseNode {
+        let tokens = try_tokenize(src, "").expect("lex");
+        parse_with_source(tokens, "").expect("parse")
+    }
+
+    #[test]
+    fn function_variables_and_parameters_are_in_scope() {
+        let root = program(
+            "!start\n\
+             !func apply(:func<(:int) -> :int> f, :int n) -> :int {\n\
+             :int r = f(n);\n\
+             !return r;\n\
+             }\n\
+             :func<(:int) -> :int> dbl = !func (:int x) -> :int { !return x * 2; };\n\
+             !for (:int i, 0, 3, 1) {\n\
+             print(\"{}\", apply(dbl, i));\n\
+             }\n\
+             !end\n",
+        );
+        assert!(check(&root, "", 8, "i > 1", true).is_ok());
+        assert!(check(&root, "", 8, "dbl(i) > 1", true).is_ok());
+        assert!(check(&root, "", 4, "f(n) == r", true).is_ok());
+        assert!(check(&root, "", 4, "f(r)", true).is_err());
+        assert!(check(&root, "", 8, "dbl(true) > 1", true).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use crate::compiler::parser::{AccessStep, AddOp, CmpOp, MulOp, ParseNode, ShiftOp, UnOp};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Float(f64),
+    Char(char),
+    Boolean(bool),
+}
+
+impl ConstValue {
+    pub fn to_node(&self, line: usize) -> ParseNode {
+        match self {
+            ConstValue::Int(n) => ParseNode::IntLit(*n, line),
+            ConstValue::Float(f) => ParseNode::FloatLit(*f, line),
+            ConstValue::Char(c) => ParseNode::CharLit(*c, line),
+            ConstValue::Boolean(b) => ParseNode::BoolLit(*b, line),
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::Int(n) => write!(f, "{}", n),
+            ConstValue::Float(v) => write!(f, "{:?}", v),
+            ConstValue::Char(c) => write!(f, "{:?}", c),
+            ConstValue::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Why an expression could not be folded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstError {
+    /// The expression reads something other than literals and `!const`s.
+    NotConstant,
+    DivisionByZero,
+    Overflow,
+    ShiftOutOfRange,
+    /// A `:float` result is infinite or NaN, which has no literal.
+    NotFinite,
+}
+
+impl fmt::Display for ConstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstError::NotConstant => write!(f, "expression is not constant"),
+            ConstError::DivisionByZero => write!(f, "division by zero in constant expression"),
+            ConstError::Overflow => write!(f, "constant expression overflows `:int`"),
+            ConstError::ShiftOutOfRange => {
+                write!(f, "shift amount out of range in constant expression")
+            }
+            ConstError::NotFinite => {
+                write!(f, "constant expression is not a finite `:float`")
+            }
+        }
+    }
+}
+
+/// Evaluates `node` at compile time, or `None` if it is not a constant or
+/// would fail at run time. See [`try_fold`] for the reason.
+pub fn fold(node: &ParseNode, lookup: &dyn Fn(&str) -> Option<ConstValue>) -> Option<ConstValue> {
+    try_fold(node, lookup).ok()
+}
+
+/// Evaluates `node` at compile time. `lookup` resolves a (possibly
+/// module-qualified) name to the value of a `!const`; anything that is not a
+/// literal, a constant, or an operator over those is `NotConstant`, and an
+/// operation that would fail at run time reports why.
+pub fn try_fold(
+    node: &ParseNode,
+    lookup: &dyn Fn(&str) -> Option<ConstValue>,
+) -> Result<ConstValue, ConstError> {
+    use ConstValue::*;
+    let fold = |n: &ParseNode| try_fold(n, lookup);
+    let not_const = || ConstError::NotConstant;
+    match node {
+        ParseNode::IntLit(n, _) => Ok(Int(*n)),
+        ParseNode::FloatLit(f, _) => finite(*f),
+        ParseNode::CharLit(c, _) => Ok(Char(*c)),
+        ParseNode::BoolLit(b, _) => Ok(Boolean(*b)),
+        ParseNode::Identifier(name, _) => lookup(name).ok_or_else(not_const),
+        ParseNode::AccessChain { base, steps, .. } => {
+            let mut name = base.clone();
+            for step in steps {
+                match step {
+                    AccessStep::Field(f, _) => name = format!("{}::{}", name, f),
+                    _ => return Err(ConstError::NotConstant),
+                }
+            }
+            lookup(&name).ok_or_else(not_const)
+        }
+
+        ParseNode::Unary { op, operand, .. } => match (op, fold(operand)?) {
+            (UnOp::Neg, Int(n)) => n.checked_neg().map(Int).ok_or(ConstError::Overflow),
+            (UnOp::Neg, Float(f)) => Ok(Float(-f)),
+            (UnOp::BitNot, Int(n)) => Ok(Int(!n)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::LogNot { operand, .. } => match fold(operand)? {
+            Boolean(b) => Ok(Boolean(!b)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::LogAnd { left, right, .. } => match (fold(left)?, fold(right)?) {
+            (Boolean(a), Boolean(b)) => Ok(Boolean(a && b)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::LogOr { left, right, .. } => match (fold(left)?, fold(right)?) {
+            (Boolean(a), Boolean(b)) => Ok(Boolean(a || b)),
+            _ => Err(ConstError::NotConstant),
+        },
+
+        ParseNode::Cmp {
+            left, op, right, ..
+        } => {
+            let l = fold(left)?;
+            let r = fold(right)?;
+            let ord = match (&l, &r) {
+                (Int(a), Int(b)) => a.partial_cmp(b),
+                (Float(a), Float(b)) => a.partial_cmp(b),
+                (Char(a), Char(b)) => a.partial_cmp(b),
+                (Boolean(a), Boolean(b)) => a.partial_cmp(b),
+                _ => return Err(ConstError::NotConstant),
+            };
+            let res = match op {
+                CmpOp::EqEq => l == r,
+                CmpOp::Ne => l != r,
+                CmpOp::Gt => ord.ok_or_else(not_const)? == std::cmp::Ordering::Greater,
+                CmpOp::Lt => ord.ok_or_else(not_const)? == std::cmp::Ordering::Less,
+                CmpOp::Ge => ord.ok_or_else(not_const)? != std::cmp::Ordering::Less,
+                CmpOp::Le => ord.ok_or_else(not_const)? != std::cmp::Ordering::Greater,
+            };
+            Ok(Boolean(res))
+        }
+
+        ParseNode::BitOr { left, right, .. } => match (fold(left)?, fold(right)?) {
+            (Int(a), Int(b)) => Ok(Int(a | b)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::BitXor { left, right, .. } => match (fold(left)?, fold(right)?) {
+            (Int(a), Int(b)) => Ok(Int(a ^ b)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::BitAnd { left, right, .. } => match (fold(left)?, fold(right)?) {
+            (Int(a), Int(b)) => Ok(Int(a & b)),
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::BitShift {
+            left, op, right, ..
+        } => match (fold(left)?, fold(right)?) {
+            (Int(a), Int(b)) => {
+                let sh = u32::try_from(b).map_err(|_| ConstError::ShiftOutOfRange)?;
+                match op {
+                    ShiftOp::Left => a.checked_shl(sh),
+                    ShiftOp::Right => a.checked_shr(sh),
+                }
+                .map(Int)
+                .ok_or(ConstError::ShiftOutOfRange)
+            }
+            _ => Err(ConstError::NotConstant),
+        },
+
+        ParseNode::Add {
+            left, op, right, ..
+        } => match (fold(left)?, fold(right)?) {
+            (Int(a), Int(b)) => match op {
+                AddOp::Add => a.checked_add(b),
+                AddOp::Sub => a.checked_sub(b),
+            }
+            .map(Int)
+            .ok_or(ConstError::Overflow),
+            (Float(a), Float(b)) => match op {
+                AddOp::Add => finite(a + b),
+                AddOp::Sub => finite(a - b),
+            },
+            _ => Err(ConstError::NotConstant),
+        },
+        ParseNode::Mul {
+            left, op, right, ..
+        } => match (fold(left)?, fold(right)?) {
+            (Int(_), Int(0)) if matches!(op, MulOp::Div | MulOp::Mod) => {
+                Err(ConstError::DivisionByZero)
+            }
+            (Int(a), Int(b)) => match op {
+                MulOp::Mul => a.checked_mul(b),
+                MulOp::Div => a.checked_div(b),
+                MulOp::Mod => a.checked_rem(b),
+            }
+            .map(Int)
+            .ok_or(ConstError::Overflow),
+            (Float(a), Float(b)) => match op {
+                MulOp::Mul => finite(a * b),
+                MulOp::Div => finite(a / b),
+                MulOp::Mod => finite(a % b),
+            },
+            _ => Err(ConstError::NotConstant),
+        },
+
+        ParseNode::Cast {
+            target_type, expr, ..
+        } => {
+            let v = fold(expr)?;
+            match (target_type.as_ref(), v) {
+                (ParseNode::TypeInt(_), Int(n)) => Ok(Int(n)),
+                (ParseNode::TypeInt(_), Float(f)) => Ok(Int(f as i64)),
+                (ParseNode::TypeInt(_), Char(c)) => Ok(Int(c as i64)),
+                (ParseNode::TypeInt(_), Boolean(b)) => Ok(Int(b as i64)),
+                (ParseNode::TypeFloat(_), Int(n)) => Ok(Float(n as f64)),
+                (ParseNode::TypeFloat(_), Float(f)) => Ok(Float(f)),
+                (ParseNode::TypeFloat(_), Boolean(b)) => Ok(Float(if b { 1.0 } else { 0.0 })),
+                (ParseNode::TypeChar(_), Char(c)) => Ok(Char(c)),
+                (ParseNode::TypeChar(_), Int(n)) => u32::try_from(n)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(Char)
+                    .ok_or(ConstError::NotConstant),
+                (ParseNode::TypeBoolean(_), Boolean(b)) => Ok(Boolean(b)),
+                (ParseNode::TypeBoolean(_), Int(n)) => Ok(Boolean(n != 0)),
+                (ParseNode::TypeBoolean(_), Float(f)) => Ok(Boolean(f != 0.0)),
+                _ => Err(ConstError::NotConstant),
+            }
+        }
+
+        _ => Err(ConstError::NotConstant),
+    }
+}
+
+fn finite(f: f64) -> Result<ConstValue, ConstError> {
+    if f.is_finite() {
+        Ok(ConstValue::Float(f))
+    } else {
+        Err(ConstError::NotFinite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::lex;
+    use crate::compiler::parser::parse;
+    use crate::compiler::semanter::analyze;
+
+    fn int(n: i64) -> Box<ParseNode> {
+        Box::new(ParseNode::IntLit(n, 1))
+    }
+
+    fn mul(left: i64, op: MulOp, right: i64) -> ParseNode {
+        ParseNode::Mul {
+            left: int(left),
+            op,
+            right: int(right),
+            line: 1,
+        }
+    }
+
+    fn no_consts(_: &str) -> Option<ConstValue> {
+        None
+    }
+
+    fn const_errors(src: &str) -> Vec<String> {
+        let tokens = lex(src, "test.fr").expect("lex");
+        let root = parse(tokens).expect("parse");
+        analyze(&root)
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[test]
+    fn folds_arithmetic_and_names() {
+        let node = ParseNode::Add {
+            left: Box::new(mul(6, MulOp::Mul, 7)),
+            op: AddOp::Sub,
+            right: Box::new(ParseNode::Identifier("k".into(), 1)),
+            line: 1,
+        };
+        let lookup = |n: &str| (n == "k").then_some(ConstValue::Int(2));
+        assert_eq!(try_fold(&node, &lookup), Ok(ConstValue::Int(40)));
+    }
+
+    #[test]
+    fn unknown_name_is_not_constant() {
+        let node = ParseNode::Identifier("x".into(), 1);
+        assert_eq!(try_fold(&node, &no_consts), Err(ConstError::NotConstant));
+        assert_eq!(fold(&node, &no_consts), None);
+    }
+
+    #[test]
+    fn division_by_zero_is_reported() {
+        for op in [MulOp::Div, MulOp::Mod] {
+            assert_eq!(
+                try_fold(&mul(1, op, 0), &no_consts),
+                Err(ConstError::DivisionByZero)
+            );
+        }
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        assert_eq!(
+            try_fold(&mul(i64::MAX, MulOp::Mul, 2), &no_consts),
+            Err(ConstError::Overflow)
+        );
+        assert_eq!(
+            try_fold(&mul(i64::MIN, MulOp::Div, -1), &no_consts),
+            Err(ConstError::Overflow)
+        );
+        let neg = ParseNode::Unary {
+            op: UnOp::Neg,
+            operand: int(i64::MIN),
+            line: 1,
+        };
+        assert_eq!(try_fold(&neg, &no_consts), Err(ConstError::Overflow));
+    }
+
+    #[test]
+    fn semanter_reports_the_fold_error() {
+        let errors = const_errors("!start\n!const :int a = 1 / 0;\n!end\n");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("division by zero in constant expression"));
+        assert!(!errors[0].contains("must be known at compile time"));
+
+        let errors = const_errors("!start\n!const :int b = 9223372036854775807 + 1;\n!end\n");
+        assert!(errors[0].contains("overflows `:int`"), "{:?}", errors);
+
+        let errors = const_errors("!start\n!const :float big = 1.0e308 * 10.0;\n!end\n");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("not a finite `:float`"), "{:?}", errors);
+    }
+
+    #[test]
+    fn non_finite_floats_are_reported() {
+        let float = |f: f64| Box::new(ParseNode::FloatLit(f, 1));
+        let node = |left: f64, op: MulOp, right: f64| ParseNode::Mul {
+            left: float(left),
+            op,
+            right: float(right),
+            line: 1,
+        };
+        for (left, op, right) in [
+            (1.0e308, MulOp::Mul, 10.0),
+            (1.0, MulOp::Div, 0.0),
+            (1.0, MulOp::Mod, 0.0),
+        ] {
+            assert_eq!(
+                try_fold(&node(left, op, right), &no_consts),
+                Err(ConstError::NotFinite)
+            );
+        }
+        assert_eq!(
+            try_fold(&node(1.5, MulOp::Mul, 2.0), &no_consts),
+            Ok(ConstValue::Float(3.0))
+        );
+    }
+}
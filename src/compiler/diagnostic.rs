@@ -0,0 +1,212 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by one of the front-end stages. The stages return these
+/// rather than printing them, so that the editor can check code as it is
+/// typed; the command-line compiler prints them with [`Diagnostic::emit`].
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Such as `E006` or `P003`; empty for semantic errors, which have none.
+    pub code: String,
+    pub message: String,
+    /// Printed under the underlined span.
+    pub label: String,
+    pub hints: Vec<String>,
+    pub notes: Vec<String>,
+    /// The file as the compiler was given it or resolved it from an import.
+    pub path: String,
+    /// Empty for the program being compiled, otherwise the module name, as
+    /// in `snapshot::Snapshot::file`.
+    pub file: String,
+    /// 1-based, or 0 when the problem has no line.
+    pub line: usize,
+    /// 1-based, or 0 when only the line is known.
+    pub col: usize,
+    /// Characters to underline from `col`; 0 for the token at `col`.
+    pub len: usize,
+    /// The text of `line`, for printing.
+    pub source_line: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: code.to_string(),
+            message: message.into(),
+            label: String::new(),
+            hints: Vec::new(),
+            notes: Vec::new(),
+            path: String::new(),
+            file: String::new(),
+            line: 0,
+            col: 0,
+            len: 0,
+            source_line: String::new(),
+        }
+    }
+
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    /// Builds a diagnostic from a message in the parser and semanter style:
+    /// a first line, then lines starting `hint:` or `note:`.
+    pub fn from_message(severity: Severity, code: &str, message: &str) -> Self {
+        let mut lines = message.lines();
+        let mut d = Self::new(severity, code, lines.next().unwrap_or(message));
+        for raw in lines {
+            let trimmed = raw.trim();
+            if let Some(t) = trimmed.strip_prefix("hint:") {
+                d = d.hint(t.trim());
+            } else if let Some(t) = trimmed.strip_prefix("note:") {
+                d = d.note(t.trim());
+            } else if !trimmed.is_empty() {
+                d = d.note(trimmed);
+            }
+        }
+        d
+    }
+
+    /// Places the diagnostic in `path`, a file of the program rooted at
+    /// `source_file`.
+    pub fn in_file(mut self, path: &str, source_file: &str) -> Self {
+        self.file = module_key(path, source_file);
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn at(mut self, line: usize, col: usize, len: usize) -> Self {
+        self.line = line;
+        self.col = col;
+        self.len = len;
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        let hint = hint.into();
+        if !hint.is_empty() {
+            self.hints.push(hint);
+        }
+        self
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn source_line(mut self, text: impl Into<String>) -> Self {
+        self.source_line = text.into();
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Prints the diagnostic to stderr the way the compiler reports
+    /// problems.
+    pub fn emit(&self) {
+        let (kind, colour) = match self.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+        };
+        if self.code.is_empty() {
+            eprintln!(
+                "\x1b[{colour}m{kind}\x1b[0m\x1b[1m: {}\x1b[0m",
+                self.message
+            );
+        } else {
+            eprintln!(
+                "\x1b[{colour}m{kind}[{}]\x1b[0m\x1b[1m: {}\x1b[0m",
+                self.code, self.message
+            );
+        }
+
+        let display_file = Path::new(&self.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(&self.path);
+        match (self.line, self.col) {
+            (0, _) => eprintln!(" \x1b[1;34m-->\x1b[0m {display_file}"),
+            (line, 0) => eprintln!(" \x1b[1;34m-->\x1b[0m {display_file}:{line}"),
+            (line, col) => eprintln!(" \x1b[1;34m-->\x1b[0m {display_file}:{line}:{col}"),
+        }
+
+        let line_str = self.line.to_string();
+        let pad = " ".repeat(line_str.len());
+        if self.line > 0 && !self.source_line.is_empty() {
+            eprintln!(" \x1b[1;34m{pad} |\x1b[0m");
+            eprintln!(" \x1b[1;34m{line_str} |\x1b[0m {}", self.source_line);
+            if self.col > 0 {
+                let caret_pad = " ".repeat(self.col - 1);
+                let underline = "^".repeat(self.span_len(&self.source_line));
+                eprintln!(
+                    " \x1b[1;34m{pad} |\x1b[0m \x1b[{colour}m{caret_pad}{underline} {}\x1b[0m",
+                    self.label
+                );
+            }
+        }
+        for hint in &self.hints {
+            eprintln!(" \x1b[1;34m{pad} =\x1b[0m \x1b[1;32mhint\x1b[0m: {hint}");
+        }
+        for note in &self.notes {
+            eprintln!(" \x1b[1;34m{pad} =\x1b[0m \x1b[1;36mnote\x1b[0m: {note}");
+        }
+        eprintln!();
+    }
+
+    /// How many characters of `line_text`, the text of the diagnostic's
+    /// line, to underline: `len`, or the token at `col` when `len` is 0.
+    pub fn span_len(&self, line_text: &str) -> usize {
+        if self.len > 0 {
+            return self.len;
+        }
+        let rest: String = line_text.chars().skip(self.col.saturating_sub(1)).collect();
+        rest.find(|c: char| c.is_whitespace() || "(){}[];,".contains(c))
+            .filter(|&n| n > 0)
+            .map(|n| rest[..n].chars().count())
+            .unwrap_or_else(|| rest.chars().count().max(1))
+    }
+}
+
+/// How diagnostics, snapshots and profiles name `path` in a program rooted at
+/// `source_file`: empty for the root and the module name for anything it
+/// imports.
+pub fn module_key(path: &str, source_file: &str) -> String {
+    if path == source_file {
+        String::new()
+    } else {
+        Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Prints every diagnostic, errors after warnings, as the compiler reports
+/// them.
+pub fn emit_all(diagnostics: &[Diagnostic]) {
+    for d in diagnostics.iter().filter(|d| !d.is_error()) {
+        d.emit();
+    }
+    for d in diagnostics.iter().filter(|d| d.is_error()) {
+        d.emit();
+    }
+}
@@ -0,0 +1,864 @@
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::lexer::{lex, Token, TokenType};
+use crate::compiler::parser::{parse_with_source, AccessStep, ParseNode};
+
+const INDENT: &str = "    ";
+
+/// Formats `src`, or returns it unchanged when it does not parse.
+pub fn format_code(src: &str) -> String {
+    try_format(src, "").unwrap_or_else(|_| src.to_string())
+}
+
+/// Lays `src` out from its parse tree: one statement per line, four spaces
+/// per block, operators spaced by what they are in the grammar, comments
+/// kept where they were and at most one blank line in a row. Line breaks
+/// inside brackets are kept after a `,` or an opening bracket and before a
+/// closing one. Formatting the result again gives it back unchanged.
+///
+/// Returns the errors of `src`, placed in `source_file`, when it cannot be
+/// tokenized or parsed. Should the result lex to different tokens than
+/// `src`, which would change what the program means, `src` is returned.
+pub fn try_format(src: &str, source_file: &str) -> Result<String, Vec<Diagnostic>> {
+    let (pieces, tokens) = pieces(src, source_file)?;
+
+    // `!import` is expanded by the preprocessor, so the parser never sees it.
+    let mut parsed = Vec::with_capacity(tokens.len());
+    let mut in_import = false;
+    for token in &tokens {
+        in_import |= token.token_type == TokenType::Import;
+        if !in_import {
+            parsed.push(token.clone());
+        } else if token.token_type == TokenType::EndL {
+            in_import = false;
+        }
+    }
+    let root =
+        parse_with_source(parsed, source_file).map_err(|e| vec![e.diagnostic(source_file)])?;
+    let Some(marks) = Layout::marks(&root, &tokens) else {
+        return Ok(src.to_string());
+    };
+    let mut marks = marks.into_iter();
+    let marks: Vec<Mark> = pieces
+        .iter()
+        .map(|p| match p.kind {
+            Kind::Token(_) => marks.next().unwrap_or(Mark::Inline),
+            Kind::LineComment | Kind::BlockComment => Mark::Inline,
+        })
+        .collect();
+
+    let mut printer = Printer::default();
+    let mut i = 0;
+    while i < pieces.len() {
+        i += printer.piece(&pieces, &marks, i);
+    }
+    let out = printer.finish();
+
+    let same = match (token_types(src), token_types(&out)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    };
+    Ok(if same { out } else { src.to_string() })
+}
+
+enum Kind {
+    Token(TokenType),
+    LineComment,
+    BlockComment,
+}
+
+struct Piece {
+    kind: Kind,
+    text: String,
+    // The line breaks between the end of the previous piece and this one.
+    breaks: usize,
+}
+
+// `src` with every comment blanked out (newlines kept, so that tokens stay on
+// their lines), and the comments with their offsets in it. Comments are found
+// the way the preprocessor strips them.
+fn split_comments(src: &str) -> (String, Vec<(usize, usize, String, bool)>) {
+    let chars: Vec<char> = src.chars().collect();
+    let mut blanked = String::with_capacity(src.len());
+    let mut comments = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                blanked.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != '\n' {
+                    let c = chars[i];
+                    blanked.push(c);
+                    i += 1;
+                    if c == '\\' && i < chars.len() {
+                        blanked.push(chars[i]);
+                        i += 1;
+                    } else if c == '"' {
+                        break;
+                    }
+                }
+            }
+            '\'' => {
+                blanked.push('\'');
+                i += 1;
+                if i < chars.len() {
+                    let c = chars[i];
+                    blanked.push(c);
+                    i += 1;
+                    if c == '\\' && i < chars.len() {
+                        blanked.push(chars[i]);
+                        i += 1;
+                    }
+                }
+                if i < chars.len() && chars[i] == '\'' {
+                    blanked.push('\'');
+                    i += 1;
+                }
+            }
+            '#' => {
+                let block = chars[i..].starts_with(&['#', '#', '#']);
+                let start = i;
+                if block {
+                    i += 3;
+                    while i < chars.len() && !chars[i..].starts_with(&['#', '#', '#']) {
+                        i += 1;
+                    }
+                    i = (i + 3).min(chars.len());
+                } else {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let at = blanked.len();
+                blanked.extend(text.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+                comments.push((at, blanked.len(), text, block));
+            }
+            c => {
+                blanked.push(c);
+                i += 1;
+            }
+        }
+    }
+    (blanked, comments)
+}
+
+// The tokens and comments of `src` in order, each with its text as written,
+// and the tokens themselves.
+fn pieces(src: &str, source_file: &str) -> Result<(Vec<Piece>, Vec<Token>), Vec<Diagnostic>> {
+    let (blanked, comments) = split_comments(src);
+    let tokens = lex(&blanked, source_file)?;
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(blanked.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset = |line: usize, col: usize| {
+        let start = starts
+            .get(line.saturating_sub(1))
+            .copied()
+            .unwrap_or(blanked.len());
+        blanked[start..]
+            .char_indices()
+            .nth(col.saturating_sub(1))
+            .map_or(blanked.len(), |(i, _)| start + i)
+    };
+    let token_at: Vec<usize> = tokens.iter().map(|t| offset(t.line, t.col)).collect();
+
+    // (start, end, kind, text)
+    let mut spans: Vec<(usize, usize, Kind, String)> = Vec::new();
+    for (k, token) in tokens.iter().enumerate() {
+        let start = token_at[k];
+        let next = token_at.get(k + 1).copied().unwrap_or(blanked.len());
+        let text = blanked[start..next.max(start)].trim_end();
+        spans.push((
+            start,
+            start + text.len(),
+            Kind::Token(token.token_type.clone()),
+            text.to_string(),
+        ));
+    }
+    for (start, end, text, block) in comments {
+        let kind = if block {
+            Kind::BlockComment
+        } else {
+            Kind::LineComment
+        };
+        spans.push((start, end, kind, text));
+    }
+    spans.sort_by_key(|s| s.0);
+
+    let mut prev_end = 0;
+    let pieces = spans
+        .into_iter()
+        .map(|(start, end, kind, text)| {
+            let breaks = blanked[prev_end.min(start)..start].matches('\n').count();
+            prev_end = end;
+            Piece { kind, text, breaks }
+        })
+        .collect();
+    Ok((pieces, tokens))
+}
+
+// The token types of `src` with its comments left out, if it lexes.
+fn token_types(src: &str) -> Option<Vec<TokenType>> {
+    let (blanked, _) = split_comments(src);
+    let tokens = lex(&blanked, "").ok()?;
+    Some(tokens.into_iter().map(|t| t.token_type).collect())
+}
+
+// Where a token goes, as the parse tree has it.
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    // Carries on the line it is on.
+    Inline,
+    // Starts a statement, nested this deep.
+    Line(usize),
+    // The `{` of a statement's block.
+    Block,
+    // The `}` or `!end` closing a block whose statement is this deep.
+    Close(usize),
+}
+
+// Walks a parse tree alongside the tokens it was parsed from, marking the
+// tokens that start and end its statements and blocks.
+struct Layout<'a> {
+    tokens: &'a [Token],
+    marks: Vec<Mark>,
+    pos: usize,
+}
+
+impl<'a> Layout<'a> {
+    // The mark of every token in `tokens`, or `None` when they do not line
+    // up with `root`.
+    fn marks(root: &ParseNode, tokens: &'a [Token]) -> Option<Vec<Mark>> {
+        let ParseNode::Program(items) = root else {
+            return None;
+        };
+        let mut layout = Layout {
+            tokens,
+            marks: vec![Mark::Inline; tokens.len()],
+            pos: 0,
+        };
+        layout.expect(&TokenType::Start, Mark::Line(0))?;
+        layout.items(items, 1)?;
+        layout.expect(&TokenType::End, Mark::Close(0))?;
+        (layout.pos == tokens.len()).then_some(layout.marks)
+    }
+
+    fn peek(&self) -> Option<&'a TokenType> {
+        self.tokens.get(self.pos).map(|t| &t.token_type)
+    }
+
+    fn mark(&mut self, mark: Mark) -> Option<()> {
+        *self.marks.get_mut(self.pos)? = mark;
+        Some(())
+    }
+
+    fn expect(&mut self, tt: &TokenType, mark: Mark) -> Option<()> {
+        if self.peek()? != tt {
+            return None;
+        }
+        self.mark(mark)?;
+        self.pos += 1;
+        Some(())
+    }
+
+    fn items(&mut self, nodes: &[ParseNode], depth: usize) -> Option<()> {
+        for node in nodes {
+            self.imports(depth)?;
+            self.stmt(node, depth)?;
+        }
+        self.imports(depth)
+    }
+
+    // `!import` statements, which the parse tree leaves out.
+    fn imports(&mut self, depth: usize) -> Option<()> {
+        while self.peek() == Some(&TokenType::Import) {
+            self.mark(Mark::Line(depth))?;
+            while self.peek()? != &TokenType::EndL {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+        Some(())
+    }
+
+    fn stmt(&mut self, node: &ParseNode, depth: usize) -> Option<()> {
+        if stmt_line(node)? != self.tokens.get(self.pos)?.line {
+            return None;
+        }
+        self.mark(Mark::Line(depth))?;
+        match node {
+            ParseNode::FuncDef { body, .. }
+            | ParseNode::For { body, .. }
+            | ParseNode::While { body, .. } => {
+                self.header()?;
+                self.block(body, depth)
+            }
+            ParseNode::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                self.header()?;
+                self.block(then_block, depth)?;
+                self.else_tail(else_block.as_deref(), depth)
+            }
+            ParseNode::StructDef { fields, .. } => {
+                self.header()?;
+                for _ in fields {
+                    self.mark(Mark::Line(depth + 1))?;
+                    self.rest(&[], depth + 1)?;
+                }
+                self.expect(&TokenType::RBrace, Mark::Close(depth))?;
+                self.expect(&TokenType::EndL, Mark::Inline)
+            }
+            _ => {
+                let mut found = Vec::new();
+                lambdas(node, &mut found);
+                self.rest(&found, depth)
+            }
+        }
+    }
+
+    // Up to and including the `{` that opens the block of a statement.
+    fn header(&mut self) -> Option<()> {
+        let mut nest = 0usize;
+        loop {
+            match self.peek()? {
+                TokenType::LBrace if nest == 0 => break,
+                TokenType::LParen | TokenType::LBracket | TokenType::LBrace => nest += 1,
+                TokenType::RParen | TokenType::RBracket | TokenType::RBrace => {
+                    nest = nest.checked_sub(1)?
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        self.expect(&TokenType::LBrace, Mark::Block)
+    }
+
+    fn block(&mut self, body: &[ParseNode], depth: usize) -> Option<()> {
+        self.items(body, depth + 1)?;
+        self.expect(&TokenType::RBrace, Mark::Close(depth))
+    }
+
+    fn else_tail(&mut self, else_block: Option<&[ParseNode]>, depth: usize) -> Option<()> {
+        let Some(nodes) = else_block else {
+            return Some(());
+        };
+        match (self.peek()?, nodes) {
+            (
+                TokenType::Elif,
+                [ParseNode::If {
+                    then_block,
+                    else_block,
+                    ..
+                }],
+            ) => {
+                self.mark(Mark::Line(depth))?;
+                self.header()?;
+                self.block(then_block, depth)?;
+                self.else_tail(else_block.as_deref(), depth)
+            }
+            (TokenType::Else, _) => {
+                self.mark(Mark::Line(depth))?;
+                self.header()?;
+                self.block(nodes, depth)
+            }
+            _ => None,
+        }
+    }
+
+    // The rest of a statement without a block, through its `;`, laying out
+    // the bodies of the `lambdas` in it on the way.
+    fn rest(&mut self, lambdas: &[&ParseNode], depth: usize) -> Option<()> {
+        let mut lambdas = lambdas.iter();
+        let mut nest = 0usize;
+        while let Some(tt) = self.peek() {
+            match tt {
+                TokenType::Func => {
+                    let ParseNode::Lambda { body, .. } = lambdas.next()? else {
+                        return None;
+                    };
+                    self.header()?;
+                    self.block(body, depth)?;
+                    continue;
+                }
+                TokenType::EndL if nest == 0 => {
+                    self.pos += 1;
+                    break;
+                }
+                // A statement at the top level may leave out its `;`.
+                TokenType::End => break,
+                TokenType::LParen | TokenType::LBracket | TokenType::LBrace => nest += 1,
+                TokenType::RParen | TokenType::RBracket | TokenType::RBrace => {
+                    match nest.checked_sub(1) {
+                        Some(n) => nest = n,
+                        None => break,
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        lambdas.next().is_none().then_some(())
+    }
+}
+
+// The line a statement starts on.
+fn stmt_line(node: &ParseNode) -> Option<usize> {
+    match node {
+        ParseNode::FuncDef { line, .. }
+        | ParseNode::StructDef { line, .. }
+        | ParseNode::StructDecl { line, .. }
+        | ParseNode::Decl { line, .. }
+        | ParseNode::ConstDecl { line, .. }
+        | ParseNode::Assign { line, .. }
+        | ParseNode::If { line, .. }
+        | ParseNode::For { line, .. }
+        | ParseNode::While { line, .. }
+        | ParseNode::Return { line, .. }
+        | ParseNode::Exit { line, .. }
+        | ParseNode::Break { line }
+        | ParseNode::Continue { line }
+        | ParseNode::ExprStmt(_, line) => Some(*line),
+        _ => None,
+    }
+}
+
+// The lambdas in `node` in source order, leaving out those in their bodies.
+fn lambdas<'n>(node: &'n ParseNode, out: &mut Vec<&'n ParseNode>) {
+    match node {
+        ParseNode::Lambda { .. } => out.push(node),
+        ParseNode::Decl { init, .. } | ParseNode::StructDecl { init, .. } => {
+            if let Some(init) = init {
+                lambdas(init, out);
+            }
+        }
+        ParseNode::ConstDecl { init: expr, .. }
+        | ParseNode::Return { expr, .. }
+        | ParseNode::Exit { expr, .. }
+        | ParseNode::ExprStmt(expr, _)
+        | ParseNode::LogNot { operand: expr, .. }
+        | ParseNode::Unary { operand: expr, .. }
+        | ParseNode::Cast { expr, .. } => lambdas(expr, out),
+        ParseNode::Assign {
+            lvalue: left,
+            expr: right,
+            ..
+        }
+        | ParseNode::LogOr { left, right, .. }
+        | ParseNode::LogAnd { left, right, .. }
+        | ParseNode::Cmp { left, right, .. }
+        | ParseNode::BitOr { left, right, .. }
+        | ParseNode::BitXor { left, right, .. }
+        | ParseNode::BitAnd { left, right, .. }
+        | ParseNode::BitShift { left, right, .. }
+        | ParseNode::Add { left, right, .. }
+        | ParseNode::Mul { left, right, .. } => {
+            lambdas(left, out);
+            lambdas(right, out);
+        }
+        ParseNode::ArrayLit(items, _) => {
+            for item in items {
+                lambdas(item, out);
+            }
+        }
+        ParseNode::StructLit(fields, _) => {
+            for (_, value) in fields {
+                lambdas(value, out);
+            }
+        }
+        ParseNode::AccessChain { steps, .. } => {
+            for step in steps {
+                match step {
+                    AccessStep::Index(index) => lambdas(index, out),
+                    AccessStep::Call(args) => {
+                        for arg in args {
+                            lambdas(arg, out);
+                        }
+                    }
+                    AccessStep::Field(..) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Open {
+    Paren,
+    Bracket,
+    Literal,
+    Generic,
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    // The open brackets, with the indent of the line each was opened on.
+    stack: Vec<(Open, usize)>,
+    // How deep the current statement is.
+    depth: usize,
+    // The indent of the current line, and whether anything is on it yet.
+    indent: usize,
+    started: bool,
+    // A line comment ended the line.
+    broken: bool,
+    // The last thing printed opened a block.
+    after_open: bool,
+    prev: Option<TokenType>,
+    // No space goes after the previous token.
+    glue: bool,
+    // The previous token ends an operand, so a `-` after it is binary.
+    operand: bool,
+    // The previous token closed a type's `<`.
+    generic_close: bool,
+}
+
+fn is_type(tt: &TokenType) -> bool {
+    matches!(
+        tt,
+        TokenType::TypeInt
+            | TokenType::TypeFloat
+            | TokenType::TypeChar
+            | TokenType::TypeBoolean
+            | TokenType::TypeArray
+            | TokenType::TypeList
+            | TokenType::TypeStruct
+            | TokenType::TypeVoid
+            | TokenType::TypeFunc
+    )
+}
+
+impl Printer {
+    fn top(&self) -> Option<Open> {
+        self.stack.last().map(|&(kind, _)| kind)
+    }
+
+    // The indent of a new line that carries on the current statement.
+    fn inner_indent(&self) -> usize {
+        match self.stack.last() {
+            Some(&(_, indent)) if indent >= self.depth => indent + 1,
+            _ => self.depth + 1,
+        }
+    }
+
+    fn new_line(&mut self, indent: usize, blank: bool) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            if blank {
+                self.out.push('\n');
+            }
+        }
+        self.indent = indent;
+        self.started = false;
+        self.broken = false;
+    }
+
+    fn write(&mut self, text: &str, space: bool) {
+        if !self.started {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.started = true;
+        } else if space {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    // Prints piece `i` and returns how many pieces it used up.
+    fn piece(&mut self, pieces: &[Piece], marks: &[Mark], i: usize) -> usize {
+        let piece = &pieces[i];
+        let mark = marks[i];
+        let tt = match &piece.kind {
+            Kind::Token(tt) => tt.clone(),
+            Kind::LineComment | Kind::BlockComment => {
+                let next = (i + 1..pieces.len())
+                    .find(|&k| matches!(pieces[k].kind, Kind::Token(_)))
+                    .map(|k| marks[k]);
+                self.comment(piece, next);
+                return 1;
+            }
+        };
+        let next = pieces[i + 1..].iter().find_map(|p| match &p.kind {
+            Kind::Token(tt) => Some(tt),
+            _ => None,
+        });
+
+        let literal = tt == TokenType::LBrace && mark == Mark::Inline;
+        let generic_open = tt == TokenType::Less
+            && matches!(
+                self.prev,
+                Some(
+                    TokenType::TypeArray
+                        | TokenType::TypeList
+                        | TokenType::TypeStruct
+                        | TokenType::TypeFunc
+                )
+            );
+        let generic_close = tt == TokenType::Greater && self.top() == Some(Open::Generic);
+        let unary =
+            matches!(tt, TokenType::Minus | TokenType::Plus | TokenType::Tilde) && !self.operand;
+        // The first `<` or `>` of a shift, printed against the second.
+        let shift = !generic_open
+            && !generic_close
+            && matches!(tt, TokenType::Less | TokenType::Greater)
+            && next == Some(&tt)
+            && !self.glue;
+        let closes = match tt {
+            TokenType::RBrace => mark == Mark::Inline && self.top() == Some(Open::Literal),
+            TokenType::RParen => self.top() == Some(Open::Paren),
+            TokenType::RBracket => self.top() == Some(Open::Bracket),
+            _ => generic_close,
+        };
+
+        // Where the token goes: on a new line or after the previous one.
+        match mark {
+            Mark::Line(depth) => {
+                let blank = piece.breaks > 1 && !self.after_open;
+                self.depth = depth;
+                self.new_line(depth, blank);
+            }
+            Mark::Close(depth) => {
+                self.depth = depth;
+                self.new_line(depth, false);
+            }
+            Mark::Inline | Mark::Block => {
+                let in_brackets = matches!(
+                    self.top(),
+                    Some(Open::Paren | Open::Bracket | Open::Literal)
+                );
+                let kept_break = in_brackets
+                    && piece.breaks > 0
+                    && (closes
+                        || matches!(
+                            self.prev,
+                            Some(
+                                TokenType::Comma
+                                    | TokenType::LParen
+                                    | TokenType::LBracket
+                                    | TokenType::LBrace
+                            )
+                        ));
+                if self.broken || kept_break {
+                    let indent = if closes {
+                        self.stack.last().map_or(0, |&(_, indent)| indent)
+                    } else {
+                        self.inner_indent()
+                    };
+                    self.new_line(indent, false);
+                }
+            }
+        }
+
+        let space = !self.glue
+            && match tt {
+                TokenType::EndL
+                | TokenType::Comma
+                | TokenType::RParen
+                | TokenType::RBracket
+                | TokenType::ColonColon
+                | TokenType::Dot => false,
+                TokenType::Less | TokenType::Greater => !generic_open && !generic_close,
+                TokenType::LParen => {
+                    !(self.generic_close
+                        || matches!(
+                            self.prev,
+                            Some(
+                                TokenType::Identifier(_) | TokenType::RParen | TokenType::RBracket
+                            )
+                        )
+                        || self.prev.as_ref().is_some_and(is_type))
+                }
+                TokenType::LBracket => !matches!(
+                    self.prev,
+                    Some(TokenType::Identifier(_) | TokenType::RParen | TokenType::RBracket)
+                ),
+                TokenType::RBrace => self.prev != Some(TokenType::LBrace),
+                _ => true,
+            };
+
+        // An empty block stays on one line.
+        let empty_block = mark == Mark::Block
+            && matches!(
+                pieces.get(i + 1),
+                Some(Piece {
+                    kind: Kind::Token(TokenType::RBrace),
+                    ..
+                })
+            );
+        if empty_block {
+            self.write("{}", space);
+        } else {
+            self.write(&piece.text, space);
+        }
+
+        self.glue = unary
+            || shift
+            || generic_open
+            || matches!(
+                tt,
+                TokenType::LParen | TokenType::LBracket | TokenType::ColonColon | TokenType::Dot
+            );
+        self.operand = matches!(
+            tt,
+            TokenType::Identifier(_)
+                | TokenType::SIntLit(_)
+                | TokenType::FloatLit(_)
+                | TokenType::CharLit(_)
+                | TokenType::StringLit(_)
+                | TokenType::BoolLit(_)
+                | TokenType::Null
+                | TokenType::RParen
+                | TokenType::RBracket
+        ) || (tt == TokenType::RBrace && closes);
+        self.generic_close = generic_close;
+        self.after_open = tt == TokenType::Start || (mark == Mark::Block && !empty_block);
+
+        match tt {
+            TokenType::LBrace if literal => self.open(Open::Literal),
+            TokenType::LParen => self.open(Open::Paren),
+            TokenType::LBracket => self.open(Open::Bracket),
+            TokenType::Less if generic_open => self.open(Open::Generic),
+            _ if closes => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+        if empty_block {
+            self.prev = Some(TokenType::RBrace);
+            2
+        } else {
+            self.prev = Some(tt);
+            1
+        }
+    }
+
+    fn open(&mut self, kind: Open) {
+        self.stack.push((kind, self.indent));
+    }
+
+    // `next` is the mark of the token after the comment, if there is one.
+    fn comment(&mut self, piece: &Piece, next: Option<Mark>) {
+        let own_line = piece.breaks > 0 || !self.started;
+        if own_line {
+            let blank = piece.breaks > 1 && !self.after_open;
+            let indent = match next {
+                Some(Mark::Line(depth)) => depth,
+                Some(Mark::Close(depth)) => depth + 1,
+                Some(Mark::Inline | Mark::Block) => self.inner_indent(),
+                None => 0,
+            };
+            self.new_line(indent, blank);
+            self.write(&piece.text, false);
+            self.broken = true;
+        } else {
+            self.write(&piece.text, true);
+            self.broken = matches!(piece.kind, Kind::LineComment);
+        }
+        self.after_open = false;
+        self.glue = false;
+    }
+
+    fn finish(self) -> String {
+        let mut out: String = self
+            .out
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let len = out.trim_end().len();
+        out.truncate(len);
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [&str; 8] = [
+        include_str!("../files/bst.fr"),
+        include_str!("../files/dataStructures.fr"),
+        include_str!("../files/dijkstra.fr"),
+        include_str!("../files/docs.fr"),
+        include_str!("../files/main.fr"),
+        include_str!("../files/mergeSort.fr"),
+        include_str!("../files/operatorTest.fr"),
+        include_str!("../files/poly.fr"),
+    ];
+
+    fn fmt(src: &str) -> String {
+        match try_format(src, "") {
+            Ok(out) => out,
+            Err(errors) => panic!("{src:?} did not format: {}", errors[0].message),
+        }
+    }
+
+    #[test]
+    fn layout_follows_the_parse_tree() {
+        let src = "!start :struct<P> { :int x; }; !func f(:int a) -> :int { \
+                   !if (a > 0) { !return a; } !elif (a == 0) { !return 1; } \
+                   !else { !return -a; } } :func<(:int) -> :int> g = \
+                   !func (:int n) -> :int { !return n * 2; }; \
+                   :struct<P> p = { x = 1 }; !while (p::x < 3) { p::x += 1; } !end";
+        assert_eq!(
+            fmt(src),
+            "!start\n    :struct<P> {\n        :int x;\n    };\n    \
+             !func f(:int a) -> :int {\n        !if (a > 0) {\n            \
+             !return a;\n        }\n        !elif (a == 0) {\n            \
+             !return 1;\n        }\n        !else {\n            \
+             !return -a;\n        }\n    }\n    \
+             :func<(:int) -> :int> g = !func (:int n) -> :int {\n        \
+             !return n * 2;\n    };\n    :struct<P> p = { x = 1 };\n    \
+             !while (p::x < 3) {\n        p::x += 1;\n    }\n!end\n"
+        );
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        for src in SAMPLES {
+            let once = fmt(src);
+            assert_eq!(fmt(&once), once);
+        }
+        let src = "!start\n!import \"./m.fr\";\n:array<:int, 3> xs = [1,\n2, 3];\n\n\n\
+                   !for (:int i, 0, 3, 1) {}\nprint(xs[0]);\n!end";
+        let once = fmt(src);
+        assert_eq!(
+            once,
+            "!start\n    !import \"./m.fr\";\n    :array<:int, 3> xs = [1,\n        2, 3];\n\n    \
+             !for (:int i, 0, 3, 1) {}\n    print(xs[0]);\n!end\n"
+        );
+        assert_eq!(fmt(&once), once);
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let src = "# head\n!start\n:int x = 1; # after x\n### a\nblock ###\n\
+                   !if (x > 0) { # opens\nx = 2;\n# before close\n}\n!end\n# tail\n";
+        let out = fmt(src);
+        assert_eq!(
+            out,
+            "# head\n!start\n    :int x = 1; # after x\n    ### a\nblock ###\n    \
+             !if (x > 0) { # opens\n        x = 2;\n        # before close\n    }\n!end\n# tail\n"
+        );
+        assert_eq!(fmt(&out), out);
+    }
+
+    #[test]
+    fn unparseable_input_is_left_alone() {
+        for src in [
+            "!start\n  :int x = ;\n!end\n",
+            "!start\n  int x = 1;\n!end\n",
+            "!start :int x = 1;",
+            "!start\n  :int x = \"open;\n!end\n",
+        ] {
+            assert!(try_format(src, "").is_err(), "{src:?} formatted");
+            assert_eq!(format_code(src), src);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::compiler::diagnostic::{emit_all, module_key, Diagnostic};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     Start,
@@ -16,6 +18,7 @@ pub enum TokenType {
     Module,
     Break,
     Continue,
+    Const,
 
     And,
     Or,
@@ -29,6 +32,7 @@ pub enum TokenType {
     TypeList,
     TypeStruct,
     TypeVoid,
+    TypeFunc,
 
     SIntLit(i64),
     FloatLit(f64),
@@ -98,28 +102,47 @@ pub struct Token {
     pub file: String,
 }
 
-fn offset_to_line_col(src: &str, offset: usize) -> (usize, usize) {
+// Where byte `offset` of the preprocessed `src` came from: its line and
+// column in the original file, following the `$SRCMAP$` markers as the lexer
+// does, and the number of its line in `src`.
+fn offset_to_line_col(src: &str, offset: usize) -> (usize, usize, usize) {
+    let indexed: Vec<(usize, char)> = src.char_indices().collect();
+    let chars: Vec<char> = indexed.iter().map(|&(_, c)| c).collect();
     let mut line = 1usize;
     let mut col = 1usize;
-    for (i, c) in src.char_indices() {
-        if i == offset {
-            break;
+    let mut src_line = 1usize;
+    let mut i = 0;
+    while i < chars.len() && indexed[i].0 < offset {
+        if chars[i] == '$' {
+            if let Some((tt, next)) = parse_module_marker(&chars, i) {
+                match tt {
+                    TokenType::FileMap(_, l) => {
+                        line = l;
+                        col = 1;
+                    }
+                    _ => col += next - i,
+                }
+                i = next;
+                continue;
+            }
         }
-        if c == '\n' {
+        if chars[i] == '\n' {
             line += 1;
             col = 1;
+            src_line += 1;
         } else {
             col += 1;
         }
+        i += 1;
     }
-    (line, col)
+    (line, col, src_line)
 }
 
 fn get_source_line(src: &str, line: usize) -> &str {
     src.lines().nth(line - 1).unwrap_or("")
 }
 
-fn emit_error(
+fn lex_error(
     src: &str,
     source_file: &str,
     offset: usize,
@@ -128,42 +151,15 @@ fn emit_error(
     title: &str,
     label: &str,
     hint: &str,
-) {
-    let (line, col) = offset_to_line_col(src, offset);
-    let src_line = get_source_line(src, line);
-    let line_str = line.to_string();
-    let pad = " ".repeat(line_str.len());
-    let underline_len = span_len.max(1);
-    let underline = "^".repeat(underline_len);
-    let caret_pad = " ".repeat(col.saturating_sub(1));
-
-    let display_file = std::path::Path::new(source_file)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or(source_file);
-
-    eprintln!(
-        "\x1b[1;31merror[{code}]\x1b[0m\x1b[1m: {title}\x1b[0m",
-        code = code,
-        title = title
-    );
-    eprintln!(
-        " \x1b[1;34m-->\x1b[0m {file}:{line}:{col}",
-        file = display_file,
-        line = line,
-        col = col
-    );
-    eprintln!(" \x1b[1;34m{pad} |\x1b[0m");
-    eprintln!(
-        " \x1b[1;34m{line_str} |\x1b[0m {src_line}",
-        line_str = line_str,
-        src_line = src_line
-    );
-    eprintln!(" \x1b[1;34m{pad} |\x1b[0m \x1b[1;31m{caret_pad}{underline} {label}\x1b[0m");
-    if !hint.is_empty() {
-        eprintln!(" \x1b[1;34m{pad} =\x1b[0m \x1b[1;32mhint\x1b[0m: {hint}");
-    }
-    eprintln!();
+) -> Diagnostic {
+    let (line, col, src_line) = offset_to_line_col(src, offset);
+    let mut d = Diagnostic::error(code, title)
+        .at(line, col, span_len.max(1))
+        .label(label)
+        .hint(hint)
+        .source_line(get_source_line(src, src_line));
+    d.path = source_file.to_string();
+    d
 }
 
 fn is_operator_char(c: char) -> bool {
@@ -204,12 +200,18 @@ fn is_identifier(s: &str) -> bool {
     chars.all(|c| c.is_alphanumeric() || c == '_')
 }
 
-fn closest_keyword(s: &str) -> Option<&'static str> {
-    const KEYWORDS: &[&str] = &[
-        "start", "end", "exit", "if", "elif", "else", "for", "while", "func", "return", "struct",
-        "import", "module", "break", "continue", "and", "or", "not", "null",
-    ];
+/// Every word that can follow `!`.
+pub const KEYWORDS: &[&str] = &[
+    "start", "end", "exit", "if", "elif", "else", "for", "while", "func", "return", "struct",
+    "import", "module", "break", "continue", "const", "and", "or", "not", "null",
+];
+
+/// Every word that can follow `:` in a type.
+pub const TYPE_NAMES: &[&str] = &[
+    "int", "float", "char", "boolean", "array", "list", "struct", "void", "func",
+];
 
+fn closest_keyword(s: &str) -> Option<&'static str> {
     KEYWORDS.iter().copied().find(|kw| {
         let a: Vec<char> = s.chars().collect();
         let b: Vec<char> = kw.chars().collect();
@@ -222,10 +224,7 @@ fn closest_keyword(s: &str) -> Option<&'static str> {
 }
 
 fn closest_type(s: &str) -> Option<&'static str> {
-    const TYPES: &[&str] = &[
-        "int", "float", "char", "boolean", "array", "list", "struct", "void",
-    ];
-    TYPES.iter().copied().find(|t| {
+    TYPE_NAMES.iter().copied().find(|t| {
         let a: Vec<char> = s.chars().collect();
         let b: Vec<char> = t.chars().collect();
         if a.len().abs_diff(b.len()) > 1 {
@@ -320,6 +319,7 @@ fn keyword_map(s: &str) -> TokenType {
         "module" => TokenType::Module,
         "break" => TokenType::Break,
         "continue" => TokenType::Continue,
+        "const" => TokenType::Const,
         "and" => TokenType::And,
         "or" => TokenType::Or,
         "not" => TokenType::Not,
@@ -338,6 +338,7 @@ fn type_map(s: &str) -> TokenType {
         "list" => TokenType::TypeList,
         "struct" => TokenType::TypeStruct,
         "void" => TokenType::TypeVoid,
+        "func" => TokenType::TypeFunc,
         _ => TokenType::NoMatch,
     }
 }
@@ -397,10 +398,37 @@ fn parse_module_marker(chars: &[char], start_index: usize) -> Option<(TokenType,
 }
 
 pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
+    match lex(program, source_file) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            emit_all(&errors);
+            let display_file = std::path::Path::new(source_file)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(source_file);
+            eprintln!(
+                "\x1b[1;31maborting\x1b[0m: lexical error(s) in `{display_file}`; \
+                       fix the above before continuing\n"
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Like [`tokenize_with_source`], but returns `None` on a lexical error
+/// instead of exiting, so that the editor can lex text the user is still
+/// typing.
+pub fn try_tokenize(program: &str, source_file: &str) -> Option<Vec<Token>> {
+    lex(program, source_file).ok()
+}
+
+/// Tokenizes the preprocessed `program`, or returns every lexical error in
+/// it, placed in the file it came from.
+pub fn lex(program: &str, source_file: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
     let chars: Vec<char> = program.chars().collect();
     let mut tokens: Vec<Token> = Vec::new();
     let mut index: usize = 0;
-    let mut had_error = false;
+    let mut errors: Vec<Diagnostic> = Vec::new();
 
     let mut map_file = source_file.to_string();
     let mut map_line: usize = 1;
@@ -472,7 +500,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                 }
                 continue;
             }
-            emit_error(
+            errors.push(lex_error(
                 program,
                 &map_file,
                 byte_off!(token_start),
@@ -481,8 +509,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                 "unexpected character `$`",
                 "not a valid token",
                 "the `$` character is reserved for internal module markers; remove it",
-            );
-            had_error = true;
+            ));
             map_col += 1;
             index += 1;
             continue;
@@ -514,7 +541,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                 index += 1;
             }
             if buffer.is_empty() {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(bang_pos),
@@ -523,9 +550,8 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     "bare `!` with no keyword",
                     "expected a keyword after `!`",
                     "valid keywords: `!if`, `!else`, `!elif`, `!for`, `!while`, `!func`, \
-                     `!return`, `!break`, `!continue`, `!import`, `!start`, `!end`, `!exit`",
-                );
-                had_error = true;
+                     `!return`, `!break`, `!continue`, `!const`, `!import`, `!start`, `!end`, `!exit`",
+                ));
                 continue;
             }
             let result = keyword_map(&buffer);
@@ -542,17 +568,17 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                         format!(
                             "unknown keyword `!{buffer}`; valid keywords: \
                              if, elif, else, for, while, func, return, break, continue, \
-                             import, start, end, exit, struct, module"
+                             const, import, start, end, exit, struct, module"
                         )
                     }
                 } else {
                     format!(
                         "unknown keyword `!{buffer}`; valid keywords: \
                          if, elif, else, for, while, func, return, break, continue, \
-                         import, start, end, exit, struct, module"
+                         const, import, start, end, exit, struct, module"
                     )
                 };
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(bang_pos),
@@ -561,8 +587,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("unknown keyword `!{}`", buffer),
                     "not a recognised keyword",
                     &hint,
-                );
-                had_error = true;
+                ));
             } else {
                 tokens.push(Token {
                     token_type: result,
@@ -589,7 +614,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                 index += 1;
             }
             if buffer.is_empty() {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(colon_pos),
@@ -598,9 +623,8 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     "bare `:` with no type name",
                     "expected a type name after `:`",
                     "types are written as `:int`, `:float`, `:char`, `:boolean`, \
-                     `:array<T,N>`, `:list<T>`, `:struct<n>`, `:void`; for field access use `::`",
-                );
-                had_error = true;
+                     `:array<T,N>`, `:list<T>`, `:struct<n>`, `:func<(T) -> R>`, `:void`; for field access use `::`",
+                ));
                 continue;
             }
             let result = type_map(&buffer);
@@ -618,7 +642,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                          int, float, char, boolean, void; generic: array<T,N>, list<T>, struct<n>"
                     )
                 };
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(colon_pos),
@@ -627,8 +651,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("unknown type `:{}`", buffer),
                     "not a recognised type",
                     &hint,
-                );
-                had_error = true;
+                ));
             } else {
                 tokens.push(Token {
                     token_type: result,
@@ -670,17 +693,16 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                         't' => '\t',
                         'r' => '\r',
                         '\'' => {
-                            emit_error(program, &map_file, byte_off!(index - 1), 2, "E006",
+                            errors.push(lex_error(program, &map_file, byte_off!(index - 1), 2, "E006",
                                 "unnecessary escape `\\'` in string literal",
                                 "invalid escape",
-                                "inside \"...\" strings, `'` does not need escaping - write it as `'` directly");
-                            had_error = true;
+                                "inside \"...\" strings, `'` does not need escaping - write it as `'` directly"));
                             '\''
                         }
                         '"' => '"',
                         '0' => '\0',
                         c => {
-                            emit_error(
+                            errors.push(lex_error(
                                 program,
                                 &map_file,
                                 byte_off!(index - 1),
@@ -689,8 +711,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                                 &format!("unknown escape sequence `\\{c}`"),
                                 "invalid escape",
                                 "valid escapes: `\\n` `\\t` `\\r` `\\\\` `\\\"` `\\0`",
-                            );
-                            had_error = true;
+                            ));
                             c
                         }
                     };
@@ -704,10 +725,9 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                 index += 1;
             }
             if !closed {
-                emit_error(program, &map_file, byte_off!(str_start), 1, "E007",
+                errors.push(lex_error(program, &map_file, byte_off!(str_start), 1, "E007",
                     "unterminated string literal", "string starts here, never closed",
-                    "add a closing `\"` at the end of the string; strings cannot span multiple lines");
-                had_error = true;
+                    "add a closing `\"` at the end of the string; strings cannot span multiple lines"));
             } else {
                 tokens.push(Token {
                     token_type: TokenType::StringLit(buf),
@@ -724,7 +744,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
             map_col += 1;
             index += 1;
             if index >= chars.len() {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(char_start),
@@ -733,12 +753,11 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     "unterminated character literal",
                     "char literal started here, never closed",
                     "a char literal must contain exactly one character: `'a'`, `'\\n'`",
-                );
-                had_error = true;
+                ));
                 continue;
             }
             if chars[index] == '\'' {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(char_start),
@@ -747,15 +766,14 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     "empty character literal `''`",
                     "no character inside the literal",
                     "a char literal must contain exactly one character, e.g. `'a'`",
-                );
-                had_error = true;
+                ));
                 map_col += 1;
                 index += 1;
                 continue;
             }
             let char_val = if chars[index] == '\\' {
                 if index + 1 >= chars.len() {
-                    emit_error(
+                    errors.push(lex_error(
                         program,
                         &map_file,
                         byte_off!(index),
@@ -764,8 +782,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                         "unterminated escape sequence in char literal",
                         "escape started here",
                         "valid escapes: `\\n`, `\\t`, `\\r`, `\\\\`, `\\'`, `\\0`",
-                    );
-                    had_error = true;
+                    ));
                     '\0'
                 } else {
                     map_col += 1;
@@ -781,7 +798,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                         '\'' => '\'',
                         '0' => '\0',
                         '"' => {
-                            emit_error(
+                            errors.push(lex_error(
                                 program,
                                 &map_file,
                                 byte_off!(index - 2),
@@ -790,12 +807,11 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                                 "unnecessary escape `\\\"` in char literal",
                                 "invalid escape",
                                 "inside '...' char literals, `\"` does not need escaping - write it as `\"` directly",
-                            );
-                            had_error = true;
+                            ));
                             '"'
                         }
                         _ => {
-                            emit_error(
+                            errors.push(lex_error(
                                 program,
                                 &map_file,
                                 byte_off!(index - 2),
@@ -804,8 +820,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                                 &format!("unknown escape sequence `\\{c}` in char literal"),
                                 "invalid escape",
                                 "valid escapes: `\\n`, `\\t`, `\\r`, `\\\\`, `\\'`, `\\0`",
-                            );
-                            had_error = true;
+                            ));
                             c
                         }
                     }
@@ -832,16 +847,15 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     index += 1;
                 }
                 let extra_len = index - extra_start + 1;
-                emit_error(program, &map_file, byte_off!(char_start), extra_len, "E010",
+                errors.push(lex_error(program, &map_file, byte_off!(char_start), extra_len, "E010",
                     "character literal contains more than one character", "too many characters",
-                    "a char literal holds exactly one character; for strings use double quotes: `\"...\"`");
-                had_error = true;
+                    "a char literal holds exactly one character; for strings use double quotes: `\"...\"`"));
                 if index < chars.len() && chars[index] == '\'' {
                     map_col += 1;
                     index += 1;
                 }
             } else {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(char_start),
@@ -850,8 +864,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     "unterminated character literal",
                     "char literal started here, never closed",
                     "close the literal with a single quote: `'a'`",
-                );
-                had_error = true;
+                ));
             }
             continue;
         }
@@ -863,7 +876,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     map_col += 1;
                     index += 1;
                 }
-                emit_error(
+                errors.push(lex_error(
                             program,
                             &map_file,
                             byte_off!(comment_start),
@@ -872,8 +885,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                             "unexpected `//`",
                             "`//` is not a comment",
                             "use `# comment` for single-line comments, or `### ... ###` for multi-line comments",
-                        );
-                had_error = true;
+                        ));
                 continue;
             }
             if index + 1 < chars.len() {
@@ -901,11 +913,10 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     file: map_file.clone(),
                 });
             } else {
-                emit_error(program, &map_file, byte_off!(token_start), 1, "E011",
+                errors.push(lex_error(program, &map_file, byte_off!(token_start), 1, "E011",
                     &format!("unexpected operator character `{}`", chars[index]),
                     "not a valid operator",
-                    "check the operator list; assignment uses `=`, equality uses `==`, not-equal uses `~=`");
-                had_error = true;
+                    "check the operator list; assignment uses `=`, equality uses `==`, not-equal uses `~=`"));
             }
             map_col += 1;
             index += 1;
@@ -977,7 +988,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
 
         if buffer.is_empty() {
             let c = chars[index];
-            emit_error(
+            errors.push(lex_error(
                 program,
                 &map_file,
                 byte_off!(token_start),
@@ -990,8 +1001,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                           remove it or check for a copy-paste artefact",
                     c as u32
                 ),
-            );
-            had_error = true;
+            ));
             map_col += 1;
             index += 1;
             continue;
@@ -999,7 +1009,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
 
         let tt = if buffer.starts_with("0b") && buffer.len() > 2 {
             if buffer[2..].chars().any(|c| c != '0' && c != '1') {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(buf_start),
@@ -1008,15 +1018,14 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("invalid binary literal `{}`", buffer),
                     "contains non-binary digit",
                     "binary literals may only contain `0` and `1`, e.g. `0b1010`",
-                );
-                had_error = true;
+                ));
                 TokenType::NoMatch
             } else {
                 classify_buffer(&buffer)
             }
         } else if buffer.starts_with("0o") && buffer.len() > 2 {
             if buffer[2..].chars().any(|c| !('0'..='7').contains(&c)) {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(buf_start),
@@ -1025,15 +1034,14 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("invalid octal literal `{}`", buffer),
                     "contains non-octal digit",
                     "octal literals may only contain digits 0–7, e.g. `0o755`",
-                );
-                had_error = true;
+                ));
                 TokenType::NoMatch
             } else {
                 classify_buffer(&buffer)
             }
         } else if buffer.starts_with("0x") && buffer.len() > 2 {
             if buffer[2..].chars().any(|c| !c.is_ascii_hexdigit()) {
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(buf_start),
@@ -1042,8 +1050,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("invalid hexadecimal literal `{}`", buffer),
                     "contains non-hex character",
                     "hex literals use digits 0–9 and letters A–F, e.g. `0xFF`",
-                );
-                had_error = true;
+                ));
                 TokenType::NoMatch
             } else {
                 classify_buffer(&buffer)
@@ -1060,7 +1067,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     format!("`{buffer}` is not a valid identifier or literal; \
                              identifiers must start with a letter or `_` and contain only letters, digits, and `_`")
                 };
-                emit_error(
+                errors.push(lex_error(
                     program,
                     &map_file,
                     byte_off!(buf_start),
@@ -1069,8 +1076,7 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
                     &format!("unrecognised token `{}`", buffer),
                     "cannot be tokenised",
                     &hint,
-                );
-                had_error = true;
+                ));
             }
             tt
         };
@@ -1085,17 +1091,12 @@ pub fn tokenize_with_source(program: &str, source_file: &str) -> Vec<Token> {
         }
     }
 
-    if had_error {
-        let display_file = std::path::Path::new(source_file)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(source_file);
-        eprintln!(
-            "\x1b[1;31maborting\x1b[0m: lexical error(s) in `{display_file}`; \
-                   fix the above before continuing\n"
-        );
-        std::process::exit(1);
+    if !errors.is_empty() {
+        for d in &mut errors {
+            d.file = module_key(&d.path, source_file);
+        }
+        return Err(errors);
     }
 
-    tokens
+    Ok(tokens)
 }
@@ -1,7 +1,14 @@
 pub mod builtins;
 pub mod codegen;
+pub mod condition;
+pub mod constfold;
+pub mod diagnostic;
+pub mod formatter;
 pub mod lexer;
+pub mod optimizer;
 pub mod parser;
 pub mod preprocessor;
+pub mod profile;
 pub mod retcheck;
 pub mod semanter;
+pub mod snapshot;
@@ -0,0 +1,800 @@
+use crate::compiler::constfold::{fold, ConstValue};
+use crate::compiler::parser::{AccessStep, ParseNode};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    O0,
+    O1,
+}
+
+impl OptLevel {
+    pub fn from_flag(flag: &str) -> Option<OptLevel> {
+        match flag {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" => Some(OptLevel::O1),
+            _ => None,
+        }
+    }
+}
+
+/// Rewrites an analysed program into a smaller equivalent one. At `-O1` this
+/// folds constant expressions, drops `!if`/`!while` branches whose condition is
+/// known at compile time, removes statements that can never run because they
+/// follow `!return`, `!exit`, `!break` or `!continue`, and removes functions
+/// that are never referenced. At `-O0` the tree is returned unchanged.
+///
+/// The input must already have passed semantic analysis.
+pub fn optimize(program: &ParseNode, level: OptLevel) -> ParseNode {
+    if level == OptLevel::O0 {
+        return program.clone();
+    }
+    let mut opt = Optimizer {
+        scopes: vec![HashMap::new()],
+    };
+    let folded = opt.opt_node(program);
+    remove_unused_functions(folded)
+}
+
+struct Optimizer {
+    scopes: Vec<HashMap<String, ConstValue>>,
+}
+
+impl Optimizer {
+    fn lookup(&self, name: &str) -> Option<ConstValue> {
+        self.scopes.iter().rev().find_map(|s| s.get(name)).cloned()
+    }
+
+    fn define(&mut self, name: &str, value: ConstValue) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), value);
+        }
+    }
+
+    fn opt_node(&mut self, node: &ParseNode) -> ParseNode {
+        match node {
+            ParseNode::Program(items) => ParseNode::Program(self.opt_items(items)),
+            other => other.clone(),
+        }
+    }
+
+    // Mirrors the semanter's two passes: modules and constants are visible to
+    // every other item regardless of where they appear in the file.
+    fn opt_items(&mut self, items: &[ParseNode]) -> Vec<ParseNode> {
+        let mut out: Vec<Option<ParseNode>> = vec![None; items.len()];
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                ParseNode::Module { name, items } => {
+                    self.scopes.push(HashMap::new());
+                    let body = self.opt_items(items);
+                    let frame = self.scopes.pop().unwrap_or_default();
+                    for (k, v) in frame {
+                        self.define(&format!("{}::{}", name, k), v);
+                    }
+                    out[i] = Some(ParseNode::Module {
+                        name: name.clone(),
+                        items: body,
+                    });
+                }
+                ParseNode::ConstDecl { .. } => out[i] = Some(self.opt_const(item)),
+                _ => {}
+            }
+        }
+
+        let mut stmts = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            match out[i].take() {
+                Some(done) => stmts.push(done),
+                None => self.opt_stmt_into(item, &mut stmts),
+            }
+        }
+        truncate_unreachable(&mut stmts);
+        stmts
+    }
+
+    fn opt_const(&mut self, node: &ParseNode) -> ParseNode {
+        let ParseNode::ConstDecl {
+            data_type,
+            name,
+            init,
+            line,
+            col,
+        } = node
+        else {
+            return node.clone();
+        };
+        if let Some(v) = fold(init, &|n| self.lookup(n)) {
+            self.define(name, v);
+        }
+        ParseNode::ConstDecl {
+            data_type: data_type.clone(),
+            name: name.clone(),
+            init: Box::new(self.opt_expr(init)),
+            line: *line,
+            col: *col,
+        }
+    }
+
+    fn opt_block(&mut self, stmts: &[ParseNode]) -> Vec<ParseNode> {
+        self.scopes.push(HashMap::new());
+        let mut out = Vec::new();
+        for stmt in stmts {
+            self.opt_stmt_into(stmt, &mut out);
+        }
+        self.scopes.pop();
+        truncate_unreachable(&mut out);
+        out
+    }
+
+    fn opt_stmt_into(&mut self, node: &ParseNode, out: &mut Vec<ParseNode>) {
+        match node {
+            ParseNode::If {
+                condition,
+                then_block,
+                else_block,
+                line,
+            } => {
+                let condition = self.opt_expr(condition);
+                let taken = match condition {
+                    ParseNode::BoolLit(true, _) => Some(self.opt_block(then_block)),
+                    ParseNode::BoolLit(false, _) => match else_block {
+                        Some(eb) => Some(self.opt_block(eb)),
+                        None => Some(Vec::new()),
+                    },
+                    _ => None,
+                };
+                match taken {
+                    // The branch is spliced into the enclosing block unless it
+                    // declares names, which must stay in their own scope.
+                    Some(block) if !declares_names(&block) => out.extend(block),
+                    Some(block) => out.push(ParseNode::If {
+                        condition: Box::new(ParseNode::BoolLit(true, *line)),
+                        then_block: block,
+                        else_block: None,
+                        line: *line,
+                    }),
+                    None => out.push(ParseNode::If {
+                        condition: Box::new(condition),
+                        then_block: self.opt_block(then_block),
+                        else_block: else_block.as_ref().map(|eb| self.opt_block(eb)),
+                        line: *line,
+                    }),
+                }
+            }
+
+            ParseNode::While {
+                condition,
+                body,
+                line,
+            } => {
+                let condition = self.opt_expr(condition);
+                if matches!(condition, ParseNode::BoolLit(false, _)) {
+                    return;
+                }
+                out.push(ParseNode::While {
+                    condition: Box::new(condition),
+                    body: self.opt_block(body),
+                    line: *line,
+                });
+            }
+
+            other => {
+                let stmt = self.opt_stmt(other);
+                out.push(stmt);
+            }
+        }
+    }
+
+    fn opt_stmt(&mut self, node: &ParseNode) -> ParseNode {
+        match node {
+            ParseNode::FuncDef {
+                name,
+                params,
+                return_type,
+                body,
+                line,
+                col,
+            } => ParseNode::FuncDef {
+                name: name.clone(),
+                params: params.clone(),
+                return_type: return_type.clone(),
+                body: self.opt_block(body),
+                line: *line,
+                col: *col,
+            },
+
+            ParseNode::ConstDecl { .. } => self.opt_const(node),
+
+            ParseNode::Decl {
+                data_type,
+                name,
+                init,
+                line,
+                col,
+            } => ParseNode::Decl {
+                data_type: data_type.clone(),
+                name: name.clone(),
+                init: init.as_ref().map(|e| Box::new(self.opt_expr(e))),
+                line: *line,
+                col: *col,
+            },
+
+            ParseNode::StructDecl {
+                struct_name,
+                var_name,
+                init,
+                line,
+                struct_col,
+                col,
+            } => ParseNode::StructDecl {
+                struct_name: struct_name.clone(),
+                var_name: var_name.clone(),
+                init: init.as_ref().map(|e| Box::new(self.opt_expr(e))),
+                line: *line,
+                struct_col: *struct_col,
+                col: *col,
+            },
+
+            ParseNode::Assign {
+                lvalue,
+                op,
+                expr,
+                line,
+            } => ParseNode::Assign {
+                lvalue: Box::new(self.opt_lvalue(lvalue)),
+                op: op.clone(),
+                expr: Box::new(self.opt_expr(expr)),
+                line: *line,
+            },
+
+            ParseNode::For {
+                var_type,
+                var_name,
+                start,
+                stop,
+                step,
+                body,
+                line,
+                col,
+            } => ParseNode::For {
+                var_type: var_type.clone(),
+                var_name: var_name.clone(),
+                start: Box::new(self.opt_expr(start)),
+                stop: Box::new(self.opt_expr(stop)),
+                step: Box::new(self.opt_expr(step)),
+                body: self.opt_block(body),
+                line: *line,
+                col: *col,
+            },
+
+            ParseNode::Return { expr, line } => ParseNode::Return {
+                expr: Box::new(self.opt_expr(expr)),
+                line: *line,
+            },
+            ParseNode::Exit { expr, line } => ParseNode::Exit {
+                expr: Box::new(self.opt_expr(expr)),
+                line: *line,
+            },
+            ParseNode::ExprStmt(e, line) => ParseNode::ExprStmt(Box::new(self.opt_expr(e)), *line),
+
+            other => other.clone(),
+        }
+    }
+
+    // Assignment targets keep their shape; only index expressions are folded.
+    fn opt_lvalue(&mut self, node: &ParseNode) -> ParseNode {
+        match node {
+            ParseNode::AccessChain {
+                base,
+                steps,
+                line,
+                col,
+            } => ParseNode::AccessChain {
+                base: base.clone(),
+                steps: self.opt_steps(steps),
+                line: *line,
+                col: *col,
+            },
+            other => other.clone(),
+        }
+    }
+
+    fn opt_steps(&mut self, steps: &[AccessStep]) -> Vec<AccessStep> {
+        steps
+            .iter()
+            .map(|s| match s {
+                AccessStep::Field(f, col) => AccessStep::Field(f.clone(), *col),
+                AccessStep::Index(i) => AccessStep::Index(Box::new(self.opt_expr(i))),
+                AccessStep::Call(args) => {
+                    AccessStep::Call(args.iter().map(|a| self.opt_expr(a)).collect())
+                }
+            })
+            .collect()
+    }
+
+    fn opt_expr(&mut self, node: &ParseNode) -> ParseNode {
+        if let Some(line) = expr_line(node) {
+            match fold(node, &|n| self.lookup(n)) {
+                Some(ConstValue::Float(f)) if !f.is_finite() => {}
+                Some(v) => return v.to_node(line),
+                None => {}
+            }
+        }
+
+        let bx = |o: &mut Self, n: &ParseNode| Box::new(o.opt_expr(n));
+        match node {
+            ParseNode::AccessChain {
+                base,
+                steps,
+                line,
+                col,
+            } => ParseNode::AccessChain {
+                base: base.clone(),
+                steps: self.opt_steps(steps),
+                line: *line,
+                col: *col,
+            },
+            ParseNode::LogOr { left, right, line } => ParseNode::LogOr {
+                left: bx(self, left),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::LogAnd { left, right, line } => ParseNode::LogAnd {
+                left: bx(self, left),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::LogNot { operand, line } => ParseNode::LogNot {
+                operand: bx(self, operand),
+                line: *line,
+            },
+            ParseNode::Cmp {
+                left,
+                op,
+                right,
+                line,
+            } => ParseNode::Cmp {
+                left: bx(self, left),
+                op: op.clone(),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::BitOr { left, right, line } => ParseNode::BitOr {
+                left: bx(self, left),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::BitXor { left, right, line } => ParseNode::BitXor {
+                left: bx(self, left),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::BitAnd { left, right, line } => ParseNode::BitAnd {
+                left: bx(self, left),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::BitShift {
+                left,
+                op,
+                right,
+                line,
+            } => ParseNode::BitShift {
+                left: bx(self, left),
+                op: op.clone(),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::Add {
+                left,
+                op,
+                right,
+                line,
+            } => ParseNode::Add {
+                left: bx(self, left),
+                op: op.clone(),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::Mul {
+                left,
+                op,
+                right,
+                line,
+            } => ParseNode::Mul {
+                left: bx(self, left),
+                op: op.clone(),
+                right: bx(self, right),
+                line: *line,
+            },
+            ParseNode::Unary { op, operand, line } => ParseNode::Unary {
+                op: op.clone(),
+                operand: bx(self, operand),
+                line: *line,
+            },
+            ParseNode::Cast {
+                target_type,
+                expr,
+                line,
+            } => ParseNode::Cast {
+                target_type: target_type.clone(),
+                expr: bx(self, expr),
+                line: *line,
+            },
+            ParseNode::ArrayLit(elems, line) => {
+                ParseNode::ArrayLit(elems.iter().map(|e| self.opt_expr(e)).collect(), *line)
+            }
+            ParseNode::StructLit(fields, line) => ParseNode::StructLit(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.opt_expr(v)))
+                    .collect(),
+                *line,
+            ),
+            ParseNode::Lambda {
+                params,
+                return_type,
+                body,
+                line,
+            } => ParseNode::Lambda {
+                params: params.clone(),
+                return_type: return_type.clone(),
+                body: self.opt_block(body),
+                line: *line,
+            },
+            other => other.clone(),
+        }
+    }
+}
+
+fn expr_line(node: &ParseNode) -> Option<usize> {
+    match node {
+        ParseNode::AccessChain { line, .. }
+        | ParseNode::LogOr { line, .. }
+        | ParseNode::LogAnd { line, .. }
+        | ParseNode::LogNot { line, .. }
+        | ParseNode::Cmp { line, .. }
+        | ParseNode::BitOr { line, .. }
+        | ParseNode::BitXor { line, .. }
+        | ParseNode::BitAnd { line, .. }
+        | ParseNode::BitShift { line, .. }
+        | ParseNode::Add { line, .. }
+        | ParseNode::Mul { line, .. }
+        | ParseNode::Unary { line, .. }
+        | ParseNode::Cast { line, .. } => Some(*line),
+        ParseNode::Identifier(_, line) => Some(*line),
+        _ => None,
+    }
+}
+
+fn declares_names(stmts: &[ParseNode]) -> bool {
+    stmts.iter().any(|s| {
+        matches!(
+            s,
+            ParseNode::Decl { .. } | ParseNode::StructDecl { .. } | ParseNode::ConstDecl { .. }
+        )
+    })
+}
+
+fn ends_flow(node: &ParseNode) -> bool {
+    matches!(
+        node,
+        ParseNode::Return { .. }
+            | ParseNode::Exit { .. }
+            | ParseNode::Break { .. }
+            | ParseNode::Continue { .. }
+    )
+}
+
+// Definitions are hoisted by codegen, so they survive even when they appear
+// textually after a top-level `!exit`.
+fn truncate_unreachable(stmts: &mut Vec<ParseNode>) {
+    if let Some(pos) = stmts.iter().position(ends_flow) {
+        let mut i = pos + 1;
+        while i < stmts.len() {
+            if matches!(
+                stmts[i],
+                ParseNode::FuncDef { .. }
+                    | ParseNode::StructDef { .. }
+                    | ParseNode::Module { .. }
+                    | ParseNode::ConstDecl { .. }
+            ) {
+                i += 1;
+            } else {
+                stmts.remove(i);
+            }
+        }
+    }
+}
+
+// A function is kept if its name is mentioned anywhere in code that can run:
+// top-level statements, module statements, and the bodies of functions that
+// are themselves kept. Names are compared without their module prefix, which
+// keeps a same-named function in another module alive rather than risk
+// removing one that is used.
+fn remove_unused_functions(program: ParseNode) -> ParseNode {
+    let mut funcs: HashMap<String, Vec<&ParseNode>> = HashMap::new();
+    let mut roots: Vec<&ParseNode> = Vec::new();
+    if let ParseNode::Program(items) = &program {
+        collect_funcs(items, &mut funcs, &mut roots);
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    let mut pending: Vec<String> = Vec::new();
+    for root in roots {
+        collect_names(root, &mut pending);
+    }
+    while let Some(name) = pending.pop() {
+        if !used.insert(name.clone()) {
+            continue;
+        }
+        if let Some(bodies) = funcs.get(&name) {
+            for body in bodies {
+                collect_names(body, &mut pending);
+            }
+        }
+    }
+
+    match program {
+        ParseNode::Program(items) => ParseNode::Program(retain_used(items, &used)),
+        other => other,
+    }
+}
+
+fn collect_funcs<'a>(
+    items: &'a [ParseNode],
+    funcs: &mut HashMap<String, Vec<&'a ParseNode>>,
+    roots: &mut Vec<&'a ParseNode>,
+) {
+    for item in items {
+        match item {
+            ParseNode::FuncDef { name, .. } => {
+                funcs.entry(name.clone()).or_default().push(item);
+            }
+            ParseNode::Module { items, .. } => collect_funcs(items, funcs, roots),
+            _ => roots.push(item),
+        }
+    }
+}
+
+fn retain_used(items: Vec<ParseNode>, used: &HashSet<String>) -> Vec<ParseNode> {
+    items
+        .into_iter()
+        .filter_map(|item| match item {
+            ParseNode::FuncDef { ref name, .. } if !used.contains(name) => None,
+            ParseNode::Module { name, items } => Some(ParseNode::Module {
+                name,
+                items: retain_used(items, used),
+            }),
+            other => Some(other),
+        })
+        .collect()
+}
+
+pub(crate) fn collect_names(node: &ParseNode, out: &mut Vec<String>) {
+    match node {
+        ParseNode::Identifier(name, _) => out.push(name.clone()),
+        ParseNode::AccessChain { base, steps, .. } => {
+            out.push(base.clone());
+            for step in steps {
+                match step {
+                    AccessStep::Field(f, _) => out.push(f.clone()),
+                    AccessStep::Index(i) => collect_names(i, out),
+                    AccessStep::Call(args) => {
+                        for a in args {
+                            collect_names(a, out);
+                        }
+                    }
+                }
+            }
+        }
+        ParseNode::FuncDef { body, .. } | ParseNode::Lambda { body, .. } => {
+            body.iter().for_each(|n| collect_names(n, out))
+        }
+        ParseNode::Decl { init, .. } | ParseNode::StructDecl { init, .. } => {
+            if let Some(e) = init {
+                collect_names(e, out);
+            }
+        }
+        ParseNode::ConstDecl { init, .. } => collect_names(init, out),
+        ParseNode::Assign { lvalue, expr, .. } => {
+            collect_names(lvalue, out);
+            collect_names(expr, out);
+        }
+        ParseNode::If {
+            condition,
+            then_block,
+            else_block,
+            ..
+        } => {
+            collect_names(condition, out);
+            for s in then_block.iter().chain(else_block.iter().flatten()) {
+                collect_names(s, out);
+            }
+        }
+        ParseNode::For {
+            start,
+            stop,
+            step,
+            body,
+            ..
+        } => {
+            for n in [start.as_ref(), stop.as_ref(), step.as_ref()]
+                .into_iter()
+                .chain(body.iter())
+            {
+                collect_names(n, out);
+            }
+        }
+        ParseNode::While {
+            condition, body, ..
+        } => {
+            collect_names(condition, out);
+            body.iter().for_each(|n| collect_names(n, out));
+        }
+        ParseNode::Return { expr, .. } | ParseNode::Exit { expr, .. } => collect_names(expr, out),
+        ParseNode::ExprStmt(e, _) => collect_names(e, out),
+        ParseNode::LogOr { left, right, .. }
+        | ParseNode::LogAnd { left, right, .. }
+        | ParseNode::Cmp { left, right, .. }
+        | ParseNode::BitOr { left, right, .. }
+        | ParseNode::BitXor { left, right, .. }
+        | ParseNode::BitAnd { left, right, .. }
+        | ParseNode::BitShift { left, right, .. }
+        | ParseNode::Add { left, right, .. }
+        | ParseNode::Mul { left, right, .. } => {
+            collect_names(left, out);
+            collect_names(right, out);
+        }
+        ParseNode::LogNot { operand, .. } | ParseNode::Unary { operand, .. } => {
+            collect_names(operand, out)
+        }
+        ParseNode::Cast { expr, .. } => collect_names(expr, out),
+        ParseNode::ArrayLit(elems, _) => elems.iter().for_each(|n| collect_names(n, out)),
+        ParseNode::StructLit(fields, _) => fields.iter().for_each(|(_, v)| collect_names(v, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::lex;
+    use crate::compiler::parser::parse;
+
+    fn optimized(src: &str) -> Vec<ParseNode> {
+        let tokens = lex(src, "test.fr").expect("lex");
+        let root = parse(tokens).expect("parse");
+        match optimize(&root, OptLevel::O1) {
+            ParseNode::Program(items) => items,
+            other => panic!("expected a program, got {:?}", other),
+        }
+    }
+
+    fn func_names(items: &[ParseNode]) -> Vec<&str> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                ParseNode::FuncDef { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn func_body<'a>(items: &'a [ParseNode], func: &str) -> &'a [ParseNode] {
+        items
+            .iter()
+            .find_map(|item| match item {
+                ParseNode::FuncDef { name, body, .. } if name == func => Some(body.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no function `{}`", func))
+    }
+
+    #[test]
+    fn o0_leaves_the_tree_alone() {
+        let src = "!start\n!if (false) { print(\"a\"); }\n!end\n";
+        let root = parse(lex(src, "test.fr").unwrap()).unwrap();
+        assert_eq!(
+            format!("{:?}", optimize(&root, OptLevel::O0)),
+            format!("{:?}", root)
+        );
+    }
+
+    #[test]
+    fn removes_dead_if_branches() {
+        let items = optimized(
+            "!start\n\
+             !if (false) { print(\"dead\"); }\n\
+             !if (1 > 2) { print(\"dead\"); } !else { print(\"live\"); }\n\
+             !end\n",
+        );
+        assert!(!items.iter().any(|n| matches!(n, ParseNode::If { .. })));
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], ParseNode::ExprStmt(..)));
+    }
+
+    #[test]
+    fn keeps_a_taken_branch_that_declares_names_in_its_own_scope() {
+        let items = optimized("!start\n!if (true) { :int x = 1; print(\"{}\", x); }\n!end\n");
+        assert_eq!(items.len(), 1);
+        match &items[0] {
+            ParseNode::If {
+                condition,
+                then_block,
+                ..
+            } => {
+                assert!(matches!(**condition, ParseNode::BoolLit(true, _)));
+                assert_eq!(then_block.len(), 2);
+            }
+            other => panic!("expected a scoped block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn removes_dead_while_loops() {
+        let items = optimized(
+            "!start\n\
+             !const :boolean DEBUG = false;\n\
+             !while (DEBUG) { print(\"dead\"); }\n\
+             print(\"live\");\n\
+             !end\n",
+        );
+        assert!(!items.iter().any(|n| matches!(n, ParseNode::While { .. })));
+        assert!(matches!(items.last(), Some(ParseNode::ExprStmt(..))));
+    }
+
+    #[test]
+    fn cuts_code_after_return() {
+        let items = optimized(
+            "!start\n\
+             !func f() -> :int {\n\
+                 !return 1;\n\
+                 print(\"unreachable\");\n\
+                 !return 2;\n\
+             }\n\
+             print(\"{}\", f());\n\
+             !end\n",
+        );
+        let body = func_body(&items, "f");
+        assert_eq!(body.len(), 1);
+        assert!(matches!(body[0], ParseNode::Return { .. }));
+    }
+
+    #[test]
+    fn keeps_definitions_after_exit() {
+        let items = optimized(
+            "!start\n\
+             print(\"{}\", g());\n\
+             !exit(0);\n\
+             print(\"unreachable\");\n\
+             !func g() -> :int { !return 1; }\n\
+             !end\n",
+        );
+        assert_eq!(func_names(&items), ["g"]);
+        assert_eq!(items.len(), 3);
+    }
+
+    #[test]
+    fn keeps_recursive_and_mutually_called_functions() {
+        let items = optimized(
+            "!start\n\
+             !func fact(:int n) -> :int {\n\
+                 !if (n < 2) { !return 1; }\n\
+                 !return n * fact(n - 1);\n\
+             }\n\
+             !func is_even(:int n) -> :boolean {\n\
+                 !if (n == 0) { !return true; }\n\
+                 !return is_odd(n - 1);\n\
+             }\n\
+             !func is_odd(:int n) -> :boolean {\n\
+                 !if (n == 0) { !return false; }\n\
+                 !return is_even(n - 1);\n\
+             }\n\
+             !func unused(:int n) -> :int { !return unused(n); }\n\
+             print(\"{} {}\", fact(5), is_even(4));\n\
+             !end\n",
+        );
+        assert_eq!(func_names(&items), ["fact", "is_even", "is_odd"]);
+    }
+}
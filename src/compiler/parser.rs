@@ -1,5 +1,8 @@
+use crate::compiler::diagnostic::{Diagnostic, Severity};
 use crate::compiler::lexer::{Token, TokenType};
 
+// A `col` is the 1-based column of the name the node declares or uses, on
+// its `line`; for `m::x` it is the column of `x`.
 #[derive(Debug, Clone)]
 pub enum ParseNode {
     Program(Vec<ParseNode>),
@@ -14,16 +17,22 @@ pub enum ParseNode {
         params: Vec<ParseNode>,
         return_type: Box<ParseNode>,
         body: Vec<ParseNode>,
+        line: usize,
+        col: usize,
     },
 
     Param {
         data_type: Box<ParseNode>,
         name: String,
+        line: usize,
+        col: usize,
     },
 
     StructDef {
         name: String,
         fields: Vec<ParseNode>,
+        line: usize,
+        col: usize,
     },
 
     StructDecl {
@@ -31,6 +40,8 @@ pub enum ParseNode {
         var_name: String,
         init: Option<Box<ParseNode>>,
         line: usize,
+        struct_col: usize,
+        col: usize,
     },
 
     Field {
@@ -43,6 +54,15 @@ pub enum ParseNode {
         name: String,
         init: Option<Box<ParseNode>>,
         line: usize,
+        col: usize,
+    },
+
+    ConstDecl {
+        data_type: Box<ParseNode>,
+        name: String,
+        init: Box<ParseNode>,
+        line: usize,
+        col: usize,
     },
 
     Assign {
@@ -67,6 +87,7 @@ pub enum ParseNode {
         step: Box<ParseNode>,
         body: Vec<ParseNode>,
         line: usize,
+        col: usize,
     },
 
     While {
@@ -96,6 +117,7 @@ pub enum ParseNode {
         base: String,
         steps: Vec<AccessStep>,
         line: usize,
+        col: usize,
     },
 
     LogOr {
@@ -187,7 +209,9 @@ pub enum ParseNode {
     TypeArray {
         elem: Box<ParseNode>,
         size: i64,
+        size_const: Option<String>,
         line: usize,
+        size_col: usize,
     },
     TypeList {
         elem: Box<ParseNode>,
@@ -196,12 +220,26 @@ pub enum ParseNode {
     TypeStruct {
         name: String,
         line: usize,
+        col: usize,
+    },
+    TypeFunc {
+        params: Vec<ParseNode>,
+        ret: Box<ParseNode>,
+        line: usize,
+    },
+
+    Lambda {
+        params: Vec<ParseNode>,
+        return_type: Box<ParseNode>,
+        body: Vec<ParseNode>,
+        line: usize,
     },
 }
 
 #[derive(Debug, Clone)]
 pub enum AccessStep {
-    Field(String),
+    /// A field, or a module's item, and the column of its name.
+    Field(String, usize),
 
     Index(Box<ParseNode>),
 
@@ -298,6 +336,14 @@ impl ParseError {
         }
     }
 
+    /// The error as a diagnostic in the program rooted at `source_file`.
+    pub fn diagnostic(&self, source_file: &str) -> Diagnostic {
+        Diagnostic::from_message(Severity::Error, "P000", &self.message)
+            .in_file(&self.source_file, source_file)
+            .at(self.line, self.col, 0)
+            .label("unexpected token here")
+    }
+
     pub fn emit(&self, _preprocessed: &str) {
         let display_file = if self.source_file.is_empty() {
             "<unknown>".to_string()
@@ -380,6 +426,15 @@ impl Parser {
         self.tokens.get(self.pos).map(|t| t.col).unwrap_or(0)
     }
 
+    // The file the current token came from, which is a module's when the
+    // parser is inside an import.
+    fn cur_file(&self) -> &str {
+        self.tokens
+            .get(self.pos)
+            .map(|t| t.file.as_str())
+            .unwrap_or(&self.source_file)
+    }
+
     fn advance(&mut self) -> Option<&TokenType> {
         let t = self.tokens.get(self.pos).map(|t| &t.token_type);
         if t.is_some() {
@@ -389,7 +444,7 @@ impl Parser {
     }
 
     fn err(&self, msg: impl Into<String>) -> ParseError {
-        ParseError::new_at(msg, self.cur_line(), self.cur_col(), &self.source_file)
+        ParseError::new_at(msg, self.cur_line(), self.cur_col(), self.cur_file())
     }
 
     fn token_name(tt: &TokenType) -> &'static str {
@@ -441,6 +496,7 @@ impl Parser {
             TokenType::Return => "`!return`",
             TokenType::Break => "`!break`",
             TokenType::Continue => "`!continue`",
+            TokenType::Const => "`!const`",
             TokenType::Struct => "`!struct`",
             TokenType::Import => "`!import`",
             TokenType::Module => "`!module`",
@@ -455,6 +511,7 @@ impl Parser {
             TokenType::TypeList => "`:list`",
             TokenType::TypeStruct => "`:struct`",
             TokenType::TypeVoid => "`:void`",
+            TokenType::TypeFunc => "`:func`",
             TokenType::Identifier(_) => "identifier",
             TokenType::SIntLit(_) => "integer literal",
             TokenType::FloatLit(_) => "float literal",
@@ -562,7 +619,8 @@ impl Parser {
                 let found = Self::opt_token_name(other.as_ref());
                 Err(self.err(format!(
                     "expected an integer literal for the array size, but found {found}\n   \
-                     note: array size must be a compile-time integer constant, e.g. `:array<:int, 5>`"
+                     note: array size must be a compile-time integer constant, e.g. `:array<:int, 5>`\n   \
+                     hint: a `!const :int` name also works: `:array<:int, SIZE>`"
                 )))
             }
         }
@@ -572,14 +630,17 @@ impl Parser {
         matches!(self.peek(), Some(TokenType::EndL))
     }
 
-    fn parse_struct_type_name(&mut self) -> PResult<String> {
+    // The name and the column of its last part.
+    fn parse_struct_type_name(&mut self) -> PResult<(String, usize)> {
+        let col = self.cur_col();
         let first = self.expect_identifier()?;
         if matches!(self.peek(), Some(TokenType::ColonColon)) {
             self.advance();
+            let col = self.cur_col();
             let second = self.expect_identifier()?;
-            Ok(format!("{}::{}", first, second))
+            Ok((format!("{}::{}", first, second), col))
         } else {
-            Ok(first)
+            Ok((first, col))
         }
     }
 
@@ -633,6 +694,12 @@ impl Parser {
 
             Some(TokenType::TypeStruct) => self.parse_struct_item(true),
 
+            Some(TokenType::Const) => {
+                let node = self.parse_const_decl()?;
+                self.expect(&TokenType::EndL)?;
+                Ok(node)
+            }
+
             Some(t) if Self::is_type_token(&t) => {
                 let node = self.parse_decl()?;
                 self.expect(&TokenType::EndL)?;
@@ -670,6 +737,7 @@ impl Parser {
                 | TokenType::TypeArray
                 | TokenType::TypeList
                 | TokenType::TypeStruct
+                | TokenType::TypeFunc
         )
     }
 
@@ -680,7 +748,9 @@ impl Parser {
                  note: move this `!func` definition to the top level, outside any `!func` body",
             ));
         }
+        let line = self.cur_line();
         self.expect(&TokenType::Func)?;
+        let col = self.cur_col();
         let name = self.expect_identifier()?;
         self.expect(&TokenType::LParen)?;
         let params = self.parse_params()?;
@@ -695,6 +765,35 @@ impl Parser {
             params,
             return_type: Box::new(return_type),
             body,
+            line,
+            col,
+        })
+    }
+
+    fn parse_lambda(&mut self) -> PResult<ParseNode> {
+        let line = self.cur_line();
+        self.expect(&TokenType::Func)?;
+        if !matches!(self.peek(), Some(TokenType::LParen)) {
+            return Err(self.err(format!(
+                "expected `(` after `!func` in an expression, but found {}\n   \
+                 note: a lambda is written `!func (:int x) -> :int {{ !return x * 2; }}`\n   \
+                 note: named functions can only be defined at the top level",
+                Self::opt_token_name(self.peek())
+            )));
+        }
+        self.advance();
+        let params = self.parse_params()?;
+        self.expect(&TokenType::RParen)?;
+        self.expect(&TokenType::Arrow)?;
+        let return_type = self.parse_datatype()?;
+        self.func_depth += 1;
+        let body = self.parse_block()?;
+        self.func_depth -= 1;
+        Ok(ParseNode::Lambda {
+            params,
+            return_type: Box::new(return_type),
+            body,
+            line,
         })
     }
 
@@ -713,10 +812,14 @@ impl Parser {
 
     fn parse_param(&mut self) -> PResult<ParseNode> {
         let data_type = self.parse_datatype()?;
+        let line = self.cur_line();
+        let col = self.cur_col();
         let name = self.expect_identifier()?;
         Ok(ParseNode::Param {
             data_type: Box::new(data_type),
             name,
+            line,
+            col,
         })
     }
 
@@ -724,7 +827,7 @@ impl Parser {
         let line = self.cur_line();
         self.expect(&TokenType::TypeStruct)?;
         self.expect(&TokenType::Less)?;
-        let type_name = self.parse_struct_type_name()?;
+        let (type_name, struct_col) = self.parse_struct_type_name()?;
         self.expect(&TokenType::Greater)?;
 
         match self.peek().cloned() {
@@ -736,10 +839,13 @@ impl Parser {
                 Ok(ParseNode::StructDef {
                     name: type_name,
                     fields,
+                    line,
+                    col: struct_col,
                 })
             }
 
             Some(TokenType::Identifier(var_name)) => {
+                let col = self.cur_col();
                 self.advance();
                 let init = if matches!(self.peek(), Some(TokenType::Equals)) {
                     self.advance();
@@ -755,6 +861,8 @@ impl Parser {
                     var_name,
                     init,
                     line,
+                    struct_col,
+                    col,
                 })
             }
 
@@ -795,16 +903,18 @@ impl Parser {
     }
 
     fn parse_struct_field(&mut self) -> PResult<ParseNode> {
+        let line = self.cur_line();
         self.expect(&TokenType::TypeStruct)?;
         self.expect(&TokenType::Less)?;
-        let type_name = self.parse_struct_type_name()?;
+        let (type_name, col) = self.parse_struct_type_name()?;
         self.expect(&TokenType::Greater)?;
         let field_name = self.expect_identifier()?;
         self.expect(&TokenType::EndL)?;
         Ok(ParseNode::Field {
             data_type: Box::new(ParseNode::TypeStruct {
                 name: type_name,
-                line: 0,
+                line,
+                col,
             }),
             name: field_name,
         })
@@ -888,6 +998,7 @@ impl Parser {
             "return" => Some("`return` is not valid here - did you mean `!return`?"),
             "break" => Some("`break` is not valid here - did you mean `!break`?"),
             "continue" => Some("`continue` is not valid here - did you mean `!continue`?"),
+            "const" => Some("`const` is not valid here - did you mean `!const`?"),
             "import" => Some("`import` is not valid here - did you mean `!import`?"),
             "struct" => Some("`struct` is not valid here - did you mean `:struct`?"),
             "int" => Some("`int` is not valid here - did you mean `:int`?"),
@@ -930,14 +1041,13 @@ impl Parser {
                 self.advance();
                 self.expect(&TokenType::LParen)?;
 
-                let (var_type, var_name) = if Self::is_type_token_ref(self.peek()) {
-                    let vt = self.parse_datatype()?;
-                    let vn = self.expect_identifier()?;
-                    (vt, vn)
+                let var_type = if Self::is_type_token_ref(self.peek()) {
+                    self.parse_datatype()?
                 } else {
-                    let vn = self.expect_identifier()?;
-                    (ParseNode::TypeVoid(0), vn)
+                    ParseNode::TypeVoid(0)
                 };
+                let col = self.cur_col();
+                let var_name = self.expect_identifier()?;
                 self.expect(&TokenType::Comma)?;
                 let start = self.parse_expression()?;
                 self.expect(&TokenType::Comma)?;
@@ -956,6 +1066,7 @@ impl Parser {
                     step: Box::new(step),
                     body,
                     line,
+                    col,
                 })
             }
 
@@ -987,7 +1098,7 @@ impl Parser {
                          hint: did you accidentally place it at the top level?",
                         line,
                         col,
-                        &self.source_file,
+                        self.cur_file(),
                     ));
                 }
                 if matches!(self.peek(), Some(TokenType::EndL)) {
@@ -1025,7 +1136,7 @@ impl Parser {
                          note: `!break` can only appear inside a `!for` or `!while` body",
                         line,
                         col,
-                        &self.source_file,
+                        self.cur_file(),
                     ));
                 }
                 self.advance();
@@ -1042,7 +1153,7 @@ impl Parser {
                          note: `!continue` can only appear inside a `!for` or `!while` body",
                         line,
                         col,
-                        &self.source_file,
+                        self.cur_file(),
                     ));
                 }
                 self.advance();
@@ -1052,6 +1163,8 @@ impl Parser {
 
             Some(TokenType::TypeStruct) => self.parse_struct_item(false),
 
+            Some(TokenType::Const) => self.parse_const_decl(),
+
             Some(ref t) if Self::is_type_token(t) => self.parse_decl(),
 
             Some(TokenType::Identifier(name)) => {
@@ -1098,6 +1211,7 @@ impl Parser {
     fn parse_decl(&mut self) -> PResult<ParseNode> {
         let line = self.cur_line();
         let data_type = self.parse_datatype()?;
+        let col = self.cur_col();
         let name = self.expect_identifier()?;
 
         let compound_op = match self.peek() {
@@ -1132,6 +1246,39 @@ impl Parser {
             name,
             init,
             line,
+            col,
+        })
+    }
+
+    fn parse_const_decl(&mut self) -> PResult<ParseNode> {
+        let line = self.cur_line();
+        self.expect(&TokenType::Const)?;
+        if !Self::is_type_token_ref(self.peek()) {
+            let found = Self::opt_token_name(self.peek());
+            return Err(self.err(format!(
+                "expected a type after `!const`, but found {found}\n   \
+                 note: constants are declared as `!const :int MAX = 100;`"
+            )));
+        }
+        let data_type = self.parse_datatype()?;
+        let col = self.cur_col();
+        let name = self.expect_identifier()?;
+        if !matches!(self.peek(), Some(TokenType::Equals)) {
+            let found = Self::opt_token_name(self.peek());
+            return Err(self.err(format!(
+                "constant `{name}` must be given a value, but found {found}\n   \
+                 note: a constant cannot change later, so it needs its value up front: \
+                 `!const :int {name} = 100;`"
+            )));
+        }
+        self.advance();
+        let init = self.parse_expression()?;
+        Ok(ParseNode::ConstDecl {
+            data_type: Box::new(data_type),
+            name,
+            init: Box::new(init),
+            line,
+            col,
         })
     }
 
@@ -1158,6 +1305,7 @@ impl Parser {
 
     fn try_parse_lvalue_chain(&mut self) -> PResult<ParseNode> {
         let line = self.cur_line();
+        let col = self.cur_col();
         let name = self.expect_identifier()?;
         let steps = self.parse_postfix_steps()?;
 
@@ -1173,6 +1321,7 @@ impl Parser {
             base: name,
             steps,
             line,
+            col,
         })
     }
 
@@ -1193,8 +1342,9 @@ impl Parser {
             match self.peek() {
                 Some(TokenType::ColonColon) => {
                     self.advance();
+                    let col = self.cur_col();
                     let field = self.expect_identifier()?;
-                    steps.push(AccessStep::Field(field));
+                    steps.push(AccessStep::Field(field, col));
                 }
                 Some(TokenType::LBracket) => {
                     self.advance();
@@ -1276,12 +1426,20 @@ impl Parser {
                         )));
                     }
                 }
-                let size = self.expect_int_lit()?;
+                let (size, size_const, size_col) =
+                    if let Some(TokenType::Identifier(_)) = self.peek() {
+                        let (name, col) = self.parse_struct_type_name()?;
+                        (0, Some(name), col)
+                    } else {
+                        (self.expect_int_lit()?, None, 0)
+                    };
                 self.expect(&TokenType::Greater)?;
                 Ok(ParseNode::TypeArray {
                     elem: Box::new(elem),
                     size,
+                    size_const,
                     line,
+                    size_col,
                 })
             }
 
@@ -1301,15 +1459,47 @@ impl Parser {
                 let line = self.cur_line();
                 self.advance();
                 self.expect(&TokenType::Less)?;
-                let name = self.parse_struct_type_name()?;
+                let (name, col) = self.parse_struct_type_name()?;
                 self.expect(&TokenType::Greater)?;
-                Ok(ParseNode::TypeStruct { name, line })
+                Ok(ParseNode::TypeStruct { name, line, col })
+            }
+
+            Some(TokenType::TypeFunc) => {
+                let line = self.cur_line();
+                self.advance();
+                self.expect(&TokenType::Less)?;
+                if !matches!(self.peek(), Some(TokenType::LParen)) {
+                    return Err(self.err(format!(
+                        "`:func` requires a parameter list: expected `(`, but found {}\n   \
+                         note: function types are written `:func<(:int, :int) -> :boolean>`",
+                        Self::opt_token_name(self.peek())
+                    )));
+                }
+                self.advance();
+                let mut params = Vec::new();
+                if !matches!(self.peek(), Some(TokenType::RParen)) {
+                    params.push(self.parse_datatype()?);
+                    while matches!(self.peek(), Some(TokenType::Comma)) {
+                        self.advance();
+                        params.push(self.parse_datatype()?);
+                    }
+                }
+                self.expect(&TokenType::RParen)?;
+                self.expect(&TokenType::Arrow)?;
+                let ret = self.parse_datatype()?;
+                self.expect(&TokenType::Greater)?;
+                Ok(ParseNode::TypeFunc {
+                    params,
+                    ret: Box::new(ret),
+                    line,
+                })
             }
 
             other => Err(self.err(format!(
                 "expected a type name here, but found {}\n   \
                  note: types must be prefixed with `:`, e.g. `:int`, `:float`, `:char`, `:boolean`, `:void`\n   \
-                 note: generic types: `:array<:int, 5>`, `:list<:float>`, `:struct<Name>`",
+                 note: generic types: `:array<:int, 5>`, `:list<:float>`, `:struct<Name>`, \
+                 `:func<(:int) -> :int>`",
                 Self::opt_token_name(other.as_ref())
             ))),
         }
@@ -1538,6 +1728,7 @@ impl Parser {
                 | Some(TokenType::TypeArray)
                 | Some(TokenType::TypeList)
                 | Some(TokenType::TypeStruct)
+                | Some(TokenType::TypeFunc)
         )
     }
 
@@ -1591,12 +1782,14 @@ impl Parser {
 
             Some(TokenType::Identifier(name)) => {
                 let line = self.cur_line();
+                let col = self.cur_col();
                 self.advance();
                 let steps = self.parse_postfix_steps()?;
                 Ok(ParseNode::AccessChain {
                     base: name,
                     steps,
                     line,
+                    col,
                 })
             }
 
@@ -1631,6 +1824,8 @@ impl Parser {
                 Ok(ParseNode::Null(line))
             }
 
+            Some(TokenType::Func) => self.parse_lambda(),
+
             other => {
                 let keyword_note = if let Some(TokenType::Identifier(ref name)) = other {
                     Self::keyword_hint(name)
@@ -1702,7 +1897,9 @@ fn node_label(node: &ParseNode) -> String {
             name,
             type_str(return_type)
         ),
-        ParseNode::Param { data_type, name } => {
+        ParseNode::Param {
+            data_type, name, ..
+        } => {
             format!("Param  \x1b[36m{}\x1b[0m : {}", name, type_str(data_type))
         }
         ParseNode::StructDef { name, .. } => format!("StructDef  \x1b[33m{}\x1b[0m", name),
@@ -1725,6 +1922,13 @@ fn node_label(node: &ParseNode) -> String {
             type_str(data_type),
             if init.is_some() { "  =" } else { "" }
         ),
+        ParseNode::ConstDecl {
+            data_type, name, ..
+        } => format!(
+            "ConstDecl  \x1b[36m{}\x1b[0m : {}  =",
+            name,
+            type_str(data_type)
+        ),
         ParseNode::Assign { op, .. } => format!("Assign  \x1b[35m{:?}\x1b[0m", op),
         ParseNode::If { .. } => "If".into(),
         ParseNode::For {
@@ -1740,7 +1944,7 @@ fn node_label(node: &ParseNode) -> String {
             let chain: String = steps
                 .iter()
                 .map(|s| match s {
-                    AccessStep::Field(f) => format!("::{}", f),
+                    AccessStep::Field(f, _) => format!("::{}", f),
                     AccessStep::Index(_) => "[…]".into(),
                     AccessStep::Call(a) => format!("({})", a.len()),
                 })
@@ -1780,11 +1984,21 @@ fn node_label(node: &ParseNode) -> String {
         ParseNode::TypeChar(_) => "TypeChar".into(),
         ParseNode::TypeBoolean(_) => "TypeBoolean".into(),
         ParseNode::TypeVoid(_) => "TypeVoid".into(),
-        ParseNode::TypeArray { elem, size, .. } => {
-            format!("TypeArray<{},{}>", type_str(elem), size)
-        }
+        ParseNode::TypeArray {
+            elem,
+            size,
+            size_const,
+            ..
+        } => match size_const {
+            Some(c) => format!("TypeArray<{},{}>", type_str(elem), c),
+            None => format!("TypeArray<{},{}>", type_str(elem), size),
+        },
         ParseNode::TypeList { elem, .. } => format!("TypeList<{}>", type_str(elem)),
         ParseNode::TypeStruct { name, .. } => format!("TypeStruct<{}>", name),
+        ParseNode::TypeFunc { .. } => format!("TypeFunc<{}>", type_str(node)),
+        ParseNode::Lambda { return_type, .. } => {
+            format!("Lambda  → {}", type_str(return_type))
+        }
     }
 }
 
@@ -1795,9 +2009,18 @@ fn type_str(node: &ParseNode) -> String {
         ParseNode::TypeChar(_) => "char".into(),
         ParseNode::TypeBoolean(_) => "bool".into(),
         ParseNode::TypeVoid(_) => "void".into(),
+        ParseNode::TypeArray {
+            elem,
+            size_const: Some(c),
+            ..
+        } => format!("array<{},{}>", type_str(elem), c),
         ParseNode::TypeArray { elem, size, .. } => format!("array<{},{}>", type_str(elem), size),
         ParseNode::TypeList { elem, .. } => format!("list<{}>", type_str(elem)),
         ParseNode::TypeStruct { name, .. } => format!("struct<{}>", name),
+        ParseNode::TypeFunc { params, ret, .. } => {
+            let ps: Vec<String> = params.iter().map(type_str).collect();
+            format!("func<({}) -> {}>", ps.join(", "), type_str(ret))
+        }
         other => format!("{:?}", other),
     }
 }
@@ -1836,7 +2059,7 @@ fn print_node_children(node: &ParseNode, prefix: &str) {
         ParseNode::Module { items, .. } => {
             print_node_list(items, prefix);
         }
-        ParseNode::FuncDef { params, body, .. } => {
+        ParseNode::FuncDef { params, body, .. } | ParseNode::Lambda { params, body, .. } => {
             if !params.is_empty() {
                 let pp = print_section_header("params", prefix, false);
                 print_node_list(params, &pp);
@@ -1859,6 +2082,10 @@ fn print_node_children(node: &ParseNode, prefix: &str) {
                 print_node(i, &ip, true);
             }
         }
+        ParseNode::ConstDecl { init, .. } => {
+            let ip = print_section_header("init", prefix, true);
+            print_node(init, &ip, true);
+        }
         ParseNode::Assign { lvalue, expr, .. } => {
             let lp = print_section_header("lvalue", prefix, false);
             print_node(lvalue, &lp, true);
@@ -1928,7 +2155,7 @@ fn print_node_children(node: &ParseNode, prefix: &str) {
                         let cp = print_section_header(&format!("call({})", i), prefix, is_last);
                         print_node_list(args, &cp);
                     }
-                    AccessStep::Field(_) => {}
+                    AccessStep::Field(..) => {}
                 }
             }
         }
@@ -1,53 +1,35 @@
 use std::fs;
-use std::io;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
 
-fn print_error_header(code: &str, title: &str) {
-    eprintln!(
-        "\x1b[1;31merror[{code}]\x1b[0m\x1b[1m: {title}\x1b[0m",
-        code = code,
-        title = title
-    );
+use crate::compiler::diagnostic::{emit_all, Diagnostic};
+
+// Where an `!import` statement is, for the diagnostics about it.
+struct ImportSite<'a> {
+    file: &'a str,
+    root: &'a str,
+    line: usize,
+    // Column of the module name, or of the end of `!import` when there is
+    // none.
+    col: usize,
+    text: String,
 }
 
-fn print_location(file: &str, line: Option<usize>) {
-    match line {
-        Some(l) => eprintln!(" \x1b[1;34m-->\x1b[0m {file}:{l}"),
-        None => eprintln!(" \x1b[1;34m-->\x1b[0m {file}"),
+impl ImportSite<'_> {
+    fn error(&self, code: &str, message: impl Into<String>, len: usize) -> Diagnostic {
+        Diagnostic::error(code, message)
+            .in_file(self.file, self.root)
+            .at(self.line, self.col, len)
+            .source_line(self.text.clone())
     }
 }
 
-fn print_hint(hint: &str) {
-    eprintln!(" \x1b[1;34m  =\x1b[0m \x1b[1;32mhint\x1b[0m: {hint}");
-}
-
-fn print_note(note: &str) {
-    eprintln!(" \x1b[1;34m  =\x1b[0m \x1b[1;36mnote\x1b[0m: {note}");
-}
-
-fn print_import_chain(chain: &[String]) {
-    eprintln!(" \x1b[1;34m  =\x1b[0m \x1b[1;36mnote\x1b[0m: import chain:");
-    for (i, file) in chain.iter().enumerate() {
-        let connector = if i == chain.len() - 1 {
-            "└─"
-        } else {
-            "├─"
-        };
-        eprintln!("       {connector} \x1b[36m{file}\x1b[0m");
-    }
-}
-
-fn blank_line() {
-    eprintln!();
-}
-
 fn module_search(
     module_name: &str,
-    current_file: &str,
-    import_line: usize,
-) -> io::Result<(Vec<char>, String)> {
+    site: &ImportSite,
+) -> Result<(Vec<char>, String), Box<Diagnostic>> {
+    let len = module_name.chars().count();
     let exe_dir = std::env::current_exe()
         .ok()
         .and_then(|p| p.parent().map(|d| d.to_path_buf()))
@@ -61,28 +43,25 @@ fn module_search(
         match file_path.extension().and_then(|e| e.to_str()) {
             Some("fr") => {}
             Some(ext) => {
-                print_error_header(
-                    "P001",
-                    &format!("cannot import file with extension `.{ext}`"),
-                );
-                print_location(current_file, Some(import_line));
-                print_hint(&format!(
-                    "only `.fr` source files can be imported - \
-                     rename the file to have a `.fr` extension, \
-                     or remove `.{ext}` from the import path (the compiler adds `.fr` automatically)"
-                ));
-                blank_line();
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("unsupported extension: {ext}"),
-                ));
+                return Err(Box::new(site
+                    .error(
+                        "P001",
+                        format!("cannot import file with extension `.{ext}`"),
+                        len,
+                    )
+                    .label("not a `.fr` file")
+                    .hint(format!(
+                        "only `.fr` source files can be imported - \
+                         rename the file to have a `.fr` extension, \
+                         or remove `.{ext}` from the import path (the compiler adds `.fr` automatically)"
+                    ))));
             }
             None => {
                 file_path.set_extension("fr");
             }
         }
 
-        let base_path = Path::new(current_file).parent().unwrap_or(Path::new("."));
+        let base_path = Path::new(site.file).parent().unwrap_or(Path::new("."));
         let resolved_path = base_path.join(&file_path);
         let canonical_path = resolved_path
             .canonicalize()
@@ -91,84 +70,65 @@ fn module_search(
         if canonical_path.exists() && canonical_path.is_file() {
             let file_contents = fs::read_to_string(&canonical_path).map_err(|e| {
                 let path_str = canonical_path.display().to_string();
-                print_error_header(
+                site.error(
                     "P002",
-                    &format!("failed to read imported file `{path_str}`"),
-                );
-                print_location(current_file, Some(import_line));
-                print_hint(&format!(
+                    format!("failed to read imported file `{path_str}`"),
+                    len,
+                )
+                .label("cannot read this file")
+                .hint(format!(
                     "the file was found but could not be read - \
                      check that the process has read permission on this file; OS error: {e}"
-                ));
-                blank_line();
-                e
+                ))
             })?;
             let canonical_str = canonical_path.to_str().unwrap_or("").to_string();
             return Ok((file_contents.chars().collect(), canonical_str));
         }
 
         let display = canonical_path.display().to_string();
-        print_error_header("P003", &format!("imported file not found: `{display}`"));
-        print_location(current_file, Some(import_line));
-        eprintln!(" \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import {module_name};\x1b[0m");
-        eprintln!(
-            " \x1b[1;34m  |\x1b[0m          \x1b[1;31m{caret}\x1b[0m no file at this path",
-            caret = "^".repeat(module_name.len())
-        );
-        print_hint(&format!(
-            "the path `{display}` does not exist - \
-             check for typos in the filename, and note that paths are resolved \
-             relative to the importing file, not the working directory"
-        ));
-        print_note(&format!("searched relative to: `{}`", base_path.display()));
-        blank_line();
-
-        Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("module not found: {module_name}"),
+        Err(Box::new(
+            site.error("P003", format!("imported file not found: `{display}`"), len)
+                .label("no file at this path")
+                .hint(format!(
+                    "the path `{display}` does not exist - \
+                 check for typos in the filename, and note that paths are resolved \
+                 relative to the importing file, not the working directory"
+                ))
+                .note(format!("searched relative to: `{}`", base_path.display())),
         ))
     } else {
         let lib_path = format!("{lib_dir}/{module_name}.fr");
         if Path::new(&lib_path).exists() {
             let contents = fs::read_to_string(&lib_path).map_err(|e| {
-                print_error_header(
+                site.error(
                     "P002",
-                    &format!("failed to read standard library module `{module_name}`"),
-                );
-                print_location(current_file, Some(import_line));
-                print_hint(&format!(
+                    format!("failed to read standard library module `{module_name}`"),
+                    len,
+                )
+                .label("cannot read this module")
+                .hint(format!(
                     "the module file was found at `{lib_path}` but could not be read - \
                      check file permissions; OS error: {e}"
-                ));
-                blank_line();
-                e
+                ))
             })?;
             Ok((contents.chars().collect(), lib_path))
         } else {
-            print_error_header(
-                "P004",
-                &format!("unknown standard library module `{module_name}`"),
-            );
-            print_location(current_file, Some(import_line));
-            eprintln!(" \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import {module_name};\x1b[0m");
-            eprintln!(
-                " \x1b[1;34m  |\x1b[0m          \x1b[1;31m{caret}\x1b[0m not a known standard library module",
-                caret = "^".repeat(module_name.len())
-            );
-            print_hint(&format!(
-                "`{module_name}` is not in the standard library - \
-                 if this is a local file, quote the path: `!import \"{module_name}.fr\";` \
-                 so it is resolved relative to the current file instead"
-            ));
-            print_note(&format!(
-                "standard library modules are looked up in `{lib_dir}/`; \
-                 if that directory is wrong, update `lib_dir` in the compiler source"
-            ));
-            blank_line();
-
-            Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("module not available: {module_name}"),
+            Err(Box::new(
+                site.error(
+                    "P004",
+                    format!("unknown standard library module `{module_name}`"),
+                    len,
+                )
+                .label("not a known standard library module")
+                .hint(format!(
+                    "`{module_name}` is not in the standard library - \
+                     if this is a local file, quote the path: `!import \"{module_name}.fr\";` \
+                     so it is resolved relative to the current file instead"
+                ))
+                .note(format!(
+                    "standard library modules are looked up in `{lib_dir}/`; \
+                     if that directory is wrong, update `lib_dir` in the compiler source"
+                )),
             ))
         }
     }
@@ -225,7 +185,12 @@ fn rfind_standalone(text: &str, marker: &str) -> Option<usize> {
     last
 }
 
-fn strip_start_end(chars: &[char], source_file: &str) -> Vec<char> {
+fn strip_start_end(
+    chars: &[char],
+    source_file: &str,
+    root: &str,
+    warnings: &mut Vec<Diagnostic>,
+) -> Vec<char> {
     let text: String = chars.iter().collect();
 
     let after_start = if let Some(pos) = find_standalone(&text, "!start") {
@@ -236,15 +201,11 @@ fn strip_start_end(chars: &[char], source_file: &str) -> Vec<char> {
             &text[end..]
         }
     } else {
-        eprintln!(
-            "\x1b[1;33mwarning\x1b[0m: \
-             `!start` marker not found in `{source_file}`"
-        );
-        eprintln!(
-            " \x1b[1;34m  =\x1b[0m \x1b[1;32mhint\x1b[0m: \
-             every source file should begin with `!start` and end with `!end`"
+        warnings.push(
+            Diagnostic::warning("", format!("`!start` marker not found in `{source_file}`"))
+                .in_file(source_file, root)
+                .hint("every source file should begin with `!start` and end with `!end`"),
         );
-        eprintln!();
         &text
     };
 
@@ -256,19 +217,33 @@ fn strip_start_end(chars: &[char], source_file: &str) -> Vec<char> {
             chunk
         }
     } else {
-        eprintln!(
-            "\x1b[1;33mwarning\x1b[0m: \
-             `!end` marker not found in `{source_file}`"
-        );
-        eprintln!(
-            " \x1b[1;34m  =\x1b[0m \x1b[1;32mhint\x1b[0m: \
-             every source file should begin with `!start` and end with `!end`"
+        warnings.push(
+            Diagnostic::warning("", format!("`!end` marker not found in `{source_file}`"))
+                .in_file(source_file, root)
+                .hint("every source file should begin with `!start` and end with `!end`"),
         );
-        eprintln!();
         after_start
     };
 
-    before_end.chars().collect()
+    let skipped_lines = text[..text.len() - after_start.len()].matches('\n').count();
+    let mut out: Vec<char> = vec!['\n'; skipped_lines];
+    out.extend(before_end.chars());
+    out
+}
+
+// The text of the line holding `chars[index]`, and the column of that
+// character in it.
+fn line_at(chars: &[char], index: usize) -> (String, usize) {
+    let index = index.min(chars.len());
+    let start = chars[..index]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |p| p + 1);
+    let end = chars[start..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |p| start + p);
+    (chars[start..end].iter().collect(), index - start + 1)
 }
 
 fn traverse(
@@ -277,7 +252,9 @@ fn traverse(
     current_file: &str,
     import_chain: &mut Vec<String>,
     is_root: bool,
-) -> (String, String) {
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(String, String), Box<Diagnostic>> {
+    let root = import_chain[0].clone();
     let mut index: usize = 0;
     let mut line: usize = 1;
 
@@ -335,6 +312,7 @@ fn traverse(
 
         if chars[index] == '#' {
             if index + 2 < chars.len() && chars[index + 1] == '#' && chars[index + 2] == '#' {
+                let (comment_text, comment_col) = line_at(chars, index);
                 index += 3;
                 let comment_start_line = line;
                 let mut closed = false;
@@ -350,15 +328,17 @@ fn traverse(
                     index += 1;
                 }
                 if !closed {
-                    print_error_header("P005", "unterminated block comment");
-                    print_location(current_file, Some(comment_start_line));
-                    eprintln!(
-                        " \x1b[1;34m  |\x1b[0m   block comment opened here with `###`, \
-                         but no matching `###` closing marker was found before end of file"
-                    );
-                    print_hint("add a closing `###` on its own line to end the block comment");
-                    blank_line();
-                    process::exit(1);
+                    return Err(Box::new(
+                        Diagnostic::error("P005", "unterminated block comment")
+                            .in_file(current_file, &root)
+                            .at(comment_start_line, comment_col, 3)
+                            .source_line(comment_text)
+                            .label(
+                                "block comment opened here with `###`, but no matching `###` \
+                             closing marker was found before end of file",
+                            )
+                            .hint("add a closing `###` on its own line to end the block comment"),
+                    ));
                 }
 
                 own_body.push_str(&format!(
@@ -399,19 +379,25 @@ fn traverse(
                 while index < chars.len() && chars[index] == ' ' {
                     index += 1;
                 }
+                let (import_text, name_col) = line_at(chars, index);
+                let mut site = ImportSite {
+                    file: current_file,
+                    root: &root,
+                    line: import_line,
+                    col: name_col,
+                    text: import_text,
+                };
 
                 if index >= chars.len() || chars[index] == '\n' || chars[index] == ';' {
-                    print_error_header("P006", "`!import` statement has no module name");
-                    print_location(current_file, Some(import_line));
-                    eprintln!(" \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import;\x1b[0m");
-                    eprintln!(" \x1b[1;34m  |\x1b[0m          \x1b[1;31m^ module name missing here\x1b[0m");
-                    print_hint(
-                        "supply a module name after `!import`: \
-                         use `!import \"./path/to/file\";` for a local file \
-                         or `!import modname;` for a standard library module",
-                    );
-                    blank_line();
-                    process::exit(1);
+                    return Err(Box::new(
+                        site.error("P006", "`!import` statement has no module name", 1)
+                            .label("module name missing here")
+                            .hint(
+                                "supply a module name after `!import`: \
+                             use `!import \"./path/to/file\";` for a local file \
+                             or `!import modname;` for a standard library module",
+                            ),
+                    ));
                 }
 
                 let mut module_name = String::new();
@@ -422,22 +408,19 @@ fn traverse(
                 let module_name = module_name.trim().to_string();
 
                 if index >= chars.len() || chars[index] == '\n' {
-                    print_error_header(
-                        "P007",
-                        &format!("missing `;` at end of `!import {module_name}`"),
-                    );
-                    print_location(current_file, Some(import_line));
-                    eprintln!(" \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import {module_name}\x1b[0m");
-                    eprintln!(
-                        " \x1b[1;34m  |\x1b[0m   {pad}\x1b[1;31m^ `;` required here\x1b[0m",
-                        pad = " ".repeat(8 + module_name.len())
-                    );
-                    print_hint(&format!(
-                        "every import statement must end with a semicolon - \
-                         write it as: `!import {module_name};`"
+                    site.col = line_at(chars, index).1;
+                    return Err(Box::new(
+                        site.error(
+                            "P007",
+                            format!("missing `;` at end of `!import {module_name}`"),
+                            1,
+                        )
+                        .label("`;` required here")
+                        .hint(format!(
+                            "every import statement must end with a semicolon - \
+                             write it as: `!import {module_name};`"
+                        )),
                     ));
-                    blank_line();
-                    process::exit(1);
                 }
 
                 index += 1;
@@ -448,92 +431,80 @@ fn traverse(
                         .next()
                         .map_or(false, |c| c.is_alphabetic() || c == '_')
                 {
-                    print_error_header("P008", &format!("invalid module name `{module_name}`"));
-                    print_location(current_file, Some(import_line));
-                    eprintln!(" \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import {module_name};\x1b[0m");
-                    eprintln!(
-                        " \x1b[1;34m  |\x1b[0m          \x1b[1;31m{caret}\x1b[0m invalid name",
-                        caret = "^".repeat(module_name.len())
-                    );
-                    print_hint(
-                        "local file paths must be in double quotes: `!import \"./mymod\";` - \
-                         standard library module names must start with a letter or `_`: `!import math;`",
-                    );
-                    blank_line();
-                    process::exit(1);
+                    return Err(Box::new(site
+                        .error(
+                            "P008",
+                            format!("invalid module name `{module_name}`"),
+                            module_name.chars().count(),
+                        )
+                        .label("invalid name")
+                        .hint(
+                            "local file paths must be in double quotes: `!import \"./mymod\";` - \
+                             standard library module names must start with a letter or `_`: `!import math;`",
+                        )));
                 }
 
-                match module_search(&module_name, current_file, import_line) {
-                    Ok((module_raw, resolved_path)) => {
-                        if import_chain.contains(&resolved_path) {
-                            let mod_name = get_module_name_from_path(&module_name);
-                            print_error_header(
-                                "P009",
-                                &format!("circular import detected: `{mod_name}` is already being imported"),
-                            );
-                            print_location(current_file, Some(import_line));
-                            eprintln!(
-                                " \x1b[1;34m  |\x1b[0m   \x1b[1;31m!import {module_name};\x1b[0m"
-                            );
-                            eprintln!(
-                                " \x1b[1;34m  |\x1b[0m          \x1b[1;31m{caret}\x1b[0m this import creates a cycle",
-                                caret = "^".repeat(module_name.len())
-                            );
-                            print_import_chain(import_chain);
-                            eprintln!(
-                                "       \x1b[1;31m└─ {resolved_path} ↩ already in the chain above\x1b[0m"
-                            );
-                            print_hint(
-                                "a file cannot directly or transitively import itself - \
-                                 extract the shared code into a separate module that neither file imports"
-                            );
-                            blank_line();
-                            process::exit(1);
-                        }
-
-                        if !visited_modules.contains(&resolved_path) {
-                            visited_modules.push(resolved_path.clone());
-
-                            let mut module_content = strip_start_end(&module_raw, &resolved_path);
-
-                            let extracted_name = get_module_name_from_path(&module_name);
-
-                            import_chain.push(resolved_path.clone());
-                            let (child_module_blocks, child_own_body) = traverse(
-                                &mut module_content,
-                                visited_modules,
-                                &resolved_path,
-                                import_chain,
-                                false,
-                            );
-                            import_chain.pop();
-
-                            let mut this_import_blocks = String::new();
-                            this_import_blocks.push_str(&child_module_blocks);
-                            this_import_blocks
-                                .push_str(&format!("$MODULE_START:{extracted_name}$\n"));
-                            this_import_blocks.push_str(&child_own_body);
-                            this_import_blocks
-                                .push_str(&format!("$MODULE_END:{extracted_name}$;\n"));
-
-                            module_blocks.push_str(&this_import_blocks);
-                        }
-
-                        if is_root {
-                            own_body.push_str(&module_blocks);
-                            module_blocks.clear();
-                        }
-
-                        own_body.push_str(&format!(
-                            "$SRCMAP:{}:{}$\n",
-                            current_file,
-                            line.saturating_sub(1)
-                        ));
-                    }
-                    Err(_) => {
-                        process::exit(1);
-                    }
+                let (module_raw, resolved_path) = module_search(&module_name, &site)?;
+                if import_chain.contains(&resolved_path) {
+                    let mod_name = get_module_name_from_path(&module_name);
+                    let chain: Vec<&str> = import_chain.iter().map(String::as_str).collect();
+                    return Err(Box::new(site
+                        .error(
+                            "P009",
+                            format!(
+                                "circular import detected: `{mod_name}` is already being imported"
+                            ),
+                            module_name.chars().count(),
+                        )
+                        .label("this import creates a cycle")
+                        .note(format!(
+                            "import chain: {} -> {resolved_path} (already in the chain)",
+                            chain.join(" -> ")
+                        ))
+                        .hint(
+                            "a file cannot directly or transitively import itself - \
+                             extract the shared code into a separate module that neither file imports",
+                        )));
                 }
+
+                if !visited_modules.contains(&resolved_path) {
+                    visited_modules.push(resolved_path.clone());
+
+                    let mut module_content =
+                        strip_start_end(&module_raw, &resolved_path, &root, warnings);
+
+                    let extracted_name = get_module_name_from_path(&module_name);
+
+                    import_chain.push(resolved_path.clone());
+                    let (child_module_blocks, child_own_body) = traverse(
+                        &mut module_content,
+                        visited_modules,
+                        &resolved_path,
+                        import_chain,
+                        false,
+                        warnings,
+                    )?;
+                    import_chain.pop();
+
+                    let mut this_import_blocks = String::new();
+                    this_import_blocks.push_str(&child_module_blocks);
+                    this_import_blocks.push_str(&format!("$MODULE_START:{extracted_name}$\n"));
+                    this_import_blocks.push_str(&child_own_body);
+                    this_import_blocks.push_str(&format!("$MODULE_END:{extracted_name}$;\n"));
+
+                    module_blocks.push_str(&this_import_blocks);
+                }
+
+                if is_root {
+                    own_body.push_str(&module_blocks);
+                    module_blocks.clear();
+                }
+
+                own_body.push_str(&format!(
+                    "$SRCMAP:{}:{}$\n",
+                    current_file,
+                    line.saturating_sub(1)
+                ));
             } else {
                 own_body.push_str(&temp);
             }
@@ -544,21 +515,47 @@ fn traverse(
         index += 1;
     }
 
-    (module_blocks, own_body)
+    Ok((module_blocks, own_body))
 }
 
+/// Inlines every module `program` imports, printing any problem and exiting
+/// on an error.
 pub fn preprocess(program: &str, source_file: &str) -> String {
+    match try_preprocess(program, source_file) {
+        Ok((output, warnings)) => {
+            emit_all(&warnings);
+            output
+        }
+        Err(diagnostics) => {
+            emit_all(&diagnostics);
+            process::exit(1);
+        }
+    }
+}
+
+/// Like [`preprocess`], but returns the expanded program with its warnings,
+/// or every diagnostic up to the first error, instead of printing them.
+pub fn try_preprocess(
+    program: &str,
+    source_file: &str,
+) -> Result<(String, Vec<Diagnostic>), Vec<Diagnostic>> {
     let mut chars: Vec<char> = program.chars().collect();
     let mut visited_modules: Vec<String> = Vec::new();
     let mut import_chain = vec![source_file.to_string()];
+    let mut warnings = Vec::new();
 
-    let (_empty, output) = traverse(
+    match traverse(
         &mut chars,
         &mut visited_modules,
         source_file,
         &mut import_chain,
         true,
-    );
-
-    output
+        &mut warnings,
+    ) {
+        Ok((_empty, output)) => Ok((output, warnings)),
+        Err(error) => {
+            warnings.push(*error);
+            Err(warnings)
+        }
+    }
 }
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the profile format, which `codegen::emit_profile_runtime`
+/// writes without serde. Bump it whenever a field changes.
+pub const PROFILE_VERSION: u32 = 1;
+
+/// What a `--profile` build of a program recorded over one run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    pub lines: Vec<LineStat>,
+    pub functions: Vec<FuncStat>,
+}
+
+/// A line's hits and the time from the statement before it up to the end of
+/// the line, which leaves out the functions it calls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineStat {
+    /// Empty for the main program, otherwise the module name, as in
+    /// `snapshot::Snapshot::file`.
+    pub file: String,
+    pub line: usize,
+    pub hits: u64,
+    pub nanos: u64,
+}
+
+/// A function's calls, the time inside it including the functions it calls
+/// (counted once for recursive calls), and the time in its own body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuncStat {
+    /// `<main>` for the top level, `<lambda>` for lambdas, and
+    /// `module::name` for functions in modules.
+    pub name: String,
+    pub calls: u64,
+    pub total_nanos: u64,
+    pub self_nanos: u64,
+}
+
+impl Profile {
+    /// The running time of the whole program.
+    pub fn total_nanos(&self) -> u64 {
+        self.functions
+            .iter()
+            .find(|f| f.name == "<main>")
+            .map(|f| f.total_nanos)
+            .unwrap_or_else(|| self.lines.iter().map(|l| l.nanos).sum())
+    }
+}
+
+/// Where a `--profile` build of `source` writes its profile.
+pub fn path_for(source: &Path) -> PathBuf {
+    source.with_extension("profile.json")
+}
+
+pub fn read(path: &Path) -> Result<Profile, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(v) if v == u64::from(PROFILE_VERSION) => {}
+        _ => {
+            return Err(format!(
+                "the profile was written by a fractal-compiler from another release \
+                 (this build reads profile format v{PROFILE_VERSION})"
+            ))
+        }
+    }
+    serde_json::from_value(value).map_err(|e| format!("malformed profile: {e}"))
+}
+
+/// Formats a duration in nanoseconds with a unit that suits its size.
+pub fn format_nanos(nanos: u64) -> String {
+    match nanos {
+        n if n >= 1_000_000_000 => format!("{:.2} s", n as f64 / 1e9),
+        n if n >= 1_000_000 => format!("{:.2} ms", n as f64 / 1e6),
+        n if n >= 1_000 => format!("{:.1} µs", n as f64 / 1e3),
+        n => format!("{n} ns"),
+    }
+}
@@ -1,4 +1,6 @@
 use crate::compiler::builtins::{BType, ALL_BUILTINS};
+use crate::compiler::constfold::{try_fold, ConstError, ConstValue};
+use crate::compiler::diagnostic::{Diagnostic, Severity};
 use crate::compiler::parser::{AccessStep, AssignOp, CmpOp, MulOp, ParseNode, UnOp};
 use crate::compiler::retcheck::check_function_returns;
 use std::collections::HashMap;
@@ -11,15 +13,24 @@ pub enum SemType {
     Char,
     Boolean,
     Void,
-    Array { elem: Box<SemType>, size: i64 },
-    List { elem: Box<SemType> },
+    Array {
+        elem: Box<SemType>,
+        size: i64,
+    },
+    List {
+        elem: Box<SemType>,
+    },
     Struct(String),
+    Func {
+        params: Vec<SemType>,
+        ret: Box<SemType>,
+    },
 
     Unknown,
 }
 
 impl SemType {
-    fn display(&self) -> String {
+    pub fn display(&self) -> String {
         match self {
             SemType::Int => ":int".into(),
             SemType::Float => ":float".into(),
@@ -29,6 +40,15 @@ impl SemType {
             SemType::Array { elem, size } => format!(":array<{}, {}>", elem.display(), size),
             SemType::List { elem } => format!(":list<{}>", elem.display()),
             SemType::Struct(n) => format!(":struct<{}>", n),
+            SemType::Func { params, ret } => format!(
+                ":func<({}) -> {}>",
+                params
+                    .iter()
+                    .map(|p| p.display())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ret.display()
+            ),
             SemType::Unknown => "<unknown>".into(),
         }
     }
@@ -56,6 +76,16 @@ pub fn sem_type_from_btype(bt: &BType) -> SemType {
     }
 }
 
+/// Where a name stands in the source: `file` is empty for the program
+/// itself, otherwise the name of the module the line is in. `col` is the
+/// 1-based column of the name, or 0 when the parser did not record one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Symbol {
     pub id: usize,
@@ -65,12 +95,19 @@ pub struct Symbol {
     pub scope_depth: usize,
     pub origin: String,
     pub use_count: usize,
+    /// Where the symbol is declared, when the parser knows the line.
+    pub def: Option<Location>,
+    /// Every place the symbol is used, once each, in analysis order.
+    pub refs: Vec<Location>,
 }
 
 impl fmt::Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let type_str = match &self.kind {
             SymbolKind::Variable => self.sem_type.display(),
+            SymbolKind::Constant { value } => {
+                format!("const {} = {}", self.sem_type.display(), value)
+            }
             SymbolKind::Function { params } => {
                 let ps: Vec<String> = params.iter().map(|p| p.display()).collect();
                 format!("fn({}) -> {}", ps.join(", "), self.sem_type.display())
@@ -94,6 +131,7 @@ impl fmt::Display for Symbol {
 #[derive(Debug, Clone)]
 pub enum SymbolKind {
     Variable,
+    Constant { value: ConstValue },
     Function { params: Vec<SemType> },
     Struct { fields: Vec<(String, SemType)> },
 }
@@ -215,6 +253,8 @@ fn format_notes(rest: &str) {
 pub struct SemanticWarning {
     pub message: String,
     pub line: Option<usize>,
+    /// Empty for the program being analysed, otherwise the module name.
+    pub file: String,
 }
 
 impl fmt::Display for SemanticWarning {
@@ -237,20 +277,24 @@ impl fmt::Display for SemanticWarning {
 pub struct SemanticError {
     pub message: String,
     pub line: Option<usize>,
+    /// Empty for the program being analysed, otherwise the module name.
+    pub file: String,
 }
 
 impl SemanticError {
-    fn new(msg: impl Into<String>) -> Self {
+    fn new(msg: impl Into<String>, file: &str) -> Self {
         SemanticError {
             message: msg.into(),
             line: None,
+            file: file.to_string(),
         }
     }
 
-    fn with_line(msg: impl Into<String>, line: usize) -> Self {
+    fn with_line(msg: impl Into<String>, line: usize, file: &str) -> Self {
         SemanticError {
             message: msg.into(),
             line: Some(line),
+            file: file.to_string(),
         }
     }
 }
@@ -303,6 +347,28 @@ impl SemanticResult {
         }
     }
 
+    /// Every warning and error, for the editor to show as the code is typed.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let warnings = self
+            .warnings
+            .iter()
+            .map(|w| (Severity::Warning, &w.message, w.line, &w.file));
+        let errors = self
+            .errors
+            .iter()
+            .map(|e| (Severity::Error, &e.message, e.line, &e.file));
+        warnings
+            .chain(errors)
+            .map(|(severity, message, line, file)| {
+                let mut d =
+                    Diagnostic::from_message(severity, "", message).at(line.unwrap_or(0), 0, 0);
+                d.file = file.clone();
+                d.path = file.clone();
+                d
+            })
+            .collect()
+    }
+
     pub fn print_symbol_table(&self) {
         let line = "═".repeat(95);
         println!("\n\x1b[1;34m╔{}╗\x1b[0m", line);
@@ -330,7 +396,10 @@ struct Analyzer {
     current_return_type: Option<SemType>,
 
     loop_depth: usize,
+    lambda_floor: Option<usize>,
     current_origin: String,
+    // Empty in the program itself, otherwise the module being analysed.
+    current_module: String,
     next_id: usize,
 }
 
@@ -349,6 +418,8 @@ impl Analyzer {
                 scope_depth: 0,
                 origin: "builtin".to_string(),
                 use_count: 0,
+                def: None,
+                refs: Vec::new(),
             });
         }
         Analyzer {
@@ -358,23 +429,28 @@ impl Analyzer {
             all_symbols: Vec::new(),
             current_return_type: None,
             loop_depth: 0,
+            lambda_floor: None,
             current_origin: "global".to_string(),
+            current_module: String::new(),
             next_id: 1,
         }
     }
 
     fn error(&mut self, msg: impl Into<String>) {
-        self.errors.push(SemanticError::new(msg));
+        self.errors
+            .push(SemanticError::new(msg, &self.current_module));
     }
 
     fn error_at(&mut self, line: usize, msg: impl Into<String>) {
-        self.errors.push(SemanticError::with_line(msg, line));
+        self.errors
+            .push(SemanticError::with_line(msg, line, &self.current_module));
     }
 
     fn warn_at(&mut self, line: usize, msg: impl Into<String>) {
         self.warnings.push(SemanticWarning {
             message: msg.into(),
             line: Some(line),
+            file: self.current_module.clone(),
         });
     }
 
@@ -393,29 +469,196 @@ impl Analyzer {
         self.scopes.define(sym);
     }
 
-    fn add_usage(&mut self, name: &str) {
+    fn location(&self, line: usize, col: usize) -> Option<Location> {
+        (line > 0).then(|| Location {
+            file: self.current_module.clone(),
+            line,
+            col,
+        })
+    }
+
+    fn add_usage(&mut self, name: &str, line: usize, col: usize) {
+        let at = self.location(line, col);
         if let Some(id) = self.scopes.lookup(name).map(|s| s.id) {
             if let Some(sym) = self.all_symbols.iter_mut().find(|s| s.id == id) {
                 sym.use_count += 1;
+                if let Some(at) = at {
+                    if !sym.refs.contains(&at) {
+                        sym.refs.push(at);
+                    }
+                }
+            }
+        }
+    }
+
+    fn const_value(&self, name: &str) -> Option<ConstValue> {
+        match self.scopes.lookup(name) {
+            Some(Symbol {
+                kind: SymbolKind::Constant { value },
+                ..
+            }) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn resolve_array_size(&mut self, name: &str, line: usize, col: usize) -> i64 {
+        match self.scopes.lookup(name).map(|s| s.kind.clone()) {
+            Some(SymbolKind::Constant {
+                value: ConstValue::Int(n),
+            }) if n > 0 => {
+                self.add_usage(name, line, col);
+                n
+            }
+            Some(SymbolKind::Constant {
+                value: ConstValue::Int(n),
+            }) => {
+                self.error_at(
+                    line,
+                    format!(
+                        "array size `{}` is {}, but an array size must be greater than zero",
+                        name, n
+                    ),
+                );
+                0
+            }
+            Some(SymbolKind::Constant { value }) => {
+                self.error_at(
+                    line,
+                    format!(
+                        "array size `{}` must be an `:int` constant, but its value is `{}`",
+                        name, value
+                    ),
+                );
+                0
+            }
+            Some(_) => {
+                self.error_at(
+                    line,
+                    format!(
+                        "array size `{}` is not a constant\n\
+                         note: array sizes are fixed at compile time, so only literals and \
+                         `!const :int` names are allowed\n\
+                         hint: declare it with `!const :int {} = ...;`",
+                        name, name
+                    ),
+                );
+                0
+            }
+            None => {
+                self.error_at(
+                    line,
+                    format!(
+                        "undefined constant `{}` used as an array size\n\
+                         note: the constant must be declared with `!const :int {} = ...;` before it is used",
+                        name, name
+                    ),
+                );
+                0
             }
         }
     }
 
-    fn resolve_type_node(&self, node: &ParseNode) -> SemType {
+    fn check_const_shadow(&mut self, name: &str, line: Option<usize>) {
+        if self.scopes.defined_in_current(name) {
+            return;
+        }
+        if let Some(SymbolKind::Constant { .. }) = self.scopes.lookup(name).map(|s| &s.kind) {
+            let msg = format!(
+                "`{}` is already a constant and cannot be redeclared\n\
+                 note: constants declared with `!const` stay visible in every nested scope\n\
+                 hint: choose a different name",
+                name
+            );
+            match line {
+                Some(ln) => self.error_at(ln, msg),
+                None => self.error(msg),
+            }
+        }
+    }
+
+    fn check_not_const_target(&mut self, target: &ParseNode, line: usize) {
+        let ParseNode::AccessChain { base, steps, .. } = target else {
+            return;
+        };
+        let name = match steps.as_slice() {
+            [] => base.clone(),
+            [AccessStep::Field(f, _)] => format!("{}::{}", base, f),
+            _ => return,
+        };
+        if let Some(SymbolKind::Constant { .. }) = self.scopes.lookup(&name).map(|s| &s.kind) {
+            self.error_at(
+                line,
+                format!(
+                    "cannot assign to constant `{}`\n\
+                     note: `{}` was declared with `!const` and its value cannot change\n\
+                     hint: declare it as a normal variable if it needs to be updated",
+                    name, name
+                ),
+            );
+        }
+    }
+
+    fn contains_func(ty: &SemType) -> bool {
+        match ty {
+            SemType::Func { .. } => true,
+            SemType::Array { elem, .. } | SemType::List { elem } => Self::contains_func(elem),
+            _ => false,
+        }
+    }
+
+    fn check_not_captured_target(&mut self, target: &ParseNode, line: usize) {
+        let (Some(floor), ParseNode::AccessChain { base, .. }) = (self.lambda_floor, target) else {
+            return;
+        };
+        let captured = matches!(
+            self.scopes.lookup(base),
+            Some(s) if matches!(s.kind, SymbolKind::Variable) && s.scope_depth < floor
+        );
+        if captured {
+            self.error_at(
+                line,
+                format!(
+                    "cannot modify `{}` inside a lambda\n\
+                     note: lambdas capture outer variables by value, so the change would not be \
+                     visible outside\n\
+                     hint: return the new value from the lambda instead",
+                    base
+                ),
+            );
+        }
+    }
+
+    fn resolve_type_node(&mut self, node: &ParseNode) -> SemType {
         match node {
             ParseNode::TypeInt(_) => SemType::Int,
             ParseNode::TypeFloat(_) => SemType::Float,
             ParseNode::TypeChar(_) => SemType::Char,
             ParseNode::TypeBoolean(_) => SemType::Boolean,
             ParseNode::TypeVoid(_) => SemType::Void,
-            ParseNode::TypeArray { elem, size, .. } => SemType::Array {
+            ParseNode::TypeArray {
+                elem,
+                size,
+                size_const,
+                line,
+                size_col,
+            } => SemType::Array {
                 elem: Box::new(self.resolve_type_node(elem)),
-                size: *size,
+                size: match size_const {
+                    Some(name) => self.resolve_array_size(name, *line, *size_col),
+                    None => *size,
+                },
             },
             ParseNode::TypeList { elem, .. } => SemType::List {
                 elem: Box::new(self.resolve_type_node(elem)),
             },
-            ParseNode::TypeStruct { name, .. } => SemType::Struct(name.clone()),
+            ParseNode::TypeStruct { name, line, col } => {
+                self.add_usage(name, *line, *col);
+                SemType::Struct(name.clone())
+            }
+            ParseNode::TypeFunc { params, ret, .. } => SemType::Func {
+                params: params.iter().map(|p| self.resolve_type_node(p)).collect(),
+                ret: Box::new(self.resolve_type_node(ret)),
+            },
             _ => SemType::Unknown,
         }
     }
@@ -432,6 +675,13 @@ impl Analyzer {
             SemType::List { elem } => SemType::List {
                 elem: Box::new(Self::qualify_struct_type(elem, module)),
             },
+            SemType::Func { params, ret } => SemType::Func {
+                params: params
+                    .iter()
+                    .map(|p| Self::qualify_struct_type(p, module))
+                    .collect(),
+                ret: Box::new(Self::qualify_struct_type(ret, module)),
+            },
             other => other.clone(),
         }
     }
@@ -453,6 +703,9 @@ impl Analyzer {
                 SymbolKind::Function { params: qparams }
             }
             SymbolKind::Variable => SymbolKind::Variable,
+            SymbolKind::Constant { value } => SymbolKind::Constant {
+                value: value.clone(),
+            },
         }
     }
 
@@ -489,9 +742,148 @@ impl Analyzer {
             return Self::types_compatible(le, ae);
         }
 
+        if let (
+            SemType::Func {
+                params: pa,
+                ret: ra,
+            },
+            SemType::Func {
+                params: pb,
+                ret: rb,
+            },
+        ) = (a, b)
+        {
+            let ret_ok = match (ra.as_ref(), rb.as_ref()) {
+                (SemType::Void, SemType::Void) | (SemType::Unknown, _) | (_, SemType::Unknown) => {
+                    true
+                }
+                (x, y) => Self::types_compatible(x, y),
+            };
+            return ret_ok
+                && pa.len() == pb.len()
+                && pa.iter().zip(pb).all(|(x, y)| Self::types_compatible(x, y));
+        }
+
         false
     }
 
+    fn check_value_call(
+        &mut self,
+        callee: &SemType,
+        args: &[ParseNode],
+        name: &str,
+        line: usize,
+    ) -> SemType {
+        let arg_types: Vec<SemType> = args.iter().map(|a| self.infer_expr(a)).collect();
+        let SemType::Func { params, ret } = callee else {
+            return SemType::Unknown;
+        };
+        if arg_types.len() != params.len() {
+            self.error_at(
+                line,
+                format!(
+                    "`{}` has type `{}` and expects {} argument(s), got {}",
+                    name,
+                    callee.display(),
+                    params.len(),
+                    arg_types.len()
+                ),
+            );
+        } else {
+            for (i, (pt, at)) in params.iter().zip(arg_types.iter()).enumerate() {
+                if !Self::types_compatible(pt, at) {
+                    self.error_at(
+                        line,
+                        format!(
+                            "argument {} of `{}` expects type `{}`, got `{}`",
+                            i + 1,
+                            name,
+                            pt.display(),
+                            at.display()
+                        ),
+                    );
+                }
+            }
+        }
+        *ret.clone()
+    }
+
+    fn check_higher_order(&mut self, name: &str, arg_types: &[SemType], line: usize) -> SemType {
+        let elem = match arg_types.first() {
+            Some(SemType::List { elem }) | Some(SemType::Array { elem, .. }) => *elem.clone(),
+            Some(SemType::Unknown) | None => SemType::Unknown,
+            Some(other) => {
+                self.error_at(
+                    line,
+                    format!(
+                        "`{}` requires a `:list` or `:array` as its first argument, got `{}`",
+                        name,
+                        other.display()
+                    ),
+                );
+                SemType::Unknown
+            }
+        };
+        let expected = match name {
+            "map" => SemType::Func {
+                params: vec![elem.clone()],
+                ret: Box::new(SemType::Unknown),
+            },
+            "filter" => SemType::Func {
+                params: vec![elem.clone()],
+                ret: Box::new(SemType::Boolean),
+            },
+            _ => {
+                let acc = arg_types.get(2).cloned().unwrap_or(SemType::Unknown);
+                SemType::Func {
+                    params: vec![acc.clone(), elem.clone()],
+                    ret: Box::new(acc),
+                }
+            }
+        };
+        let func_ty = arg_types.get(1).cloned().unwrap_or(SemType::Unknown);
+        let ret = match &func_ty {
+            SemType::Func { ret, .. } if Self::types_compatible(&expected, &func_ty) => {
+                *ret.clone()
+            }
+            SemType::Unknown => SemType::Unknown,
+            other => {
+                let wanted = match name {
+                    "map" => format!("a function taking one `{}` argument", elem.display()),
+                    _ => format!("a function of type `{}`", expected.display()),
+                };
+                self.error_at(
+                    line,
+                    format!(
+                        "argument 2 of `{}` must be {}, got `{}`",
+                        name,
+                        wanted,
+                        other.display()
+                    ),
+                );
+                SemType::Unknown
+            }
+        };
+        if name == "map" && matches!(ret, SemType::Void) {
+            self.error_at(
+                line,
+                "the function passed to `map` must return a value, not `:void`".to_string(),
+            );
+        }
+        match name {
+            "map" => SemType::List {
+                elem: Box::new(ret),
+            },
+            "filter" => SemType::List {
+                elem: Box::new(elem),
+            },
+            _ => match arg_types.get(2) {
+                Some(SemType::Unknown) | None => ret,
+                Some(init) => init.clone(),
+            },
+        }
+    }
+
     fn infer_expr(&mut self, node: &ParseNode) -> SemType {
         match node {
             ParseNode::IntLit(_, _) => SemType::Int,
@@ -508,7 +900,7 @@ impl Analyzer {
                 let resolved = self.scopes.lookup(name).map(|s| s.sem_type.clone());
                 match resolved {
                     Some(ty) => {
-                        self.add_usage(name);
+                        self.add_usage(name, *line, 0);
                         ty
                     }
                     None => {
@@ -529,9 +921,14 @@ impl Analyzer {
                 }
             }
 
-            ParseNode::AccessChain { base, steps, line } => {
+            ParseNode::AccessChain {
+                base,
+                steps,
+                line,
+                col,
+            } => {
                 let qualified_key: Option<String> =
-                    if let Some(AccessStep::Field(first_field)) = steps.first() {
+                    if let Some(AccessStep::Field(first_field, _)) = steps.first() {
                         let key = format!("{}::{}", base, first_field);
                         if self.scopes.lookup(&key).is_some() {
                             Some(key)
@@ -542,53 +939,88 @@ impl Analyzer {
                         None
                     };
 
-                let (mut ty, remaining_steps): (SemType, &[AccessStep]) =
-                    if let Some(ref qkey) = qualified_key {
-                        let t = self.scopes.lookup(qkey).unwrap().sem_type.clone();
-                        (t, &steps[1..])
-} else {
-                        let (t, _base): (SemType, bool) = {
-                            let found_sym = self.scopes.lookup(base);
-                            if let Some(sym) = found_sym {
-                                let st = sym.sem_type.clone();
-                                self.add_usage(base);
-                                (st, false)
-                            } else {
-                                (SemType::Unknown, true)
-                            }
-                        };
-                        if _base {
-                            let is_bare_call =
-                                steps.len() == 1 && matches!(steps[0], AccessStep::Call(_));
-                            if !is_bare_call {
-                                let qualified = steps
-                                    .iter()
-                                    .take_while(|s| matches!(s, AccessStep::Field(_)))
-                                    .fold(base.clone(), |acc, s| {
-                                        if let AccessStep::Field(f) = s {
-                                            format!("{}::{}", acc, f)
-                                        } else {
-                                            acc
-                                        }
-                                    });
-                                let suggestion =
-                                    suggest_similar(&qualified, self.scopes.all_names());
-                                let msg = match suggestion {
-                                    Some(ref s) => format!(
-                                        "undefined identifier `{}`\nhint: did you mean `{}`?",
-                                        qualified, s
-                                    ),
-                                    None => format!("undefined identifier `{}`", qualified),
-                                };
-                                self.error_at(*line, msg);
-                            }
-                        };
-                        (t, steps.as_slice())
+                let (mut ty, remaining_steps): (SemType, &[AccessStep]) = if let Some(ref qkey) =
+                    qualified_key
+                {
+                    let t = self.scopes.lookup(qkey).unwrap().sem_type.clone();
+                    // A module's item is found at the name after `::`.
+                    let field_col = match &steps[0] {
+                        AccessStep::Field(_, c) => *c,
+                        _ => 0,
+                    };
+                    self.add_usage(qkey, *line, field_col);
+                    (t, &steps[1..])
+                } else {
+                    let (t, _base): (SemType, bool) = {
+                        let found_sym = self.scopes.lookup(base);
+                        if let Some(sym) = found_sym {
+                            let st = sym.sem_type.clone();
+                            self.add_usage(base, *line, *col);
+                            (st, false)
+                        } else {
+                            (SemType::Unknown, true)
+                        }
+                    };
+                    if _base {
+                        let is_bare_call =
+                            steps.len() == 1 && matches!(steps[0], AccessStep::Call(_));
+                        if !is_bare_call {
+                            let qualified = steps
+                                .iter()
+                                .take_while(|s| matches!(s, AccessStep::Field(..)))
+                                .fold(base.clone(), |acc, s| {
+                                    if let AccessStep::Field(f, _) = s {
+                                        format!("{}::{}", acc, f)
+                                    } else {
+                                        acc
+                                    }
+                                });
+                            let suggestion = suggest_similar(&qualified, self.scopes.all_names());
+                            let msg = match suggestion {
+                                Some(ref s) => format!(
+                                    "undefined identifier `{}`\nhint: did you mean `{}`?",
+                                    qualified, s
+                                ),
+                                None => format!("undefined identifier `{}`", qualified),
+                            };
+                            self.error_at(*line, msg);
+                        }
                     };
+                    (t, steps.as_slice())
+                };
+
+                let callee_name = qualified_key.clone().unwrap_or_else(|| base.clone());
+                let named_func = match self.scopes.lookup(&callee_name) {
+                    Some(Symbol {
+                        kind: SymbolKind::Function { params },
+                        sem_type,
+                        origin,
+                        ..
+                    }) => Some((params.clone(), sem_type.clone(), origin == "builtin")),
+                    _ => None,
+                };
+                let mut named_call = false;
+                if let Some((params, ret, is_builtin)) = named_func {
+                    if matches!(remaining_steps.first(), Some(AccessStep::Call(_))) {
+                        named_call = true;
+                    } else if is_builtin {
+                        self.error_at(*line, format!(
+                            "built-in function `{}` cannot be used as a value\n\
+                             hint: wrap it in a lambda, e.g. `!func (:int x) -> :int {{ !return {}(x); }}`",
+                            callee_name, callee_name
+                        ));
+                        ty = SemType::Unknown;
+                    } else {
+                        ty = SemType::Func {
+                            params,
+                            ret: Box::new(ret),
+                        };
+                    }
+                }
 
                 for step in remaining_steps {
                     ty = match step {
-                        AccessStep::Field(field) => match &ty {
+                        AccessStep::Field(field, _) => match &ty {
                             SemType::Struct(struct_name) => {
                                 let struct_name = struct_name.clone();
                                 match self.scopes.lookup(&struct_name) {
@@ -688,6 +1120,11 @@ impl Analyzer {
                                 }
                             }
                         }
+                        AccessStep::Call(args)
+                            if !named_call && matches!(ty, SemType::Func { .. }) =>
+                        {
+                            self.check_value_call(&ty, args, &callee_name, *line)
+                        }
                         AccessStep::Call(args) => {
                             let arg_types: Vec<SemType> =
                                 args.iter().map(|a| self.infer_expr(a)).collect();
@@ -729,13 +1166,13 @@ impl Analyzer {
                                     );
                                 }
 
-                                if variadic && param_types.len() == 1
+                                if variadic
+                                    && param_types.len() == 1
                                     && matches!(param_types[0], SemType::Unknown)
                                 {
                                     let needs_exactly_one = matches!(
                                         func_name.as_str(),
-                                        "pop" | "len" | "abs"
-                                            | "to_int" | "to_float" | "to_str"
+                                        "pop" | "len" | "abs" | "to_int" | "to_float" | "to_str"
                                     ) || func_name.ends_with("::pop")
                                         || func_name.ends_with("::len")
                                         || func_name.ends_with("::abs")
@@ -786,6 +1223,9 @@ impl Analyzer {
                                     || func_name.ends_with("::delete");
 
                                 if list_only_func {
+                                    if let Some(target) = args.first() {
+                                        self.check_not_captured_target(target, *line);
+                                    }
                                     if let Some(at) = arg_types.first() {
                                         if !matches!(at, SemType::List { .. } | SemType::Unknown) {
                                             self.error_at(
@@ -1076,7 +1516,7 @@ impl Analyzer {
                                     }
                                 }
 
-let is_len = func_name == "len" || func_name.ends_with("::len");
+                                let is_len = func_name == "len" || func_name.ends_with("::len");
                                 if is_len {
                                     if let Some(at) = arg_types.first() {
                                         if !matches!(
@@ -1097,7 +1537,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                                     }
                                 }
 
-                                let is_input = func_name == "input" || func_name.ends_with("::input");
+                                let is_input =
+                                    func_name == "input" || func_name.ends_with("::input");
                                 if is_input && arg_types.len() < 2 {
                                     self.error_at(
                                         *line,
@@ -1108,6 +1549,64 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                                             .to_string(),
                                     );
                                 }
+                                if is_input && !arg_types.is_empty() {
+                                    if !matches!(args.first(), Some(ParseNode::StringLit(_, _))) {
+                                        self.error_at(
+                                            *line,
+                                            "first argument to `input` must be a string literal \
+                                             prompt; e.g. `input(\"Age: \", age)`"
+                                                .to_string(),
+                                        );
+                                    }
+                                    for (i, (arg, at)) in
+                                        args.iter().zip(arg_types.iter()).enumerate().skip(1)
+                                    {
+                                        let is_target = matches!(
+                                            arg,
+                                            ParseNode::AccessChain { steps, .. }
+                                                if !steps.iter().any(|s| matches!(s, AccessStep::Call(_)))
+                                        );
+                                        if !is_target {
+                                            self.error_at(*line, format!(
+                                                "`input` argument {} must be a variable, field, or element to fill\n\
+                                                 note: the input is stored in the variable, not returned from the function",
+                                                i + 1
+                                            ));
+                                            continue;
+                                        }
+                                        self.check_not_const_target(arg, *line);
+                                        self.check_not_captured_target(arg, *line);
+                                        let readable = match at {
+                                            SemType::Int
+                                            | SemType::Float
+                                            | SemType::Char
+                                            | SemType::Boolean
+                                            | SemType::Unknown => true,
+                                            SemType::List { elem }
+                                            | SemType::Array { elem, .. } => {
+                                                matches!(
+                                                    elem.as_ref(),
+                                                    SemType::Char | SemType::Unknown
+                                                )
+                                            }
+                                            _ => false,
+                                        };
+                                        if !readable {
+                                            self.error_at(*line, format!(
+                                                "`input` cannot read a value of type `{}` (argument {})\n\
+                                                 note: `input` fills `:int`, `:float`, `:char`, `:boolean`, \
+                                                 or a whole line into `:list<:char>` / `:array<:char, N>`\n\
+                                                 hint: read each struct field separately, e.g. `input(\"\", p::age)`",
+                                                at.display(),
+                                                i + 1
+                                            ));
+                                        }
+                                    }
+                                }
+
+                                if matches!(func_name.as_str(), "map" | "filter" | "reduce") {
+                                    return self.check_higher_order(&func_name, &arg_types, *line);
+                                }
 
                                 ret.clone()
                             } else {
@@ -1135,6 +1634,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                             }
                         }
                     };
+                    named_call = false;
                 }
                 ty
             }
@@ -1211,6 +1711,72 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 SemType::Unknown
             }
 
+            ParseNode::Lambda {
+                params,
+                return_type,
+                body,
+                line,
+            } => {
+                let ret = self.resolve_type_node(return_type);
+                let prev_ret = self.current_return_type.replace(ret.clone());
+                let prev_loop = std::mem::replace(&mut self.loop_depth, 0);
+
+                self.scopes.push();
+                let prev_floor = self.lambda_floor.replace(self.scope_depth());
+                let mut param_types = Vec::new();
+                for param in params {
+                    if let ParseNode::Param {
+                        data_type,
+                        name: pname,
+                        line: pline,
+                        col: pcol,
+                    } = param
+                    {
+                        self.check_const_shadow(pname, Some(*line));
+                        let pt = self.resolve_type_node(data_type);
+                        if matches!(pt, SemType::Void) {
+                            self.error_at(
+                                *line,
+                                format!("lambda parameter `{}` cannot have type `:void`", pname),
+                            );
+                        } else if self.scopes.defined_in_current(pname) {
+                            self.error_at(*line, format!("duplicate lambda parameter `{}`", pname));
+                        } else {
+                            self.declare_sym(Symbol {
+                                id: 0,
+                                name: pname.clone(),
+                                kind: SymbolKind::Variable,
+                                sem_type: pt.clone(),
+                                scope_depth: self.scope_depth(),
+                                origin: "param:<lambda>".to_string(),
+                                use_count: 0,
+                                def: self.location(*pline, *pcol),
+                                refs: Vec::new(),
+                            });
+                        }
+                        param_types.push(pt);
+                    }
+                }
+                for stmt in body {
+                    self.analyze_node(stmt);
+                }
+
+                let mut return_errors: Vec<String> = Vec::new();
+                check_function_returns("<lambda>", return_type, body, &mut return_errors);
+                for e in return_errors {
+                    self.error_at(*line, e);
+                }
+                self.scopes.pop();
+                self.lambda_floor = prev_floor;
+                self.loop_depth = prev_loop;
+                self.current_return_type = prev_ret;
+
+                SemType::Func {
+                    params: param_types,
+                    ret: Box::new(ret),
+                }
+            }
+
             ParseNode::LogOr { left, right, line } => {
                 let lt = self.infer_expr(left);
                 let rt = self.infer_expr(right);
@@ -1684,7 +2250,12 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
     fn analyze_items(&mut self, items: &[ParseNode]) {
         for item in items {
             match item {
-                ParseNode::StructDef { name, fields } => {
+                ParseNode::StructDef {
+                    name,
+                    fields,
+                    line,
+                    col,
+                } => {
                     if self.scopes.defined_in_current(name) {
                         self.error(format!(
                             "struct `{}` is already defined in this scope",
@@ -1725,6 +2296,16 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                                 continue;
                             }
 
+                            if Self::contains_func(&fty) {
+                                self.error(format!(
+                                    "field `{}` of struct `{}` cannot hold a function value\n\
+                                     note: struct fields must have a default value, and `:func` types have none",
+                                    fname, name
+                                ));
+                                had_field_error = true;
+                                continue;
+                            }
+
                             if resolved_fields.iter().any(|(n, _)| n == fname) {
                                 self.error(format!(
                                     "struct `{}` has duplicate field `{}`",
@@ -1747,6 +2328,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                             scope_depth: self.scope_depth(),
                             origin: self.current_origin.clone(),
                             use_count: 0,
+                            def: self.location(*line, *col),
+                            refs: Vec::new(),
                         });
                     }
                 }
@@ -1754,6 +2337,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     name,
                     params,
                     return_type,
+                    line,
+                    col,
                     ..
                 } => {
                     if self.scopes.defined_in_current(name) {
@@ -1797,6 +2382,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         scope_depth: self.scope_depth(),
                         origin: format!("func:{}", name),
                         use_count: 0,
+                        def: self.location(*line, *col),
+                        refs: Vec::new(),
                     });
                 }
                 ParseNode::Module {
@@ -1805,8 +2392,10 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 } => {
                     let saved_origin = self.current_origin.clone();
                     self.current_origin = format!("module:{}", name);
+                    let saved_module = std::mem::replace(&mut self.current_module, name.clone());
                     self.scopes.push();
                     self.analyze_items(mod_items);
+                    self.current_module = saved_module;
                     let frame = self.scopes.pop_with_frame();
                     for (sym_name, sym) in &frame {
                         if sym.origin == "builtin" {
@@ -1816,7 +2405,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         if !self.scopes.defined_in_current(&qualified) {
                             let qualified_kind = Self::qualify_symbol_kind(&sym.kind, name);
                             let qualified_sem_type = Self::qualify_struct_type(&sym.sem_type, name);
-                            let qualified_sym = Symbol {
+                            self.declare_sym(Symbol {
                                 id: 0,
                                 name: qualified.clone(),
                                 kind: qualified_kind,
@@ -1824,9 +2413,9 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                                 scope_depth: self.scope_depth(),
                                 origin: format!("module:{}", name),
                                 use_count: 0,
-                            };
-                            self.scopes.define(qualified_sym.clone());
-                            self.all_symbols.push(qualified_sym);
+                                def: sym.def.clone(),
+                                refs: Vec::new(),
+                            });
                         }
                     }
 
@@ -1839,21 +2428,116 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                             scope_depth: self.scope_depth(),
                             origin: format!("module:{}", name),
                             use_count: 0,
+                            def: None,
+                            refs: Vec::new(),
                         });
                     }
                     self.current_origin = saved_origin;
                 }
+                ParseNode::ConstDecl { .. } => self.analyze_node(item),
                 _ => {}
             }
         }
 
         for item in items {
-            if !matches!(item, ParseNode::Module { .. }) {
+            if !matches!(item, ParseNode::Module { .. } | ParseNode::ConstDecl { .. }) {
                 self.analyze_node(item);
             }
         }
     }
 
+    // Checks the initialiser of a declaration of `name` against `decl_ty`.
+    fn check_decl_init(
+        &mut self,
+        name: &str,
+        decl_ty: &SemType,
+        init_expr: &ParseNode,
+        line: usize,
+    ) {
+        let is_empty_literal = matches!(init_expr, ParseNode::ArrayLit(e, _) if e.is_empty());
+        if is_empty_literal {
+            if !matches!(
+                decl_ty,
+                SemType::Array { .. } | SemType::List { .. } | SemType::Unknown
+            ) {
+                self.error_at(
+                    line,
+                    format!(
+                        "cannot initialise `{}` (type `{}`) with `[]`; \
+                     `[]` is only valid for `:array` and `:list` types",
+                        name,
+                        decl_ty.display()
+                    ),
+                );
+            }
+        } else {
+            if matches!(init_expr, ParseNode::Null(_))
+                && !matches!(decl_ty, SemType::Struct(_) | SemType::Unknown)
+            {
+                self.error_at(
+                    line,
+                    format!(
+                        "cannot initialise `{}` with `!null`; \
+                     `!null` can only be assigned to struct-type variables",
+                        name
+                    ),
+                );
+                return;
+            }
+            let init_ty = self.infer_expr(init_expr);
+
+            if matches!(init_ty, SemType::Void) {
+                self.error_at(
+                    line,
+                    format!(
+                        "cannot initialise `{}` with a `:void` value; \
+                     `:void` functions return no value",
+                        name
+                    ),
+                );
+                return;
+            }
+
+            if matches!(decl_ty, SemType::List { .. })
+                && matches!(init_ty, SemType::Array { .. })
+                && !matches!(init_expr, ParseNode::ArrayLit(_, _))
+            {
+                self.error_at(
+                    line,
+                    format!(
+                        "cannot initialise `{}` (type `{}`) with value of type `{}`; \
+                     arrays and lists are distinct types",
+                        name,
+                        decl_ty.display(),
+                        init_ty.display()
+                    ),
+                );
+            } else if !Self::types_compatible(decl_ty, &init_ty) {
+                let msg = match (decl_ty, &init_ty) {
+                    (
+                        SemType::Array { elem: de, size: ds },
+                        SemType::Array {
+                            elem: ie,
+                            size: is_,
+                        },
+                    ) if de == ie => format!(
+                        "array `{}` declared with size {}, \
+                         but initializer has {} element(s)",
+                        name, ds, is_
+                    ),
+                    _ => format!(
+                        "cannot initialise `{}` (type `{}`) \
+                         with expression of type `{}`",
+                        name,
+                        decl_ty.display(),
+                        init_ty.display()
+                    ),
+                };
+                self.error_at(line, msg);
+            }
+        }
+    }
+
     fn analyze_node(&mut self, node: &ParseNode) {
         match node {
             ParseNode::Program(items) => {
@@ -1863,8 +2547,10 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
             ParseNode::Module { name, items } => {
                 let saved_origin = self.current_origin.clone();
                 self.current_origin = format!("module:{}", name);
+                let saved_module = std::mem::replace(&mut self.current_module, name.clone());
                 self.scopes.push();
                 self.analyze_items(items);
+                self.current_module = saved_module;
                 let frame = self.scopes.pop_with_frame();
 
                 for (sym_name, sym) in &frame {
@@ -1874,7 +2560,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     let qualified = format!("{}::{}", name, sym_name);
                     let qualified_kind = Self::qualify_symbol_kind(&sym.kind, name);
                     let qualified_sem_type = Self::qualify_struct_type(&sym.sem_type, name);
-                    let qualified_sym = Symbol {
+                    self.declare_sym(Symbol {
                         id: 0,
                         name: qualified.clone(),
                         kind: qualified_kind,
@@ -1882,9 +2568,9 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         scope_depth: self.scope_depth(),
                         origin: format!("module:{}", name),
                         use_count: 0,
-                    };
-                    self.scopes.define(qualified_sym.clone());
-                    self.all_symbols.push(qualified_sym);
+                        def: sym.def.clone(),
+                        refs: Vec::new(),
+                    });
                 }
                 self.current_origin = saved_origin;
             }
@@ -1904,6 +2590,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 params,
                 return_type,
                 body,
+                ..
             } => {
                 if self.current_return_type.is_some() {
                     self.error(format!(
@@ -1925,8 +2612,11 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     if let ParseNode::Param {
                         data_type,
                         name: pname,
+                        line: pline,
+                        col: pcol,
                     } = param
                     {
+                        self.check_const_shadow(pname, None);
                         let pt = self.resolve_type_node(data_type);
                         if matches!(pt, SemType::Void) {
                             self.error(format!(
@@ -1950,6 +2640,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                                 scope_depth: self.scope_depth(),
                                 origin: format!("param:{}", name),
                                 use_count: 0,
+                                def: self.location(*pline, *pcol),
+                                refs: Vec::new(),
                             });
                         }
                     }
@@ -1968,12 +2660,105 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 self.current_return_type = prev_ret;
             }
 
+            ParseNode::ConstDecl {
+                data_type,
+                name,
+                init,
+                line,
+                col,
+            } => {
+                let decl_ty = self.resolve_type_node(data_type);
+                if !matches!(
+                    decl_ty,
+                    SemType::Int | SemType::Float | SemType::Char | SemType::Boolean
+                ) {
+                    self.error_at(
+                        *line,
+                        format!(
+                            "constant `{}` cannot have type `{}`\n\
+                             note: only `:int`, `:float`, `:char`, and `:boolean` values can be `!const`",
+                            name,
+                            decl_ty.display()
+                        ),
+                    );
+                    return;
+                }
+                if self.scopes.defined_in_current(name) {
+                    self.error_at(
+                        *line,
+                        format!("`{}` is already declared in this scope", name),
+                    );
+                    return;
+                }
+                self.check_const_shadow(name, Some(*line));
+
+                // Folding first means a reference to a run-time variable is
+                // reported once, as "not constant", rather than also as an
+                // undefined name when the variable is declared further down.
+                let value = try_fold(init, &|n| self.const_value(n));
+                let kind = match value {
+                    Ok(value) => {
+                        let init_ty = self.infer_expr(init);
+                        if !Self::types_compatible(&decl_ty, &init_ty) {
+                            self.error_at(
+                                *line,
+                                format!(
+                                    "cannot initialise constant `{}` (type `{}`) with expression of type `{}`",
+                                    name,
+                                    decl_ty.display(),
+                                    init_ty.display()
+                                ),
+                            );
+                        }
+                        SymbolKind::Constant { value }
+                    }
+                    Err(ConstError::NotConstant) => {
+                        self.error_at(
+                            *line,
+                            format!(
+                                "the value of constant `{}` must be known at compile time\n\
+                                 note: only literals, other `!const` names, casts, and operators \
+                                 over them are allowed (no variables or function calls)\n\
+                                 hint: use a normal variable if the value is computed at run time",
+                                name
+                            ),
+                        );
+                        SymbolKind::Variable
+                    }
+                    Err(err) => {
+                        self.error_at(
+                            *line,
+                            format!(
+                                "constant `{}` cannot be computed: {}\n\
+                                 note: `!const` values are evaluated by the compiler, so this \
+                                 is reported here instead of failing when the program runs",
+                                name, err
+                            ),
+                        );
+                        SymbolKind::Variable
+                    }
+                };
+                self.declare_sym(Symbol {
+                    id: 0,
+                    name: name.clone(),
+                    kind,
+                    sem_type: decl_ty,
+                    scope_depth: self.scope_depth(),
+                    origin: self.current_origin.clone(),
+                    use_count: 0,
+                    def: self.location(*line, *col),
+                    refs: Vec::new(),
+                });
+            }
+
             ParseNode::Decl {
                 data_type,
                 name,
                 init,
                 line,
+                col,
             } => {
+                self.check_const_shadow(name, Some(*line));
                 let decl_ty = self.resolve_type_node(data_type);
                 if matches!(decl_ty, SemType::Void) {
                     self.error_at(
@@ -1986,6 +2771,29 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     );
                     return;
                 }
+                if let SemType::Array { elem, .. } = &decl_ty {
+                    if Self::contains_func(elem) {
+                        self.error_at(
+                            *line,
+                            format!(
+                                "cannot declare array `{}` of function values\n\
+                                 hint: use a `:list<...>` of functions instead",
+                                name
+                            ),
+                        );
+                        return;
+                    }
+                }
+                if matches!(decl_ty, SemType::Func { .. }) && init.is_none() {
+                    self.error_at(
+                        *line,
+                        format!(
+                            "function variable `{}` must be initialised\n\
+                             hint: assign a lambda or a named function, e.g. `= !func (:int x) -> :int {{ !return x; }};`",
+                            name
+                        ),
+                    );
+                }
                 if let SemType::Array { elem, .. } = &decl_ty {
                     if matches!(elem.as_ref(), SemType::Void) {
                         self.error_at(
@@ -2004,21 +2812,12 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         return;
                     }
                 }
-                if self.scopes.defined_in_current(name) {
+                let fresh = !self.scopes.defined_in_current(name);
+                if !fresh {
                     self.error_at(*line, format!(
                         "variable `{}` is already declared in this scope\nnote: each variable name must be unique within a block - choose a different name, or remove the duplicate declaration",
                         name
                     ));
-                } else {
-                    self.declare_sym(Symbol {
-                        id: 0,
-                        name: name.clone(),
-                        kind: SymbolKind::Variable,
-                        sem_type: decl_ty.clone(),
-                        scope_depth: self.scope_depth(),
-                        origin: self.current_origin.clone(),
-                        use_count: 0,
-                    });
                 }
 
                 if init.is_none() {
@@ -2050,90 +2849,23 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         _ => {}
                     }
                 }
+                // The initialiser is checked first, so that a name it reads
+                // resolves to what was in scope before this declaration.
                 if let Some(init_expr) = init {
-                    let is_empty_literal =
-                        matches!(init_expr.as_ref(), ParseNode::ArrayLit(e, _) if e.is_empty());
-                    if is_empty_literal {
-                        if !matches!(
-                            decl_ty,
-                            SemType::Array { .. } | SemType::List { .. } | SemType::Unknown
-                        ) {
-                            self.error_at(
-                                *line,
-                                format!(
-                                    "cannot initialise `{}` (type `{}`) with `[]`; \
-                                 `[]` is only valid for `:array` and `:list` types",
-                                    name,
-                                    decl_ty.display()
-                                ),
-                            );
-                        }
-                    } else {
-                        if matches!(init_expr.as_ref(), ParseNode::Null(_))
-                            && !matches!(decl_ty, SemType::Struct(_) | SemType::Unknown)
-                        {
-                            self.error_at(
-                                *line,
-                                format!(
-                                    "cannot initialise `{}` with `!null`; \
-                                 `!null` can only be assigned to struct-type variables",
-                                    name
-                                ),
-                            );
-                            return;
-                        }
-                        let init_ty = self.infer_expr(init_expr);
-
-                        if matches!(init_ty, SemType::Void) {
-                            self.error_at(
-                                *line,
-                                format!(
-                                    "cannot initialise `{}` with a `:void` value; \
-                                 `:void` functions return no value",
-                                    name
-                                ),
-                            );
-                            return;
-                        }
-
-                        if matches!(decl_ty, SemType::List { .. })
-                            && matches!(init_ty, SemType::Array { .. })
-                            && !matches!(init_expr.as_ref(), ParseNode::ArrayLit(_, _))
-                        {
-                            self.error_at(
-                                *line,
-                                format!(
-                                    "cannot initialise `{}` (type `{}`) with value of type `{}`; \
-                                 arrays and lists are distinct types",
-                                    name,
-                                    decl_ty.display(),
-                                    init_ty.display()
-                                ),
-                            );
-                        } else if !Self::types_compatible(&decl_ty, &init_ty) {
-                            let msg = match (&decl_ty, &init_ty) {
-                                (
-                                    SemType::Array { elem: de, size: ds },
-                                    SemType::Array {
-                                        elem: ie,
-                                        size: is_,
-                                    },
-                                ) if de == ie => format!(
-                                    "array `{}` declared with size {}, \
-                                     but initializer has {} element(s)",
-                                    name, ds, is_
-                                ),
-                                _ => format!(
-                                    "cannot initialise `{}` (type `{}`) \
-                                     with expression of type `{}`",
-                                    name,
-                                    decl_ty.display(),
-                                    init_ty.display()
-                                ),
-                            };
-                            self.error_at(*line, msg);
-                        }
-                    }
+                    self.check_decl_init(name, &decl_ty, init_expr, *line);
+                }
+                if fresh {
+                    self.declare_sym(Symbol {
+                        id: 0,
+                        name: name.clone(),
+                        kind: SymbolKind::Variable,
+                        sem_type: decl_ty.clone(),
+                        scope_depth: self.scope_depth(),
+                        origin: self.current_origin.clone(),
+                        use_count: 0,
+                        def: self.location(*line, *col),
+                        refs: Vec::new(),
+                    });
                 }
             }
 
@@ -2142,8 +2874,12 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 var_name,
                 init,
                 line,
+                struct_col,
+                col,
             } => {
+                self.check_const_shadow(var_name, Some(*line));
                 let sem_ty = SemType::Struct(struct_name.clone());
+                self.add_usage(struct_name, *line, *struct_col);
 
                 if let Some(existing) = self.scopes.lookup(var_name) {
                     if existing.origin.starts_with("module:") {
@@ -2187,12 +2923,22 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     };
                     self.error_at(*line, msg);
                 }
-                if self.scopes.defined_in_current(var_name) {
+                let fresh = !self.scopes.defined_in_current(var_name);
+                if !fresh {
                     self.error_at(
                         *line,
                         format!("variable `{}` is already declared in this scope", var_name),
                     );
-                } else {
+                }
+                if let Some(init_expr) = init {
+                    if let ParseNode::StructLit(_, _) = init_expr.as_ref() {
+                        self.validate_struct_lit(struct_name, init_expr.as_ref());
+                    } else if matches!(init_expr.as_ref(), ParseNode::Null(_)) {
+                    } else {
+                        self.infer_expr(init_expr);
+                    }
+                }
+                if fresh {
                     self.declare_sym(Symbol {
                         id: 0,
                         name: var_name.clone(),
@@ -2201,16 +2947,10 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                         scope_depth: self.scope_depth(),
                         origin: self.current_origin.clone(),
                         use_count: 0,
+                        def: self.location(*line, *col),
+                        refs: Vec::new(),
                     });
                 }
-                if let Some(init_expr) = init {
-                    if let ParseNode::StructLit(_, _) = init_expr.as_ref() {
-                        self.validate_struct_lit(struct_name, init_expr.as_ref());
-                    } else if matches!(init_expr.as_ref(), ParseNode::Null(_)) {
-                    } else {
-                        self.infer_expr(init_expr);
-                    }
-                }
             }
 
             ParseNode::Assign {
@@ -2220,9 +2960,14 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 line,
                 ..
             } => {
-                if let ParseNode::AccessChain { base, steps, .. } = lvalue.as_ref() {
+                self.check_not_const_target(lvalue, *line);
+                self.check_not_captured_target(lvalue, *line);
+                if let ParseNode::AccessChain {
+                    base, steps, col, ..
+                } = lvalue.as_ref()
+                {
                     if steps.is_empty() {
-                        self.add_usage(base);
+                        self.add_usage(base, *line, *col);
                     }
                 }
                 let lv_ty = self.infer_expr(lvalue);
@@ -2426,7 +3171,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 step,
                 body,
                 line,
-                ..
+                col,
             } => {
                 let vt = self.resolve_type_node(var_type);
                 if !vt.is_integer() && !matches!(vt, SemType::Unknown) {
@@ -2498,6 +3243,8 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                     scope_depth: self.scope_depth(),
                     origin: self.current_origin.clone(),
                     use_count: 0,
+                    def: self.location(*line, *col),
+                    refs: Vec::new(),
                 });
                 self.loop_depth += 1;
                 for stmt in body {
@@ -2600,7 +3347,7 @@ let is_len = func_name == "len" || func_name.ends_with("::len");
                 }
             }
 
-_ => {}
+            _ => {}
         }
     }
 
@@ -2613,11 +3360,17 @@ _ => {}
                         continue;
                     }
                     warns.push(SemanticWarning {
-                        message: format!(
-                            "variable `{}` is declared but never used",
-                            sym.name
-                        ),
+                        message: format!("variable `{}` is declared but never used", sym.name),
+                        line: None,
+                        file: String::new(),
+                    });
+                } else if matches!(sym.kind, SymbolKind::Constant { .. })
+                    && !sym.origin.starts_with("module:")
+                {
+                    warns.push(SemanticWarning {
+                        message: format!("constant `{}` is declared but never used", sym.name),
                         line: None,
+                        file: String::new(),
                     });
                 }
             }
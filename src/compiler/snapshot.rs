@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Version of the debug snapshot format. The compiler writes it into the
+/// `.debug-meta` sidecar and the debug runtime into every snapshot line;
+/// readers refuse any other version rather than misread the fields. Bump it
+/// whenever a field is added, removed or changes meaning.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// The first line of a `.debug.jsonl` file: the program as the compiler saw
+/// it, so that a recording can be replayed without the files it was built
+/// from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    /// The preprocessed program, with every import inlined.
+    pub program: String,
+    /// The main file first, then each imported module.
+    pub sources: Vec<Source>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Source {
+    /// Empty for the main program, otherwise the module name, as in
+    /// `Snapshot::file`.
+    pub file: String,
+    pub path: PathBuf,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Record {
+    Header(Header),
+    Snapshot(Box<Snapshot>),
+}
+
+/// The program state after a statement, one per line after the header.
+/// The runtime emitted by `codegen::emit_debug_runtime` writes exactly these
+/// fields, as it is compiled without serde.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub step: usize,
+    pub label: String,
+    pub line: usize,
+    /// Empty for the main program, otherwise the module name.
+    pub file: String,
+    /// Function names, outermost first, starting with `<main>`.
+    pub stack: Vec<String>,
+    /// One scope per stack frame, innermost first.
+    pub scopes: Vec<Scope>,
+    pub output: String,
+    pub finished: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scope {
+    pub label: String,
+    pub vars: Vec<Var>,
+}
+
+/// A variable as the runtime records it. `value` is an int, float, string
+/// (a char), bool or null, `{"list": [..]}`, `{"array": [..]}`,
+/// `{"struct": name, "fields": [[name, value], ..]}`, `{"func": null}` or
+/// `{"float": "NaN" | "inf" | "-inf"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Var {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_label: String,
+    pub value: serde_json::Value,
+    pub changed: bool,
+}
+
+/// The `.debug-meta` sidecar a debug build leaves next to its source, naming
+/// the file the binary writes snapshots to.
+#[derive(Debug, Serialize, Deserialize)]
+struct DebugMeta {
+    version: u32,
+    snapshots: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Written by a compiler using another format version; `None` when it
+    /// predates versioning.
+    Incompatible(Option<u32>),
+    Malformed(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Incompatible(Some(v)) => write!(
+                f,
+                "the program was built by a fractal-compiler writing debug format v{v}, \
+                 but this build reads v{SNAPSHOT_VERSION}; use a compiler and editor from \
+                 the same release"
+            ),
+            SnapshotError::Incompatible(None) => write!(
+                f,
+                "the program was built by a fractal-compiler older than debug format \
+                 v{SNAPSHOT_VERSION}; use a compiler and editor from the same release"
+            ),
+            SnapshotError::Malformed(msg) => write!(f, "malformed debug snapshot: {msg}"),
+        }
+    }
+}
+
+// The version is checked before the other fields, so a newer format with
+// different fields is reported as incompatible rather than malformed.
+fn check_version(value: &serde_json::Value) -> Result<(), SnapshotError> {
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(v) if v == u64::from(SNAPSHOT_VERSION) => Ok(()),
+        other => Err(SnapshotError::Incompatible(other.map(|v| v as u32))),
+    }
+}
+
+/// Parses one line of a `.debug.jsonl` file.
+pub fn parse_line(line: &str) -> Result<Record, SnapshotError> {
+    let value: serde_json::Value =
+        serde_json::from_str(line).map_err(|e| SnapshotError::Malformed(e.to_string()))?;
+    if !value.is_object() {
+        return Err(SnapshotError::Malformed(format!(
+            "expected an object, found `{value}`"
+        )));
+    }
+    check_version(&value)?;
+    serde_json::from_value(value).map_err(|e| SnapshotError::Malformed(e.to_string()))
+}
+
+/// The header of the recording at `path`.
+pub fn read_header(path: &Path) -> Result<Header, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let first = text.lines().next().unwrap_or_default();
+    match parse_line(first) {
+        Ok(Record::Header(header)) => Ok(header),
+        Ok(Record::Snapshot(_)) | Err(SnapshotError::Malformed(_)) => {
+            Err("not a recorded Fractal debug session".into())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// The header for a debug build of `source_file`, whose text is `contents`
+/// and preprocessed form `processed`. Imported modules are read back from the
+/// paths the preprocessor marked them with.
+pub fn header(source_file: &str, contents: &str, processed: &str) -> Header {
+    let mut sources = vec![Source {
+        file: String::new(),
+        path: PathBuf::from(source_file),
+        text: contents.to_string(),
+    }];
+    for line in processed.lines() {
+        let Some(path) = line
+            .trim()
+            .strip_prefix("$SRCMAP:")
+            .and_then(|m| m.strip_suffix('$'))
+            .and_then(|m| m.rsplit_once(':'))
+            .map(|(path, _)| PathBuf::from(path))
+        else {
+            continue;
+        };
+        if sources.iter().any(|s| s.path == path) {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let file = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        sources.push(Source { file, path, text });
+    }
+    Header {
+        version: SNAPSHOT_VERSION,
+        program: processed.to_string(),
+        sources,
+    }
+}
+
+/// Records next to `source` that its debug build writes snapshots to
+/// `snapshots`.
+pub fn write_meta(source: &Path, snapshots: &Path) -> io::Result<()> {
+    let meta = DebugMeta {
+        version: SNAPSHOT_VERSION,
+        snapshots: snapshots.to_path_buf(),
+    };
+    let text = serde_json::to_string(&meta).map_err(io::Error::other)?;
+    fs::write(source.with_extension("debug-meta"), text)
+}
+
+/// The snapshot file of the debug build of `source`, provided the compiler
+/// that built it writes this version of the format.
+pub fn read_meta(source: &Path) -> Result<PathBuf, String> {
+    let text = fs::read_to_string(source.with_extension("debug-meta"))
+        .map_err(|_| "debug compile succeeded but left no .debug-meta sidecar file")?;
+    // Older compilers wrote the bare path, which is not JSON.
+    let value = serde_json::from_str(&text).unwrap_or(serde_json::Value::Null);
+    check_version(&value).map_err(|e| e.to_string())?;
+    serde_json::from_value::<DebugMeta>(value)
+        .map(|meta| meta.snapshots)
+        .map_err(|e| SnapshotError::Malformed(e.to_string()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    // Values the runtime has to escape, and text that looks like JSON.
+    const TRICKY: &[&str] = &[
+        "",
+        "\"",
+        "\\",
+        "\\\"",
+        "\"},{\"version\":1}",
+        "{\"list\": [1, 2]}",
+        "]]}}[[{{",
+        "line\nbreak\ttab\rreturn",
+        "\u{0}\u{1}\u{1f}\u{7f}",
+        "ünïcödé ✓ 日本語 🦀",
+        "'a' ':' ','",
+    ];
+
+    const ALPHABET: &[char] = &[
+        '"', '\\', '{', '}', '[', ']', ',', ':', '\'', '\n', '\t', '\r', '\u{0}', '\u{1b}', ' ',
+        'a', 'Z', '0', 'é', 'ß', '✓', '日', '🦀', '\u{200b}',
+    ];
+
+    // Strings of characters from ALPHABET, the same ones on every run.
+    fn random_strings(count: usize) -> Vec<String> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        (0..count)
+            .map(|_| {
+                let len = next() % 24;
+                (0..len)
+                    .map(|_| ALPHABET[next() % ALPHABET.len()])
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn var(name: &str, type_label: &str, value: Value) -> Var {
+        Var {
+            name: name.to_string(),
+            type_label: type_label.to_string(),
+            value,
+            changed: false,
+        }
+    }
+
+    // A snapshot with `text` in every field the runtime writes a string to.
+    fn snapshot_with(text: &str) -> Snapshot {
+        let first = text.chars().next().unwrap_or(' ').to_string();
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            step: 7,
+            label: text.to_string(),
+            line: 3,
+            file: text.to_string(),
+            stack: vec!["<main>".to_string(), text.to_string()],
+            scopes: vec![Scope {
+                label: text.to_string(),
+                vars: vec![
+                    var("c", ":char", json!(first)),
+                    var("s", ":string", json!(text)),
+                    var(text, ":list<:string>", json!({ "list": [text, text] })),
+                    var(
+                        "n",
+                        ":struct<node>",
+                        json!({ "struct": "node", "fields": [[text, text], ["next", null]] }),
+                    ),
+                ],
+            }],
+            output: text.to_string(),
+            finished: false,
+            error: Some(text.to_string()),
+        }
+    }
+
+    fn round_trip(record: &Record) -> Record {
+        let line = serde_json::to_string(record).unwrap();
+        assert!(
+            !line.contains('\n'),
+            "a record must fit on one line: {line}"
+        );
+        parse_line(&line).unwrap_or_else(|e| panic!("{e}: {line}"))
+    }
+
+    #[test]
+    fn snapshots_with_arbitrary_text_round_trip() {
+        let texts = TRICKY
+            .iter()
+            .map(|s| s.to_string())
+            .chain(random_strings(500));
+        for text in texts {
+            let snapshot = snapshot_with(&text);
+            match round_trip(&Record::Snapshot(Box::new(snapshot.clone()))) {
+                Record::Snapshot(parsed) => assert_eq!(
+                    serde_json::to_value(&*parsed).unwrap(),
+                    serde_json::to_value(&snapshot).unwrap(),
+                ),
+                Record::Header(_) => panic!("snapshot read back as a header: {text:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn headers_with_arbitrary_text_round_trip() {
+        for text in random_strings(100) {
+            let header = Header {
+                version: SNAPSHOT_VERSION,
+                program: text.clone(),
+                sources: vec![Source {
+                    file: String::new(),
+                    path: PathBuf::from("main.fr"),
+                    text: text.clone(),
+                }],
+            };
+            match round_trip(&Record::Header(header)) {
+                Record::Header(parsed) => {
+                    assert_eq!(parsed.program, text);
+                    assert_eq!(parsed.sources[0].text, text);
+                }
+                Record::Snapshot(_) => panic!("header read back as a snapshot: {text:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn the_runtime_escapes_parse() {
+        // As written by `__fractal_debug_json_escape`, which uses `\u` only
+        // for control characters and leaves the rest of Unicode as is.
+        let line = format!(
+            "{{\"version\":{SNAPSHOT_VERSION},\"step\":1,\"label\":\"Decl s\",\"line\":2,\
+             \"file\":\"\",\"stack\":[\"<main>\"],\"scopes\":[{{\"label\":\"<main>\",\"vars\":[\
+             {{\"name\":\"s\",\"type\":\":string\",\"value\":\"q\\\"b\\\\n\\n\\t\\u001b ✓\",\
+             \"changed\":true}}]}}],\"output\":\"\",\"finished\":false,\"error\":null}}"
+        );
+        let Ok(Record::Snapshot(s)) = parse_line(&line) else {
+            panic!("not parsed as a snapshot: {line}");
+        };
+        assert_eq!(s.scopes[0].vars[0].value, json!("q\"b\\n\n\t\u{1b} ✓"));
+    }
+
+    #[test]
+    fn a_missing_or_other_version_is_incompatible() {
+        let mut value = serde_json::to_value(snapshot_with("x")).unwrap();
+        value.as_object_mut().unwrap().remove("version");
+        assert!(matches!(
+            parse_line(&value.to_string()),
+            Err(SnapshotError::Incompatible(None))
+        ));
+
+        for version in [0, 1, SNAPSHOT_VERSION + 1] {
+            value["version"] = json!(version);
+            assert!(matches!(
+                parse_line(&value.to_string()),
+                Err(SnapshotError::Incompatible(Some(v))) if v == version
+            ));
+        }
+
+        value["version"] = json!("2");
+        assert!(matches!(
+            parse_line(&value.to_string()),
+            Err(SnapshotError::Incompatible(None))
+        ));
+    }
+
+    #[test]
+    fn truncated_or_garbage_lines_are_malformed() {
+        let line = serde_json::to_string(&Record::Snapshot(Box::new(snapshot_with("\"{}\\\n🦀"))))
+            .unwrap();
+        for (end, _) in line.char_indices().skip(1) {
+            assert!(
+                matches!(parse_line(&line[..end]), Err(SnapshotError::Malformed(_))),
+                "accepted a truncated line: {}",
+                &line[..end]
+            );
+        }
+        for garbage in ["", "garbage", "{", "[]", "\u{feff}{}", "{\"version\":2"] {
+            assert!(
+                matches!(parse_line(garbage), Err(SnapshotError::Malformed(_))),
+                "accepted {garbage:?}"
+            );
+        }
+        let missing_fields = format!("{{\"version\":{SNAPSHOT_VERSION},\"step\":1}}");
+        assert!(matches!(
+            parse_line(&missing_fields),
+            Err(SnapshotError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn meta_is_read_back_only_at_this_version() {
+        let dir = std::env::temp_dir().join(format!("fractal-meta-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("prog.fr");
+        let snapshots = dir.join("prog.debug.jsonl");
+
+        write_meta(&source, &snapshots).unwrap();
+        assert_eq!(read_meta(&source).unwrap(), snapshots);
+
+        let meta = source.with_extension("debug-meta");
+        fs::write(
+            &meta,
+            json!({ "version": 1, "snapshots": snapshots }).to_string(),
+        )
+        .unwrap();
+        assert!(read_meta(&source).unwrap_err().contains("v1"));
+        // Older compilers wrote the bare path.
+        fs::write(&meta, snapshots.to_string_lossy().as_bytes()).unwrap();
+        assert!(read_meta(&source).unwrap_err().contains("older"));
+        fs::write(&meta, json!({ "version": SNAPSHOT_VERSION }).to_string()).unwrap();
+        assert!(read_meta(&source).unwrap_err().contains("malformed"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
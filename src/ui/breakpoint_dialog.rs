@@ -0,0 +1,96 @@
+use crate::ui::icons as ic;
+use crate::ui::theme::Theme;
+use eframe::egui;
+
+pub enum BreakpointAction {
+    Pending,
+    Apply(usize, String),
+    Remove(usize),
+}
+
+#[derive(Default)]
+pub struct BreakpointDialog {
+    pub visible: bool,
+    line: usize,
+    text: String,
+    error: Option<String>,
+    focus: bool,
+}
+
+impl BreakpointDialog {
+    pub fn open(&mut self, line: usize, condition: &str) {
+        self.line = line;
+        self.text = condition.to_string();
+        self.error = None;
+        self.focus = true;
+        self.visible = true;
+    }
+
+    pub fn set_error(&mut self, message: String) {
+        self.error = Some(message);
+        self.focus = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, theme: &Theme) -> BreakpointAction {
+        if !self.visible {
+            return BreakpointAction::Pending;
+        }
+
+        let mut action = BreakpointAction::Pending;
+        let mut open = true;
+
+        egui::Window::new(format!("Breakpoint - line {}", self.line))
+            .id(egui::Id::new("fractal_breakpoint_dialog"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(340.0)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .frame(egui::Frame::window(&ctx.style()).fill(theme.panel_bg))
+            .show(ctx, |ui| {
+                ui.add_space(4.0);
+                ui.label(
+                    egui::RichText::new("Stop only when this expression is true:")
+                        .size(13.0)
+                        .color(theme.tab_active_fg),
+                );
+                ui.add_space(6.0);
+
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut self.text)
+                        .font(egui::TextStyle::Monospace)
+                        .hint_text("always stop   e.g. i == 37")
+                        .desired_width(f32::INFINITY),
+                );
+                if self.focus {
+                    edit.request_focus();
+                    self.focus = false;
+                }
+                let submitted = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                if let Some(ref err) = self.error {
+                    ui.add_space(4.0);
+                    ui.label(
+                        egui::RichText::new(format!("{}  {}", ic::ERROR, err))
+                            .size(12.0)
+                            .color(theme.terminal_error),
+                    );
+                }
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button(format!("{}  Apply", ic::SUCCESS)).clicked() || submitted {
+                        action = BreakpointAction::Apply(self.line, self.text.trim().to_string());
+                    }
+                    if ui.button(format!("{}  Remove", ic::DISCARD)).clicked() {
+                        action = BreakpointAction::Remove(self.line);
+                    }
+                });
+            });
+
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.visible = false;
+        }
+        action
+    }
+}
@@ -0,0 +1,456 @@
+use crate::compiler::builtins::{BType, BuiltinDef, ALL_BUILTINS};
+use crate::compiler::lexer::{KEYWORDS, TYPE_NAMES};
+use crate::compiler::semanter::{sem_type_from_btype, SemType, Symbol, SymbolKind};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Keyword,
+    Type,
+    Builtin,
+    Function,
+    Variable,
+    Constant,
+    Field,
+    Struct,
+    Module,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionItem {
+    pub label: String,
+    /// The signature or type shown next to the label.
+    pub detail: String,
+    /// Replaces the text from [`Completions::start`] to the cursor.
+    pub insert: String,
+    pub kind: ItemKind,
+}
+
+pub struct Completions {
+    /// Char index where the text replaced by an item starts.
+    pub start: usize,
+    pub items: Vec<CompletionItem>,
+}
+
+// A step of the expression in front of `.` or `::`, such as `g`, `[i]` and
+// `adj` in `g::adj[i]::`.
+enum Step {
+    Name(String),
+    Index,
+}
+
+/// What can be typed at char index `cursor` of `code`, given the symbols of
+/// the last check of it. Without `explicit`, a plain identifier has to be
+/// started before anything is offered.
+pub fn complete(
+    code: &str,
+    cursor: usize,
+    symbols: &[Symbol],
+    explicit: bool,
+) -> Option<Completions> {
+    let chars: Vec<char> = code.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let func = enclosing_function(&chars, cursor)?;
+    let func = func.as_deref();
+
+    let mut word_start = cursor;
+    while word_start > 0 && is_ident(chars[word_start - 1]) {
+        word_start -= 1;
+    }
+    let word: String = chars[word_start..cursor].iter().collect();
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let before = |i: usize| i.checked_sub(1).map(|j| chars[j]);
+
+    let (start, items) = match before(word_start) {
+        Some('!') => (word_start, keyword_items()),
+        Some(':') if before(word_start - 1) == Some(':') => {
+            let steps = receiver(&chars, word_start - 2)?;
+            (word_start, member_items(&steps, symbols, func))
+        }
+        Some(':') => (word_start, type_items()),
+        Some('<') if chars[..word_start - 1].ends_with(&[':', 's', 't', 'r', 'u', 'c', 't']) => {
+            (word_start, struct_items(symbols))
+        }
+        // Fields are reached with `::`; offering them after `.` as well
+        // saves having to remember that.
+        Some('.') => {
+            let steps = receiver(&chars, word_start - 1)?;
+            let mut items = member_items(&steps, symbols, func);
+            for item in &mut items {
+                item.insert = format!("::{}", item.insert);
+            }
+            (word_start - 1, items)
+        }
+        _ if !word.is_empty() || explicit => (word_start, scope_items(symbols, func)),
+        _ => return None,
+    };
+
+    let prefix = word.to_lowercase();
+    let mut items: Vec<CompletionItem> = items
+        .into_iter()
+        .filter(|item| item.label.to_lowercase().starts_with(&prefix))
+        .collect();
+    if items.iter().all(|item| item.label == word) {
+        return None;
+    }
+    items.sort_by(|a, b| {
+        (!a.label.starts_with(&word), &a.label).cmp(&(!b.label.starts_with(&word), &b.label))
+    });
+    Some(Completions { start, items })
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The function whose body `cursor` is in, as `Some(None)` outside any; `None`
+// when `cursor` is in a comment or a string, where nothing is offered.
+fn enclosing_function(chars: &[char], cursor: usize) -> Option<Option<String>> {
+    let mut bodies: Vec<Option<String>> = Vec::new();
+    let mut pending: Option<String> = None;
+    let mut i = 0;
+    while i < cursor {
+        let c = chars[i];
+        if c == '#' && chars[i..].starts_with(&['#', '#', '#']) {
+            match (i + 3..chars.len()).find(|&j| chars[j..].starts_with(&['#', '#', '#'])) {
+                Some(end) if end + 3 <= cursor => i = end + 3,
+                _ => return None,
+            }
+            continue;
+        }
+        match c {
+            '#' => match (i..cursor).find(|&j| chars[j] == '\n') {
+                Some(end) => i = end,
+                None => return None,
+            },
+            '"' | '\'' => {
+                let mut j = i + 1;
+                while j < cursor && chars[j] != c && chars[j] != '\n' {
+                    j += if chars[j] == '\\' { 2 } else { 1 };
+                }
+                if j >= cursor {
+                    return None;
+                }
+                i = j;
+            }
+            '!' if chars[i + 1..].starts_with(&['f', 'u', 'n', 'c']) => {
+                let mut j = i + 5;
+                while j < chars.len() && chars[j] == ' ' {
+                    j += 1;
+                }
+                let name: String = chars[j..].iter().take_while(|&&c| is_ident(c)).collect();
+                pending = Some(name);
+                i = j;
+                continue;
+            }
+            '{' => bodies.push(pending.take().filter(|n| !n.is_empty())),
+            '}' => {
+                bodies.pop();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Some(bodies.into_iter().rev().flatten().next())
+}
+
+// The expression ending just before char index `end`, base name first.
+fn receiver(chars: &[char], end: usize) -> Option<Vec<Step>> {
+    let mut i = end;
+    let mut steps = Vec::new();
+    loop {
+        if i > 0 && chars[i - 1] == ']' {
+            let mut depth = 0;
+            loop {
+                i = i.checked_sub(1)?;
+                match chars[i] {
+                    ']' => depth += 1,
+                    '[' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            steps.push(Step::Index);
+            continue;
+        }
+        let stop = i;
+        while i > 0 && is_ident(chars[i - 1]) {
+            i -= 1;
+        }
+        let name: String = chars[i..stop].iter().collect();
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        steps.push(Step::Name(name));
+        if i >= 2 && chars[i - 1] == ':' && chars[i - 2] == ':' {
+            i -= 2;
+        } else {
+            break;
+        }
+    }
+    steps.reverse();
+    Some(steps)
+}
+
+fn keyword_items() -> Vec<CompletionItem> {
+    KEYWORDS
+        .iter()
+        // Structs are declared with the `:struct` type, never `!struct`.
+        .filter(|&&kw| kw != "struct")
+        .map(|&kw| CompletionItem {
+            label: kw.to_string(),
+            detail: keyword_usage(kw).to_string(),
+            insert: kw.to_string(),
+            kind: ItemKind::Keyword,
+        })
+        .collect()
+}
+
+fn keyword_usage(kw: &str) -> &'static str {
+    match kw {
+        "start" => "!start ... !end",
+        "end" => "ends the program",
+        "exit" => "!exit; terminates the program",
+        "if" => "!if (cond) { ... }",
+        "elif" => "!elif (cond) { ... }",
+        "else" => "!else { ... }",
+        "for" => "!for (:int i, start, end, step) { ... }",
+        "while" => "!while (cond) { ... }",
+        "func" => "!func name(:int x) -> :int { ... }",
+        "return" => "!return value;",
+        "import" => "!import \"./file.fr\";",
+        "module" => "!module Name { ... }",
+        "break" => "!break;",
+        "continue" => "!continue;",
+        "const" => "!const :int N = 8;",
+        "and" => "a !and b",
+        "or" => "a !or b",
+        "not" => "!not a",
+        "null" => "no struct",
+        _ => "",
+    }
+}
+
+fn type_items() -> Vec<CompletionItem> {
+    TYPE_NAMES
+        .iter()
+        .map(|&ty| CompletionItem {
+            label: ty.to_string(),
+            detail: match ty {
+                "int" => "whole number",
+                "float" => "decimal number",
+                "char" => "single character",
+                "boolean" => "true or false",
+                "array" => ":array<:int, 8>, fixed size",
+                "list" => ":list<:int>, grows",
+                "struct" => ":struct<Name>",
+                "void" => "no value",
+                "func" => ":func<(:int) -> :int>",
+                _ => "",
+            }
+            .to_string(),
+            insert: ty.to_string(),
+            kind: ItemKind::Type,
+        })
+        .collect()
+}
+
+fn struct_items(symbols: &[Symbol]) -> Vec<CompletionItem> {
+    symbols
+        .iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Struct { .. }) && s.scope_depth == 0)
+        .map(|s| item(&s.name, s))
+        .collect()
+}
+
+// Builtins, functions, modules and the variables visible in `func`.
+fn scope_items(symbols: &[Symbol], func: Option<&str>) -> Vec<CompletionItem> {
+    let mut items: Vec<CompletionItem> = ALL_BUILTINS
+        .iter()
+        .filter(|b| !b.name.contains("::"))
+        .map(|b| CompletionItem {
+            label: b.name.to_string(),
+            detail: builtin_signature(b),
+            insert: b.name.to_string(),
+            kind: ItemKind::Builtin,
+        })
+        .collect();
+
+    let mut seen: BTreeSet<String> = items.iter().map(|i| i.label.clone()).collect();
+    let mut modules = BTreeSet::new();
+    for s in symbols {
+        if let Some((module, _)) = s.name.split_once("::") {
+            modules.insert(module.to_string());
+            continue;
+        }
+        let visible = match s.kind {
+            // Functions of inlined modules are only reachable through `::`.
+            SymbolKind::Function { .. } => s.scope_depth == 0,
+            SymbolKind::Struct { .. } => false,
+            _ => {
+                s.origin == "global"
+                    || func.is_some_and(|f| {
+                        s.origin == format!("fn:{f}") || s.origin == format!("param:{f}")
+                    })
+            }
+        };
+        if visible && seen.insert(s.name.clone()) {
+            items.push(item(&s.name, s));
+        }
+    }
+    for module in modules {
+        if seen.insert(module.clone()) {
+            items.push(CompletionItem {
+                detail: "module".to_string(),
+                insert: module.clone(),
+                label: module,
+                kind: ItemKind::Module,
+            });
+        }
+    }
+    items
+}
+
+// The members of a module, or the fields of a struct value.
+fn member_items(steps: &[Step], symbols: &[Symbol], func: Option<&str>) -> Vec<CompletionItem> {
+    let Some(Step::Name(base)) = steps.first() else {
+        return Vec::new();
+    };
+    let prefix = format!("{base}::");
+    if steps.len() == 1 && symbols.iter().any(|s| s.name.starts_with(&prefix)) {
+        return symbols
+            .iter()
+            .filter_map(|s| {
+                let member = s.name.strip_prefix(&prefix)?;
+                (!member.contains("::")).then(|| item(member, s))
+            })
+            .collect();
+    }
+
+    let Some(mut ty) = variable_type(base, symbols, func) else {
+        return Vec::new();
+    };
+    for step in &steps[1..] {
+        ty = match (step, ty) {
+            (Step::Index, SemType::Array { elem, .. } | SemType::List { elem }) => *elem,
+            (Step::Name(field), SemType::Struct(name)) => {
+                match struct_fields(&name, symbols).and_then(|fields| {
+                    fields
+                        .iter()
+                        .find(|(f, _)| f == field)
+                        .map(|(_, t)| t.clone())
+                }) {
+                    Some(t) => t,
+                    None => return Vec::new(),
+                }
+            }
+            _ => return Vec::new(),
+        };
+    }
+    let SemType::Struct(name) = ty else {
+        return Vec::new();
+    };
+    struct_fields(&name, symbols)
+        .map(|fields| {
+            fields
+                .iter()
+                .map(|(f, t)| CompletionItem {
+                    label: f.clone(),
+                    detail: t.display(),
+                    insert: f.clone(),
+                    kind: ItemKind::Field,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// The type of the variable `name` as seen from `func`, preferring its locals
+// over globals of the same name.
+fn variable_type(name: &str, symbols: &[Symbol], func: Option<&str>) -> Option<SemType> {
+    let candidates: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|s| {
+            s.name == name && matches!(s.kind, SymbolKind::Variable | SymbolKind::Constant { .. })
+        })
+        .collect();
+    let local = func.and_then(|f| {
+        candidates
+            .iter()
+            .find(|s| s.origin == format!("fn:{f}") || s.origin == format!("param:{f}"))
+    });
+    local
+        .or_else(|| candidates.iter().find(|s| s.origin == "global"))
+        .or(candidates.first())
+        .map(|s| s.sem_type.clone())
+}
+
+fn struct_fields<'a>(name: &str, symbols: &'a [Symbol]) -> Option<&'a [(String, SemType)]> {
+    symbols.iter().find_map(|s| match &s.kind {
+        SymbolKind::Struct { fields } if s.name == name => Some(fields.as_slice()),
+        _ => None,
+    })
+}
+
+/// How a symbol is shown in completion lists and tooltips: a variable's type,
+/// a function's signature, a constant's value or a struct's fields.
+pub fn signature(name: &str, s: &Symbol) -> String {
+    match &s.kind {
+        SymbolKind::Variable => s.sem_type.display(),
+        SymbolKind::Constant { value } => format!("const {} = {}", s.sem_type.display(), value),
+        SymbolKind::Function { params } => format!(
+            "{name}({}) -> {}",
+            params
+                .iter()
+                .map(SemType::display)
+                .collect::<Vec<_>>()
+                .join(", "),
+            s.sem_type.display()
+        ),
+        SymbolKind::Struct { fields } => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|(f, t)| format!("{f}: {}", t.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// How a builtin is shown: its name with the types it takes and returns.
+pub fn builtin_signature(b: &BuiltinDef) -> String {
+    format!(
+        "{}({}) -> {}",
+        b.name,
+        b.params.iter().map(btype).collect::<Vec<_>>().join(", "),
+        btype(&b.ret)
+    )
+}
+
+fn item(label: &str, s: &Symbol) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        detail: signature(label, s),
+        insert: label.to_string(),
+        kind: match s.kind {
+            SymbolKind::Variable => ItemKind::Variable,
+            SymbolKind::Constant { .. } => ItemKind::Constant,
+            SymbolKind::Function { .. } => ItemKind::Function,
+            SymbolKind::Struct { .. } => ItemKind::Struct,
+        },
+    }
+}
+
+fn btype(bt: &BType) -> String {
+    match bt {
+        BType::Any => "any".to_string(),
+        other => sem_type_from_btype(other).display(),
+    }
+}
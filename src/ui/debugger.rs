@@ -1,4 +1,5 @@
 use crate::compiler::parser::ParseNode;
+use crate::compiler::snapshot::{self, Record, Snapshot, SnapshotError, Var};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
@@ -12,7 +13,8 @@ pub enum FractalValue {
     Str(String),
     Array(Vec<FractalValue>),
     List(Vec<FractalValue>),
-    Struct(HashMap<String, FractalValue>),
+    /// The struct name and its fields in declaration order.
+    Struct(String, Vec<(String, FractalValue)>),
     Null,
     Void,
 }
@@ -31,7 +33,7 @@ impl FractalValue {
                 let inner: Vec<String> = v.iter().map(|x| x.display()).collect();
                 format!("[{}]", inner.join(", "))
             }
-            Self::Struct(f) => {
+            Self::Struct(_, f) => {
                 let p: Vec<String> = f
                     .iter()
                     .map(|(k, v)| format!("{}: {}", k, v.display()))
@@ -40,6 +42,75 @@ impl FractalValue {
             }
         }
     }
+
+    /// Decodes a value as the debug runtime records it: numbers, one-char
+    /// strings for chars, booleans, `null`, and `{"list": [..]}`,
+    /// `{"array": [..]}`, `{"struct": name, "fields": [[name, value], ..]}`,
+    /// `{"float": "NaN"}` or `{"func": null}` objects.
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        use serde_json::Value as J;
+        match value {
+            J::Null => Self::Null,
+            J::Bool(b) => Self::Bool(*b),
+            J::Number(n) => match n.as_i64() {
+                Some(i) => Self::Int(i),
+                None => Self::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            J::String(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Self::Char(c),
+                    _ => Self::Str(s.clone()),
+                }
+            }
+            J::Array(items) => Self::List(items.iter().map(Self::from_json).collect()),
+            J::Object(obj) => {
+                let items = |v: &J| {
+                    v.as_array()
+                        .map(|a| a.iter().map(Self::from_json).collect())
+                };
+                if let Some(list) = obj.get("list").and_then(items) {
+                    Self::List(list)
+                } else if let Some(array) = obj.get("array").and_then(items) {
+                    Self::Array(array)
+                } else if let Some(fields) = obj.get("fields").and_then(|f| f.as_array()) {
+                    let name = obj
+                        .get("struct")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or_default();
+                    Self::Struct(
+                        name.to_string(),
+                        fields
+                            .iter()
+                            .filter_map(|f| {
+                                let name = f.get(0)?.as_str()?.to_string();
+                                Some((name, Self::from_json(f.get(1)?)))
+                            })
+                            .collect(),
+                    )
+                } else if let Some(f) = obj.get("float").and_then(|f| f.as_str()) {
+                    Self::Float(f.parse().unwrap_or(f64::NAN))
+                } else {
+                    Self::Void
+                }
+            }
+        }
+    }
+
+    /// The elements of a list or array, labelled `[i]`, and the fields of a
+    /// struct.
+    pub fn children(&self) -> Vec<(String, &FractalValue)> {
+        match self {
+            Self::Array(items) | Self::List(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (format!("[{i}]"), v))
+                .collect(),
+            Self::Struct(_, fields) => fields.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            _ => vec![],
+        }
+    }
+
     pub fn type_label(&self) -> &'static str {
         match self {
             Self::Int(_) => ":int",
@@ -49,7 +120,7 @@ impl FractalValue {
             Self::Str(_) => ":str",
             Self::Array(_) => ":array",
             Self::List(_) => ":list",
-            Self::Struct(_) => ":struct",
+            Self::Struct(..) => ":struct",
             Self::Null => "null",
             Self::Void => "void",
         }
@@ -105,6 +176,12 @@ fn children_of(n: &ParseNode) -> Vec<&ParseNode> {
             body,
             return_type,
             ..
+        }
+        | ParseNode::Lambda {
+            params,
+            body,
+            return_type,
+            ..
         } => {
             let mut c: Vec<&ParseNode> = params.iter().collect();
             c.push(return_type);
@@ -114,6 +191,7 @@ fn children_of(n: &ParseNode) -> Vec<&ParseNode> {
         ParseNode::StructDef { fields, .. } => fields.iter().collect(),
         ParseNode::StructDecl { init, .. } => init.as_deref().into_iter().collect(),
         ParseNode::Decl { init, .. } => init.as_deref().into_iter().collect(),
+        ParseNode::ConstDecl { init, .. } => vec![init.as_ref()],
         ParseNode::Assign { lvalue, expr, .. } => vec![lvalue.as_ref(), expr.as_ref()],
         ParseNode::If {
             condition,
@@ -181,7 +259,10 @@ fn node_label(n: &ParseNode) -> String {
         ParseNode::FuncDef {
             name, return_type, ..
         } => format!("FuncDef {}  → {}", name, type_str(return_type)),
-        ParseNode::Param { data_type, name } => format!("Param {} : {}", name, type_str(data_type)),
+        ParseNode::Lambda { return_type, .. } => format!("Lambda → {}", type_str(return_type)),
+        ParseNode::Param {
+            data_type, name, ..
+        } => format!("Param {} : {}", name, type_str(data_type)),
         ParseNode::StructDef { name, .. } => format!("StructDef {}", name),
         ParseNode::StructDecl {
             var_name,
@@ -199,6 +280,9 @@ fn node_label(n: &ParseNode) -> String {
             type_str(data_type),
             if init.is_some() { " =" } else { "" }
         ),
+        ParseNode::ConstDecl {
+            data_type, name, ..
+        } => format!("ConstDecl {} : {} =", name, type_str(data_type)),
         ParseNode::Assign { op, .. } => format!("Assign {:?}", op),
         ParseNode::If { .. } => "If".into(),
         ParseNode::For { var_name, .. } => format!("For {}", var_name),
@@ -212,7 +296,7 @@ fn node_label(n: &ParseNode) -> String {
             let chain: String = steps
                 .iter()
                 .map(|s| match s {
-                    AccessStep::Field(f) => format!("::{}", f),
+                    AccessStep::Field(f, _) => format!("::{}", f),
                     AccessStep::Index(_) => "[…]".into(),
                     AccessStep::Call(a) => format!("({})", a.len()),
                 })
@@ -245,9 +329,18 @@ fn node_label(n: &ParseNode) -> String {
         ParseNode::TypeChar(_) => ":char".into(),
         ParseNode::TypeBoolean(_) => ":bool".into(),
         ParseNode::TypeVoid(_) => ":void".into(),
-        ParseNode::TypeArray { elem, size, .. } => format!(":array<{},{}>", type_str(elem), size),
+        ParseNode::TypeArray {
+            elem,
+            size,
+            size_const,
+            ..
+        } => match size_const {
+            Some(c) => format!(":array<{},{}>", type_str(elem), c),
+            None => format!(":array<{},{}>", type_str(elem), size),
+        },
         ParseNode::TypeList { elem, .. } => format!(":list<{}>", type_str(elem)),
         ParseNode::TypeStruct { name, .. } => format!(":struct<{}>", name),
+        ParseNode::TypeFunc { .. } => type_str(n),
         _ => "Node".into(),
     }
 }
@@ -259,9 +352,22 @@ fn type_str(n: &ParseNode) -> String {
         ParseNode::TypeChar(_) => ":char".into(),
         ParseNode::TypeBoolean(_) => ":bool".into(),
         ParseNode::TypeVoid(_) => ":void".into(),
-        ParseNode::TypeArray { elem, size, .. } => format!(":array<{},{}>", type_str(elem), size),
+        ParseNode::TypeArray {
+            elem,
+            size,
+            size_const,
+            ..
+        } => match size_const {
+            Some(c) => format!(":array<{},{}>", type_str(elem), c),
+            None => format!(":array<{},{}>", type_str(elem), size),
+        },
         ParseNode::TypeList { elem, .. } => format!(":list<{}>", type_str(elem)),
         ParseNode::TypeStruct { name, .. } => format!(":struct<{}>", name),
+        ParseNode::TypeFunc { params, ret, .. } => format!(
+            ":func<({}) -> {}>",
+            params.iter().map(type_str).collect::<Vec<_>>().join(", "),
+            type_str(ret)
+        ),
         _ => "?".into(),
     }
 }
@@ -289,7 +395,7 @@ pub struct ScopeSnapshot {
 pub struct VarRow {
     pub name: String,
     pub type_label: String,
-    pub value: String,
+    pub value: FractalValue,
     pub changed: bool,
 }
 
@@ -304,17 +410,22 @@ pub struct DebugFrame {
     pub finished: bool,
     pub error: Option<String>,
     pub buffered_output: String,
+    /// The innermost scope's variables as they were at the previous step of
+    /// the same call, for diffing changed values element by element.
+    pub previous_values: HashMap<String, FractalValue>,
 }
 
 pub struct DebugSession {
     debug_file: PathBuf,
     snapshots: Vec<DebugSnapshot>,
     cursor: usize,
+    viewing: Option<usize>,
     file_offset: u64,
     pub tree: Vec<TreeNode>,
     pub finished: bool,
     label_to_node: HashMap<String, usize>,
     last_stepped_frame: Option<DebugFrame>,
+    format_error: Option<String>,
 }
 
 impl DebugSession {
@@ -330,15 +441,20 @@ impl DebugSession {
             debug_file,
             snapshots: Vec::new(),
             cursor: 0,
+            viewing: None,
             file_offset: 0,
             tree,
             finished: false,
             label_to_node,
             last_stepped_frame: None,
+            format_error: None,
         }
     }
 
     pub fn poll_file(&mut self) {
+        if self.format_error.is_some() {
+            return;
+        }
         let Ok(content) = fs::read_to_string(&self.debug_file) else {
             return;
         };
@@ -346,27 +462,40 @@ impl DebugSession {
         if offset > content.len() {
             return;
         }
-        let new_part = &content[offset..];
-        if new_part.is_empty() {
+        // A line the binary is still writing is left for the next poll.
+        let Some(end) = content[offset..].rfind('\n') else {
             return;
-        }
+        };
 
-        let mut consumed = 0usize;
-        for raw_line in new_part.lines() {
-            let line_bytes = raw_line.len() + 1;
+        let mut consumed = offset;
+        for raw_line in content[offset..offset + end + 1].split_inclusive('\n') {
             let trimmed = raw_line.trim();
             if !trimmed.is_empty() {
-                if let Some(snap) = parse_snapshot_line(trimmed) {
-                    let was_finished = snap.finished;
-                    self.snapshots.push(snap);
-                    if was_finished {
-                        self.finished = true;
+                match snapshot::parse_line(trimmed) {
+                    Ok(Record::Header(_)) => {}
+                    Ok(Record::Snapshot(snap)) => {
+                        let was_finished = snap.finished;
+                        self.snapshots.push(to_debug_snapshot(*snap));
+                        if was_finished {
+                            self.finished = true;
+                        }
                     }
+                    Err(e @ SnapshotError::Incompatible(_)) => {
+                        self.format_error = Some(e.to_string());
+                        break;
+                    }
+                    Err(SnapshotError::Malformed(_)) => {}
                 }
             }
-            consumed += line_bytes;
+            consumed += raw_line.len();
         }
-        self.file_offset = (offset + consumed) as u64;
+        self.file_offset = consumed as u64;
+    }
+
+    /// Why the snapshot file cannot be read, when it was written by an
+    /// incompatible compiler.
+    pub fn format_error(&self) -> Option<&str> {
+        self.format_error.as_deref()
     }
 
     pub fn total_steps(&self) -> usize {
@@ -383,17 +512,85 @@ impl DebugSession {
 
     pub fn current_frame(&self) -> DebugFrame {
         if !self.snapshots.is_empty() {
-            let idx = self.cursor.min(self.snapshots.len() - 1);
-            return self.snap_to_frame(&self.snapshots[idx]);
+            return self.snap_to_frame(self.position());
         }
         placeholder_frame()
     }
 
+    pub fn live_position(&self) -> usize {
+        self.cursor.min(self.snapshots.len().saturating_sub(1))
+    }
+
+    pub fn position(&self) -> usize {
+        self.viewing.unwrap_or_else(|| self.live_position())
+    }
+
+    pub fn is_rewound(&self) -> bool {
+        self.viewing.is_some()
+    }
+
+    pub fn jump_to(&mut self, index: usize) -> Option<DebugFrame> {
+        if self.snapshots.is_empty() {
+            return None;
+        }
+        let live = self.live_position();
+        self.viewing = if index < live { Some(index) } else { None };
+        Some(self.current_frame())
+    }
+
+    pub fn step_back(&mut self) -> Option<DebugFrame> {
+        let pos = self.position();
+        if pos == 0 {
+            return None;
+        }
+        self.jump_to(pos - 1)
+    }
+
+    pub fn step_forward_recorded(&mut self) -> Option<DebugFrame> {
+        let pos = self.viewing?;
+        self.jump_to(pos + 1)
+    }
+
+    pub fn return_to_live(&mut self) {
+        self.viewing = None;
+    }
+
+    pub fn previous_write(&mut self, var: &str) -> Option<DebugFrame> {
+        let pos = self.position();
+        let found = (0..pos).rev().find(|&i| {
+            let snap = &self.snapshots[i];
+            let Some(now) = snapshot_value(snap, var) else {
+                return false;
+            };
+            let before = self.snapshots[..i]
+                .iter()
+                .rev()
+                .find(|p| p.call_stack == snap.call_stack)
+                .and_then(|p| snapshot_value(p, var));
+            before != Some(now)
+        })?;
+        self.jump_to(found)
+    }
+
+    // Where frame `depth` of the current call stack (0 is the outermost) last
+    // stopped: the file and line of its latest recorded statement.
+    pub fn frame_location(&self, depth: usize) -> Option<(String, usize)> {
+        let pos = self.position();
+        let stack = &self.snapshots.get(pos)?.call_stack;
+        let prefix = stack.get(..=depth)?;
+        self.snapshots[..=pos]
+            .iter()
+            .rev()
+            .find(|s| s.call_stack == prefix)
+            .map(|s| (s.source_file.clone(), s.source_line))
+    }
+
     pub fn step(&mut self) -> Option<DebugFrame> {
         if self.cursor >= self.snapshots.len() {
             return None;
         }
-        let frame = self.snap_to_frame(&self.snapshots[self.cursor]);
+        self.viewing = None;
+        let frame = self.snap_to_frame(self.cursor);
         self.cursor += 1;
         if frame.finished {
             self.finished = true;
@@ -402,6 +599,40 @@ impl DebugSession {
         Some(frame)
     }
 
+    /// Steps until a snapshot satisfies `stop` or the program ends. Replaying
+    /// a recording has no binary to run ahead to the next pause, so stepping
+    /// over, out and to breakpoints searches the recorded snapshots instead.
+    /// The frame returned carries the output of every step taken.
+    pub fn step_until(&mut self, stop: impl Fn(&DebugFrame) -> bool) -> Option<DebugFrame> {
+        let mut output = String::new();
+        let mut last = None;
+        while let Some(frame) = self.step() {
+            output.push_str(&frame.buffered_output);
+            let done = stop(&frame) || frame.finished || frame.error.is_some();
+            last = Some(frame);
+            if done {
+                break;
+            }
+        }
+        last.map(|frame| DebugFrame {
+            buffered_output: output,
+            ..frame
+        })
+    }
+
+    pub fn seek_latest(&mut self) -> String {
+        let Some(last) = self.snapshots.len().checked_sub(1) else {
+            return String::new();
+        };
+        let skipped: String = self.snapshots[self.cursor.min(last)..last]
+            .iter()
+            .map(|s| s.output_since_last.as_str())
+            .collect();
+        self.cursor = last;
+        self.viewing = None;
+        skipped
+    }
+
     pub fn toggle_collapsed(&mut self, node_id: usize) {
         if let Some(n) = self.tree.get_mut(node_id) {
             n.collapsed = !n.collapsed;
@@ -434,8 +665,22 @@ impl DebugSession {
         }
     }
 
-    fn snap_to_frame(&self, snap: &DebugSnapshot) -> DebugFrame {
+    fn snap_to_frame(&self, index: usize) -> DebugFrame {
+        let snap = &self.snapshots[index];
         let active_node_id = self.find_node_for_label(&snap.label, snap.step);
+        let previous_values = self.snapshots[..index]
+            .iter()
+            .rev()
+            .find(|p| p.call_stack == snap.call_stack)
+            .and_then(|p| p.scopes.first())
+            .map(|scope| {
+                scope
+                    .vars
+                    .iter()
+                    .map(|v| (v.name.clone(), v.value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
         DebugFrame {
             active_node_id,
             step_label: snap.label.clone(),
@@ -446,6 +691,7 @@ impl DebugSession {
             finished: snap.finished,
             error: snap.error.clone(),
             buffered_output: snap.output_since_last.clone(),
+            previous_values,
         }
     }
 
@@ -468,6 +714,15 @@ impl DebugSession {
     }
 }
 
+fn snapshot_value<'a>(snap: &'a DebugSnapshot, var: &str) -> Option<&'a FractalValue> {
+    snap.scopes
+        .first()?
+        .vars
+        .iter()
+        .find(|v| v.name == var)
+        .map(|v| &v.value)
+}
+
 fn placeholder_frame() -> DebugFrame {
     DebugFrame {
         active_node_id: 0,
@@ -479,254 +734,41 @@ fn placeholder_frame() -> DebugFrame {
         finished: false,
         error: None,
         buffered_output: String::new(),
+        previous_values: HashMap::new(),
     }
 }
 
-fn parse_snapshot_line(line: &str) -> Option<DebugSnapshot> {
-    let step = extract_u64(line, "\"step\"")?;
-    let label = extract_str(line, "\"label\"").unwrap_or_default();
-    let source_line = extract_u64(line, "\"line\"").unwrap_or(0) as usize;
-    let source_file = extract_str(line, "\"file\"").unwrap_or_default();
-    let output = extract_str(line, "\"output\"").unwrap_or_default();
-    let finished = extract_bool(line, "\"finished\"").unwrap_or(false);
-    let error = extract_nullable_str(line, "\"error\"");
-    let call_stack = extract_str_array(line, "\"stack\"");
-    let scopes = extract_scopes(line);
-
-    Some(DebugSnapshot {
-        step: step as usize,
-        label,
-        source_line,
-        source_file,
-        scopes,
-        call_stack,
-        output_since_last: output,
-        finished,
-        error,
-    })
-}
-
-fn extract_u64(s: &str, key: &str) -> Option<u64> {
-    let pos = s.find(key)?;
-    let after = &s[pos + key.len()..];
-    let colon = after.find(':')? + 1;
-    let digits: String = after[colon..]
-        .chars()
-        .skip_while(|c| c.is_whitespace())
-        .take_while(|c| c.is_ascii_digit())
-        .collect();
-    digits.parse().ok()
-}
-
-fn extract_bool(s: &str, key: &str) -> Option<bool> {
-    let pos = s.find(key)?;
-    let after = &s[pos + key.len()..];
-    let colon = after.find(':')? + 1;
-    let val: String = after[colon..]
-        .chars()
-        .skip_while(|c| c.is_whitespace())
-        .take_while(|c| c.is_alphabetic())
+fn to_debug_snapshot(snap: Snapshot) -> DebugSnapshot {
+    let scopes = snap
+        .scopes
+        .into_iter()
+        .map(|scope| ScopeSnapshot {
+            label: scope.label,
+            vars: scope.vars.into_iter().map(to_var_row).collect(),
+        })
         .collect();
-    match val.as_str() {
-        "true" => Some(true),
-        "false" => Some(false),
-        _ => None,
-    }
-}
-
-fn extract_str(s: &str, key: &str) -> Option<String> {
-    let pos = s.find(key)?;
-    let after = &s[pos + key.len()..];
-    let colon = after.find(':')? + 1;
-    let rest = after[colon..].trim_start();
-    if !rest.starts_with('"') {
-        return None;
-    }
-    Some(parse_json_string(&rest[1..]))
-}
-
-fn extract_nullable_str(s: &str, key: &str) -> Option<String> {
-    let pos = s.find(key)?;
-    let after = &s[pos + key.len()..];
-    let colon = after.find(':')? + 1;
-    let rest = after[colon..].trim_start();
-    if rest.starts_with("null") {
-        return None;
-    }
-    if rest.starts_with('"') {
-        return Some(parse_json_string(&rest[1..]));
-    }
-    None
-}
-
-fn parse_json_string(s: &str) -> String {
-    let mut out = String::new();
-    let mut chars = s.chars();
-    while let Some(c) = chars.next() {
-        if c == '"' {
-            break;
-        }
-        if c == '\\' {
-            match chars.next() {
-                Some('n') => out.push('\n'),
-                Some('t') => out.push('\t'),
-                Some('r') => out.push('\r'),
-                Some('"') => out.push('"'),
-                Some('\\') => out.push('\\'),
-                Some(other) => {
-                    out.push('\\');
-                    out.push(other);
-                }
-                None => break,
-            }
-        } else {
-            out.push(c);
-        }
-    }
-    out
-}
-
-fn extract_str_array(s: &str, key: &str) -> Vec<String> {
-    let Some(pos) = s.find(key) else {
-        return vec![];
-    };
-    let after = &s[pos + key.len()..];
-    let Some(colon) = after.find(':') else {
-        return vec![];
-    };
-    let rest = after[colon + 1..].trim_start();
-    if !rest.starts_with('[') {
-        return vec![];
-    }
-    let Some(end) = rest.find(']') else {
-        return vec![];
-    };
-    let inner = &rest[1..end];
-    let mut result = Vec::new();
-    for part in inner.split(',') {
-        let p = part.trim();
-        if p.starts_with('"') && p.ends_with('"') && p.len() >= 2 {
-            result.push(p[1..p.len() - 1].to_string());
-        }
+    DebugSnapshot {
+        step: snap.step,
+        label: snap.label,
+        source_line: snap.line,
+        source_file: snap.file,
+        scopes,
+        call_stack: snap.stack,
+        output_since_last: snap.output,
+        finished: snap.finished,
+        error: snap.error,
     }
-    result
 }
 
-fn extract_scopes(s: &str) -> Vec<ScopeSnapshot> {
-    let Some(pos) = s.find("\"scopes\"") else {
-        return vec![];
-    };
-    let after = &s[pos + 8..];
-    let Some(colon) = after.find(':') else {
-        return vec![];
+fn to_var_row(var: Var) -> VarRow {
+    let name = match var.name.strip_prefix("fractal_") {
+        Some(stripped) => stripped.to_string(),
+        None => var.name,
     };
-    let rest = after[colon + 1..].trim_start();
-    if !rest.starts_with('[') {
-        return vec![];
-    }
-
-    let mut scopes = Vec::new();
-    let bytes = rest.as_bytes();
-    let mut i = 1usize;
-    let mut depth = 0i32;
-    let mut obj_start = None;
-
-    while i < bytes.len() {
-        match bytes[i] as char {
-            '{' => {
-                if depth == 0 {
-                    obj_start = Some(i);
-                }
-                depth += 1;
-            }
-            '}' => {
-                depth -= 1;
-                if depth == 0 {
-                    if let Some(start) = obj_start {
-                        let obj = &rest[start..=i];
-                        if let Some(sc) = parse_scope_object(obj) {
-                            scopes.push(sc);
-                        }
-                        obj_start = None;
-                    }
-                }
-            }
-            ']' if depth == 0 => break,
-            _ => {}
-        }
-        i += 1;
-    }
-    scopes
-}
-
-fn parse_scope_object(obj: &str) -> Option<ScopeSnapshot> {
-    let label = extract_str(obj, "\"label\"").unwrap_or_default();
-    let vars = extract_vars(obj);
-    Some(ScopeSnapshot { label, vars })
-}
-
-fn extract_vars(obj: &str) -> Vec<VarRow> {
-    let Some(pos) = obj.find("\"vars\"") else {
-        return vec![];
-    };
-    let after = &obj[pos + 6..];
-    let Some(colon) = after.find(':') else {
-        return vec![];
-    };
-    let rest = after[colon + 1..].trim_start();
-    if !rest.starts_with('[') {
-        return vec![];
-    }
-
-    let mut vars = Vec::new();
-    let bytes = rest.as_bytes();
-    let mut i = 1usize;
-    let mut depth = 0i32;
-    let mut obj_start = None;
-
-    while i < bytes.len() {
-        match bytes[i] as char {
-            '{' => {
-                if depth == 0 {
-                    obj_start = Some(i);
-                }
-                depth += 1;
-            }
-            '}' => {
-                depth -= 1;
-                if depth == 0 {
-                    if let Some(start) = obj_start {
-                        let var_obj = &rest[start..=i];
-                        if let Some(row) = parse_var_object(var_obj) {
-                            vars.push(row);
-                        }
-                        obj_start = None;
-                    }
-                }
-            }
-            ']' if depth == 0 => break,
-            _ => {}
-        }
-        i += 1;
-    }
-    vars
-}
-
-fn parse_var_object(obj: &str) -> Option<VarRow> {
-    let raw_name = extract_str(obj, "\"name\"")?;
-    let type_label = extract_str(obj, "\"type\"").unwrap_or_default();
-    let value = extract_str(obj, "\"value\"").unwrap_or_default();
-    let changed = extract_bool(obj, "\"changed\"").unwrap_or(false);
-
-    let name = raw_name
-        .strip_prefix("fractal_")
-        .unwrap_or(&raw_name)
-        .to_string();
-
-    Some(VarRow {
+    VarRow {
         name,
-        type_label,
-        value,
-        changed,
-    })
+        type_label: var.type_label,
+        value: FractalValue::from_json(&var.value),
+        changed: var.changed,
+    }
 }
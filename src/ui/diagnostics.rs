@@ -0,0 +1,431 @@
+use super::tab::Tab;
+use super::theme::Theme;
+use crate::compiler::diagnostic::{module_key, Diagnostic, Severity};
+use crate::compiler::lexer::lex;
+use crate::compiler::parser::{parse_with_source, ParseNode};
+use crate::compiler::preprocessor::try_preprocess;
+use crate::compiler::semanter::{analyze, Symbol};
+use eframe::egui;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long the code has to stay unchanged before it is checked again.
+const DEBOUNCE: Duration = Duration::from_millis(400);
+
+/// What checking a file found.
+#[derive(Default)]
+pub struct CheckResult {
+    pub diagnostics: Vec<Diagnostic>,
+    /// The symbol table, when the file got as far as the semanter.
+    pub symbols: Option<Vec<Symbol>>,
+    /// The parse tree, when the file parsed.
+    pub program: Option<ParseNode>,
+    /// The file of every module the program imports, by module name.
+    pub modules: BTreeMap<String, PathBuf>,
+}
+
+/// Runs the front end over `code`, the text of `source_file`, and returns
+/// everything it finds, stopping at the first stage that fails. No stage
+/// panics or exits on bad input, as this runs on code that is half typed.
+pub fn check_source(code: &str, source_file: &str) -> CheckResult {
+    let (program, mut diagnostics) = match try_preprocess(code, source_file) {
+        Ok(expanded) => expanded,
+        Err(diagnostics) => {
+            return CheckResult {
+                diagnostics,
+                ..CheckResult::default()
+            }
+        }
+    };
+    let modules = module_files(&program, source_file);
+    let tokens = match lex(&program, source_file) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            diagnostics.extend(errors);
+            return CheckResult {
+                diagnostics,
+                modules,
+                ..CheckResult::default()
+            };
+        }
+    };
+    match parse_with_source(tokens, source_file) {
+        Ok(root) => {
+            let result = analyze(&root);
+            diagnostics.extend(result.diagnostics());
+            CheckResult {
+                diagnostics,
+                symbols: Some(result.symbol_table),
+                program: Some(root),
+                modules,
+            }
+        }
+        Err(err) => {
+            diagnostics.push(err.diagnostic(source_file));
+            CheckResult {
+                diagnostics,
+                modules,
+                ..CheckResult::default()
+            }
+        }
+    }
+}
+
+// Reads the files of the imported modules back from the `$SRCMAP$` markers
+// the preprocessor left in `program`.
+fn module_files(program: &str, source_file: &str) -> BTreeMap<String, PathBuf> {
+    program
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .strip_prefix("$SRCMAP:")
+                .and_then(|m| m.strip_suffix('$'))
+                .and_then(|m| m.rsplit_once(':'))
+                .map(|(path, _)| path)
+        })
+        .filter(|path| *path != source_file)
+        .map(|path| (module_key(path, source_file), PathBuf::from(path)))
+        .collect()
+}
+
+/// The diagnostics of one tab, re-checked in the background once its code
+/// has been left alone for a moment.
+#[derive(Default)]
+pub struct LiveCheck {
+    /// From the last finished check; kept while a newer one runs so that the
+    /// squiggles do not flicker.
+    pub diagnostics: Vec<Diagnostic>,
+    /// From the last check that got as far as the semanter, so that
+    /// completion keeps working while the code is half typed.
+    pub symbols: Vec<Symbol>,
+    /// The files of the modules [`Self::symbols`] came from, by module name.
+    pub modules: BTreeMap<String, PathBuf>,
+    /// The parse tree [`Self::symbols`] came from.
+    pub program: Option<ParseNode>,
+    // The code the last check was started for.
+    checked: Option<String>,
+    // The code `symbols` are of.
+    analysed: Option<String>,
+    last_code: String,
+    edited_at: Option<Instant>,
+    job: Option<Arc<Mutex<Option<CheckResult>>>>,
+}
+
+impl LiveCheck {
+    /// Collects a finished check and starts the next one when `code` has
+    /// changed since the last and has not been edited for [`DEBOUNCE`].
+    pub fn update(&mut self, code: &str, source_file: &str, ctx: &egui::Context) {
+        if let Some(job) = &self.job {
+            let finished = job.lock().unwrap().take();
+            match finished {
+                Some(found) => {
+                    self.diagnostics = found.diagnostics;
+                    if let Some(symbols) = found.symbols {
+                        self.symbols = symbols;
+                        self.modules = found.modules;
+                        self.program = found.program;
+                        self.analysed = self.checked.clone();
+                    }
+                    self.job = None;
+                }
+                None => {
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                    return;
+                }
+            }
+        }
+
+        if code != self.last_code {
+            self.last_code = code.to_string();
+            self.edited_at = Some(Instant::now());
+        }
+        if self.checked.as_deref() == Some(code) {
+            return;
+        }
+        let quiet_for = self.edited_at.map(|t| t.elapsed()).unwrap_or(DEBOUNCE);
+        if quiet_for < DEBOUNCE {
+            ctx.request_repaint_after(DEBOUNCE - quiet_for);
+            return;
+        }
+
+        self.checked = Some(code.to_string());
+        let job = Arc::new(Mutex::new(None));
+        let result = Arc::clone(&job);
+        let code = code.to_string();
+        let source_file = source_file.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let found = check_source(&code, &source_file);
+            *result.lock().unwrap() = Some(found);
+            ctx.request_repaint();
+        });
+        self.job = Some(job);
+    }
+
+    /// Whether [`Self::symbols`] are of `code` as it is now, so that their
+    /// lines can be trusted.
+    pub fn is_current(&self, code: &str) -> bool {
+        self.analysed.as_deref() == Some(code)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+}
+
+/// Where a diagnostic clicked in the problems panel is.
+pub struct ProblemTarget {
+    pub tab: usize,
+    pub diagnostic: Diagnostic,
+}
+
+#[derive(Default)]
+pub struct ProblemsWindow {
+    pub open: bool,
+}
+
+impl ProblemsWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists the diagnostics of every open tab and returns the one clicked.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        tabs: &[Tab],
+        theme: &Theme,
+    ) -> Option<ProblemTarget> {
+        if !self.open {
+            return None;
+        }
+
+        let t = *theme;
+        let mut open = self.open;
+        let mut clicked = None;
+
+        egui::Window::new("Problems")
+            .id(egui::Id::new("fractal_problems"))
+            .open(&mut open)
+            .default_size([520.0, 300.0])
+            .min_size([300.0, 140.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                let errors: usize = tabs
+                    .iter()
+                    .map(|tab| tab.live_check.count(Severity::Error))
+                    .sum();
+                let warnings: usize = tabs
+                    .iter()
+                    .map(|tab| tab.live_check.count(Severity::Warning))
+                    .sum();
+
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Problems")
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            );
+                            ui.add_space(8.0);
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{}, {}",
+                                    plural(errors, "error"),
+                                    plural(warnings, "warning")
+                                ))
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                            );
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                if errors + warnings == 0 {
+                    ui.add_space(16.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new("No problems in the open files")
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                        );
+                    });
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .id_salt("problems_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Frame::new()
+                            .inner_margin(egui::Margin::symmetric(12, 8))
+                            .show(ui, |ui| {
+                                for (i, tab) in tabs.iter().enumerate() {
+                                    let found = &tab.live_check.diagnostics;
+                                    if found.is_empty() {
+                                        continue;
+                                    }
+                                    ui.label(
+                                        egui::RichText::new(tab.display_name())
+                                            .size(12.0)
+                                            .color(t.tab_inactive_fg)
+                                            .strong(),
+                                    );
+                                    let mut sorted: Vec<&Diagnostic> = found.iter().collect();
+                                    sorted.sort_by(|a, b| {
+                                        (&a.file, a.line, a.severity)
+                                            .cmp(&(&b.file, b.line, b.severity))
+                                    });
+                                    for d in sorted {
+                                        if problem_row(ui, d, &t).clicked() {
+                                            clicked = Some(ProblemTarget {
+                                                tab: i,
+                                                diagnostic: d.clone(),
+                                            });
+                                        }
+                                    }
+                                    ui.add_space(6.0);
+                                }
+                            });
+                    });
+            });
+
+        self.open = open;
+        clicked
+    }
+}
+
+fn problem_row(ui: &mut egui::Ui, d: &Diagnostic, t: &Theme) -> egui::Response {
+    let place = match (d.file.is_empty(), d.line) {
+        (true, 0) => String::new(),
+        (true, line) => format!("line {line}"),
+        (false, 0) => d.file.clone(),
+        (false, line) => format!("{}: line {line}", d.file),
+    };
+    let resp = ui
+        .horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(super::icons::ERROR)
+                    .size(12.0)
+                    .color(severity_color(d.severity, t)),
+            );
+            ui.label(
+                egui::RichText::new(&d.message)
+                    .size(12.0)
+                    .color(t.tab_active_fg),
+            );
+            ui.label(
+                egui::RichText::new(place)
+                    .size(11.0)
+                    .color(t.tab_inactive_fg),
+            );
+        })
+        .response
+        .interact(egui::Sense::click());
+    if resp.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    resp.on_hover_text(describe(d))
+}
+
+/// The message of `d` with its hints and notes, one per line, as the editor
+/// shows it on hover.
+pub fn describe(d: &Diagnostic) -> String {
+    let kind = match d.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let mut text = if d.code.is_empty() {
+        format!("{kind}: {}", d.message)
+    } else {
+        format!("{kind}[{}]: {}", d.code, d.message)
+    };
+    for hint in &d.hints {
+        text.push_str(&format!("\nhint: {hint}"));
+    }
+    for note in &d.notes {
+        text.push_str(&format!("\nnote: {note}"));
+    }
+    text
+}
+
+pub fn severity_color(severity: Severity, t: &Theme) -> egui::Color32 {
+    match severity {
+        Severity::Error => t.terminal_error,
+        Severity::Warning => t.terminal_warning,
+    }
+}
+
+fn plural(n: usize, what: &str) -> String {
+    if n == 1 {
+        format!("1 {what}")
+    } else {
+        format!("{n} {what}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every prefix of a program stands in for code as it is being typed.
+    fn check_every_prefix(code: &str) {
+        for (i, _) in code.char_indices() {
+            check_source(&code[..i], "prefix.fr");
+        }
+    }
+
+    #[test]
+    fn half_typed_code_does_not_panic() {
+        check_every_prefix(include_str!("../files/bst.fr"));
+        check_every_prefix(include_str!("../files/errors.fr"));
+        check_every_prefix(include_str!("../files/operatorTest.fr"));
+    }
+
+    #[test]
+    fn reports_each_stage() {
+        let lexed = check_source("!start\n:int x = 1 $ 2;\n!end\n", "a.fr");
+        assert!(lexed
+            .diagnostics
+            .iter()
+            .any(|d| d.is_error() && d.line == 2));
+        assert!(lexed.symbols.is_none());
+
+        let parsed = check_source("!start\n:int x = ;\n!end\n", "a.fr");
+        assert!(parsed
+            .diagnostics
+            .iter()
+            .any(|d| d.is_error() && d.line == 2));
+        assert!(parsed.program.is_none());
+
+        let analysed = check_source("!start\n:int x = y;\n!end\n", "a.fr");
+        assert!(analysed
+            .diagnostics
+            .iter()
+            .any(|d| d.is_error() && d.line == 2));
+        assert!(analysed.symbols.is_some());
+    }
+}
@@ -114,6 +114,33 @@ fn load_docs_json() -> DocsJson {
 static DOCS_JSON: std::sync::LazyLock<DocsJson> =
     std::sync::LazyLock::new(load_docs_json);
 
+/// What the Standard Library chapter says about the builtin `name`: its rows
+/// of the function tables, or the text of its own section.
+pub fn builtin_doc(name: &str) -> Option<String> {
+    let chapter = DOCS_JSON.chapters.iter().find(|c| c.id == "stdlib")?;
+    let call = format!("{name}(");
+    let mut lines = Vec::new();
+    for section in &chapter.sections {
+        if let Some(table) = &section.table {
+            for row in &table.rows {
+                if let [usage, description, returns, ..] = row.as_slice() {
+                    if usage.starts_with(&call) {
+                        lines.push(format!("{usage} - {description}, returns {returns}"));
+                    }
+                }
+            }
+        }
+        for sub in section.subsections.iter().flatten() {
+            if sub.title == name {
+                if let Some(text) = sub.description.as_ref().or(sub.code.as_ref()) {
+                    lines.push(text.clone());
+                }
+            }
+        }
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
 pub struct DocsWindow {
     pub open: bool,
     chapter: Chapter,
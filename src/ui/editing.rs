@@ -0,0 +1,464 @@
+use super::folding::code_chars;
+
+/// One level of indentation.
+pub const INDENT: &str = "    ";
+
+// A selection as char indices: where it was started, then the cursor.
+type Selection = (usize, usize);
+
+fn byte_at(code: &str, at: usize) -> usize {
+    code.char_indices().nth(at).map_or(code.len(), |(i, _)| i)
+}
+
+// Replaces chars `start..end` of `code` with `text`.
+fn splice(code: &mut String, start: usize, end: usize, text: &str) {
+    let (a, b) = (byte_at(code, start), byte_at(code, end));
+    code.replace_range(a..b, text);
+}
+
+// Makes `edits`, each a char index, a number of chars to remove there and
+// the text to put in their place, in order and not overlapping, and returns
+// where the ends of `sel` end up.
+fn apply(code: &mut String, edits: &[(usize, usize, String)], sel: Selection) -> Selection {
+    for (at, remove, text) in edits.iter().rev() {
+        splice(code, *at, at + remove, text);
+    }
+    let moved = |p: usize| {
+        let mut q = p as isize;
+        for (at, remove, text) in edits {
+            if p >= at + remove {
+                q += text.chars().count() as isize - *remove as isize;
+            } else if p > *at {
+                q -= (p - at) as isize;
+            }
+        }
+        q as usize
+    };
+    (moved(sel.0), moved(sel.1))
+}
+
+// The char index each line of `chars` starts at.
+fn line_starts(chars: &[char]) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(
+            chars
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| c == '\n')
+                .map(|(i, _)| i + 1),
+        )
+        .collect()
+}
+
+// The first char of every line `sel` touches. A selection ending at the
+// start of a line leaves that line out.
+fn selected_lines(chars: &[char], sel: Selection) -> Vec<usize> {
+    let (start, end) = (sel.0.min(sel.1), sel.0.max(sel.1));
+    let starts = line_starts(chars);
+    let first = starts.partition_point(|&s| s <= start) - 1;
+    let last = starts.partition_point(|&s| s < end.max(start + 1)) - 1;
+    starts[first..=last.max(first)].to_vec()
+}
+
+fn indent_len(chars: &[char], line_start: usize) -> usize {
+    chars[line_start..]
+        .iter()
+        .take_while(|&&c| c == ' ' || c == '\t')
+        .count()
+}
+
+fn is_blank(chars: &[char], line_start: usize) -> bool {
+    chars[line_start..]
+        .iter()
+        .take_while(|&&c| c != '\n')
+        .all(|c| c.is_whitespace())
+}
+
+/// Whether `sel` covers a line break.
+pub fn spans_lines(code: &str, sel: Selection) -> bool {
+    let (start, end) = (sel.0.min(sel.1), sel.0.max(sel.1));
+    code.chars()
+        .skip(start)
+        .take(end - start)
+        .any(|c| c == '\n')
+}
+
+/// Every bracket of `code` paired with the one closing it, as char indices
+/// of the opening and the closing bracket. `<` and `>` count only around a
+/// type, as in `:list<:int>`; brackets in strings and comments not at all.
+pub fn bracket_pairs(code: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = code.chars().collect();
+    let offsets: Vec<usize> = code.char_indices().map(|(i, _)| i).collect();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut pairs = Vec::new();
+    for (byte, c) in code_chars(code) {
+        let at = offsets.partition_point(|&o| o < byte);
+        let opener = match c {
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            '>' if at > 0 && chars[at - 1] != '-' => '<',
+            '(' | '[' | '{' => {
+                open.push((c, at));
+                continue;
+            }
+            '<' if after_type_name(&chars, at) => {
+                open.push((c, at));
+                continue;
+            }
+            _ => continue,
+        };
+        // A `<` left open is a comparison after all.
+        while c != '>' && open.last().is_some_and(|o| o.0 == '<') {
+            open.pop();
+        }
+        if open.last().is_some_and(|o| o.0 == opener) {
+            let (_, start) = open.pop().unwrap();
+            pairs.push((start, at));
+        }
+    }
+    pairs
+}
+
+/// The bracket just after char index `at` of `code`, or else the one just
+/// before it, and the bracket it pairs with.
+pub fn matching_bracket(code: &str, at: usize) -> Option<(usize, usize)> {
+    let pairs = bracket_pairs(code);
+    let partner = |i: usize| {
+        pairs.iter().find_map(|&(o, c)| {
+            if o == i {
+                Some((o, c))
+            } else if c == i {
+                Some((c, o))
+            } else {
+                None
+            }
+        })
+    };
+    partner(at).or_else(|| partner(at.checked_sub(1)?))
+}
+
+// Whether the chars before index `at` are a type name, as in `:list`.
+fn after_type_name(chars: &[char], at: usize) -> bool {
+    let name = chars[..at]
+        .iter()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || **c == '_')
+        .count();
+    name > 0 && at > name && chars[at - name - 1] == ':'
+}
+
+// Whether char index `at` is in code rather than in a string, a char
+// literal or a line comment, judging by its line alone.
+fn in_code(chars: &[char], at: usize) -> bool {
+    let line_start = chars[..at]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |i| i + 1);
+    let mut quote = None;
+    let mut i = line_start;
+    while i < at {
+        match (quote, chars[i]) {
+            (Some(_), '\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(chars[i]),
+            (None, '#') => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    quote.is_none()
+}
+
+/// Types `c` over `sel` when it is a bracket or a quote, and returns the new
+/// selection; otherwise leaves the typing to the text edit. An opening
+/// bracket or quote gets its closing one, or wraps the selection; typing a
+/// closing one that is already next to the cursor steps over it; and `}`
+/// typed on an empty line lines up with the line of its `{`.
+pub fn type_char(code: &mut String, sel: Selection, c: char) -> Option<Selection> {
+    let (start, end) = (sel.0.min(sel.1), sel.0.max(sel.1));
+    let chars: Vec<char> = code.chars().collect();
+    let next = chars.get(end).copied();
+    let prev = start.checked_sub(1).map(|i| chars[i]);
+    let quote = c == '"' || c == '\'';
+
+    if start == end && next == Some(c) && (quote || matches!(c, ')' | ']' | '}' | '>')) {
+        return Some((end + 1, end + 1));
+    }
+
+    let close = match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '<' if after_type_name(&chars, start) => Some('>'),
+        '"' | '\'' => Some(c),
+        _ => None,
+    };
+    if let Some(close) = close {
+        if start != end {
+            let edits = [(start, 0, c.to_string()), (end, 0, close.to_string())];
+            let (a, b) = apply(code, &edits, sel);
+            // The wrapped text stays selected, not the brackets.
+            return Some(if sel.0 <= sel.1 {
+                (a, b - 1)
+            } else {
+                (a - 1, b)
+            });
+        }
+        let closes_well = next
+            .is_none_or(|n| n.is_whitespace() || matches!(n, ')' | ']' | '}' | '>' | ';' | ','));
+        let after_word = prev.is_some_and(|p| p.is_alphanumeric() || p == '_');
+        if closes_well && in_code(&chars, start) && !(quote && after_word) {
+            splice(code, start, end, &format!("{c}{close}"));
+            return Some((start + 1, start + 1));
+        }
+        return None;
+    }
+
+    if c == '}' && start == end {
+        let line_start = chars[..start]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        if line_start == start || chars[line_start..start].iter().any(|c| !c.is_whitespace()) {
+            return None;
+        }
+        let indent = open_brace_indent(&chars, code, start)?;
+        splice(code, line_start, start, &format!("{indent}}}"));
+        let at = line_start + indent.chars().count() + 1;
+        return Some((at, at));
+    }
+    None
+}
+
+// The indentation of the line of the innermost `{` still open at char
+// index `at`.
+fn open_brace_indent(chars: &[char], code: &str, at: usize) -> Option<String> {
+    let before = &code[..byte_at(code, at)];
+    let mut open = Vec::new();
+    for (byte, c) in code_chars(before) {
+        match c {
+            '{' => open.push(byte),
+            '}' => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    let brace = before[..*open.last()?].chars().count();
+    let line_start = chars[..brace]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |i| i + 1);
+    Some(
+        chars[line_start..line_start + indent_len(chars, line_start)]
+            .iter()
+            .collect(),
+    )
+}
+
+/// Indents every line `sel` touches by one level, or with `unindent` takes
+/// one level, or what there is of it, off each.
+pub fn indent_lines(code: &mut String, sel: Selection, unindent: bool) -> Selection {
+    let chars: Vec<char> = code.chars().collect();
+    let lines = selected_lines(&chars, sel);
+    let edits: Vec<(usize, usize, String)> = if unindent {
+        lines
+            .into_iter()
+            .filter_map(|s| {
+                let remove = if chars.get(s) == Some(&'\t') {
+                    1
+                } else {
+                    indent_len(&chars, s).min(INDENT.len())
+                };
+                (remove > 0).then(|| (s, remove, String::new()))
+            })
+            .collect()
+    } else {
+        lines
+            .into_iter()
+            .filter(|&s| !is_blank(&chars, s))
+            .map(|s| (s, 0, INDENT.to_string()))
+            .collect()
+    };
+    apply(code, &edits, sel)
+}
+
+/// Replaces `sel` with the spaces up to the next indentation stop.
+pub fn insert_indent(code: &mut String, sel: Selection) -> Selection {
+    let (start, end) = (sel.0.min(sel.1), sel.0.max(sel.1));
+    let chars: Vec<char> = code.chars().collect();
+    let col = start
+        - chars[..start]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+    let spaces = INDENT.len() - col % INDENT.len();
+    splice(code, start, end, &" ".repeat(spaces));
+    (start + spaces, start + spaces)
+}
+
+/// Comments out every line `sel` touches with `# `, at the indentation of
+/// the least indented one, or uncomments them when each already is.
+pub fn toggle_comment(code: &mut String, sel: Selection) -> Selection {
+    let chars: Vec<char> = code.chars().collect();
+    let lines = selected_lines(&chars, sel);
+    let mut filled: Vec<usize> = lines
+        .iter()
+        .copied()
+        .filter(|&s| !is_blank(&chars, s))
+        .collect();
+    if filled.is_empty() {
+        filled = lines;
+    }
+    let commented = filled
+        .iter()
+        .all(|&s| chars.get(s + indent_len(&chars, s)) == Some(&'#'));
+    let edits: Vec<(usize, usize, String)> = if commented {
+        filled
+            .iter()
+            .map(|&s| {
+                let at = s + indent_len(&chars, s);
+                let remove = if chars.get(at + 1) == Some(&' ') {
+                    2
+                } else {
+                    1
+                };
+                (at, remove, String::new())
+            })
+            .collect()
+    } else {
+        let indent = filled
+            .iter()
+            .map(|&s| indent_len(&chars, s))
+            .min()
+            .unwrap_or(0);
+        filled
+            .iter()
+            .map(|&s| (s + indent, 0, "# ".to_string()))
+            .collect()
+    };
+    apply(code, &edits, sel)
+}
+
+/// Indents the line a line break was just typed onto, at char index `at`,
+/// like the line above, one level deeper after an opening bracket. A closing
+/// bracket pushed onto the new line goes down to a line of its own, lined up
+/// with the line above. Returns where the cursor goes.
+pub fn indent_new_line(code: &mut String, at: usize) -> usize {
+    let chars: Vec<char> = code.chars().collect();
+    if at == 0 || chars.get(at - 1) != Some(&'\n') {
+        return at;
+    }
+    let above = chars[..at - 1]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |i| i + 1);
+    let indent: String = chars[above..above + indent_len(&chars, above)]
+        .iter()
+        .collect();
+    let opened = chars[above..at - 1]
+        .iter()
+        .rev()
+        .find(|c| !c.is_whitespace())
+        .is_some_and(|c| matches!(c, '{' | '(' | '['))
+        && in_code(&chars, at - 1);
+    if !opened {
+        splice(code, at, at, &indent);
+        return at + indent.chars().count();
+    }
+    let inner = format!("{indent}{INDENT}");
+    let closed = chars.get(at).is_some_and(|c| matches!(c, '}' | ')' | ']'));
+    if closed {
+        splice(code, at, at, &format!("{inner}\n{indent}"));
+    } else {
+        splice(code, at, at, &inner);
+    }
+    at + inner.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Types `c` at `at` the way the editor does, then lets the text edit
+    // insert it when `type_char` does not.
+    fn typed(code: &str, at: usize, c: char) -> (String, usize) {
+        let mut code = code.to_string();
+        match type_char(&mut code, (at, at), c) {
+            Some((_, cursor)) => (code, cursor),
+            None => {
+                splice(&mut code, at, at, &c.to_string());
+                (code, at + 1)
+            }
+        }
+    }
+
+    #[test]
+    fn brackets_pair_outside_strings_and_comments() {
+        let code = "f(a[1], \"(\") # {\n:list<:int> l;\nx < y;";
+        let pairs = bracket_pairs(code);
+        assert!(pairs.contains(&(1, 11)));
+        assert!(pairs.contains(&(3, 5)));
+        let lt = code.find('<').unwrap();
+        assert!(pairs.contains(&(lt, lt + 5)));
+        assert_eq!(pairs.len(), 3);
+
+        // Either side of a bracket finds its partner.
+        assert_eq!(matching_bracket(code, 1), Some((1, 11)));
+        assert_eq!(matching_bracket(code, 12), Some((11, 1)));
+        assert_eq!(matching_bracket(code, 8), None);
+    }
+
+    #[test]
+    fn a_comparison_does_not_swallow_a_closing_bracket() {
+        let code = "!if (a < b) { }";
+        assert_eq!(bracket_pairs(code), [(4, 10), (12, 14)]);
+    }
+
+    #[test]
+    fn opening_brackets_and_quotes_close_themselves() {
+        assert_eq!(typed("x = ;", 4, '('), ("x = ();".to_string(), 5));
+        assert_eq!(typed(":list", 5, '<'), (":list<>".to_string(), 6));
+        assert_eq!(typed("print()", 6, '"'), ("print(\"\")".to_string(), 7));
+        // Not before a word, nor a quote straight after one.
+        assert_eq!(typed("x = a;", 4, '('), ("x = (a;".to_string(), 5));
+        assert_eq!(typed("don", 3, '\''), ("don'".to_string(), 4));
+        // Nor in a comment.
+        assert_eq!(typed("# note ", 7, '('), ("# note (".to_string(), 8));
+    }
+
+    #[test]
+    fn a_closing_bracket_steps_over_the_one_already_there() {
+        assert_eq!(typed("f()", 2, ')'), ("f()".to_string(), 3));
+        assert_eq!(typed("\"\"", 1, '"'), ("\"\"".to_string(), 2));
+    }
+
+    #[test]
+    fn brackets_wrap_a_selection() {
+        let mut code = "x = a + b;".to_string();
+        let sel = type_char(&mut code, (4, 9), '(');
+        assert_eq!(code, "x = (a + b);");
+        assert_eq!(sel, Some((5, 10)));
+    }
+
+    #[test]
+    fn a_closing_brace_lines_up_with_its_opening_line() {
+        let code = "    !if (x) {\n        y;\n        ";
+        let (code, cursor) = typed(code, code.chars().count(), '}');
+        assert_eq!(code, "    !if (x) {\n        y;\n    }");
+        assert_eq!(cursor, code.chars().count());
+    }
+
+    #[test]
+    fn a_new_line_is_indented_after_an_opening_bracket() {
+        let mut code = "    !func f() -> :int {\n}".to_string();
+        let at = indent_new_line(&mut code, 24);
+        assert_eq!(code, "    !func f() -> :int {\n        \n    }");
+        assert_eq!(at, 32);
+
+        let mut code = "    x;\n".to_string();
+        assert_eq!(indent_new_line(&mut code, 7), 11);
+        assert_eq!(code, "    x;\n    ");
+    }
+}
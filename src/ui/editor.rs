@@ -1,15 +1,224 @@
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::parser::ParseNode;
+use crate::compiler::semanter::Symbol;
+use crate::ui::completion::{complete, CompletionItem, Completions, ItemKind};
+use crate::ui::diagnostics::{describe, severity_color};
+use crate::ui::editing::{
+    indent_lines, indent_new_line, insert_indent, matching_bracket, spans_lines, toggle_comment,
+    type_char,
+};
+use crate::ui::folding::{fold_regions, hidden_ranges, hide_in_job, shift_folds, FoldRegion};
 use crate::ui::highlighter::Highlighter;
+use crate::ui::hover::hover_text;
 use crate::ui::icons as ic;
 use crate::ui::theme::Theme;
 use eframe::egui;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct CodeEditor {
     theme: Theme,
+    // A line and column to move the cursor to and scroll into view on the
+    // next frame.
+    goto: Option<(usize, usize)>,
+    completion: Option<CompletionPopup>,
+    // Where the cursor was when the editor was last drawn.
+    cursor: Option<usize>,
+    // Where the code was Ctrl-clicked, until the app asks.
+    definition_click: Option<usize>,
+    // The blocks that can be folded and those that are, with the code they
+    // are of, to move them when it is edited.
+    regions: Vec<FoldRegion>,
+    folds: Vec<FoldRegion>,
+    folds_code: String,
+}
+
+// The width of the fold markers at the right of the gutter.
+const FOLD_MARKER_W: f32 = 16.0;
+
+struct CompletionPopup {
+    list: Completions,
+    selected: usize,
+    // The cursor the list was made for; moving it closes the popup.
+    cursor: usize,
+    // Where the popup was drawn, so that clicking it does not close it.
+    rect: egui::Rect,
+    scroll_to_selected: bool,
+}
+
+/// How much of a profiled run one line took, for the gutter heatmap.
+#[derive(Debug, Clone)]
+pub struct LineHeat {
+    /// From 0 for the coolest line to 1 for the hottest line of the run.
+    pub level: f32,
+    /// Shown when the line number is hovered.
+    pub label: String,
+}
+
+/// What the editor shows around the code of one tab, for
+/// [`CodeEditor::show_with_id`].
+#[derive(Clone, Copy)]
+pub struct EditorView<'a> {
+    pub tab_id: usize,
+    pub font_size: f32,
+    pub show_line_numbers: bool,
+    /// A char range to select, such as the current search match.
+    pub select_range: Option<(usize, usize)>,
+    /// The line a paused debug session is on.
+    pub debug_line: Option<usize>,
+    pub conditions: &'a BTreeMap<usize, String>,
+    pub heat: &'a BTreeMap<usize, LineHeat>,
+    pub diagnostics: &'a [Diagnostic],
+    pub symbols: &'a [Symbol],
+    /// The parse tree of the code as it is now, for folding.
+    pub program: Option<&'a ParseNode>,
 }
 
 impl CodeEditor {
     pub fn new(theme: Theme) -> Self {
-        Self { theme }
+        Self {
+            theme,
+            goto: None,
+            completion: None,
+            cursor: None,
+            definition_click: None,
+            regions: Vec::new(),
+            folds: Vec::new(),
+            folds_code: String::new(),
+        }
+    }
+
+    /// Puts the cursor at the start of `line` and scrolls to it the next
+    /// time the editor is drawn.
+    pub fn jump_to_line(&mut self, line: usize) {
+        self.jump_to(line, 0);
+    }
+
+    /// Like [`Self::jump_to_line`], with the cursor `col` chars into the line.
+    pub fn jump_to(&mut self, line: usize, col: usize) {
+        self.goto = Some((line, col));
+    }
+
+    /// The char index of the cursor, if the editor has one.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    /// Folds every block of the code that can be folded.
+    pub fn fold_all(&mut self) {
+        self.folds = self.regions.clone();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+
+    fn toggle_fold(&mut self, region: FoldRegion) {
+        if let Some(i) = self.folds.iter().position(|f| f.start == region.start) {
+            self.folds.remove(i);
+        } else {
+            self.folds.push(region);
+        }
+    }
+
+    // Unfolds whatever hides `line`.
+    fn reveal(&mut self, line: usize) {
+        self.folds.retain(|f| !f.hides(line));
+    }
+
+    // Moves the folds for the edits made to `code` since the last call, and
+    // drops those whose block is gone.
+    fn sync_folds(&mut self, code: &str) {
+        if self.folds_code == code {
+            return;
+        }
+        self.folds = shift_folds(&self.folds, &self.folds_code, code);
+        self.regions = shift_folds(&self.regions, &self.folds_code, code);
+        self.folds_code = code.to_string();
+        if !self.folds.is_empty() {
+            let blocks = fold_regions(code, None);
+            self.folds
+                .retain_mut(|f| match blocks.iter().find(|b| b.start == f.start) {
+                    Some(b) => {
+                        f.end = b.end;
+                        true
+                    }
+                    None => false,
+                });
+        }
+    }
+
+    /// The char index last Ctrl-clicked, to go to the definition of.
+    pub fn take_definition_click(&mut self) -> Option<usize> {
+        self.definition_click.take()
+    }
+
+    // Draws the completion popup at `pos` and returns the item clicked.
+    fn show_completion(
+        &mut self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        pos: egui::Pos2,
+        font_size: f32,
+    ) -> Option<usize> {
+        let theme = self.theme;
+        let popup = self.completion.as_mut()?;
+        let mut clicked = None;
+        let area = egui::Area::new(id.with("completion"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style())
+                    .fill(theme.menu_bg)
+                    .stroke(egui::Stroke::new(1.0, theme.border))
+                    .inner_margin(egui::Margin::same(4))
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(220.0)
+                            .show(ui, |ui| {
+                                for (i, item) in popup.list.items.iter().enumerate() {
+                                    let selected = i == popup.selected;
+                                    let resp =
+                                        completion_row(ui, item, selected, font_size, &theme);
+                                    if selected && popup.scroll_to_selected {
+                                        resp.scroll_to_me(None);
+                                    }
+                                    if resp.clicked() {
+                                        clicked = Some(i);
+                                    }
+                                }
+                            });
+                    });
+            });
+        popup.rect = area.response.rect;
+        popup.scroll_to_selected = false;
+        clicked
+    }
+
+    // Replaces the word being completed with item `index` of the popup.
+    fn accept_completion(
+        &mut self,
+        ctx: &egui::Context,
+        code: &mut String,
+        id: egui::Id,
+        index: usize,
+    ) {
+        use egui::TextBuffer as _;
+        let Some(popup) = self.completion.take() else {
+            return;
+        };
+        let Some(item) = popup.list.items.get(index) else {
+            return;
+        };
+        let start = popup.list.start;
+        code.delete_char_range(start..popup.cursor.min(code.chars().count()));
+        code.insert_text(&item.insert, start);
+        let at = egui::text::CCursor::new(start + item.insert.chars().count());
+        let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(at)));
+        state.store(ctx, id);
+        ctx.memory_mut(|m| m.request_focus(id));
     }
 
     pub fn update_theme(&mut self, theme: Theme) {
@@ -20,12 +229,21 @@ impl CodeEditor {
         &mut self,
         ui: &mut egui::Ui,
         code: &mut String,
-        tab_id: usize,
-        font_size: f32,
-        show_line_numbers: bool,
-        select_range: Option<(usize, usize)>,
-        debug_line: Option<usize>,
-    ) {
+        breakpoints: &mut BTreeSet<usize>,
+        view: &EditorView,
+    ) -> Option<usize> {
+        let EditorView {
+            tab_id,
+            font_size,
+            show_line_numbers,
+            select_range,
+            debug_line,
+            conditions,
+            heat,
+            diagnostics,
+            symbols,
+            program,
+        } = *view;
         ui.painter().rect_filled(
             ui.available_rect_before_wrap(),
             egui::CornerRadius::ZERO,
@@ -35,26 +253,97 @@ impl CodeEditor {
         let line_count = code.lines().count().max(1);
         let width_chars = line_count.to_string().len();
         let line_num_width = if show_line_numbers {
-            (width_chars as f32 * 9.0 + 24.0).max(44.0)
+            (width_chars as f32 * 9.0 + 38.0).max(58.0) + FOLD_MARKER_W
         } else {
             0.0
         };
 
         let text_edit_id = egui::Id::new("code_editor").with(tab_id);
 
+        // The completion popup takes the keys it needs before the text edit
+        // sees them.
+        let mut explicit_completion = false;
+        let mut accept = None;
+        let mut dismiss = false;
+        if ui.memory(|m| m.has_focus(text_edit_id)) {
+            ui.input_mut(|i| {
+                explicit_completion = i.consume_key(egui::Modifiers::COMMAND, egui::Key::Space);
+                let Some(popup) = self.completion.as_mut() else {
+                    return;
+                };
+                let n = popup.list.items.len();
+                if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                    popup.selected = (popup.selected + 1) % n;
+                    popup.scroll_to_selected = true;
+                } else if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                    popup.selected = (popup.selected + n - 1) % n;
+                    popup.scroll_to_selected = true;
+                } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)
+                    || i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)
+                {
+                    accept = Some(popup.selected);
+                } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
+                    dismiss = true;
+                }
+            });
+        }
+        if ui.memory(|m| m.has_focus(text_edit_id)) {
+            edit_keys(ui, code, text_edit_id);
+        }
+        if dismiss {
+            self.completion = None;
+        }
+        if let Some(index) = accept {
+            self.accept_completion(ui.ctx(), code, text_edit_id, index);
+        }
+
+        // The regions come from the parse when it is of this code, and from
+        // the braces otherwise. Whatever is about to be shown is unfolded.
+        self.sync_folds(code);
+        self.regions = fold_regions(code, program);
+        let goto = self.goto.take();
+        let mut shown = Vec::new();
+        shown.extend(goto.map(|(line, _)| line));
+        shown.extend(debug_line);
+        if let Some((byte_start, _)) = select_range {
+            shown.extend(code.get(..byte_start).map(|s| s.matches('\n').count() + 1));
+        }
+        for line in shown {
+            self.reveal(line);
+        }
+
         let theme = self.theme;
         let highlighter = Highlighter::new(theme);
+        // Folded lines are laid out with no height. An edit made this frame
+        // moves the folds the same way `sync_folds` will.
+        let fold_base = (!self.folds.is_empty()).then(|| (self.folds.clone(), code.clone()));
         let mut layouter = move |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
             let font_id = egui::FontId::monospace(font_size);
             let mut job = highlighter.highlight_to_layout_job(text.as_str(), font_id);
             job.wrap.max_width = wrap_width;
+            if let Some((folds, base)) = &fold_base {
+                let hidden = if text.as_str() == base {
+                    hidden_ranges(base, folds)
+                } else {
+                    hidden_ranges(text.as_str(), &shift_folds(folds, base, text.as_str()))
+                };
+                hide_in_job(&mut job, &hidden);
+            }
             ui.fonts_mut(|f| f.layout_job(job))
         };
 
+        // Only problems in this file; those in its imports are listed in the
+        // problems panel.
+        let diagnostics: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|d| d.file.is_empty() && d.line > 0 && d.line <= line_count)
+            .collect();
+
         if select_range.is_some() || debug_line.is_some() {
             ui.ctx().request_repaint();
         }
 
+        let mut edit_condition = None;
         egui::ScrollArea::both()
             .auto_shrink([false, false])
             .show(ui, |ui| {
@@ -76,6 +365,98 @@ impl CodeEditor {
                         .layouter(&mut layouter);
 
                     let mut output = text_edit.show(ui);
+                    self.sync_folds(code);
+
+                    // The cursor never rests on a folded line: moving onto one
+                    // goes on past the fold, or back to its first line when
+                    // moving up.
+                    if let Some(mut range) = output.cursor_range {
+                        let at = range.primary.index;
+                        let (line, col) = line_col(code, at);
+                        let hiding = self.folds.iter().filter(|f| f.hides(line));
+                        let target = if self.cursor.is_some_and(|c| c > at) {
+                            hiding.map(|f| f.start).min()
+                        } else {
+                            hiding.map(|f| f.end).max()
+                        };
+                        if let Some(target) = target {
+                            range.primary = egui::text::CCursor::new(char_index(code, target, col));
+                            output.state.cursor.set_char_range(Some(range));
+                            output.state.clone().store(ui.ctx(), output.response.id);
+                            output.cursor_range = Some(range);
+                        }
+                    }
+                    let folds = self.folds.clone();
+                    let regions = self.regions.clone();
+                    let is_hidden = |line: usize| folds.iter().any(|f| f.hides(line));
+
+                    let cursor = output.cursor_range.map(|r| r.primary.index);
+                    self.cursor = cursor;
+
+                    // Hovering a name describes it. With Ctrl it is underlined
+                    // instead, and a click goes to where it is declared.
+                    let hovered_word = output.response.hover_pos().and_then(|pos| {
+                        let local = pos - output.galley_pos;
+                        let at = output.galley.cursor_from_pos(local);
+                        let (start, end) = word_span(code, at.index)?;
+                        let a = output
+                            .galley
+                            .pos_from_cursor(egui::text::CCursor::new(start));
+                        let b = output.galley.pos_from_cursor(egui::text::CCursor::new(end));
+                        let over = (a.min.x..=b.min.x).contains(&local.x)
+                            && (a.min.y..=a.max.y).contains(&local.y);
+                        over.then_some((start, a, b))
+                    });
+                    let mut symbol_tip = None;
+                    if let Some((start, a, b)) = hovered_word {
+                        if ui.input(|i| i.modifiers.command) {
+                            let y = output.galley_pos.y + a.max.y - 1.0;
+                            ui.painter().hline(
+                                output.galley_pos.x + a.min.x..=output.galley_pos.x + b.min.x,
+                                y,
+                                egui::Stroke::new(1.0, theme.accent),
+                            );
+                            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+                            if output.response.clicked() {
+                                self.definition_click = Some(start);
+                            }
+                        } else {
+                            symbol_tip = hover_text(code, start, symbols);
+                        }
+                    }
+                    if output.response.changed() || explicit_completion {
+                        self.completion = cursor.and_then(|c| {
+                            Some(CompletionPopup {
+                                list: complete(code, c, symbols, explicit_completion)?,
+                                selected: 0,
+                                cursor: c,
+                                rect: egui::Rect::NOTHING,
+                                scroll_to_selected: false,
+                            })
+                        });
+                    } else if let Some(popup) = &self.completion {
+                        let on_popup = ui
+                            .ctx()
+                            .pointer_hover_pos()
+                            .is_some_and(|p| popup.rect.contains(p));
+                        if cursor != Some(popup.cursor)
+                            || (!output.response.has_focus() && !on_popup)
+                        {
+                            self.completion = None;
+                        }
+                    }
+
+                    if let Some(popup) = &self.completion {
+                        let anchor = output
+                            .galley
+                            .pos_from_cursor(egui::text::CCursor::new(popup.list.start));
+                        let pos = output.galley_pos + anchor.left_bottom().to_vec2();
+                        if let Some(index) =
+                            self.show_completion(ui.ctx(), text_edit_id, pos, font_size)
+                        {
+                            self.accept_completion(ui.ctx(), code, text_edit_id, index);
+                        }
+                    }
 
                     if show_line_numbers {
                         let gpos = output.galley_pos;
@@ -93,11 +474,74 @@ impl CodeEditor {
                             theme.line_numbers_bg,
                         );
 
+                        let gutter_resp = ui.interact(
+                            gutter_rect,
+                            text_edit_id.with("gutter"),
+                            egui::Sense::click(),
+                        );
+                        if gutter_resp.hovered() {
+                            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+                        }
+                        let click_pos = gutter_resp
+                            .interact_pointer_pos()
+                            .filter(|_| gutter_resp.clicked() || gutter_resp.secondary_clicked());
+                        let clicked_line = click_pos
+                            .and_then(|pos| {
+                                rows.iter().position(|row| {
+                                    let rr = row.rect();
+                                    pos.y >= gpos.y + rr.min.y && pos.y < gpos.y + rr.max.y
+                                })
+                            })
+                            .filter(|&i| i < line_count)
+                            .map(|i| i + 1);
+                        let on_markers =
+                            click_pos.is_some_and(|p| p.x >= gutter_rect.max.x - FOLD_MARKER_W);
+                        let clicked_region = clicked_line
+                            .filter(|_| on_markers && gutter_resp.clicked())
+                            .and_then(|line| regions.iter().find(|r| r.start == line));
+                        if let Some(region) = clicked_region {
+                            self.toggle_fold(*region);
+                            ui.ctx().request_repaint();
+                        } else if let Some(line) = clicked_line {
+                            if gutter_resp.secondary_clicked() {
+                                edit_condition = Some(line);
+                            } else if !breakpoints.remove(&line) {
+                                breakpoints.insert(line);
+                            }
+                        }
+                        let hovered_line = gutter_resp
+                            .hover_pos()
+                            .and_then(|pos| {
+                                rows.iter().position(|row| {
+                                    let rr = row.rect();
+                                    pos.y >= gpos.y + rr.min.y && pos.y < gpos.y + rr.max.y
+                                })
+                            })
+                            .map(|i| i + 1);
+                        if let Some(line) = hovered_line {
+                            let mut tip: Vec<String> = diagnostics
+                                .iter()
+                                .filter(|d| d.line == line)
+                                .map(|d| describe(d))
+                                .collect();
+                            if let Some(h) = heat.get(&line) {
+                                tip.push(h.label.clone());
+                            }
+                            if !tip.is_empty() {
+                                gutter_resp
+                                    .clone()
+                                    .on_hover_text_at_pointer(tip.join("\n\n"));
+                            }
+                        }
+
                         for (i, row) in rows.iter().enumerate() {
                             let line_num = i + 1;
                             if line_num > line_count {
                                 break;
                             }
+                            if is_hidden(line_num) {
+                                continue;
+                            }
 
                             let row_rect = row.rect();
 
@@ -108,6 +552,52 @@ impl CodeEditor {
                             let is_debug_line =
                                 debug_line.map(|d| d > 0 && d == line_num).unwrap_or(false);
 
+                            if let Some(h) = heat.get(&line_num) {
+                                let cell = egui::Rect::from_min_max(
+                                    egui::pos2(gutter_rect.min.x, row_top),
+                                    egui::pos2(gutter_rect.max.x, row_bottom),
+                                );
+                                let alpha = (30.0 + h.level.clamp(0.0, 1.0) * 150.0) as u8;
+                                painter.rect_filled(
+                                    cell,
+                                    egui::CornerRadius::ZERO,
+                                    egui::Color32::from_rgba_unmultiplied(
+                                        theme.terminal_error.r(),
+                                        theme.terminal_error.g(),
+                                        theme.terminal_error.b(),
+                                        alpha,
+                                    ),
+                                );
+                            }
+
+                            if breakpoints.contains(&line_num) {
+                                let center = egui::pos2(gutter_rect.min.x + 10.0, row_mid_y);
+                                let radius = (font_size * 0.3).max(3.0);
+                                painter.circle_filled(center, radius, theme.terminal_error);
+                                if conditions.contains_key(&line_num) {
+                                    painter.circle_filled(
+                                        center,
+                                        radius * 0.45,
+                                        theme.terminal_warning,
+                                    );
+                                }
+                            }
+
+                            let worst = diagnostics
+                                .iter()
+                                .filter(|d| d.line == line_num)
+                                .map(|d| d.severity)
+                                .min();
+                            if let Some(severity) = worst {
+                                painter.text(
+                                    egui::pos2(gutter_rect.min.x + 24.0, row_mid_y),
+                                    egui::Align2::CENTER_CENTER,
+                                    ic::ERROR,
+                                    egui::FontId::proportional(font_size - 2.0),
+                                    severity_color(severity, &theme),
+                                );
+                            }
+
                             if is_debug_line {
                                 let cell = egui::Rect::from_min_max(
                                     egui::pos2(gutter_rect.min.x, row_top),
@@ -131,7 +621,7 @@ impl CodeEditor {
                                     theme.accent,
                                 );
                                 painter.text(
-                                    egui::pos2(gutter_rect.max.x - 8.0, row_mid_y),
+                                    egui::pos2(gutter_rect.max.x - FOLD_MARKER_W - 4.0, row_mid_y),
                                     egui::Align2::RIGHT_CENTER,
                                     format!("{:>width$}", line_num, width = width_chars),
                                     egui::FontId::monospace(font_size - 1.0),
@@ -139,13 +629,170 @@ impl CodeEditor {
                                 );
                             } else {
                                 painter.text(
-                                    egui::pos2(gutter_rect.max.x - 8.0, row_mid_y),
+                                    egui::pos2(gutter_rect.max.x - FOLD_MARKER_W - 4.0, row_mid_y),
                                     egui::Align2::RIGHT_CENTER,
                                     format!("{:>width$}", line_num, width = width_chars),
                                     egui::FontId::monospace(font_size - 1.0),
                                     theme.line_numbers_fg,
                                 );
                             }
+
+                            // Open blocks show their marker while the gutter is
+                            // hovered, folded ones always.
+                            if regions.iter().any(|r| r.start == line_num) {
+                                let folded = folds.iter().any(|f| f.start == line_num);
+                                if folded || gutter_resp.hovered() {
+                                    painter.text(
+                                        egui::pos2(
+                                            gutter_rect.max.x - FOLD_MARKER_W * 0.5 - 2.0,
+                                            row_mid_y,
+                                        ),
+                                        egui::Align2::CENTER_CENTER,
+                                        if folded {
+                                            ic::CARET_RIGHT
+                                        } else {
+                                            ic::CARET_DOWN
+                                        },
+                                        egui::FontId::proportional(font_size - 3.0),
+                                        theme.line_numbers_fg,
+                                    );
+                                }
+                            }
+                        }
+                    }
+
+                    // A folded block shows `⋯` after its first line, which
+                    // unfolds it when clicked.
+                    for fold in &folds {
+                        if is_hidden(fold.start) {
+                            continue;
+                        }
+                        let Some(row) = output.galley.rows.get(fold.start - 1) else {
+                            continue;
+                        };
+                        let rr = row.rect().translate(output.galley_pos.to_vec2());
+                        let marker = egui::Rect::from_min_size(
+                            egui::pos2(rr.right() + 6.0, rr.top() + 1.0),
+                            egui::vec2(font_size * 1.6, rr.height() - 2.0),
+                        );
+                        let resp = ui.interact(
+                            marker,
+                            text_edit_id.with(("fold", fold.start)),
+                            egui::Sense::click(),
+                        );
+                        ui.painter().rect_filled(
+                            marker,
+                            egui::CornerRadius::same(3),
+                            if resp.hovered() {
+                                theme.button_hover_bg
+                            } else {
+                                theme.button_bg
+                            },
+                        );
+                        ui.painter().text(
+                            marker.center(),
+                            egui::Align2::CENTER_CENTER,
+                            "⋯",
+                            egui::FontId::monospace(font_size - 1.0),
+                            theme.comment,
+                        );
+                        let hidden = fold.end - fold.start - 1;
+                        let resp = resp.on_hover_text(format!(
+                            "{hidden} folded {}",
+                            if hidden == 1 { "line" } else { "lines" }
+                        ));
+                        if resp.hovered() {
+                            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+                        }
+                        if resp.clicked() {
+                            self.toggle_fold(*fold);
+                        }
+                    }
+
+                    // The bracket by the cursor and its partner get a box.
+                    let bracket = cursor
+                        .filter(|_| output.cursor_range.is_some_and(|r| r.is_empty()))
+                        .and_then(|c| matching_bracket(code, c));
+                    if let Some((a, b)) = bracket {
+                        for at in [a, b] {
+                            let from = output.galley.pos_from_cursor(egui::text::CCursor::new(at));
+                            let to = output
+                                .galley
+                                .pos_from_cursor(egui::text::CCursor::new(at + 1));
+                            if from.height() < 1.0 || to.min.y != from.min.y {
+                                continue;
+                            }
+                            let rect = egui::Rect::from_min_max(
+                                from.min,
+                                egui::pos2(to.min.x, from.max.y),
+                            )
+                            .translate(output.galley_pos.to_vec2());
+                            ui.painter().rect_stroke(
+                                rect,
+                                egui::CornerRadius::same(2),
+                                egui::Stroke::new(1.0, theme.line_numbers_fg),
+                                egui::StrokeKind::Inside,
+                            );
+                        }
+                    }
+
+                    let mut hovered_problems = Vec::new();
+                    let hover_pos = output.response.hover_pos();
+                    for d in &diagnostics {
+                        let Some(row) = output.galley.rows.get(d.line - 1) else {
+                            continue;
+                        };
+                        if is_hidden(d.line) {
+                            continue;
+                        }
+                        let text = code.lines().nth(d.line - 1).unwrap_or("");
+                        let (start, len) = if d.col > 0 {
+                            (d.col - 1, d.span_len(text))
+                        } else {
+                            let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+                            (indent, text.chars().count().saturating_sub(indent))
+                        };
+                        let origin = output.galley_pos + row.pos.to_vec2();
+                        let x0 = origin.x + row.x_offset(start);
+                        let x1 = (origin.x + row.x_offset(start + len)).max(x0 + 8.0);
+                        let area = egui::Rect::from_min_max(
+                            egui::pos2(x0, origin.y),
+                            egui::pos2(x1, origin.y + row.height()),
+                        );
+                        squiggle(
+                            ui.painter(),
+                            x0,
+                            x1,
+                            area.max.y - 1.5,
+                            severity_color(d.severity, &theme),
+                        );
+                        if hover_pos.is_some_and(|p| area.contains(p)) {
+                            hovered_problems.push(describe(d));
+                        }
+                    }
+                    hovered_problems.extend(symbol_tip);
+                    if !hovered_problems.is_empty() {
+                        output
+                            .response
+                            .clone()
+                            .on_hover_text_at_pointer(hovered_problems.join("\n\n"));
+                    }
+
+                    if let Some((line, col)) = goto {
+                        let line = line.clamp(1, line_count);
+                        let ccursor = egui::text::CCursor::new(char_index(code, line, col));
+                        output
+                            .state
+                            .cursor
+                            .set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
+                        output.state.clone().store(ui.ctx(), output.response.id);
+                        output.response.request_focus();
+                        if let Some(row) = output.galley.rows.get(line - 1) {
+                            let rect = row.rect().translate(output.galley_pos.to_vec2());
+                            ui.scroll_to_rect(
+                                rect.expand2(egui::vec2(0.0, font_size * 3.0)),
+                                Some(egui::Align::Center),
+                            );
                         }
                     }
 
@@ -252,11 +899,8 @@ impl CodeEditor {
                     {
                         if let Some(cursor_range) = output.cursor_range {
                             let pos = cursor_range.primary.index;
-                            let indent = indent_for_line_above(code, pos);
-                            if !indent.is_empty() {
-                                use egui::TextBuffer as _;
-                                code.insert_text(&indent, pos);
-                                let new_pos = pos + indent.chars().count();
+                            let new_pos = indent_new_line(code, pos);
+                            if new_pos != pos {
                                 let new_ccursor = egui::text::CCursor::new(new_pos);
                                 output.state.cursor.set_char_range(Some(
                                     egui::text::CCursorRange::one(new_ccursor),
@@ -267,9 +911,109 @@ impl CodeEditor {
                     }
                 });
             });
+        edit_condition
     }
 }
 
+// One entry of the completion popup: its kind, its name and its signature.
+fn completion_row(
+    ui: &mut egui::Ui,
+    item: &CompletionItem,
+    selected: bool,
+    font_size: f32,
+    theme: &Theme,
+) -> egui::Response {
+    let (tag, tag_color) = match item.kind {
+        ItemKind::Keyword => ("kw", theme.keyword),
+        ItemKind::Type => ("type", theme.type_name),
+        ItemKind::Builtin | ItemKind::Function => ("fn", theme.fn_name),
+        ItemKind::Variable => ("var", theme.identifier),
+        ItemKind::Constant => ("const", theme.number),
+        ItemKind::Field => ("field", theme.identifier),
+        ItemKind::Struct => ("struct", theme.struct_name),
+        ItemKind::Module => ("mod", theme.struct_name),
+    };
+    let font = egui::FontId::monospace((font_size - 2.0).max(9.0));
+    let (rect, resp) =
+        ui.allocate_exact_size(egui::vec2(440.0, font_size + 6.0), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    if selected || resp.hovered() {
+        painter.rect_filled(rect, egui::CornerRadius::same(3), theme.menu_hover_bg);
+    }
+    painter.text(
+        egui::pos2(rect.left() + 6.0, rect.center().y),
+        egui::Align2::LEFT_CENTER,
+        tag,
+        egui::FontId::proportional(10.0),
+        tag_color,
+    );
+    let label = painter.text(
+        egui::pos2(rect.left() + 46.0, rect.center().y),
+        egui::Align2::LEFT_CENTER,
+        &item.label,
+        font.clone(),
+        theme.menu_fg,
+    );
+    painter
+        .with_clip_rect(rect.with_min_x(label.right() + 12.0))
+        .text(
+            egui::pos2(rect.right() - 6.0, rect.center().y),
+            egui::Align2::RIGHT_CENTER,
+            &item.detail,
+            font,
+            theme.line_numbers_fg,
+        );
+    resp
+}
+
+// A wavy underline from `x0` to `x1` along `y`.
+fn squiggle(painter: &egui::Painter, x0: f32, x1: f32, y: f32, color: egui::Color32) {
+    const STEP: f32 = 2.0;
+    let mut points = Vec::new();
+    let mut x = x0;
+    let mut up = true;
+    while x < x1 {
+        points.push(egui::pos2(x, if up { y - 1.5 } else { y + 1.0 }));
+        x += STEP;
+        up = !up;
+    }
+    points.push(egui::pos2(x1, y));
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+}
+
+// The identifier at char index `at` of `code`, as a char range.
+fn word_span(code: &str, at: usize) -> Option<(usize, usize)> {
+    let is_ident = |c: &char| c.is_alphanumeric() || *c == '_';
+    let before = code.chars().take(at).collect::<Vec<_>>();
+    let start = at - before.iter().rev().take_while(|c| is_ident(c)).count();
+    let end = at + code.chars().skip(at).take_while(is_ident).count();
+    (start < end).then_some((start, end))
+}
+
+// The line and char column of char index `at` of `code`.
+fn line_col(code: &str, at: usize) -> (usize, usize) {
+    let before: Vec<char> = code.chars().take(at).collect();
+    let line_start = before.iter().rposition(|&c| c == '\n').map_or(0, |p| p + 1);
+    (
+        before.iter().filter(|&&c| c == '\n').count() + 1,
+        before.len() - line_start,
+    )
+}
+
+// The char index of column `col` of `line`, or of the line's end when it is
+// shorter.
+fn char_index(code: &str, line: usize, col: usize) -> usize {
+    let line_len = code
+        .split('\n')
+        .nth(line - 1)
+        .map_or(0, |l| l.chars().count());
+    code.split('\n')
+        .take(line - 1)
+        .map(|l| l.chars().count() + 1)
+        .sum::<usize>()
+        + col.min(line_len)
+}
+
 fn byte_offset_to_char_index(s: &str, byte_offset: usize) -> usize {
     let clamped = byte_offset.min(s.len());
     s[..clamped].chars().count()
@@ -362,19 +1106,72 @@ pub fn show_empty_state(ui: &mut egui::Ui, t: &Theme, full_rect: egui::Rect) ->
     action
 }
 
-fn indent_for_line_above(text: &str, cursor_pos: usize) -> String {
-    if cursor_pos == 0 {
-        return String::new();
+// Typing brackets, quotes and `}`, Tab, Shift-Tab and Ctrl+/ edit the code
+// here rather than in the text edit. Only the keys before the first one left
+// to the text edit are taken, so that the edits keep their order.
+fn edit_keys(ui: &egui::Ui, code: &mut String, id: egui::Id) {
+    let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), id) else {
+        return;
+    };
+    let Some(range) = state.cursor.char_range() else {
+        return;
+    };
+    let mut sel = (range.secondary.index, range.primary.index);
+    let mut handled = Vec::new();
+    ui.input_mut(|i| {
+        for (n, event) in i.events.iter().enumerate() {
+            let edit = match event {
+                egui::Event::Text(text) => {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => type_char(code, sel, c),
+                        _ => None,
+                    }
+                }
+                egui::Event::Key {
+                    key: egui::Key::Tab,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some(if modifiers.shift {
+                    indent_lines(code, sel, true)
+                } else if spans_lines(code, sel) {
+                    indent_lines(code, sel, false)
+                } else {
+                    insert_indent(code, sel)
+                }),
+                egui::Event::Key {
+                    key: egui::Key::Slash,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if modifiers.command => Some(toggle_comment(code, sel)),
+                egui::Event::Key { pressed: true, .. }
+                | egui::Event::Paste(_)
+                | egui::Event::Cut
+                | egui::Event::Ime(_) => None,
+                _ => continue,
+            };
+            match edit {
+                Some(new_sel) => {
+                    sel = new_sel;
+                    handled.push(n);
+                }
+                None => break,
+            }
+        }
+        let mut n = 0;
+        i.events.retain(|_| {
+            n += 1;
+            !handled.contains(&(n - 1))
+        });
+    });
+    if !handled.is_empty() {
+        state.cursor.set_char_range(Some(egui::text::CCursorRange {
+            primary: egui::text::CCursor::new(sel.1),
+            secondary: egui::text::CCursor::new(sel.0),
+            h_pos: None,
+        }));
+        state.store(ui.ctx(), id);
     }
-    let chars: Vec<char> = text.chars().collect();
-    let prev_end = cursor_pos - 1;
-    let prev_start = chars[..prev_end]
-        .iter()
-        .rposition(|&c| c == '\n')
-        .map(|p| p + 1)
-        .unwrap_or(0);
-    chars[prev_start..prev_end]
-        .iter()
-        .take_while(|&&c| c == ' ' || c == '\t')
-        .collect()
 }
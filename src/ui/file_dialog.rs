@@ -7,6 +7,8 @@ use std::path::PathBuf;
 pub enum FileDialogMode {
     Open,
     Save,
+    /// Picks a directory: the one selected, or else the one shown.
+    Folder,
 }
 
 pub struct FileDialog {
@@ -65,6 +67,16 @@ impl FileDialog {
         self.needs_focus = true;
     }
 
+    pub fn open_for_folder(&mut self) {
+        self.mode = FileDialogMode::Folder;
+        self.selected = None;
+        self.filename_input.clear();
+        self.refresh_entries();
+        self.visible = true;
+        self.result = None;
+        self.needs_focus = false;
+    }
+
     pub fn open_for_save(&mut self, suggested_name: &str) {
         self.open_for_save_in(suggested_name, None);
     }
@@ -139,6 +151,7 @@ impl FileDialog {
         let title = match self.mode {
             FileDialogMode::Open => "Open File",
             FileDialogMode::Save => "Save File",
+            FileDialogMode::Folder => "Open Folder",
         };
 
         let mut close_requested = false;
@@ -418,24 +431,44 @@ impl FileDialog {
 
                         ui.add_space(10.0);
 
-                        ui.horizontal(|ui| {
-                            ui.label(
-                                egui::RichText::new("Name")
-                                    .size(12.5)
-                                    .color(t.tab_inactive_fg),
-                            );
-                            let te = ui.add(
-                                egui::TextEdit::singleline(&mut self.filename_input)
-                                    .font(egui::FontId::proportional(13.0))
-                                    .text_color(t.menu_fg)
-                                    .desired_width(f32::INFINITY),
-                            );
+                        if self.mode == FileDialogMode::Folder {
+                            let folder = self
+                                .selected
+                                .as_ref()
+                                .filter(|p| p.is_dir())
+                                .unwrap_or(&self.current_dir);
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("Folder")
+                                        .size(12.5)
+                                        .color(t.tab_inactive_fg),
+                                );
+                                ui.label(
+                                    egui::RichText::new(folder.to_string_lossy())
+                                        .size(13.0)
+                                        .color(t.menu_fg),
+                                );
+                            });
+                        } else {
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("Name")
+                                        .size(12.5)
+                                        .color(t.tab_inactive_fg),
+                                );
+                                let te = ui.add(
+                                    egui::TextEdit::singleline(&mut self.filename_input)
+                                        .font(egui::FontId::proportional(13.0))
+                                        .text_color(t.menu_fg)
+                                        .desired_width(f32::INFINITY),
+                                );
 
-                            if self.needs_focus {
-                                te.request_focus();
-                                self.needs_focus = false;
-                            }
-                        });
+                                if self.needs_focus {
+                                    te.request_focus();
+                                    self.needs_focus = false;
+                                }
+                            });
+                        }
 
                         ui.add_space(12.0);
 
@@ -459,7 +492,7 @@ impl FileDialog {
                             ui.add_space(6.0);
 
                             let confirm_label = match self.mode {
-                                FileDialogMode::Open => "Open",
+                                FileDialogMode::Open | FileDialogMode::Folder => "Open",
                                 FileDialogMode::Save => "Save",
                             };
                             let can_confirm = match self.mode {
@@ -472,6 +505,9 @@ impl FileDialog {
                                                 .is_file())
                                 }
                                 FileDialogMode::Save => !self.filename_input.is_empty(),
+                                FileDialogMode::Folder => {
+                                    self.selected.as_ref().is_none_or(|p| p.is_dir())
+                                }
                             };
                             if ui
                                 .add_enabled(
@@ -487,7 +523,11 @@ impl FileDialog {
                                 )
                                 .clicked()
                             {
-                                let path = if self.mode == FileDialogMode::Open {
+                                let path = if self.mode == FileDialogMode::Folder {
+                                    self.selected
+                                        .clone()
+                                        .unwrap_or_else(|| self.current_dir.clone())
+                                } else if self.mode == FileDialogMode::Open {
                                     self.selected.clone().unwrap_or_else(|| {
                                         self.current_dir.join(&self.filename_input)
                                     })
@@ -0,0 +1,748 @@
+use super::icons as ic;
+use super::search_bar::{option_toggle, Matcher, SearchOptions};
+use super::tab::Tab;
+use super::theme::Theme;
+use super::workspace::source_files;
+use eframe::egui;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Past this many matches the search stops, so that a one-letter query in a
+// big folder does not stall the editor.
+const MAX_MATCHES: usize = 2000;
+
+/// A line with at least one match.
+pub struct LineMatch {
+    pub line: usize,
+    pub text: String,
+    /// Byte ranges of the matches within `text`.
+    pub ranges: Vec<(usize, usize)>,
+}
+
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub lines: Vec<LineMatch>,
+    pub count: usize,
+}
+
+/// The new text of a file after a replace in files.
+pub struct FileChange {
+    pub path: PathBuf,
+    /// The text the replace was made on, so that it is not applied over
+    /// edits made since.
+    pub before: String,
+    pub after: String,
+    pub count: usize,
+}
+
+pub enum FindAction {
+    None,
+    /// Open the file and put the cursor at this line and char column.
+    Open(PathBuf, usize, usize),
+    Apply(Vec<FileChange>),
+}
+
+/// The files a search in files looks at: the `.fr` files under `root` and
+/// the files of the open tabs, with the text of the tab for a file that is
+/// open, saved or not.
+pub fn search_sources(root: Option<&Path>, tabs: &[Tab]) -> Vec<(PathBuf, String)> {
+    let open: Vec<(PathBuf, &PathBuf, &Tab)> = tabs
+        .iter()
+        .filter_map(|tab| {
+            let path = tab.current_file.as_ref()?;
+            Some((canonical(path), path, tab))
+        })
+        .collect();
+    let mut seen = Vec::new();
+    let mut sources = Vec::new();
+    for path in root.map(source_files).unwrap_or_default() {
+        let key = canonical(&path);
+        match open.iter().find(|(k, _, _)| *k == key) {
+            Some((_, path, tab)) => sources.push(((*path).clone(), tab.code.clone())),
+            None => match fs::read_to_string(&path) {
+                Ok(text) => sources.push((path, text)),
+                Err(_) => continue,
+            },
+        }
+        seen.push(key);
+    }
+    for (key, path, tab) in open {
+        if !seen.contains(&key) {
+            sources.push((path.clone(), tab.code.clone()));
+        }
+    }
+    sources
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Every match of `matcher` in `sources`, by file and line, and whether the
+/// search stopped at [`MAX_MATCHES`].
+pub fn search(sources: &[(PathBuf, String)], matcher: &Matcher) -> (Vec<FileMatches>, bool) {
+    let mut results = Vec::new();
+    let mut total = 0;
+    for (path, text) in sources {
+        let mut lines: Vec<LineMatch> = Vec::new();
+        let mut count = 0;
+        for (start, end) in matcher.find_all(text) {
+            if total == MAX_MATCHES {
+                break;
+            }
+            total += 1;
+            count += 1;
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+            let range = (start - line_start, end.min(line_end) - line_start);
+            match lines.last_mut() {
+                Some(last) if last.line == line_number(text, line_start) => last.ranges.push(range),
+                _ => lines.push(LineMatch {
+                    line: line_number(text, line_start),
+                    text: text[line_start..line_end].to_string(),
+                    ranges: vec![range],
+                }),
+            }
+        }
+        if count > 0 {
+            results.push(FileMatches {
+                path: path.clone(),
+                lines,
+                count,
+            });
+        }
+    }
+    (results, total == MAX_MATCHES)
+}
+
+fn line_number(text: &str, at: usize) -> usize {
+    text[..at].matches('\n').count() + 1
+}
+
+/// A run of changed lines: the line it starts at, and its lines before and
+/// after the replace.
+pub struct Hunk {
+    pub line: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+/// The lines that differ between `before` and `after`. A replace that keeps
+/// the line count gives a hunk per changed line; one that adds or removes
+/// lines gives a single hunk from the first change to the last.
+pub fn changed_lines(before: &str, after: &str) -> Vec<Hunk> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    if old.len() == new.len() {
+        return old
+            .iter()
+            .zip(&new)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| Hunk {
+                line: i + 1,
+                old: vec![a.to_string()],
+                new: vec![b.to_string()],
+            })
+            .collect();
+    }
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    vec![Hunk {
+        line: prefix + 1,
+        old: old[prefix..old.len() - suffix]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        new: new[prefix..new.len() - suffix]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    }]
+}
+
+struct Preview {
+    change: FileChange,
+    hunks: Vec<Hunk>,
+    include: bool,
+}
+
+#[derive(Default)]
+pub struct FindInFilesWindow {
+    pub open: bool,
+    query: String,
+    replacement: String,
+    options: SearchOptions,
+    results: Vec<FileMatches>,
+    truncated: bool,
+    error: Option<String>,
+    // The query and options `results` are for; `None` searches again.
+    searched: Option<(String, SearchOptions)>,
+    preview: Option<Vec<Preview>>,
+    focus: bool,
+}
+
+impl FindInFilesWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the window with the query box focused, filled with `query`
+    /// when it is not empty.
+    pub fn open_with(&mut self, query: &str) {
+        if !query.is_empty() {
+            self.query = query.to_string();
+        }
+        self.open = true;
+        self.focus = true;
+        self.preview = None;
+        self.searched = None;
+    }
+
+    /// Searches the `.fr` files of the workspace at `root` and the open
+    /// `tabs` whenever the query or options change, lists the matches by
+    /// file, and previews a replace in all of them before it is applied.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        root: Option<&Path>,
+        tabs: &[Tab],
+        theme: &Theme,
+    ) -> FindAction {
+        if !self.open {
+            return FindAction::None;
+        }
+        let key = (self.query.clone(), self.options);
+        if self.searched.as_ref() != Some(&key) {
+            self.run_search(root, tabs);
+            self.searched = Some(key);
+        }
+
+        let t = *theme;
+        let mut open = self.open;
+        let mut action = FindAction::None;
+        let mut wants_preview = false;
+
+        egui::Window::new("Find in Files")
+            .id(egui::Id::new("fractal_find_in_files"))
+            .open(&mut open)
+            .default_size([560.0, 420.0])
+            .min_size([340.0, 200.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        self.header(ui, &t);
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                if self.preview.is_some() {
+                    action = self.show_preview(ui, root, &t);
+                } else {
+                    action = self.show_results(ui, root, &t, &mut wants_preview);
+                }
+            });
+
+        if wants_preview {
+            self.build_preview(root, tabs);
+        }
+        if matches!(action, FindAction::Apply(_)) {
+            self.preview = None;
+            self.searched = None;
+        }
+        self.open = open;
+        action
+    }
+
+    fn run_search(&mut self, root: Option<&Path>, tabs: &[Tab]) {
+        self.results.clear();
+        self.truncated = false;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+        match Matcher::new(&self.query, self.options) {
+            Ok(matcher) => {
+                let sources = search_sources(root, tabs);
+                (self.results, self.truncated) = search(&sources, &matcher);
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    // Builds the changes of a replace in every file with a match, for the
+    // preview.
+    fn build_preview(&mut self, root: Option<&Path>, tabs: &[Tab]) {
+        let Ok(matcher) = Matcher::new(&self.query, self.options) else {
+            return;
+        };
+        let previews = search_sources(root, tabs)
+            .into_iter()
+            .filter_map(|(path, before)| {
+                let (after, count) = matcher.replace_all(&before, &self.replacement);
+                (count > 0).then(|| Preview {
+                    hunks: changed_lines(&before, &after),
+                    change: FileChange {
+                        path,
+                        before,
+                        after,
+                        count,
+                    },
+                    include: true,
+                })
+            })
+            .collect();
+        self.preview = Some(previews);
+    }
+
+    fn header(&mut self, ui: &mut egui::Ui, t: &Theme) {
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new("Find in Files")
+                    .size(14.0)
+                    .color(t.tab_active_fg)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let count: usize = self.results.iter().map(|f| f.count).sum();
+                let summary = if self.error.is_some() {
+                    egui::RichText::new("Invalid regex")
+                        .color(t.terminal_error)
+                        .size(12.0)
+                } else if self.query.is_empty() {
+                    egui::RichText::new("")
+                } else {
+                    egui::RichText::new(format!(
+                        "{}{} {} in {} {}",
+                        count,
+                        if self.truncated { "+" } else { "" },
+                        if count == 1 { "match" } else { "matches" },
+                        self.results.len(),
+                        if self.results.len() == 1 {
+                            "file"
+                        } else {
+                            "files"
+                        }
+                    ))
+                    .size(12.0)
+                    .color(t.tab_inactive_fg)
+                };
+                let resp = ui.label(summary);
+                if let Some(err) = &self.error {
+                    resp.on_hover_text(err);
+                }
+            });
+        });
+        ui.add_space(4.0);
+
+        let toggles_w = 3.0 * 32.0;
+        ui.horizontal(|ui| {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .desired_width(ui.available_width() - toggles_w)
+                    .hint_text("Search")
+                    .font(egui::TextStyle::Monospace),
+            );
+            if std::mem::take(&mut self.focus) {
+                edit.request_focus();
+            }
+            if edit.changed() {
+                self.preview = None;
+            }
+            let mut changed = false;
+            changed |= option_toggle(ui, &mut self.options.match_case, "Aa", "Match case", t);
+            changed |= option_toggle(
+                ui,
+                &mut self.options.whole_word,
+                "ab",
+                "Match whole word",
+                t,
+            );
+            changed |= option_toggle(
+                ui,
+                &mut self.options.regex,
+                ".*",
+                "Use regular expression",
+                t,
+            );
+            if changed {
+                self.preview = None;
+            }
+        });
+        ui.horizontal(|ui| {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut self.replacement)
+                    .desired_width(ui.available_width() - toggles_w)
+                    .hint_text("Replace")
+                    .font(egui::TextStyle::Monospace),
+            );
+            if edit.changed() {
+                self.preview = None;
+            }
+        });
+    }
+
+    fn show_results(
+        &self,
+        ui: &mut egui::Ui,
+        root: Option<&Path>,
+        t: &Theme,
+        wants_preview: &mut bool,
+    ) -> FindAction {
+        let mut action = FindAction::None;
+        let empty = if self.query.is_empty() {
+            Some(if root.is_some() {
+                "Search every .fr file in the folder"
+            } else {
+                "Search the open files, or open a folder to search it"
+            })
+        } else if self.error.is_some() {
+            Some("The query is not a valid regular expression")
+        } else if self.results.is_empty() {
+            Some("No results")
+        } else {
+            None
+        };
+        if let Some(message) = empty {
+            ui.add_space(16.0);
+            ui.vertical_centered(|ui| {
+                ui.label(
+                    egui::RichText::new(message)
+                        .size(12.0)
+                        .color(t.tab_inactive_fg),
+                );
+            });
+            return action;
+        }
+
+        egui::Frame::new()
+            .inner_margin(egui::Margin::symmetric(12, 6))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(format!("{}  Replace All…", ic::ARROWS_CLOCKWISE))
+                        .on_hover_text("Preview the replace in every file before applying it")
+                        .clicked()
+                    {
+                        *wants_preview = true;
+                    }
+                    if self.truncated {
+                        ui.label(
+                            egui::RichText::new(format!("Showing the first {MAX_MATCHES} matches"))
+                                .size(11.0)
+                                .color(t.terminal_warning),
+                        );
+                    }
+                });
+            });
+
+        egui::ScrollArea::vertical()
+            .id_salt("find_in_files_scroll")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                egui::Frame::new()
+                    .inner_margin(egui::Margin::symmetric(12, 4))
+                    .show(ui, |ui| {
+                        for file in &self.results {
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new(relative(&file.path, root))
+                                        .size(12.0)
+                                        .color(t.tab_inactive_fg)
+                                        .strong(),
+                                );
+                                ui.label(
+                                    egui::RichText::new(file.count.to_string())
+                                        .size(11.0)
+                                        .color(t.line_numbers_fg),
+                                );
+                            });
+                            for m in &file.lines {
+                                if match_row(ui, m, t).clicked() {
+                                    let col = m.text[..m.ranges[0].0].chars().count();
+                                    action = FindAction::Open(file.path.clone(), m.line, col);
+                                }
+                            }
+                            ui.add_space(6.0);
+                        }
+                    });
+            });
+        action
+    }
+
+    fn show_preview(&mut self, ui: &mut egui::Ui, root: Option<&Path>, t: &Theme) -> FindAction {
+        let mut action = FindAction::None;
+        let Some(previews) = &mut self.preview else {
+            return action;
+        };
+        let files = previews.iter().filter(|p| p.include).count();
+        let count: usize = previews
+            .iter()
+            .filter(|p| p.include)
+            .map(|p| p.change.count)
+            .sum();
+        let mut cancel = false;
+
+        egui::Frame::new()
+            .inner_margin(egui::Margin::symmetric(12, 6))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let apply = ui.add_enabled(
+                        files > 0,
+                        egui::Button::new(format!(
+                            "{}  Replace {} in {} {}",
+                            ic::ARROWS_CLOCKWISE,
+                            count,
+                            files,
+                            if files == 1 { "file" } else { "files" }
+                        )),
+                    );
+                    if apply.clicked() {
+                        action = FindAction::Apply(
+                            std::mem::take(previews)
+                                .into_iter()
+                                .filter(|p| p.include)
+                                .map(|p| p.change)
+                                .collect(),
+                        );
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if cancel {
+            self.preview = None;
+            return action;
+        }
+        if previews.is_empty() {
+            ui.add_space(16.0);
+            ui.vertical_centered(|ui| {
+                ui.label(
+                    egui::RichText::new("Nothing to replace")
+                        .size(12.0)
+                        .color(t.tab_inactive_fg),
+                );
+            });
+            return action;
+        }
+
+        egui::ScrollArea::vertical()
+            .id_salt("find_in_files_preview_scroll")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                egui::Frame::new()
+                    .inner_margin(egui::Margin::symmetric(12, 4))
+                    .show(ui, |ui| {
+                        for preview in previews.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut preview.include, "");
+                                ui.label(
+                                    egui::RichText::new(relative(&preview.change.path, root))
+                                        .size(12.0)
+                                        .color(t.tab_inactive_fg)
+                                        .strong(),
+                                );
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "{} {}",
+                                        preview.change.count,
+                                        if preview.change.count == 1 {
+                                            "replacement"
+                                        } else {
+                                            "replacements"
+                                        }
+                                    ))
+                                    .size(11.0)
+                                    .color(t.line_numbers_fg),
+                                );
+                            });
+                            if preview.include {
+                                for hunk in &preview.hunks {
+                                    hunk_rows(ui, hunk, t);
+                                }
+                            }
+                            ui.add_space(6.0);
+                        }
+                    });
+            });
+        action
+    }
+}
+
+// A matched line, with its matches highlighted.
+fn match_row(ui: &mut egui::Ui, m: &LineMatch, t: &Theme) -> egui::Response {
+    let font = egui::FontId::monospace(12.0);
+    let indent = m.text.len() - m.text.trim_start().len();
+    let mut job = egui::text::LayoutJob::default();
+    let mut at = indent;
+    for &(start, end) in &m.ranges {
+        let start = start.max(at);
+        job.append(
+            &m.text[at..start],
+            0.0,
+            egui::TextFormat::simple(font.clone(), t.tab_active_fg),
+        );
+        job.append(
+            &m.text[start..end.max(start)],
+            0.0,
+            egui::TextFormat {
+                background: t.selection,
+                ..egui::TextFormat::simple(font.clone(), t.tab_active_fg)
+            },
+        );
+        at = end.max(start);
+    }
+    job.append(
+        m.text[at..].trim_end(),
+        0.0,
+        egui::TextFormat::simple(font, t.tab_active_fg),
+    );
+    job.wrap.max_rows = 1;
+    job.wrap.break_anywhere = true;
+
+    let resp = ui
+        .horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("{:>5}", m.line))
+                    .size(11.0)
+                    .monospace()
+                    .color(t.line_numbers_fg),
+            );
+            ui.label(job);
+        })
+        .response
+        .interact(egui::Sense::click());
+    if resp.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    resp
+}
+
+// The lines of a hunk before the replace, then after it.
+fn hunk_rows(ui: &mut egui::Ui, hunk: &Hunk, t: &Theme) {
+    let rows = hunk
+        .old
+        .iter()
+        .map(|l| ('-', l, t.terminal_error))
+        .chain(hunk.new.iter().map(|l| ('+', l, t.string)));
+    for (i, (sign, text, color)) in rows.enumerate() {
+        ui.horizontal(|ui| {
+            let line = if i == 0 {
+                hunk.line.to_string()
+            } else {
+                String::new()
+            };
+            ui.label(
+                egui::RichText::new(format!("{line:>5}"))
+                    .size(11.0)
+                    .monospace()
+                    .color(t.line_numbers_fg),
+            );
+            ui.label(
+                egui::RichText::new(format!("{sign} {text}"))
+                    .size(12.0)
+                    .monospace()
+                    .color(color),
+            );
+        });
+    }
+}
+
+fn relative(path: &Path, root: Option<&Path>) -> String {
+    root.and_then(|root| {
+        path.strip_prefix(root)
+            .or_else(|_| path.strip_prefix(canonical(root)))
+            .ok()
+    })
+    .unwrap_or(path)
+    .to_string_lossy()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_are_grouped_by_line() {
+        let sources = vec![
+            (
+                PathBuf::from("a.fr"),
+                "x = x + 1;\ny = 2;\nx;\n".to_string(),
+            ),
+            (PathBuf::from("b.fr"), "nothing here\n".to_string()),
+        ];
+        let matcher = Matcher::new("x", SearchOptions::default()).unwrap();
+        let (results, truncated) = search(&sources, &matcher);
+        assert!(!truncated);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].count, 3);
+        let lines: Vec<_> = results[0]
+            .lines
+            .iter()
+            .map(|l| (l.line, l.ranges.clone()))
+            .collect();
+        assert_eq!(lines, [(1, vec![(0, 1), (4, 5)]), (3, vec![(0, 1)])]);
+    }
+
+    #[test]
+    fn a_replace_is_previewed_line_by_line() {
+        let hunks = changed_lines("a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(
+            (hunks[0].line, &hunks[0].old[..], &hunks[0].new[..]),
+            (2, &["b".to_string()][..], &["B".to_string()][..])
+        );
+
+        // A replace that adds lines is one hunk from the first change to the
+        // last.
+        let hunks = changed_lines("a\nb\nc\nd\n", "a\nb1\nb2\nc\nD\n");
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].line, 2);
+        assert_eq!(hunks[0].old, ["b", "c", "d"]);
+        assert_eq!(hunks[0].new, ["b1", "b2", "c", "D"]);
+    }
+
+    #[test]
+    fn an_open_file_is_searched_as_it_is_in_its_tab() {
+        let dir = std::env::temp_dir().join(format!("fractal-find-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let open = dir.join("open.fr");
+        fs::write(&open, "saved\n").unwrap();
+        fs::write(dir.join("closed.fr"), "on disk\n").unwrap();
+        let tab = Tab::from_file(open.clone(), "unsaved\n".to_string(), Theme::dark());
+
+        let mut sources = search_sources(Some(&dir), &[tab]);
+        sources.sort();
+        assert_eq!(
+            sources,
+            [
+                (dir.join("closed.fr"), "on disk\n".to_string()),
+                (open, "unsaved\n".to_string()),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
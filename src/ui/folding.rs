@@ -0,0 +1,287 @@
+use crate::compiler::parser::ParseNode;
+use eframe::egui;
+use std::cmp::Reverse;
+
+/// A block of code that can be folded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldRegion {
+    /// The line of the block's `{`.
+    pub start: usize,
+    /// The line of its `}`. Folding hides the lines between the two.
+    pub end: usize,
+}
+
+impl FoldRegion {
+    pub fn hides(&self, line: usize) -> bool {
+        line > self.start && line < self.end
+    }
+}
+
+/// The chars of `code` that are code, with their byte offsets: those outside
+/// comments, strings and char literals.
+pub fn code_chars(code: &str) -> Vec<(usize, char)> {
+    let mut out = Vec::new();
+    let mut chars = code.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' if code[i..].starts_with("###") => {
+                let end = code[i + 3..].find("###").map_or(code.len(), |j| i + j + 6);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            }
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            '"' | '\'' => {
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d != '\n') {
+                    if d == '\\' {
+                        chars.next_if(|&(_, d)| d != '\n');
+                    } else if d == c {
+                        break;
+                    }
+                }
+            }
+            _ => out.push((i, c)),
+        }
+    }
+    out
+}
+
+/// The byte offsets of every `{` of `code` and its `}`, in the order of the
+/// `{`. Braces left unmatched are skipped.
+pub fn brace_pairs(code: &str) -> Vec<(usize, usize)> {
+    let mut open = Vec::new();
+    let mut pairs = Vec::new();
+    for (i, c) in code_chars(code) {
+        match c {
+            '{' => open.push(i),
+            '}' => {
+                if let Some(o) = open.pop() {
+                    pairs.push((o, i));
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+/// The blocks of `code` that span more than two lines and so can be folded,
+/// by their first line. With `program`, the parse of `code`, they are the
+/// bodies of functions, `!if`, `!else`, `!for` and `!while`, structs and
+/// `!module` sections; without it, every pair of braces.
+pub fn fold_regions(code: &str, program: Option<&ParseNode>) -> Vec<FoldRegion> {
+    let pairs = brace_pairs(code);
+    let starts = line_starts(code);
+    let line_of = |byte: usize| starts.partition_point(|&s| s <= byte);
+    let blocks: Vec<(usize, usize)> = match program {
+        Some(ParseNode::Program(items)) => {
+            let mut opens = Vec::new();
+            block_braces(items, &starts, &pairs, &mut opens);
+            pairs
+                .iter()
+                .copied()
+                .filter(|&(o, _)| {
+                    let line = &code[starts[line_of(o) - 1]..o];
+                    opens.contains(&o) || line.trim_start().starts_with("!module")
+                })
+                .collect()
+        }
+        _ => pairs,
+    };
+    let mut regions: Vec<FoldRegion> = blocks
+        .into_iter()
+        .map(|(o, c)| FoldRegion {
+            start: line_of(o),
+            end: line_of(c),
+        })
+        .filter(|r| r.end > r.start + 1)
+        .collect();
+    // Of blocks opened on the same line, the outermost.
+    regions.sort_by_key(|r| (r.start, Reverse(r.end)));
+    regions.dedup_by_key(|r| r.start);
+    regions
+}
+
+// The byte offset of the `{` of every block in `items` and in the blocks
+// inside them. A statement's block is the first pair of braces opened on or
+// after its line, and an `!else` block the first opened after the `!if`'s.
+fn block_braces(
+    items: &[ParseNode],
+    starts: &[usize],
+    pairs: &[(usize, usize)],
+    opens: &mut Vec<usize>,
+) {
+    let pair_from = |byte: usize| pairs.iter().find(|&&(o, _)| o >= byte).copied();
+    let at_line = |line: usize| pair_from(starts.get(line.wrapping_sub(1)).copied()?);
+    for item in items {
+        match item {
+            ParseNode::FuncDef { body, line, .. }
+            | ParseNode::For { body, line, .. }
+            | ParseNode::While { body, line, .. } => {
+                opens.extend(at_line(*line).map(|p| p.0));
+                block_braces(body, starts, pairs, opens);
+            }
+            ParseNode::StructDef { line, .. } => opens.extend(at_line(*line).map(|p| p.0)),
+            ParseNode::If {
+                then_block,
+                else_block,
+                line,
+                ..
+            } => {
+                let then = at_line(*line);
+                opens.extend(then.map(|p| p.0));
+                block_braces(then_block, starts, pairs, opens);
+                if let Some(else_block) = else_block {
+                    let els = then.and_then(|(_, close)| pair_from(close + 1));
+                    opens.extend(els.map(|p| p.0));
+                    block_braces(else_block, starts, pairs, opens);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The byte offset each line of `code` starts at.
+pub(crate) fn line_starts(code: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+// The edit that turned `old` into `new`: the byte it starts at, the byte of
+// `old` it ends at, and how many lines it added (negative if it removed some).
+pub(crate) fn edit_span(old: &str, new: &str) -> (usize, usize, isize) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|&((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    let delta = new[prefix..new_end].matches('\n').count() as isize
+        - old[prefix..old_end].matches('\n').count() as isize;
+    (prefix, old_end, delta)
+}
+
+/// `folds` moved for the edit that turned `old` into `new`: those after the
+/// edit move with their lines and those before it stay. A fold whose hidden
+/// lines, or the line break before them, were edited is dropped.
+pub fn shift_folds(folds: &[FoldRegion], old: &str, new: &str) -> Vec<FoldRegion> {
+    let (prefix, old_end, delta) = edit_span(old, new);
+    let starts = line_starts(old);
+    folds
+        .iter()
+        .filter_map(|f| {
+            // The line break ending the first line, and the start of the last.
+            let first_break = starts.get(f.start)?.checked_sub(1)?;
+            let last_start = *starts.get(f.end - 1)?;
+            if prefix >= last_start {
+                Some(*f)
+            } else if old_end < first_break || (old_end == first_break && delta == 0) {
+                Some(FoldRegion {
+                    start: f.start.checked_add_signed(delta)?,
+                    end: f.end.checked_add_signed(delta)?,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// The byte ranges of `code` that `folds` hide: each from the line break
+/// ending a fold's first line to the start of its last line.
+pub fn hidden_ranges(code: &str, folds: &[FoldRegion]) -> Vec<(usize, usize)> {
+    let starts = line_starts(code);
+    let mut ranges: Vec<(usize, usize)> = folds
+        .iter()
+        .filter_map(|f| Some((*starts.get(f.start)? - 1, *starts.get(f.end - 1)?)))
+        .collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (a, b) in ranges {
+        match merged.last_mut() {
+            Some(last) if a <= last.1 => last.1 = last.1.max(b),
+            _ => merged.push((a, b)),
+        }
+    }
+    merged
+}
+
+/// Lays the text of `job` in `ranges` out with no height and no color, so
+/// that the rows it is on take no room.
+pub fn hide_in_job(job: &mut egui::text::LayoutJob, ranges: &[(usize, usize)]) {
+    if ranges.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + ranges.len() * 2);
+    for section in job.sections.drain(..) {
+        let mut at = section.byte_range.start;
+        let end = section.byte_range.end;
+        while at < end {
+            let hidden = ranges.iter().find(|&&(a, b)| a <= at && at < b);
+            let next = match hidden {
+                Some(&(_, b)) => b.min(end),
+                None => ranges
+                    .iter()
+                    .map(|&(a, _)| a)
+                    .filter(|&a| a > at)
+                    .min()
+                    .map_or(end, |a| a.min(end)),
+            };
+            let mut format = section.format.clone();
+            if hidden.is_some() {
+                format.line_height = Some(0.0);
+                format.color = egui::Color32::TRANSPARENT;
+                format.background = egui::Color32::TRANSPARENT;
+            }
+            sections.push(egui::text::LayoutSection {
+                leading_space: if at == section.byte_range.start {
+                    section.leading_space
+                } else {
+                    0.0
+                },
+                byte_range: at..next,
+                format,
+            });
+            at = next;
+        }
+    }
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "!start\n!func f() -> :void {\n    print(\"a\");\n    print(\"b\");\n}\nprint(\"c\");\n!end\n";
+
+    fn fold(start: usize, end: usize) -> FoldRegion {
+        FoldRegion { start, end }
+    }
+
+    #[test]
+    fn folds_move_with_lines_added_above() {
+        let new = CODE.replacen("!start\n", "!start\n# one\n# two\n", 1);
+        assert_eq!(shift_folds(&[fold(2, 5)], CODE, &new), [fold(4, 7)]);
+    }
+
+    #[test]
+    fn folds_stay_for_edits_below() {
+        let new = CODE.replace("print(\"c\");", "print(\"c\");\nprint(\"d\");");
+        assert_eq!(shift_folds(&[fold(2, 5)], CODE, &new), [fold(2, 5)]);
+    }
+
+    #[test]
+    fn folds_are_dropped_when_their_lines_are_edited() {
+        let new = CODE.replace("    print(\"b\");\n", "");
+        assert_eq!(shift_folds(&[fold(2, 5)], CODE, &new), []);
+    }
+}
@@ -0,0 +1,484 @@
+use super::debugger::{DebugFrame, FractalValue, VarRow};
+use super::theme::{Theme, ThemeVariant};
+use eframe::egui;
+use std::collections::HashMap;
+
+const BOX_W: f32 = 170.0;
+const HEADER_H: f32 = 22.0;
+const ROW_H: f32 = 18.0;
+const COL_GAP: f32 = 56.0;
+const ROW_GAP: f32 = 16.0;
+const ROOT_W: f32 = 96.0;
+const MARGIN: f32 = 12.0;
+// A long linked list would otherwise lay out thousands of boxes.
+const NODE_LIMIT: usize = 300;
+const VALUE_CHARS: usize = 14;
+
+// Where an arrow starts: a variable in the left column, or a field row of a
+// box.
+#[derive(Clone, Copy)]
+enum Anchor {
+    Var(usize),
+    Field(usize, usize),
+}
+
+struct Node {
+    // Where the instance lives, such as `h.next.next` or `l[2].left`.
+    path: String,
+    name: String,
+    rows: Vec<(String, String)>,
+    changed: bool,
+    rect: egui::Rect,
+}
+
+struct Edge {
+    from: Anchor,
+    to: usize,
+    label: String,
+}
+
+struct Root {
+    name: String,
+    rect: egui::Rect,
+}
+
+#[derive(Default)]
+struct Graph {
+    roots: Vec<Root>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    // Top of the free space in the root column and in each box column.
+    root_free: f32,
+    column_free: Vec<f32>,
+    truncated: bool,
+}
+
+#[derive(Default)]
+pub struct HeapViewWindow {
+    pub open: bool,
+}
+
+impl HeapViewWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws the structs reachable from the innermost scope of `frame` as
+    /// boxes, with an arrow for each struct held in a field, list or
+    /// variable. Boxes whose fields changed since the previous step of the
+    /// same call are highlighted.
+    pub fn show(&mut self, ctx: &egui::Context, frame: Option<&DebugFrame>, theme: &Theme) {
+        if !self.open {
+            return;
+        }
+
+        let t = *theme;
+        let mut open = self.open;
+        let scope = frame.and_then(|f| f.scopes.first().map(|s| (f, s)));
+        let graph = scope
+            .map(|(f, s)| build_graph(&s.vars, &f.previous_values))
+            .unwrap_or_default();
+        let empty = if frame.is_some() {
+            "No structs in scope"
+        } else {
+            "No debug session active"
+        };
+
+        egui::Window::new("Heap Graph")
+            .id(egui::Id::new("fractal_heap_view"))
+            .open(&mut open)
+            .default_size([560.0, 420.0])
+            .min_size([300.0, 200.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Heap Graph")
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    let count = if graph.truncated {
+                                        format!("first {NODE_LIMIT} structs")
+                                    } else {
+                                        format!("{} structs", graph.nodes.len())
+                                    };
+                                    ui.label(
+                                        egui::RichText::new(count)
+                                            .size(12.0)
+                                            .color(t.tab_inactive_fg),
+                                    );
+                                    if let Some((_, scope)) = scope {
+                                        ui.add_space(8.0);
+                                        ui.label(
+                                            egui::RichText::new(&scope.label)
+                                                .size(12.0)
+                                                .color(t.tab_inactive_fg),
+                                        );
+                                    }
+                                },
+                            );
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                if graph.roots.is_empty() {
+                    ui.add_space(16.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new(empty)
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                        );
+                    });
+                    return;
+                }
+
+                egui::ScrollArea::both()
+                    .id_salt("heap_view_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| draw_graph(ui, &graph, &t));
+            });
+
+        self.open = open;
+    }
+}
+
+fn build_graph(vars: &[VarRow], previous: &HashMap<String, FractalValue>) -> Graph {
+    let mut graph = Graph {
+        root_free: MARGIN,
+        ..Default::default()
+    };
+    for var in vars {
+        if !holds_struct(&var.value) {
+            continue;
+        }
+        let index = graph.roots.len();
+        let top = graph.root_free;
+        graph.roots.push(Root {
+            name: var.name.clone(),
+            rect: egui::Rect::from_min_size(
+                egui::pos2(MARGIN, top),
+                egui::vec2(ROOT_W - 2.0 * MARGIN, ROW_H + 4.0),
+            ),
+        });
+        // Unchanged variables are not diffed, as in the variable panel.
+        let before = var.changed.then(|| previous.get(&var.name));
+        graph.visit(
+            &var.value,
+            var.name.clone(),
+            Anchor::Var(index),
+            String::new(),
+            0,
+            top,
+            before,
+        );
+        // The next variable starts below this one's first column of boxes.
+        let below = graph.column_free.first().copied().unwrap_or(MARGIN);
+        graph.root_free = below.max(top + ROW_H + 4.0 + ROW_GAP);
+    }
+    graph
+}
+
+impl Graph {
+    // Lays out `value` at column `depth`, no higher than `top`, and links it
+    // from `from`. `before` is `None` when the value is not diffed, and
+    // `Some(None)` when it did not exist at the previous step.
+    #[allow(clippy::too_many_arguments)]
+    fn visit(
+        &mut self,
+        value: &FractalValue,
+        path: String,
+        from: Anchor,
+        label: String,
+        depth: usize,
+        top: f32,
+        before: Option<Option<&FractalValue>>,
+    ) {
+        match value {
+            FractalValue::Struct(name, fields) => {
+                if self.nodes.len() >= NODE_LIMIT {
+                    self.truncated = true;
+                    return;
+                }
+                let rows = struct_rows(fields);
+                let changed = match before {
+                    Some(Some(FractalValue::Struct(_, old))) => struct_rows(old) != rows,
+                    Some(_) => true,
+                    None => false,
+                };
+                if self.column_free.len() <= depth {
+                    self.column_free.resize(depth + 1, MARGIN);
+                }
+                let y = self.column_free[depth].max(top);
+                let x = ROOT_W + depth as f32 * (BOX_W + COL_GAP);
+                let height = HEADER_H + rows.len() as f32 * ROW_H + 4.0;
+                self.column_free[depth] = y + height + ROW_GAP;
+
+                let index = self.nodes.len();
+                self.nodes.push(Node {
+                    path: path.clone(),
+                    name: name.clone(),
+                    rows,
+                    changed,
+                    rect: egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(BOX_W, height)),
+                });
+                self.edges.push(Edge {
+                    from,
+                    to: index,
+                    label,
+                });
+
+                for (row, (field, child)) in fields.iter().enumerate() {
+                    if !holds_struct(child) {
+                        continue;
+                    }
+                    let child_before = before.map(|b| match b {
+                        Some(FractalValue::Struct(_, old)) => {
+                            old.iter().find(|(k, _)| k == field).map(|(_, v)| v)
+                        }
+                        _ => None,
+                    });
+                    self.visit(
+                        child,
+                        format!("{path}.{field}"),
+                        Anchor::Field(index, row),
+                        String::new(),
+                        depth + 1,
+                        y,
+                        child_before,
+                    );
+                }
+            }
+            FractalValue::List(items) | FractalValue::Array(items) => {
+                let old_items = match before {
+                    Some(Some(FractalValue::List(old) | FractalValue::Array(old))) => {
+                        Some(old.as_slice())
+                    }
+                    _ => None,
+                };
+                for (i, item) in items.iter().enumerate() {
+                    if !holds_struct(item) {
+                        continue;
+                    }
+                    let item_before = before.map(|_| old_items.and_then(|old| old.get(i)));
+                    self.visit(
+                        item,
+                        format!("{path}[{i}]"),
+                        from,
+                        format!("[{i}]"),
+                        depth,
+                        top,
+                        item_before,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn holds_struct(value: &FractalValue) -> bool {
+    match value {
+        FractalValue::Struct(..) => true,
+        FractalValue::List(items) | FractalValue::Array(items) => items.iter().any(holds_struct),
+        _ => false,
+    }
+}
+
+// The text of each field row. Structs are drawn as arrows, so their rows
+// only show whether the field is set.
+fn struct_rows(fields: &[(String, FractalValue)]) -> Vec<(String, String)> {
+    fields
+        .iter()
+        .map(|(name, value)| {
+            let text = match value {
+                FractalValue::Struct(..) => "●".to_string(),
+                v if holds_struct(v) => format!("{} ●", v.children().len()),
+                v => {
+                    let text = v.display();
+                    if text.chars().count() > VALUE_CHARS {
+                        let cut: String = text.chars().take(VALUE_CHARS - 1).collect();
+                        format!("{cut}…")
+                    } else {
+                        text
+                    }
+                }
+            };
+            (name.clone(), text)
+        })
+        .collect()
+}
+
+fn draw_graph(ui: &mut egui::Ui, graph: &Graph, t: &Theme) {
+    let width = graph
+        .nodes
+        .iter()
+        .map(|n| n.rect.right())
+        .fold(ROOT_W, f32::max)
+        + MARGIN;
+    let height = graph
+        .nodes
+        .iter()
+        .map(|n| n.rect.bottom())
+        .chain(graph.roots.iter().map(|r| r.rect.bottom()))
+        .fold(0.0, f32::max)
+        + MARGIN;
+    let (canvas, _) = ui.allocate_exact_size(egui::vec2(width, height), egui::Sense::hover());
+    let offset = canvas.min.to_vec2();
+    let painter = ui.painter_at(canvas);
+
+    let changed_bg = if t.variant == ThemeVariant::Dark {
+        egui::Color32::from_rgba_premultiplied(255, 220, 100, 180)
+    } else {
+        egui::Color32::from_rgba_premultiplied(255, 200, 80, 120)
+    };
+    let changed_fg = egui::Color32::from_rgb(255, 200, 80);
+    let edge_col = t.tab_inactive_fg;
+
+    for root in &graph.roots {
+        let rect = root.rect.translate(offset);
+        painter.rect_filled(rect, egui::CornerRadius::same(3), t.tab_bar_bg);
+        painter.text(
+            rect.left_center() + egui::vec2(6.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            &root.name,
+            egui::FontId::monospace(12.0),
+            t.identifier,
+        );
+    }
+
+    for edge in &graph.edges {
+        let start = match edge.from {
+            Anchor::Var(i) => graph.roots[i].rect.right_center(),
+            Anchor::Field(node, row) => {
+                let rect = graph.nodes[node].rect;
+                egui::pos2(
+                    rect.right(),
+                    rect.top() + HEADER_H + (row as f32 + 0.5) * ROW_H,
+                )
+            }
+        } + offset;
+        let end = graph.nodes[edge.to].rect.left_top() + egui::vec2(0.0, HEADER_H / 2.0) + offset;
+        draw_arrow(&painter, start, end, edge_col);
+        if !edge.label.is_empty() {
+            painter.text(
+                end - egui::vec2(4.0, 2.0),
+                egui::Align2::RIGHT_BOTTOM,
+                &edge.label,
+                egui::FontId::monospace(10.0),
+                edge_col,
+            );
+        }
+    }
+
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let rect = node.rect.translate(offset);
+        let stroke = if node.changed {
+            egui::Stroke::new(2.0, changed_fg)
+        } else {
+            egui::Stroke::new(1.0, t.border)
+        };
+        painter.rect_filled(rect, egui::CornerRadius::same(4), t.editor_bg);
+        let header = egui::Rect::from_min_size(rect.min, egui::vec2(BOX_W, HEADER_H));
+        painter.rect_filled(
+            header,
+            egui::CornerRadius {
+                nw: 4,
+                ne: 4,
+                sw: 0,
+                se: 0,
+            },
+            if node.changed {
+                changed_bg
+            } else {
+                t.tab_bar_bg
+            },
+        );
+        painter.rect_stroke(
+            rect,
+            egui::CornerRadius::same(4),
+            stroke,
+            egui::StrokeKind::Inside,
+        );
+        painter.text(
+            header.left_center() + egui::vec2(8.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            &node.name,
+            egui::FontId::monospace(12.0),
+            if node.changed {
+                egui::Color32::from_rgb(20, 20, 40)
+            } else {
+                t.struct_name
+            },
+        );
+
+        for (row, (field, value)) in node.rows.iter().enumerate() {
+            let y = header.bottom() + (row as f32 + 0.5) * ROW_H;
+            painter.text(
+                egui::pos2(rect.left() + 8.0, y),
+                egui::Align2::LEFT_CENTER,
+                field,
+                egui::FontId::monospace(11.5),
+                t.tab_inactive_fg,
+            );
+            painter.text(
+                egui::pos2(rect.right() - 8.0, y),
+                egui::Align2::RIGHT_CENTER,
+                value,
+                egui::FontId::monospace(11.5),
+                t.tab_active_fg,
+            );
+        }
+
+        ui.interact(rect, egui::Id::new(("heap_node", i)), egui::Sense::hover())
+            .on_hover_text(&node.path);
+    }
+}
+
+/// A curved arrow from `start` to `end`, as the graph windows draw edges.
+pub fn draw_arrow(
+    painter: &egui::Painter,
+    start: egui::Pos2,
+    end: egui::Pos2,
+    color: egui::Color32,
+) {
+    let reach = ((end.x - start.x).abs() / 2.0).max(24.0);
+    let curve = egui::epaint::CubicBezierShape::from_points_stroke(
+        [
+            start,
+            start + egui::vec2(reach, 0.0),
+            end - egui::vec2(reach, 0.0),
+            end,
+        ],
+        false,
+        egui::Color32::TRANSPARENT,
+        egui::Stroke::new(1.2, color),
+    );
+    painter.add(curve);
+    painter.add(egui::Shape::convex_polygon(
+        vec![end, end - egui::vec2(7.0, 4.0), end - egui::vec2(7.0, -4.0)],
+        color,
+        egui::Stroke::NONE,
+    ));
+}
@@ -378,6 +378,7 @@ impl Highlighter {
                 | "module"
                 | "break"
                 | "continue"
+                | "const"
                 | "and"
                 | "or"
                 | "not"
@@ -388,7 +389,7 @@ impl Highlighter {
     fn is_type(s: &str) -> bool {
         matches!(
             s,
-            "int" | "float" | "char" | "boolean" | "array" | "list" | "struct" | "void"
+            "int" | "float" | "char" | "boolean" | "array" | "list" | "struct" | "void" | "func"
         )
     }
 
@@ -0,0 +1,78 @@
+use crate::compiler::semanter::{Symbol, SymbolKind};
+use crate::ui::completion::builtin_signature;
+use crate::ui::docs::builtin_doc;
+use crate::ui::references::{target_at, Target};
+
+/// The tooltip for the identifier at char index `at` of `code`, from the
+/// symbols of its last check.
+pub fn hover_text(code: &str, at: usize, symbols: &[Symbol]) -> Option<String> {
+    match target_at(code, at, symbols)? {
+        Target::Symbol(s) => Some(describe(s, symbols)),
+        Target::Module(m) => Some(format!("module {m}")),
+        Target::Builtin(b) => Some(match builtin_doc(b.name) {
+            Some(doc) => format!("{}\n\n{doc}", builtin_signature(b)),
+            None => format!("{}\nbuilt-in function", builtin_signature(b)),
+        }),
+    }
+}
+
+// A symbol the way it would be declared, and what it belongs to.
+fn describe(s: &Symbol, symbols: &[Symbol]) -> String {
+    let ty = s.sem_type.display();
+    match &s.kind {
+        SymbolKind::Variable => format!("{ty} {}\n{}", s.name, owner(&s.origin)),
+        SymbolKind::Constant { value } => {
+            format!("!const {ty} {} = {value}\n{}", s.name, owner(&s.origin))
+        }
+        SymbolKind::Function { params } => {
+            let names = param_names(s, symbols);
+            let params: Vec<String> = params
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, p)| match names.get(i).filter(|_| names.len() == params.len()) {
+                        Some(name) => format!("{} {name}", p.display()),
+                        None => p.display(),
+                    },
+                )
+                .collect();
+            format!("!func {}({}) -> {ty}", s.name, params.join(", "))
+        }
+        SymbolKind::Struct { fields } => {
+            let mut text = format!("{ty} {{");
+            for (name, fty) in fields {
+                text.push_str(&format!("\n    {} {name};", fty.display()));
+            }
+            text.push_str("\n}");
+            text
+        }
+    }
+}
+
+// The names of the parameters of function `f`, in order, when its check saw
+// them.
+fn param_names(f: &Symbol, symbols: &[Symbol]) -> Vec<String> {
+    let base = f.name.rsplit("::").next().unwrap_or(&f.name);
+    let origin = format!("param:{base}");
+    let file = f.def.as_ref().map(|d| &d.file);
+    let mut params: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|p| p.origin == origin && p.def.as_ref().map(|d| &d.file) == file)
+        .collect();
+    params.sort_by_key(|p| p.id);
+    params.into_iter().map(|p| p.name.clone()).collect()
+}
+
+fn owner(origin: &str) -> String {
+    if let Some(f) = origin.strip_prefix("fn:") {
+        format!("local variable of {f}")
+    } else if origin == "param:<lambda>" {
+        "lambda parameter".to_string()
+    } else if let Some(f) = origin.strip_prefix("param:") {
+        format!("parameter of {f}")
+    } else if let Some(m) = origin.strip_prefix("module:") {
+        format!("global of module {m}")
+    } else {
+        "global".to_string()
+    }
+}
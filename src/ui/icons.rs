@@ -40,6 +40,7 @@ pub const ARROWS_CLOCKWISE: &str = ph::ARROWS_CLOCKWISE;
 pub use ph::ARROW_FAT_RIGHT as DEBUG_STEP;
 pub use ph::BUG as DEBUG_RUN;
 pub use ph::CIRCLE as TAB_DIRTY_DOT;
+pub use ph::FILM_STRIP as RECORDING;
 pub use ph::FLOPPY_DISK as SAVE_FILE;
 pub use ph::MAGNIFYING_GLASS as SEARCH;
 pub use ph::STOP as DEBUG_STOP;
@@ -48,4 +49,21 @@ pub use ph::TEXT_T as FORMAT_CODE;
 pub use ph::TREE_STRUCTURE as TREE_VIEW;
 
 pub const WINDOW_CLOSE: &str = ph::X_SQUARE;
-pub const VAR_VIEW: &str = ph::STACK;
\ No newline at end of file
+pub const VAR_VIEW: &str = ph::STACK;
+pub const HEAP_VIEW: &str = ph::GRAPH;
+pub const PROFILE: &str = ph::CHART_BAR_HORIZONTAL;
+pub const PROBLEMS: &str = ph::WARNING;
+pub const DEFINITION: &str = ph::ARROW_SQUARE_OUT;
+pub const REFERENCES: &str = ph::LIST_MAGNIFYING_GLASS;
+pub const FIND_IN_FILES: &str = ph::FILE_MAGNIFYING_GLASS;
+pub const RENAME: &str = ph::PENCIL_SIMPLE;
+pub const OUTLINE: &str = ph::LIST_BULLETS;
+pub const FOLDER: &str = ph::FOLDER;
+pub const FILE: &str = ph::FILE_TEXT;
+pub const NEW_FOLDER: &str = ph::FOLDER_PLUS;
+pub const OPEN_FOLDER: &str = ph::FOLDERS;
+pub const EXPLORER: &str = ph::SIDEBAR;
+pub const IMPORT: &str = ph::ARROW_BEND_DOWN_RIGHT;
+pub const IMPORT_GRAPH: &str = ph::FLOW_ARROW;
+pub const FOLD: &str = ph::ARROWS_IN_LINE_VERTICAL;
+pub const UNFOLD: &str = ph::ARROWS_OUT_LINE_VERTICAL;
\ No newline at end of file
@@ -0,0 +1,306 @@
+use super::heap_view::draw_arrow;
+use super::theme::Theme;
+use super::workspace::{imports, source_files};
+use eframe::egui;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const BOX_W: f32 = 170.0;
+const BOX_H: f32 = 28.0;
+const COL_GAP: f32 = 80.0;
+const ROW_GAP: f32 = 12.0;
+const MARGIN: f32 = 16.0;
+// How often the files are read again while the window is open.
+const RESCAN: Duration = Duration::from_secs(2);
+
+struct Node {
+    path: PathBuf,
+    // The canonical path, to compare with the active file.
+    key: PathBuf,
+    label: String,
+    rect: egui::Rect,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    // Importer, then imported, as node indices.
+    edges: Vec<(usize, usize)>,
+}
+
+// Every `.fr` file under `root`, in columns: a file is placed right of every
+// file that imports it, so the programs end up on the left.
+fn build_graph(root: &Path) -> Graph {
+    let files = source_files(root);
+    let index: HashMap<PathBuf, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (canonical(f), i))
+        .collect();
+    let mut edges = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let code = fs::read_to_string(file).unwrap_or_default();
+        for import in imports(&code, Some(file)) {
+            if let Some(&j) = import
+                .path
+                .as_deref()
+                .and_then(|p| index.get(&canonical(p)))
+            {
+                if !edges.contains(&(i, j)) {
+                    edges.push((i, j));
+                }
+            }
+        }
+    }
+
+    // Longest chain of importers, capped so that a cycle stops growing it.
+    let mut level = vec![0; files.len()];
+    for _ in 0..files.len() {
+        let mut changed = false;
+        for &(from, to) in &edges {
+            if level[to] <= level[from] && level[from] + 1 < files.len() {
+                level[to] = level[from] + 1;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut rows = HashMap::new();
+    let nodes = files
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let row = rows.entry(level[i]).or_insert(0);
+            let min = egui::pos2(
+                MARGIN + level[i] as f32 * (BOX_W + COL_GAP),
+                MARGIN + *row as f32 * (BOX_H + ROW_GAP),
+            );
+            *row += 1;
+            Node {
+                key: canonical(&path),
+                label: path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string(),
+                path,
+                rect: egui::Rect::from_min_size(min, egui::vec2(BOX_W, BOX_H)),
+            }
+        })
+        .collect();
+    Graph { nodes, edges }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Default)]
+pub struct ImportGraphWindow {
+    pub open: bool,
+    graph: Graph,
+    // The folder the graph is of, and when it was read.
+    scanned: Option<(PathBuf, Instant)>,
+}
+
+impl ImportGraphWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws the `.fr` files of the workspace at `root` as boxes, with an
+    /// arrow from each file to every file it imports, and returns the file
+    /// clicked. `active` is highlighted with its arrows.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        root: Option<&Path>,
+        active: Option<&Path>,
+        theme: &Theme,
+    ) -> Option<PathBuf> {
+        if !self.open {
+            return None;
+        }
+        match root {
+            Some(root) => {
+                let stale = self
+                    .scanned
+                    .as_ref()
+                    .is_none_or(|(dir, at)| dir != root || at.elapsed() >= RESCAN);
+                if stale {
+                    self.graph = build_graph(root);
+                    self.scanned = Some((root.to_path_buf(), Instant::now()));
+                }
+                ctx.request_repaint_after(RESCAN);
+            }
+            None => {
+                self.graph = Graph::default();
+                self.scanned = None;
+            }
+        }
+
+        let t = *theme;
+        let mut open = self.open;
+        let mut clicked = None;
+        let graph = &self.graph;
+        let empty = if root.is_none() {
+            "Open a folder to see how its files import each other"
+        } else {
+            "No .fr files in this folder"
+        };
+
+        egui::Window::new("Import Graph")
+            .id(egui::Id::new("fractal_import_graph"))
+            .open(&mut open)
+            .default_size([560.0, 380.0])
+            .min_size([300.0, 180.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Import Graph")
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "{} files, {} imports",
+                                            graph.nodes.len(),
+                                            graph.edges.len()
+                                        ))
+                                        .size(12.0)
+                                        .color(t.tab_inactive_fg),
+                                    );
+                                },
+                            );
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                if graph.nodes.is_empty() {
+                    ui.add_space(16.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new(empty)
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                        );
+                    });
+                    return;
+                }
+
+                egui::ScrollArea::both()
+                    .id_salt("import_graph_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| clicked = draw_graph(ui, graph, active, &t));
+            });
+
+        self.open = open;
+        clicked
+    }
+}
+
+fn draw_graph(
+    ui: &mut egui::Ui,
+    graph: &Graph,
+    active: Option<&Path>,
+    t: &Theme,
+) -> Option<PathBuf> {
+    let size = graph
+        .nodes
+        .iter()
+        .fold(egui::Vec2::ZERO, |s, n| s.max(n.rect.max.to_vec2()))
+        + egui::vec2(MARGIN, MARGIN);
+    let (canvas, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let offset = canvas.min.to_vec2();
+    let painter = ui.painter_at(canvas);
+    let active = active.map(canonical);
+    let is_active = |i: usize| active.as_ref() == Some(&graph.nodes[i].key);
+
+    for &(from, to) in &graph.edges {
+        let color = if is_active(from) || is_active(to) {
+            t.accent
+        } else {
+            t.tab_inactive_fg
+        };
+        let start = graph.nodes[from].rect.right_center() + offset;
+        let end = graph.nodes[to].rect.left_center() + offset;
+        draw_arrow(&painter, start, end, color);
+    }
+
+    let mut clicked = None;
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let rect = node.rect.translate(offset);
+        let resp = ui.interact(
+            rect,
+            egui::Id::new(("import_node", i)),
+            egui::Sense::click(),
+        );
+        let stroke = if is_active(i) {
+            egui::Stroke::new(2.0, t.accent)
+        } else {
+            egui::Stroke::new(1.0, t.border)
+        };
+        painter.rect_filled(
+            rect,
+            egui::CornerRadius::same(4),
+            if resp.hovered() {
+                t.menu_hover_bg
+            } else {
+                t.editor_bg
+            },
+        );
+        painter.rect_stroke(
+            rect,
+            egui::CornerRadius::same(4),
+            stroke,
+            egui::StrokeKind::Inside,
+        );
+        painter.with_clip_rect(rect.shrink(4.0)).text(
+            rect.left_center() + egui::vec2(8.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            &node.label,
+            egui::FontId::monospace(12.0),
+            t.tab_active_fg,
+        );
+
+        let out = graph.edges.iter().filter(|e| e.0 == i).count();
+        let into = graph.edges.iter().filter(|e| e.1 == i).count();
+        if resp.hovered() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        if resp
+            .on_hover_text(format!("{}\nimports {out}, imported by {into}", node.label))
+            .clicked()
+        {
+            clicked = Some(node.path.clone());
+        }
+    }
+    clicked
+}
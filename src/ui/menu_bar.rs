@@ -9,21 +9,43 @@ pub struct MenuBarState {}
 
 pub enum MenuAction {
     OpenDialog,
+    OpenFolder,
     SaveDialog,
     SaveCurrent,
     New,
     Run,
+    Profile,
 
     StepRun,
 
+    StepInto,
+    StepOver,
+    StepOut,
+    StepBack,
+    StepContinue,
     StepStop,
     ToggleDocs,
     ToggleTreeView,
     ToggleVarView,
+    ToggleHeapView,
+    ToggleProfileView,
+    ToggleProblems,
+    ToggleOutline,
+    ToggleExplorer,
+    ToggleImportGraph,
+    FoldAll,
+    UnfoldAll,
     OpenSettings,
     OpenRecent(PathBuf),
+    OpenRecording,
+    SaveRecording,
     Search,
     Replace,
+    FindInFiles,
+    GoToDefinition,
+    FindReferences,
+    RenameSymbol,
+    FormatDocument,
     None,
 }
 
@@ -32,25 +54,75 @@ const BTN_H: f32 = 28.0;
 const BTN_ROUNDING: f32 = 5.0;
 const ICON_BTN_W: f32 = 34.0;
 
+/// What the menu bar reflects of the app: the file, the run, and which
+/// windows and panels are open.
+#[derive(Clone, Copy)]
+pub struct MenuBarView<'a> {
+    pub current_file: Option<&'a PathBuf>,
+    pub recent_files: &'a [PathBuf],
+    pub is_running: bool,
+    pub is_debugging: bool,
+    pub search_bar_visible: bool,
+    pub docs_open: bool,
+    pub tree_view_open: bool,
+    pub var_view_open: bool,
+    pub heap_view_open: bool,
+    pub profile_view_open: bool,
+    pub problems_open: bool,
+    pub outline_open: bool,
+    pub explorer_open: bool,
+    pub import_graph_open: bool,
+}
+
 pub fn show_menu_bar(
     ctx: &egui::Context,
     _state: &mut MenuBarState,
-    current_file: Option<&PathBuf>,
-    is_running: bool,
-    is_debugging: bool,
-    docs_open: bool,
-    tree_view_open: bool,
-    var_view_open: bool,
+    view: &MenuBarView,
     theme: &Theme,
-    recent_files: &[PathBuf],
-    search_bar_visible: bool,
 ) -> MenuAction {
+    let MenuBarView {
+        current_file,
+        recent_files,
+        is_running,
+        is_debugging,
+        search_bar_visible,
+        docs_open,
+        tree_view_open,
+        var_view_open,
+        heap_view_open,
+        profile_view_open,
+        problems_open,
+        outline_open,
+        explorer_open,
+        import_graph_open,
+    } = *view;
     let mut action = MenuAction::None;
 
     ctx.input_mut(|i| {
         let ctrl = i.modifiers.ctrl || i.modifiers.mac_cmd;
         if ctrl && i.modifiers.shift && i.key_pressed(egui::Key::S) {
             action = MenuAction::SaveDialog;
+        } else if ctrl && i.modifiers.shift && i.key_pressed(egui::Key::M) {
+            action = MenuAction::ToggleProblems;
+        } else if ctrl && i.modifiers.shift && i.key_pressed(egui::Key::O) {
+            action = MenuAction::ToggleOutline;
+        } else if ctrl && i.modifiers.shift && i.key_pressed(egui::Key::E) {
+            action = MenuAction::ToggleExplorer;
+        } else if ctrl && i.modifiers.shift && i.key_pressed(egui::Key::F) {
+            action = MenuAction::FindInFiles;
+        } else if i.modifiers.shift && i.modifiers.alt && i.key_pressed(egui::Key::F) {
+            action = MenuAction::FormatDocument;
+        } else if ctrl
+            && i.modifiers.shift
+            && (i.key_pressed(egui::Key::OpenBracket) || i.key_pressed(egui::Key::OpenCurlyBracket))
+        {
+            action = MenuAction::FoldAll;
+        } else if ctrl
+            && i.modifiers.shift
+            && (i.key_pressed(egui::Key::CloseBracket)
+                || i.key_pressed(egui::Key::CloseCurlyBracket))
+        {
+            action = MenuAction::UnfoldAll;
         } else if ctrl && i.key_pressed(egui::Key::S) {
             action = if current_file.is_some() {
                 MenuAction::SaveCurrent
@@ -67,10 +139,26 @@ pub fn show_menu_bar(
             action = MenuAction::Search;
         } else if ctrl && i.key_pressed(egui::Key::H) {
             action = MenuAction::Replace;
+        } else if i.modifiers.shift && i.key_pressed(egui::Key::F12) {
+            action = MenuAction::FindReferences;
+        } else if i.key_pressed(egui::Key::F12) {
+            action = MenuAction::GoToDefinition;
+        } else if i.key_pressed(egui::Key::F2) {
+            action = MenuAction::RenameSymbol;
         } else if i.key_pressed(egui::Key::F5) {
             action = MenuAction::StepRun;
         } else if i.key_pressed(egui::Key::F6) {
             action = MenuAction::StepStop;
+        } else if i.key_pressed(egui::Key::F8) && is_debugging {
+            action = MenuAction::StepContinue;
+        } else if i.key_pressed(egui::Key::F7) && is_debugging {
+            action = MenuAction::StepBack;
+        } else if i.key_pressed(egui::Key::F10) && is_debugging {
+            action = MenuAction::StepOver;
+        } else if i.modifiers.shift && i.key_pressed(egui::Key::F11) && is_debugging {
+            action = MenuAction::StepOut;
+        } else if i.key_pressed(egui::Key::F11) && is_debugging {
+            action = MenuAction::StepInto;
         }
     });
 
@@ -163,6 +251,10 @@ pub fn show_menu_bar(
                             action = MenuAction::OpenDialog;
                             Popup::close_id(ctx, file_id);
                         }
+                        if icon_menu_item(ui, ic::OPEN_FOLDER, "Open Folder…", "", t) {
+                            action = MenuAction::OpenFolder;
+                            Popup::close_id(ctx, file_id);
+                        }
                         if icon_menu_item(ui, ic::FILE_SAVE, "Save", "Ctrl+S", t) {
                             action = if current_file.is_some() {
                                 MenuAction::SaveCurrent
@@ -178,6 +270,17 @@ pub fn show_menu_bar(
 
                         styled_separator(ui, t);
 
+                        if icon_menu_item(ui, ic::RECORDING, "Open Recorded Session…", "", t) {
+                            action = MenuAction::OpenRecording;
+                            Popup::close_id(ctx, file_id);
+                        }
+                        if icon_menu_item(ui, ic::FILE_SAVE_AS, "Save Recorded Session…", "", t) {
+                            action = MenuAction::SaveRecording;
+                            Popup::close_id(ctx, file_id);
+                        }
+
+                        styled_separator(ui, t);
+
                         let submenu_open = ui
                             .ctx()
                             .data(|d| d.get_temp::<bool>(recent_submenu_open_id).unwrap_or(false));
@@ -353,6 +456,36 @@ pub fn show_menu_bar(
                             action = MenuAction::Replace;
                             Popup::close_id(ctx, file_id);
                         }
+                        if icon_menu_item(
+                            ui,
+                            ic::FIND_IN_FILES,
+                            "Find in Files…",
+                            "Ctrl+Shift+F",
+                            t,
+                        ) {
+                            action = MenuAction::FindInFiles;
+                            Popup::close_id(ctx, file_id);
+                        }
+
+                        styled_separator(ui, t);
+
+                        if icon_menu_item(ui, ic::DEFINITION, "Go to Definition", "F12", t) {
+                            action = MenuAction::GoToDefinition;
+                            Popup::close_id(ctx, file_id);
+                        }
+                        if icon_menu_item(ui, ic::REFERENCES, "Find References", "Shift+F12", t) {
+                            action = MenuAction::FindReferences;
+                            Popup::close_id(ctx, file_id);
+                        }
+                        if icon_menu_item(ui, ic::RENAME, "Rename Symbol…", "F2", t) {
+                            action = MenuAction::RenameSymbol;
+                            Popup::close_id(ctx, file_id);
+                        }
+                        if icon_menu_item(ui, ic::FORMAT_CODE, "Format Document", "Shift+Alt+F", t)
+                        {
+                            action = MenuAction::FormatDocument;
+                            Popup::close_id(ctx, file_id);
+                        }
 
                         styled_separator(ui, t);
 
@@ -397,7 +530,7 @@ pub fn show_menu_bar(
                 ui.add_space(6.0);
 
                 let step_label = if is_debugging {
-                    "Step  F5"
+                    "Into  F5"
                 } else {
                     "Debug  F5"
                 };
@@ -410,10 +543,41 @@ pub fn show_menu_bar(
 
                 let step_resp = ui.interact(step_rect, step_id, egui::Sense::click());
                 if step_resp.clicked() {
-                    action = MenuAction::StepRun;
+                    action = if is_debugging {
+                        MenuAction::StepInto
+                    } else {
+                        MenuAction::StepRun
+                    };
+                }
+
+                if !is_debugging {
+                    ui.add_space(4.0);
+                    let label = format!("{}  Profile", ic::PROFILE);
+                    if paint_debug_button(ui, egui::Id::new("menu_profile_btn"), &label, t)
+                        && !is_running
+                    {
+                        action = MenuAction::Profile;
+                    }
                 }
 
                 if is_debugging {
+                    let debug_buttons = [
+                        ("menu_back_btn", "↶  Back  F7", MenuAction::StepBack),
+                        ("menu_over_btn", "↷  Over  F10", MenuAction::StepOver),
+                        ("menu_out_btn", "↑  Out  ⇧F11", MenuAction::StepOut),
+                        (
+                            "menu_continue_btn",
+                            "▶▶  Continue  F8",
+                            MenuAction::StepContinue,
+                        ),
+                    ];
+                    for (id, label, btn_action) in debug_buttons {
+                        ui.add_space(4.0);
+                        if paint_debug_button(ui, egui::Id::new(id), label, t) {
+                            action = btn_action;
+                        }
+                    }
+
                     ui.add_space(4.0);
                     let stop_id = egui::Id::new("menu_stop_btn");
                     let (stop_rect, _) =
@@ -507,6 +671,71 @@ pub fn show_menu_bar(
                             action = MenuAction::ToggleVarView;
                             Popup::close_id(ctx, view_id);
                         }
+                        let heap_label = if heap_view_open {
+                            "✓  Heap Graph"
+                        } else {
+                            "   Heap Graph"
+                        };
+                        if icon_menu_item(ui, ic::HEAP_VIEW, heap_label, "", t) {
+                            action = MenuAction::ToggleHeapView;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        let profile_label = if profile_view_open {
+                            "✓  Profile"
+                        } else {
+                            "   Profile"
+                        };
+                        if icon_menu_item(ui, ic::PROFILE, profile_label, "", t) {
+                            action = MenuAction::ToggleProfileView;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        let problems_label = if problems_open {
+                            "✓  Problems"
+                        } else {
+                            "   Problems"
+                        };
+                        if icon_menu_item(ui, ic::PROBLEMS, problems_label, "Ctrl+Shift+M", t) {
+                            action = MenuAction::ToggleProblems;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        let outline_label = if outline_open {
+                            "✓  Outline"
+                        } else {
+                            "   Outline"
+                        };
+                        if icon_menu_item(ui, ic::OUTLINE, outline_label, "Ctrl+Shift+O", t) {
+                            action = MenuAction::ToggleOutline;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        let explorer_label = if explorer_open {
+                            "✓  Explorer"
+                        } else {
+                            "   Explorer"
+                        };
+                        if icon_menu_item(ui, ic::EXPLORER, explorer_label, "Ctrl+Shift+E", t) {
+                            action = MenuAction::ToggleExplorer;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        let graph_label = if import_graph_open {
+                            "✓  Import Graph"
+                        } else {
+                            "   Import Graph"
+                        };
+                        if icon_menu_item(ui, ic::IMPORT_GRAPH, graph_label, "", t) {
+                            action = MenuAction::ToggleImportGraph;
+                            Popup::close_id(ctx, view_id);
+                        }
+
+                        styled_separator(ui, t);
+
+                        if icon_menu_item(ui, ic::FOLD, "   Fold All", "Ctrl+Shift+[", t) {
+                            action = MenuAction::FoldAll;
+                            Popup::close_id(ctx, view_id);
+                        }
+                        if icon_menu_item(ui, ic::UNFOLD, "   Unfold All", "Ctrl+Shift+]", t) {
+                            action = MenuAction::UnfoldAll;
+                            Popup::close_id(ctx, view_id);
+                        }
                         ui.add_space(4.0);
                     });
                 }
@@ -719,6 +948,34 @@ fn icon_menu_item(ui: &mut egui::Ui, icon: &str, label: &str, shortcut: &str, t:
     resp.clicked()
 }
 
+fn paint_debug_button(ui: &mut egui::Ui, id: egui::Id, label: &str, t: &Theme) -> bool {
+    let width = ui
+        .painter()
+        .layout_no_wrap(
+            label.to_string(),
+            egui::FontId::proportional(12.5),
+            t.accent,
+        )
+        .size()
+        .x
+        + 20.0;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, BTN_H), egui::Sense::hover());
+    let hovered = ui.rect_contains_pointer(rect);
+    if hovered {
+        ui.painter()
+            .rect_filled(rect, egui::CornerRadius::same(BTN_ROUNDING as u8), t.accent);
+    }
+    let fg = if hovered { t.tab_bar_bg } else { t.accent };
+    ui.painter().text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        label,
+        egui::FontId::proportional(12.5),
+        fg,
+    );
+    ui.interact(rect, id, egui::Sense::click()).clicked()
+}
+
 fn paint_step_button(
     ui: &egui::Ui,
     rect: egui::Rect,
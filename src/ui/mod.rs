@@ -1,16 +1,30 @@
+pub mod breakpoint_dialog;
 pub mod close_confirm;
+pub mod completion;
+pub mod diagnostics;
 pub mod docs;
+pub mod editing;
 pub mod editor;
 pub mod file_dialog;
-pub mod formatter;
+pub mod find_in_files;
+pub mod folding;
+pub mod heap_view;
 pub mod highlighter;
+pub mod hover;
 pub mod icons;
+pub mod import_graph;
 pub mod menu_bar;
+pub mod outline;
+pub mod profile_view;
+pub mod references;
 pub mod tab;
 pub mod terminal;
 pub mod theme;
+pub mod timeline;
 pub mod user_profile;
 pub mod search_bar;
 pub mod tree_view;
 pub mod var_view;
+pub mod watch;
+pub mod workspace;
 pub mod debugger;
\ No newline at end of file
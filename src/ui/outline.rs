@@ -0,0 +1,345 @@
+use super::theme::Theme;
+use super::workspace;
+use crate::compiler::parser::ParseNode;
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+    Module,
+    Function,
+    Struct,
+    Constant,
+    Variable,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub kind: OutlineKind,
+    pub name: String,
+    /// The signature, fields or type shown next to the name.
+    pub detail: String,
+    pub line: usize,
+}
+
+/// The modules a file imports and the functions, structs and variables it
+/// declares at the top level, in the order they appear. `root` is the parse
+/// of `code`; the modules are found by their `!import` lines.
+pub fn outline(root: &ParseNode, code: &str) -> Vec<OutlineEntry> {
+    let ParseNode::Program(items) = root else {
+        return Vec::new();
+    };
+    let mut entries = imports(code);
+    for item in items {
+        let entry = match item {
+            ParseNode::FuncDef {
+                name,
+                params,
+                return_type,
+                line,
+                ..
+            } => OutlineEntry {
+                kind: OutlineKind::Function,
+                name: name.clone(),
+                detail: format!(
+                    "({}) -> {}",
+                    params.iter().map(named).collect::<Vec<_>>().join(", "),
+                    type_text(return_type)
+                ),
+                line: *line,
+            },
+            ParseNode::StructDef {
+                name, fields, line, ..
+            } => OutlineEntry {
+                kind: OutlineKind::Struct,
+                name: name.clone(),
+                detail: format!(
+                    "{{ {} }}",
+                    fields
+                        .iter()
+                        .map(|f| format!("{};", named(f)))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+                line: *line,
+            },
+            ParseNode::ConstDecl {
+                data_type,
+                name,
+                line,
+                ..
+            } => OutlineEntry {
+                kind: OutlineKind::Constant,
+                name: name.clone(),
+                detail: type_text(data_type),
+                line: *line,
+            },
+            ParseNode::Decl {
+                data_type,
+                name,
+                line,
+                ..
+            } => OutlineEntry {
+                kind: OutlineKind::Variable,
+                name: name.clone(),
+                detail: type_text(data_type),
+                line: *line,
+            },
+            ParseNode::StructDecl {
+                struct_name,
+                var_name,
+                line,
+                ..
+            } => OutlineEntry {
+                kind: OutlineKind::Variable,
+                name: var_name.clone(),
+                detail: format!(":struct<{struct_name}>"),
+                line: *line,
+            },
+            _ => continue,
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+// The modules of the `!import` lines of `code`.
+fn imports(code: &str) -> Vec<OutlineEntry> {
+    workspace::imports(code, None)
+        .into_iter()
+        .map(|import| OutlineEntry {
+            kind: OutlineKind::Module,
+            name: import.module,
+            detail: import.spec,
+            line: import.line,
+        })
+        .collect()
+}
+
+// A parameter or field as it is written, such as `:int x`.
+fn named(node: &ParseNode) -> String {
+    match node {
+        ParseNode::Param {
+            data_type, name, ..
+        }
+        | ParseNode::Field { data_type, name } => {
+            format!("{} {name}", type_text(data_type))
+        }
+        other => type_text(other),
+    }
+}
+
+// A type node the way the semanter displays the type it resolves to.
+fn type_text(node: &ParseNode) -> String {
+    match node {
+        ParseNode::TypeInt(_) => ":int".into(),
+        ParseNode::TypeFloat(_) => ":float".into(),
+        ParseNode::TypeChar(_) => ":char".into(),
+        ParseNode::TypeBoolean(_) => ":boolean".into(),
+        ParseNode::TypeVoid(_) => ":void".into(),
+        ParseNode::TypeArray {
+            elem,
+            size,
+            size_const,
+            ..
+        } => match size_const {
+            Some(c) => format!(":array<{}, {c}>", type_text(elem)),
+            None => format!(":array<{}, {size}>", type_text(elem)),
+        },
+        ParseNode::TypeList { elem, .. } => format!(":list<{}>", type_text(elem)),
+        ParseNode::TypeStruct { name, .. } => format!(":struct<{name}>"),
+        ParseNode::TypeFunc { params, ret, .. } => format!(
+            ":func<({}) -> {}>",
+            params.iter().map(type_text).collect::<Vec<_>>().join(", "),
+            type_text(ret)
+        ),
+        _ => "?".into(),
+    }
+}
+
+/// A side panel listing the outline of the active file.
+#[derive(Default)]
+pub struct OutlinePanel {
+    pub open: bool,
+    filter: String,
+    focus_filter: bool,
+}
+
+impl OutlinePanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.focus_filter = self.open;
+    }
+
+    /// Shows the entries of `root`, the last parse of `code`, that match the
+    /// filter, and returns the line of the one clicked. Enter in the filter
+    /// picks the first.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        root: Option<&ParseNode>,
+        code: &str,
+        theme: &Theme,
+    ) -> Option<usize> {
+        if !self.open {
+            return None;
+        }
+
+        let t = *theme;
+        let mut clicked = None;
+        let entries = root.map(|root| outline(root, code)).unwrap_or_default();
+        let filter = self.filter.to_lowercase();
+        let shown: Vec<&OutlineEntry> = entries
+            .iter()
+            .filter(|e| e.name.to_lowercase().contains(&filter))
+            .collect();
+
+        egui::SidePanel::right("fractal_outline")
+            .resizable(true)
+            .default_width(260.0)
+            .min_width(180.0)
+            .frame(
+                egui::Frame::new()
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border)),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Outline")
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    let close = ui.add(
+                                        egui::Button::new(
+                                            egui::RichText::new(super::icons::WINDOW_CLOSE)
+                                                .size(14.0)
+                                                .color(t.tab_inactive_fg),
+                                        )
+                                        .frame(false),
+                                    );
+                                    if close.clicked() {
+                                        self.open = false;
+                                    }
+                                },
+                            );
+                        });
+                        let filter_resp = ui.add(
+                            egui::TextEdit::singleline(&mut self.filter)
+                                .desired_width(f32::INFINITY)
+                                .hint_text("Filter…")
+                                .font(egui::FontId::proportional(13.0)),
+                        );
+                        if std::mem::take(&mut self.focus_filter) {
+                            filter_resp.request_focus();
+                        }
+                        if filter_resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))
+                        {
+                            clicked = shown.first().map(|e| e.line);
+                        }
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                let empty = if root.is_none() {
+                    Some("The outline appears once the file parses")
+                } else if entries.is_empty() {
+                    Some("Nothing is declared in this file")
+                } else if shown.is_empty() {
+                    Some("No entries match the filter")
+                } else {
+                    None
+                };
+                if let Some(message) = empty {
+                    ui.add_space(16.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new(message)
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                        );
+                    });
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .id_salt("outline_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Frame::new()
+                            .inner_margin(egui::Margin::symmetric(8, 6))
+                            .show(ui, |ui| {
+                                ui.spacing_mut().item_spacing.y = 2.0;
+                                for entry in &shown {
+                                    if outline_row(ui, entry, &t).clicked() {
+                                        clicked = Some(entry.line);
+                                    }
+                                }
+                            });
+                    });
+            });
+
+        clicked
+    }
+}
+
+// One entry of the outline: its kind, its name and its detail.
+fn outline_row(ui: &mut egui::Ui, entry: &OutlineEntry, t: &Theme) -> egui::Response {
+    let (tag, tag_color) = match entry.kind {
+        OutlineKind::Module => ("mod", t.struct_name),
+        OutlineKind::Function => ("fn", t.fn_name),
+        OutlineKind::Struct => ("struct", t.struct_name),
+        OutlineKind::Constant => ("const", t.number),
+        OutlineKind::Variable => ("var", t.identifier),
+    };
+    let font = egui::FontId::monospace(12.0);
+    let (rect, resp) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 20.0), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    if resp.hovered() {
+        painter.rect_filled(rect, egui::CornerRadius::same(3), t.menu_hover_bg);
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    painter.text(
+        egui::pos2(rect.left() + 4.0, rect.center().y),
+        egui::Align2::LEFT_CENTER,
+        tag,
+        egui::FontId::proportional(10.0),
+        tag_color,
+    );
+    let label = painter.text(
+        egui::pos2(rect.left() + 42.0, rect.center().y),
+        egui::Align2::LEFT_CENTER,
+        &entry.name,
+        font.clone(),
+        t.tab_active_fg,
+    );
+    painter.text(
+        egui::pos2(label.right() + 8.0, rect.center().y),
+        egui::Align2::LEFT_CENTER,
+        &entry.detail,
+        font,
+        t.line_numbers_fg,
+    );
+    resp.on_hover_text(format!(
+        "{} {}\nline {}",
+        entry.name, entry.detail, entry.line
+    ))
+}
@@ -0,0 +1,287 @@
+use super::editor::LineHeat;
+use super::theme::Theme;
+use crate::compiler::profile::{format_nanos, FuncStat, LineStat, Profile};
+use eframe::egui;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Lines listed under the function table.
+const HOT_LINES: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum SortBy {
+    Calls,
+    #[default]
+    Total,
+    Own,
+}
+
+struct Run {
+    source: PathBuf,
+    profile: Profile,
+}
+
+#[derive(Default)]
+pub struct ProfileWindow {
+    pub open: bool,
+    // One run per program, so that two programs can be compared side by side.
+    runs: Vec<Run>,
+    selected: usize,
+    sort: SortBy,
+}
+
+impl ProfileWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the profile of a run of `source`, replacing the previous run of
+    /// the same program, and selects it.
+    pub fn add_run(&mut self, source: PathBuf, profile: Profile) {
+        match self.runs.iter().position(|r| r.source == source) {
+            Some(i) => {
+                self.runs[i].profile = profile;
+                self.selected = i;
+            }
+            None => {
+                self.runs.push(Run { source, profile });
+                self.selected = self.runs.len() - 1;
+            }
+        }
+        self.open = true;
+    }
+
+    /// Draws the functions of the selected run sorted by calls, total or own
+    /// time, followed by its hottest lines.
+    pub fn show(&mut self, ctx: &egui::Context, theme: &Theme) {
+        if !self.open {
+            return;
+        }
+
+        let t = *theme;
+        let mut open = self.open;
+
+        egui::Window::new("Profile")
+            .id(egui::Id::new("fractal_profile_view"))
+            .open(&mut open)
+            .default_size([520.0, 460.0])
+            .min_size([320.0, 200.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(
+                                egui::RichText::new("Profile")
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            );
+                            ui.add_space(8.0);
+                            for (i, run) in self.runs.iter().enumerate() {
+                                let text = format!(
+                                    "{}  {}",
+                                    file_name(&run.source),
+                                    format_nanos(run.profile.total_nanos())
+                                );
+                                if ui
+                                    .selectable_label(
+                                        i == self.selected,
+                                        egui::RichText::new(text).size(12.0),
+                                    )
+                                    .clicked()
+                                {
+                                    self.selected = i;
+                                }
+                            }
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                let Some(run) = self.runs.get(self.selected) else {
+                    ui.add_space(16.0);
+                    ui.vertical_centered(|ui| {
+                        ui.label(
+                            egui::RichText::new("No profile yet - press Profile to time a run")
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                        );
+                    });
+                    return;
+                };
+
+                let total = run.profile.total_nanos().max(1);
+                let mut functions: Vec<&FuncStat> = run.profile.functions.iter().collect();
+                match self.sort {
+                    SortBy::Calls => functions.sort_by_key(|f| Reverse(f.calls)),
+                    SortBy::Total => functions.sort_by_key(|f| Reverse(f.total_nanos)),
+                    SortBy::Own => functions.sort_by_key(|f| Reverse(f.self_nanos)),
+                }
+                let mut lines: Vec<&LineStat> = run.profile.lines.iter().collect();
+                lines.sort_by_key(|l| Reverse(l.nanos));
+                lines.truncate(HOT_LINES);
+
+                egui::ScrollArea::vertical()
+                    .id_salt("profile_view_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Frame::new()
+                            .inner_margin(egui::Margin::symmetric(12, 8))
+                            .show(ui, |ui| {
+                                egui::Grid::new("profile_functions")
+                                    .num_columns(5)
+                                    .striped(true)
+                                    .spacing([16.0, 4.0])
+                                    .show(ui, |ui| {
+                                        ui.label(heading("Function", &t));
+                                        for (label, sort) in [
+                                            ("Calls", SortBy::Calls),
+                                            ("Total", SortBy::Total),
+                                            ("Self", SortBy::Own),
+                                        ] {
+                                            let text = if self.sort == sort {
+                                                format!("{label} ▼")
+                                            } else {
+                                                label.to_string()
+                                            };
+                                            if ui
+                                                .selectable_label(
+                                                    self.sort == sort,
+                                                    heading(&text, &t),
+                                                )
+                                                .clicked()
+                                            {
+                                                self.sort = sort;
+                                            }
+                                        }
+                                        ui.label(heading("% of run", &t));
+                                        ui.end_row();
+
+                                        for f in &functions {
+                                            ui.label(
+                                                egui::RichText::new(&f.name)
+                                                    .monospace()
+                                                    .color(t.tab_active_fg),
+                                            );
+                                            ui.label(cell(&f.calls.to_string(), &t));
+                                            ui.label(cell(&format_nanos(f.total_nanos), &t));
+                                            ui.label(cell(&format_nanos(f.self_nanos), &t));
+                                            share_bar(ui, f.self_nanos as f32 / total as f32, &t);
+                                            ui.end_row();
+                                        }
+                                    });
+
+                                ui.add_space(14.0);
+                                ui.label(heading("Hottest lines", &t));
+                                ui.add_space(4.0);
+                                egui::Grid::new("profile_lines")
+                                    .num_columns(4)
+                                    .striped(true)
+                                    .spacing([16.0, 4.0])
+                                    .show(ui, |ui| {
+                                        for l in &lines {
+                                            let place = if l.file.is_empty() {
+                                                format!("line {}", l.line)
+                                            } else {
+                                                format!("{}: line {}", l.file, l.line)
+                                            };
+                                            ui.label(cell(&place, &t));
+                                            ui.label(cell(&hits(l.hits), &t));
+                                            ui.label(cell(&format_nanos(l.nanos), &t));
+                                            share_bar(ui, l.nanos as f32 / total as f32, &t);
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                    });
+            });
+
+        self.open = open;
+    }
+}
+
+/// The heatmap for the lines of `file` in `profile`, where `file` is the key
+/// the runtime uses for it: empty for the profiled program, otherwise the
+/// module name. Levels are relative to the hottest line of the whole run.
+pub fn line_heat(profile: &Profile, file: &str) -> BTreeMap<usize, LineHeat> {
+    let hottest = profile
+        .lines
+        .iter()
+        .map(|l| l.nanos)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let total = profile.total_nanos().max(1);
+    profile
+        .lines
+        .iter()
+        .filter(|l| l.file == file)
+        .map(|l| {
+            let heat = LineHeat {
+                level: l.nanos as f32 / hottest as f32,
+                label: format!(
+                    "{}, {} ({:.1}% of the run)",
+                    hits(l.hits),
+                    format_nanos(l.nanos),
+                    l.nanos as f64 * 100.0 / total as f64
+                ),
+            };
+            (l.line, heat)
+        })
+        .collect()
+}
+
+fn hits(n: u64) -> String {
+    if n == 1 {
+        "1 hit".to_string()
+    } else {
+        format!("{n} hits")
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn heading(text: &str, t: &Theme) -> egui::RichText {
+    egui::RichText::new(text)
+        .size(12.0)
+        .color(t.tab_inactive_fg)
+        .strong()
+}
+
+fn cell(text: &str, t: &Theme) -> egui::RichText {
+    egui::RichText::new(text).size(12.0).color(t.tab_active_fg)
+}
+
+fn share_bar(ui: &mut egui::Ui, share: f32, t: &Theme) {
+    let share = share.clamp(0.0, 1.0);
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(80.0, 10.0), egui::Sense::hover());
+        ui.painter()
+            .rect_filled(rect, egui::CornerRadius::same(2), t.button_bg);
+        let filled =
+            egui::Rect::from_min_size(rect.min, egui::vec2(rect.width() * share, rect.height()));
+        ui.painter()
+            .rect_filled(filled, egui::CornerRadius::same(2), t.terminal_error);
+        ui.label(cell(&format!("{:.1}%", share * 100.0), t));
+    });
+}
@@ -0,0 +1,873 @@
+use super::icons as ic;
+use super::tab::Tab;
+use super::theme::Theme;
+use super::workspace::imports;
+use crate::compiler::builtins::{BuiltinDef, ALL_BUILTINS};
+use crate::compiler::semanter::{Location, Symbol};
+use eframe::egui;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file a symbol is written in: an open tab, or an imported module that is
+/// not open.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileRef {
+    Tab(usize),
+    Path(PathBuf),
+}
+
+/// A name in a file: `col` is the 1-based column it starts at, or 0 for the
+/// start of the line when there is no one name to point at.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Place {
+    pub file: FileRef,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// A place a symbol is declared or used at.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub place: Place,
+    /// The name as written there: `m::x` for a use of a module's `x` from
+    /// outside the module.
+    pub written: String,
+    pub is_def: bool,
+    /// The line itself, for the references list.
+    pub text: String,
+}
+
+/// Everything one symbol is known by across the open tabs.
+#[derive(Debug, Clone)]
+pub struct Found {
+    /// The name as declared, without any module.
+    pub name: String,
+    pub occurrences: Vec<Occurrence>,
+}
+
+/// What the identifier at a cursor names.
+pub enum Target<'a> {
+    Symbol(&'a Symbol),
+    Module(String),
+    Builtin(&'static BuiltinDef),
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn base_name(name: &str) -> &str {
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// Which chars of `chars` are code rather than comments or strings.
+fn code_mask(chars: &[char]) -> Vec<bool> {
+    let mut mask = vec![true; chars.len()];
+    let mut i = 0;
+    while i < chars.len() {
+        let end = match chars[i] {
+            '#' if chars[i..].starts_with(&['#', '#', '#']) => (i + 3..chars.len())
+                .find(|&j| chars[j..].starts_with(&['#', '#', '#']))
+                .map_or(chars.len(), |j| j + 3),
+            '#' => (i..chars.len())
+                .find(|&j| chars[j] == '\n')
+                .unwrap_or(chars.len()),
+            q @ ('"' | '\'') => {
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] != q && chars[j] != '\n' {
+                    j += if chars[j] == '\\' { 2 } else { 1 };
+                }
+                (j + 1).min(chars.len())
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        mask[i..end].iter_mut().for_each(|m| *m = false);
+        i = end;
+    }
+    mask
+}
+
+// The char range of the identifier `cursor` is in or just after.
+fn word_at(chars: &[char], cursor: usize) -> Option<(usize, usize)> {
+    let cursor = cursor.min(chars.len());
+    let mut start = cursor;
+    while start > 0 && is_ident(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = cursor;
+    while end < chars.len() && is_ident(chars[end]) {
+        end += 1;
+    }
+    (start < end && !chars[start].is_ascii_digit()).then_some((start, end))
+}
+
+// The identifier in front of the `::` that ends at `end`, if there is one.
+fn qualifier(chars: &[char], end: usize) -> Option<String> {
+    if end < 2 || chars[end - 2..end] != [':', ':'] {
+        return None;
+    }
+    let (start, _) = word_at(chars, end - 2)?;
+    Some(chars[start..end - 2].iter().collect())
+}
+
+/// The symbol, module or builtin named at char index `cursor` of `code`,
+/// going by the places `symbols` were last seen at.
+pub fn target_at<'a>(code: &str, cursor: usize, symbols: &'a [Symbol]) -> Option<Target<'a>> {
+    let chars: Vec<char> = code.chars().collect();
+    let (start, end) = word_at(&chars, cursor)?;
+    if !code_mask(&chars)[start] {
+        return None;
+    }
+    let word: String = chars[start..end].iter().collect();
+    let line = chars[..start].iter().filter(|&&c| c == '\n').count() + 1;
+    let line_start = chars[..start]
+        .iter()
+        .rposition(|&c| c == '\n')
+        .map_or(0, |n| n + 1);
+
+    let is_module = |m: &str| {
+        symbols
+            .iter()
+            .any(|s| s.name.split_once("::").is_some_and(|(q, _)| q == m))
+    };
+    if chars[end..].starts_with(&[':', ':']) && is_module(&word) {
+        return Some(Target::Module(word));
+    }
+    if let Some(q) = qualifier(&chars, start) {
+        // Anything else after `::` is a struct field.
+        let qualified = format!("{q}::{word}");
+        return symbols
+            .iter()
+            .find(|s| s.name == qualified)
+            .map(Target::Symbol);
+    }
+
+    let here = Location {
+        file: String::new(),
+        line,
+        col: start - line_start + 1,
+    };
+    let named = || symbols.iter().filter(|s| s.name == word);
+    named()
+        .filter(|s| s.def.as_ref() == Some(&here) || s.refs.contains(&here))
+        .max_by_key(|s| s.scope_depth)
+        // The places may be stale while the code is being edited.
+        .or_else(|| named().find(|s| s.scope_depth == 0))
+        .map(Target::Symbol)
+        .or_else(|| {
+            ALL_BUILTINS
+                .iter()
+                .find(|b| b.name == word)
+                .map(Target::Builtin)
+        })
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (fs::canonicalize(a), fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+fn open_tab(tabs: &[Tab], path: &Path) -> Option<usize> {
+    tabs.iter()
+        .position(|t| t.source_path().is_some_and(|p| same_file(p, path)))
+}
+
+// Where `loc`, as the check of tab `index` saw it, is now.
+fn place_of(tabs: &[Tab], index: usize, loc: &Location) -> Option<Place> {
+    let file = if loc.file.is_empty() {
+        FileRef::Tab(index)
+    } else {
+        let path = tabs[index].live_check.modules.get(&loc.file)?;
+        match open_tab(tabs, path) {
+            Some(i) => FileRef::Tab(i),
+            None => FileRef::Path(path.clone()),
+        }
+    };
+    Some(Place {
+        file,
+        line: loc.line,
+        col: loc.col,
+    })
+}
+
+/// The place the identifier at char index `cursor` of tab `index` is
+/// declared, with the name to look for on that line. A module name, or an
+/// `!import` of it, leads to the top of its file.
+pub fn definition(tabs: &[Tab], index: usize, cursor: usize) -> Option<(Place, String)> {
+    let tab = tabs.get(index)?;
+    let line = tab.code.chars().take(cursor).filter(|&c| c == '\n').count() + 1;
+    let source = tab.source_path().map(PathBuf::as_path);
+    if let Some(import) = imports(&tab.code, source)
+        .into_iter()
+        .find(|i| i.line == line)
+    {
+        let path = import.path.filter(|p| p.is_file())?;
+        let file = match open_tab(tabs, &path) {
+            Some(i) => FileRef::Tab(i),
+            None => FileRef::Path(path),
+        };
+        return Some((
+            Place {
+                file,
+                line: 1,
+                col: 0,
+            },
+            import.module,
+        ));
+    }
+    match target_at(&tab.code, cursor, &tab.live_check.symbols)? {
+        Target::Symbol(s) => {
+            let place = place_of(tabs, index, s.def.as_ref()?)?;
+            Some((place, base_name(&s.name).to_string()))
+        }
+        Target::Module(m) => {
+            let path = tab.live_check.modules.get(&m)?;
+            let file = match open_tab(tabs, path) {
+                Some(i) => FileRef::Tab(i),
+                None => FileRef::Path(path.clone()),
+            };
+            Some((
+                Place {
+                    file,
+                    line: 1,
+                    col: 0,
+                },
+                m,
+            ))
+        }
+        Target::Builtin(_) => None,
+    }
+}
+
+/// Every place the symbol at char index `cursor` of tab `index` is declared
+/// or used at, in any open tab and the modules they import.
+pub fn find(tabs: &[Tab], index: usize, cursor: usize) -> Result<Found, String> {
+    let tab = tabs.get(index).ok_or("No file is open.")?;
+    let symbol = match target_at(&tab.code, cursor, &tab.live_check.symbols) {
+        Some(Target::Symbol(s)) => s,
+        Some(Target::Module(m)) => return Err(format!("`{m}` is a module, not a symbol.")),
+        Some(Target::Builtin(b)) => return Err(format!("`{}` is built in.", b.name)),
+        None => return Err("Put the cursor on a variable, function or struct name.".into()),
+    };
+    let name = base_name(&symbol.name).to_string();
+    let def = symbol
+        .def
+        .as_ref()
+        .and_then(|d| place_of(tabs, index, d))
+        .ok_or_else(|| format!("Cannot tell where `{name}` is declared."))?;
+
+    // The same symbol is seen by every tab that imports its file, and by its
+    // module under the qualified name; the place it is declared ties them.
+    let mut places: BTreeMap<Place, (String, bool)> = BTreeMap::new();
+    places.insert(def.clone(), (name.clone(), true));
+    for j in 0..tabs.len() {
+        for s in &tabs[j].live_check.symbols {
+            let same = base_name(&s.name) == name
+                && s.def.as_ref().and_then(|d| place_of(tabs, j, d)).as_ref() == Some(&def);
+            if !same {
+                continue;
+            }
+            for r in &s.refs {
+                let Some(place) = place_of(tabs, j, r) else {
+                    continue;
+                };
+                // An open file is left to its own check, which is of its code
+                // as it is now rather than as saved.
+                if matches!(place.file, FileRef::Tab(k)
+                    if k != j && tabs[k].live_check.is_current(&tabs[k].code))
+                {
+                    continue;
+                }
+                places
+                    .entry(place)
+                    .or_insert_with(|| (s.name.clone(), false));
+            }
+        }
+    }
+
+    let mut texts: BTreeMap<FileRef, Vec<String>> = BTreeMap::new();
+    let occurrences = places
+        .into_iter()
+        .map(|(place, (written, is_def))| {
+            let lines = texts.entry(place.file.clone()).or_insert_with(|| {
+                let code = match &place.file {
+                    FileRef::Tab(i) => tabs[*i].code.clone(),
+                    FileRef::Path(p) => fs::read_to_string(p).unwrap_or_default(),
+                };
+                code.lines().map(str::to_string).collect()
+            });
+            let text = lines.get(place.line - 1).cloned().unwrap_or_default();
+            Occurrence {
+                place,
+                written,
+                is_def,
+                text,
+            }
+        })
+        .collect();
+    Ok(Found { name, occurrences })
+}
+
+/// Why `found` cannot be renamed yet, if it cannot: every tab it touches has
+/// to be checked as it is now, and no tab may be importing an unsaved one.
+pub fn check_rename(tabs: &[Tab], found: &Found) -> Result<(), String> {
+    let touched: BTreeSet<usize> = found
+        .occurrences
+        .iter()
+        .filter_map(|o| match o.place.file {
+            FileRef::Tab(i) => Some(i),
+            FileRef::Path(_) => None,
+        })
+        .collect();
+    for &i in &touched {
+        let tab = &tabs[i];
+        if !tab.live_check.is_current(&tab.code) {
+            return Err(format!(
+                "{} has not been checked since it last changed - fix its errors or try again in a moment.",
+                tab.display_name()
+            ));
+        }
+        let Some(path) = tab.source_path().filter(|_| tab.is_dirty()) else {
+            continue;
+        };
+        let importer = tabs
+            .iter()
+            .enumerate()
+            .find(|(j, t)| *j != i && t.live_check.modules.values().any(|m| same_file(m, path)));
+        if let Some((_, t)) = importer {
+            return Err(format!(
+                "Save {} first - {} imports it.",
+                tab.display_name(),
+                t.display_name()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The char ranges of `code` on `line` where the name `written` stands: the
+/// last part of `m::x`, or `x` on its own but not as a field after `::`.
+pub fn name_spans(code: &str, line: usize, written: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = code.chars().collect();
+    let mask = code_mask(&chars);
+    let mut start = 0;
+    for _ in 1..line {
+        match chars[start..].iter().position(|&c| c == '\n') {
+            Some(n) => start += n + 1,
+            None => return Vec::new(),
+        }
+    }
+    let end = chars[start..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |n| start + n);
+
+    let (module, name) = match written.rsplit_once("::") {
+        Some((m, n)) => (Some(m), n),
+        None => (None, written),
+    };
+    let mut spans = Vec::new();
+    let mut i = start;
+    while i < end {
+        if !is_ident(chars[i]) || (i > 0 && is_ident(chars[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let mut j = i;
+        while j < end && is_ident(chars[j]) {
+            j += 1;
+        }
+        let word: String = chars[i..j].iter().collect();
+        if mask[i] && word == name && qualifier(&chars, i).as_deref() == module {
+            spans.push((i, j));
+        }
+        i = j;
+    }
+    spans
+}
+
+/// How far into `line` the name `written` first stands, or 0 when it is not
+/// there.
+pub fn name_column(code: &str, line: usize, written: &str) -> usize {
+    let start: usize = code
+        .split('\n')
+        .take(line.saturating_sub(1))
+        .map(|l| l.chars().count() + 1)
+        .sum();
+    name_spans(code, line, written)
+        .first()
+        .map_or(0, |&(s, _)| s - start)
+}
+
+// The char range of `name` at column `col` of `line`, if it still stands
+// there on its own.
+fn name_at(chars: &[char], line: usize, col: usize, name: &str) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for _ in 1..line {
+        start += chars[start..].iter().position(|&c| c == '\n')? + 1;
+    }
+    let start = start + col.checked_sub(1)?;
+    let end = start + name.chars().count();
+    let fits = end <= chars.len()
+        && chars[start..end].iter().copied().eq(name.chars())
+        && (start == 0 || !is_ident(chars[start - 1]))
+        && !chars.get(end).is_some_and(|&c| is_ident(c));
+    fits.then_some((start, end))
+}
+
+/// Renames every occurrence in `found` to `new_name`, in the tabs they are
+/// in; modules that were not open have to be opened first. Only the name at
+/// each occurrence's column is changed, so a shadowed name or a field of the
+/// same name on the line is left alone. Returns how many names were changed.
+pub fn rename(tabs: &mut [Tab], found: &Found, new_name: &str) -> usize {
+    let mut edits: BTreeMap<usize, BTreeSet<(usize, usize)>> = BTreeMap::new();
+    let mut texts: BTreeMap<usize, Vec<char>> = BTreeMap::new();
+    for o in &found.occurrences {
+        let index = match &o.place.file {
+            FileRef::Tab(i) => Some(*i),
+            FileRef::Path(p) => open_tab(tabs, p),
+        };
+        let Some(i) = index else {
+            continue;
+        };
+        let chars = texts
+            .entry(i)
+            .or_insert_with(|| tabs[i].code.chars().collect());
+        if let Some(span) = name_at(chars, o.place.line, o.place.col, &found.name) {
+            edits.entry(i).or_default().insert(span);
+        }
+    }
+
+    let mut count = 0;
+    for (i, spans) in edits {
+        let mut chars = texts.remove(&i).unwrap_or_default();
+        for &(start, end) in spans.iter().rev() {
+            chars.splice(start..end, new_name.chars());
+            count += 1;
+        }
+        tabs[i].code = chars.into_iter().collect();
+    }
+    count
+}
+
+/// Whether `name` can be used as a variable, function or struct name.
+pub fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(is_ident)
+}
+
+#[derive(Default)]
+pub struct ReferencesWindow {
+    pub open: bool,
+    found: Option<Found>,
+}
+
+impl ReferencesWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn show_found(&mut self, found: Found) {
+        self.found = Some(found);
+        self.open = true;
+    }
+
+    /// Lists the occurrences grouped by file and returns the one clicked,
+    /// with the name written there.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        tabs: &[Tab],
+        theme: &Theme,
+    ) -> Option<(Place, String)> {
+        if !self.open {
+            return None;
+        }
+        let Some(found) = &self.found else {
+            return None;
+        };
+
+        let t = *theme;
+        let mut open = self.open;
+        let mut clicked = None;
+
+        egui::Window::new("References")
+            .id(egui::Id::new("fractal_references"))
+            .open(&mut open)
+            .default_size([520.0, 300.0])
+            .min_size([300.0, 140.0])
+            .resizable(true)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border))
+                    .inner_margin(egui::Margin::ZERO),
+            )
+            .show(ctx, |ui| {
+                let files: BTreeSet<&FileRef> =
+                    found.occurrences.iter().map(|o| &o.place.file).collect();
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new(&found.name)
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .monospace()
+                                    .strong(),
+                            );
+                            ui.add_space(8.0);
+                            let uses = found.occurrences.iter().filter(|o| !o.is_def).count();
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{} {} in {} {}",
+                                    uses,
+                                    if uses == 1 { "use" } else { "uses" },
+                                    files.len(),
+                                    if files.len() == 1 { "file" } else { "files" }
+                                ))
+                                .size(12.0)
+                                .color(t.tab_inactive_fg),
+                            );
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                egui::ScrollArea::vertical()
+                    .id_salt("references_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Frame::new()
+                            .inner_margin(egui::Margin::symmetric(12, 8))
+                            .show(ui, |ui| {
+                                for file in files {
+                                    ui.label(
+                                        egui::RichText::new(file_label(file, tabs))
+                                            .size(12.0)
+                                            .color(t.tab_inactive_fg)
+                                            .strong(),
+                                    );
+                                    for o in
+                                        found.occurrences.iter().filter(|o| &o.place.file == file)
+                                    {
+                                        if reference_row(ui, o, &t).clicked() {
+                                            clicked = Some((o.place.clone(), o.written.clone()));
+                                        }
+                                    }
+                                    ui.add_space(6.0);
+                                }
+                            });
+                    });
+            });
+
+        self.open = open;
+        clicked
+    }
+}
+
+fn file_label(file: &FileRef, tabs: &[Tab]) -> String {
+    match file {
+        FileRef::Tab(i) => tabs.get(*i).map(Tab::display_name).unwrap_or_default(),
+        FileRef::Path(p) => p
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn reference_row(ui: &mut egui::Ui, o: &Occurrence, t: &Theme) -> egui::Response {
+    let resp = ui
+        .horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("{:>5}", o.place.line))
+                    .size(11.0)
+                    .monospace()
+                    .color(t.line_numbers_fg),
+            );
+            ui.label(
+                egui::RichText::new(o.text.trim())
+                    .size(12.0)
+                    .monospace()
+                    .color(t.tab_active_fg),
+            );
+            if o.is_def {
+                ui.label(egui::RichText::new("definition").size(11.0).color(t.accent));
+            }
+        })
+        .response
+        .interact(egui::Sense::click());
+    if resp.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    resp
+}
+
+pub enum RenameAction {
+    Pending,
+    Apply(Found, String),
+}
+
+#[derive(Default)]
+pub struct RenameDialog {
+    pub visible: bool,
+    found: Option<Found>,
+    text: String,
+    error: Option<String>,
+    focus: bool,
+}
+
+impl RenameDialog {
+    pub fn open(&mut self, found: Found) {
+        self.text = found.name.clone();
+        self.found = Some(found);
+        self.error = None;
+        self.focus = true;
+        self.visible = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, theme: &Theme) -> RenameAction {
+        if !self.visible {
+            return RenameAction::Pending;
+        }
+        let Some(found) = &self.found else {
+            self.visible = false;
+            return RenameAction::Pending;
+        };
+
+        let mut action = RenameAction::Pending;
+        let mut open = true;
+
+        egui::Window::new(format!("Rename {}", found.name))
+            .id(egui::Id::new("fractal_rename_dialog"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(340.0)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .frame(egui::Frame::window(&ctx.style()).fill(theme.panel_bg))
+            .show(ctx, |ui| {
+                ui.add_space(4.0);
+                let places = found.occurrences.len();
+                ui.label(
+                    egui::RichText::new(format!(
+                        "New name, for {} {}:",
+                        places,
+                        if places == 1 { "place" } else { "places" }
+                    ))
+                    .size(13.0)
+                    .color(theme.tab_active_fg),
+                );
+                ui.add_space(6.0);
+
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut self.text)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY),
+                );
+                if self.focus {
+                    edit.request_focus();
+                    self.focus = false;
+                }
+                let submitted = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                if let Some(ref err) = self.error {
+                    ui.add_space(4.0);
+                    ui.label(
+                        egui::RichText::new(format!("{}  {}", ic::ERROR, err))
+                            .size(12.0)
+                            .color(theme.terminal_error),
+                    );
+                }
+
+                ui.add_space(10.0);
+                if ui.button(format!("{}  Rename", ic::RENAME)).clicked() || submitted {
+                    let name = self.text.trim();
+                    if !is_identifier(name) {
+                        self.error = Some(format!("`{name}` is not a valid name."));
+                        self.focus = true;
+                    } else if name != found.name {
+                        action = RenameAction::Apply(found.clone(), name.to_string());
+                    } else {
+                        self.visible = false;
+                    }
+                }
+            });
+
+        if matches!(action, RenameAction::Apply(..))
+            || !open
+            || ctx.input(|i| i.key_pressed(egui::Key::Escape))
+        {
+            self.visible = false;
+        }
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::diagnostic::Severity;
+    use crate::ui::diagnostics::check_source;
+
+    // A tab of `code`, saved at `path`, with a finished check of it.
+    fn checked_tab(path: &Path, code: &str) -> Tab {
+        fs::write(path, code).unwrap();
+        let mut tab = Tab::from_file(path.to_path_buf(), code.to_string(), Theme::dark());
+        let result = check_source(code, &path.to_string_lossy());
+        let errors: Vec<_> = result
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| &d.message)
+            .collect();
+        assert!(errors.is_empty(), "{errors:?}");
+        tab.live_check.symbols = result.symbols.unwrap();
+        tab.live_check.modules = result.modules;
+        tab
+    }
+
+    // The char index of the `nth` (from 0) `needle` in `code`.
+    fn cursor(code: &str, needle: &str, nth: usize) -> usize {
+        let (at, _) = code.match_indices(needle).nth(nth).unwrap();
+        code[..at].chars().count()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fractal-references-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn name_spans_tell_names_from_fields_and_qualified_names() {
+        let code = "!start\n:int x = m::x + p::x + x; # x\nprint(\"x\");\n!end\n";
+        let line_start = cursor(code, ":int", 0);
+        let spans = |written| -> Vec<(usize, usize)> {
+            name_spans(code, 2, written)
+                .into_iter()
+                .map(|(s, e)| (s - line_start, e - line_start))
+                .collect()
+        };
+        assert_eq!(spans("x"), [(5, 6), (23, 24)]);
+        assert_eq!(spans("m::x"), [(12, 13)]);
+        assert_eq!(spans("p::x"), [(19, 20)]);
+        assert!(name_spans(code, 3, "x").is_empty());
+        assert!(name_spans(code, 9, "x").is_empty());
+        assert_eq!(name_column(code, 2, "m::x"), 12);
+    }
+
+    #[test]
+    fn rename_leaves_a_shadowing_name_on_the_same_line_alone() {
+        let dir = scratch_dir("shadow");
+        let code = "!start\n\
+                    :int x = 1;\n\
+                    !if (x > 0) { :int x = 2; print(\"{}\\n\", x); }\n\
+                    print(\"{}\\n\", x);\n\
+                    !end\n";
+        let mut tabs = vec![checked_tab(&dir.join("main.fr"), code)];
+
+        let found = find(&tabs, 0, cursor(code, "x", 0)).unwrap();
+        assert_eq!(found.occurrences.len(), 3);
+        assert_eq!(rename(&mut tabs, &found, "total"), 3);
+        assert_eq!(
+            tabs[0].code,
+            "!start\n\
+             :int total = 1;\n\
+             !if (total > 0) { :int x = 2; print(\"{}\\n\", x); }\n\
+             print(\"{}\\n\", total);\n\
+             !end\n"
+        );
+
+        // The inner `x` on its own, once the renamed code is checked.
+        let code = tabs[0].code.clone();
+        tabs[0] = checked_tab(&dir.join("main.fr"), &code);
+        let found = find(&tabs, 0, cursor(&code, "x", 0)).unwrap();
+        assert_eq!(rename(&mut tabs, &found, "inner"), 2);
+        assert!(tabs[0]
+            .code
+            .contains("{ :int inner = 2; print(\"{}\\n\", inner); }"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_reaches_the_outer_name_in_a_shadowing_initialiser() {
+        let dir = scratch_dir("initialiser");
+        let code = "!start\n\
+                    :int x = 1;\n\
+                    !func add(:int a, :int b) -> :int { !return a + b; }\n\
+                    !for (:int i, 0, 3, 1) { :int x = i + add(x, i); print(\"{}\\n\", x); }\n\
+                    print(\"{}\\n\", x);\n\
+                    !end\n";
+        let mut tabs = vec![checked_tab(&dir.join("main.fr"), code)];
+
+        let inner = find(&tabs, 0, cursor(code, "x", 1)).unwrap();
+        assert_eq!(inner.occurrences.len(), 2);
+
+        let found = find(&tabs, 0, cursor(code, "x", 0)).unwrap();
+        assert_eq!(rename(&mut tabs, &found, "total"), 3);
+        assert_eq!(
+            tabs[0].code,
+            "!start\n\
+             :int total = 1;\n\
+             !func add(:int a, :int b) -> :int { !return a + b; }\n\
+             !for (:int i, 0, 3, 1) { :int x = i + add(total, i); print(\"{}\\n\", x); }\n\
+             print(\"{}\\n\", total);\n\
+             !end\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_follows_module_qualification_but_not_fields() {
+        let dir = scratch_dir("module");
+        let module = "!start\n\
+                      :int x = 1;\n\
+                      !func get() -> :int { !return x; }\n\
+                      !end\n";
+        let main = "!start\n\
+                    !import \"./m.fr\";\n\
+                    :struct<P> { :int x; };\n\
+                    :struct<P> p = { x = 2 };\n\
+                    :int x = m::x + p::x;\n\
+                    print(\"{}\\n\", x + m::get());\n\
+                    !end\n";
+        let module_tab = checked_tab(&dir.join("m.fr"), module);
+        let mut tabs = vec![checked_tab(&dir.join("main.fr"), main), module_tab];
+
+        // From `m::x` in the program: the module's `x` everywhere it is used.
+        let found = find(&tabs, 0, cursor(main, "m::x", 0) + 3).unwrap();
+        assert_eq!(found.name, "x");
+        assert_eq!(rename(&mut tabs, &found, "y"), 3);
+        assert!(tabs[0].code.contains(":int x = m::y + p::x;"));
+        assert!(tabs[0].code.contains("print(\"{}\\n\", x + m::get());"));
+        assert_eq!(
+            tabs[1].code,
+            "!start\n:int y = 1;\n!func get() -> :int { !return y; }\n!end\n"
+        );
+
+        // A field after `::` is not a symbol.
+        let code = tabs[0].code.clone();
+        assert!(find(&tabs, 0, cursor(&code, "p::x", 0) + 3).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,105 @@
 use crate::ui::icons as ic;
 use crate::ui::theme::Theme;
 use eframe::egui;
+use regex::{Regex, RegexBuilder};
+
+/// How a query is matched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub match_case: bool,
+    /// Only matches not joined to a letter, digit or `_` on either side.
+    pub whole_word: bool,
+    /// The query is a regular expression, and `$1` in a replacement stands
+    /// for its first group.
+    pub regex: bool,
+}
+
+/// A query compiled for some [`SearchOptions`].
+pub struct Matcher {
+    re: Regex,
+    whole_word: bool,
+    regex: bool,
+}
+
+impl Matcher {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, String> {
+        let pattern = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let re = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.match_case)
+            .multi_line(true)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => message
+                    .lines()
+                    .last()
+                    .unwrap_or("invalid regular expression")
+                    .trim_start_matches("error: ")
+                    .to_string(),
+                other => other.to_string(),
+            })?;
+        Ok(Self {
+            re,
+            whole_word: options.whole_word,
+            regex: options.regex,
+        })
+    }
+
+    /// The byte ranges of the matches in `text`, in order. Empty matches are
+    /// skipped.
+    pub fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        self.re
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .filter(|&(start, end)| start < end && (!self.whole_word || is_word(text, start, end)))
+            .collect()
+    }
+
+    /// What the match at `range` of `text` is replaced with.
+    fn expand(&self, text: &str, range: (usize, usize), replacement: &str) -> String {
+        if !self.regex {
+            return replacement.to_string();
+        }
+        let mut out = String::new();
+        if let Some(caps) = self.re.captures_at(text, range.0) {
+            caps.expand(replacement, &mut out);
+        }
+        out
+    }
+
+    /// `text` with the match at `range` replaced.
+    pub fn replace_one(&self, text: &str, range: (usize, usize), replacement: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        out.push_str(&text[..range.0]);
+        out.push_str(&self.expand(text, range, replacement));
+        out.push_str(&text[range.1..]);
+        out
+    }
+
+    /// `text` with every match replaced, and how many there were.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> (String, usize) {
+        let ranges = self.find_all(text);
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for &range in &ranges {
+            out.push_str(&text[last..range.0]);
+            out.push_str(&self.expand(text, range, replacement));
+            last = range.1;
+        }
+        out.push_str(&text[last..]);
+        (out, ranges.len())
+    }
+}
+
+// Whether `start..end` of `text` is not part of a longer identifier.
+fn is_word(text: &str, start: usize, end: usize) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    !text[..start].chars().next_back().is_some_and(is_ident)
+        && !text[end..].chars().next().is_some_and(is_ident)
+}
 
 #[derive(Default)]
 pub struct SearchBar {
@@ -9,6 +108,10 @@ pub struct SearchBar {
     pub query: String,
     pub replace_text: String,
     pub match_case: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    /// Why the query does not compile, in regex mode.
+    pub error: Option<String>,
     pub current_match: usize,
     pub total_matches: usize,
     pub focus_search: bool,
@@ -61,9 +164,24 @@ impl SearchBar {
         self.focus_search = false;
         self.focus_replace = false;
         self.current_match_byte_range = None;
+        self.error = None;
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        SearchOptions {
+            match_case: self.match_case,
+            whole_word: self.whole_word,
+            regex: self.regex,
+        }
+    }
+
+    /// The query compiled with the current options.
+    pub fn matcher(&self) -> Result<Matcher, String> {
+        Matcher::new(&self.query, self.options())
     }
 
     pub fn update_matches(&mut self, code: &str) {
+        self.error = None;
         if self.query.is_empty() {
             self.total_matches = 0;
             self.current_match = 0;
@@ -71,65 +189,11 @@ impl SearchBar {
             return;
         }
 
-        let ranges: Vec<(usize, usize)> = if self.match_case {
-            let qlen = self.query.len();
-            let mut v = Vec::new();
-            let mut start = 0;
-            while start + qlen <= code.len() {
-                if code[start..].starts_with(self.query.as_str()) {
-                    v.push((start, start + qlen));
-                    start += qlen;
-                } else {
-                    start += code[start..]
-                        .chars()
-                        .next()
-                        .map(|c| c.len_utf8())
-                        .unwrap_or(1);
-                }
-            }
-            v
-        } else {
-            let query_lower = self.query.to_lowercase();
-            let qlen = query_lower.len();
-            if query_lower.is_ascii() {
-                let qb = query_lower.as_bytes();
-                let cb = code.as_bytes();
-                let mut v = Vec::new();
-                let mut i = 0;
-                while i + qlen <= cb.len() {
-                    if cb[i..i + qlen]
-                        .iter()
-                        .zip(qb)
-                        .all(|(a, b)| a.to_ascii_lowercase() == *b)
-                    {
-                        v.push((i, i + qlen));
-                        i += qlen;
-                    } else {
-                        i += 1;
-
-                        while i < cb.len() && (cb[i] & 0xC0) == 0x80 {
-                            i += 1;
-                        }
-                    }
-                }
-                v
-            } else {
-                let code_lower = code.to_lowercase();
-                let mut v = Vec::new();
-                let mut start = 0;
-                while start + qlen <= code_lower.len() {
-                    if code_lower[start..].starts_with(query_lower.as_str()) {
-                        v.push((start, start + qlen));
-                        start += qlen;
-                    } else {
-                        start += code_lower[start..]
-                            .chars()
-                            .next()
-                            .map(|c| c.len_utf8())
-                            .unwrap_or(1);
-                    }
-                }
-                v
+        let ranges = match self.matcher() {
+            Ok(matcher) => matcher.find_all(code),
+            Err(e) => {
+                self.error = Some(e);
+                Vec::new()
             }
         };
 
@@ -230,7 +294,14 @@ impl SearchBar {
                         self.current_match = 0;
                     }
 
-                    if has_query {
+                    if let Some(ref err) = self.error {
+                        ui.label(
+                            egui::RichText::new("Invalid regex")
+                                .size(11.5)
+                                .color(t.terminal_error),
+                        )
+                        .on_hover_text(err);
+                    } else if has_query {
                         let label = if self.total_matches == 0 {
                             "No results".to_string()
                         } else {
@@ -245,27 +316,18 @@ impl SearchBar {
                         ));
                     }
 
-                    let case_col = if self.match_case {
-                        t.tab_bar_bg
-                    } else {
-                        t.tab_inactive_fg
-                    };
-                    let case_fill = if self.match_case {
-                        t.accent
-                    } else {
-                        t.button_bg
-                    };
-                    let case_btn = ui.add(
-                        egui::Button::new(egui::RichText::new("Aa").size(11.5).color(case_col))
-                            .fill(case_fill)
-                            .stroke(egui::Stroke::new(1.0, t.border))
-                            .min_size(egui::vec2(28.0, 22.0)),
-                    );
-                    if case_btn.clicked() {
-                        self.match_case = !self.match_case;
+                    let toggles = [
+                        (&mut self.match_case, "Aa", "Match case"),
+                        (&mut self.whole_word, "ab", "Match whole word"),
+                        (&mut self.regex, ".*", "Use regular expression"),
+                    ];
+                    let mut toggled = false;
+                    for (on, label, tip) in toggles {
+                        toggled |= option_toggle(ui, on, label, tip, t);
+                    }
+                    if toggled {
                         self.current_match = 0;
                     }
-                    case_btn.on_hover_text("Match case");
 
                     ui.add_space(4.0);
 
@@ -425,3 +487,74 @@ impl SearchBar {
         }
     }
 }
+
+/// A search option button, filled while the option is on. Returns whether it
+/// was clicked.
+pub fn option_toggle(ui: &mut egui::Ui, on: &mut bool, label: &str, tip: &str, t: &Theme) -> bool {
+    let (color, fill) = if *on {
+        (t.tab_bar_bg, t.accent)
+    } else {
+        (t.tab_inactive_fg, t.button_bg)
+    };
+    let btn = ui
+        .add(
+            egui::Button::new(egui::RichText::new(label).size(11.5).color(color))
+                .fill(fill)
+                .stroke(egui::Stroke::new(1.0, t.border))
+                .min_size(egui::vec2(28.0, 22.0)),
+        )
+        .on_hover_text(tip);
+    if btn.clicked() {
+        *on = !*on;
+    }
+    btn.clicked()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(query: &str, match_case: bool, whole_word: bool, regex: bool) -> Matcher {
+        let options = SearchOptions {
+            match_case,
+            whole_word,
+            regex,
+        };
+        Matcher::new(query, options).unwrap()
+    }
+
+    #[test]
+    fn replace_all_respects_case_and_whole_words() {
+        let text = "count = Count + counter;";
+        assert_eq!(
+            matcher("count", false, false, false).replace_all(text, "n"),
+            ("n = n + ner;".to_string(), 3)
+        );
+        assert_eq!(
+            matcher("count", true, true, false).replace_all(text, "n"),
+            ("n = Count + counter;".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn a_regex_replacement_can_use_its_groups() {
+        let m = matcher(r"(\w+)\[(\d)\]", true, false, true);
+        assert_eq!(
+            m.replace_all("a[1] + bb[2]", "get($1, $2)"),
+            ("get(a, 1) + get(bb, 2)".to_string(), 2)
+        );
+        assert_eq!(m.replace_one("a[1] + bb[2]", (7, 12), "$1"), "a[1] + bb");
+        // Without the regex option the query and the replacement are literal.
+        let m = matcher("a.b", true, false, false);
+        assert_eq!(m.replace_all("a.b axb", "$1"), ("$1 axb".to_string(), 1));
+    }
+
+    #[test]
+    fn a_bad_regex_is_reported() {
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert!(Matcher::new("(a", options).is_err());
+    }
+}
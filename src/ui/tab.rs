@@ -1,6 +1,9 @@
-use crate::ui::editor::CodeEditor;
+use crate::ui::diagnostics::LiveCheck;
+use crate::ui::editor::{CodeEditor, LineHeat};
+use crate::ui::folding::{edit_span, line_starts};
 use crate::ui::icons as ic;
 use crate::ui::theme::Theme;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
@@ -13,6 +16,19 @@ pub struct Tab {
     pub output_rx: Option<Arc<Mutex<Vec<String>>>>,
     pub is_running: bool,
     pub id: usize,
+    pub breakpoints: BTreeSet<usize>,
+    pub breakpoint_conditions: BTreeMap<usize, String>,
+    /// The code the breakpoint lines refer to, so that they can be moved
+    /// with the edits made since.
+    breakpoints_code: String,
+    /// Where a source restored from a recorded debug session was recorded.
+    /// Such a tab has no `current_file`, so it is never autosaved over the
+    /// file at that path, which may not even exist here.
+    pub recorded_path: Option<PathBuf>,
+    /// The gutter heatmap from the last profiled run, cleared once the code
+    /// is edited and the lines no longer match.
+    pub line_heat: BTreeMap<usize, LineHeat>,
+    pub live_check: LiveCheck,
 }
 
 static TAB_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -26,44 +42,126 @@ impl Tab {
         let code = String::from("!start\n# code here\n!end\n");
         Self {
             last_saved_code: code.clone(),
+            breakpoints_code: code.clone(),
             code,
             current_file: None,
             editor: CodeEditor::new(theme),
             output_rx: None,
             is_running: false,
             id: Self::next_id(),
+            breakpoints: BTreeSet::new(),
+            breakpoint_conditions: BTreeMap::new(),
+            recorded_path: None,
+            line_heat: BTreeMap::new(),
+            live_check: LiveCheck::default(),
         }
     }
 
     pub fn from_file(path: PathBuf, content: String, theme: Theme) -> Self {
         Self {
             last_saved_code: content.clone(),
+            breakpoints_code: content.clone(),
             code: content,
             current_file: Some(path),
             editor: CodeEditor::new(theme),
             output_rx: None,
             is_running: false,
             id: Self::next_id(),
+            breakpoints: BTreeSet::new(),
+            breakpoint_conditions: BTreeMap::new(),
+            recorded_path: None,
+            line_heat: BTreeMap::new(),
+            live_check: LiveCheck::default(),
         }
     }
 
+    pub fn from_recording(path: PathBuf, content: String, theme: Theme) -> Self {
+        let mut tab = Self::new(theme);
+        tab.last_saved_code = content.clone();
+        tab.breakpoints_code = content.clone();
+        tab.code = content;
+        tab.recorded_path = Some(path);
+        tab
+    }
+
+    /// The file this tab shows: the one it is saved to, or where it was
+    /// recorded.
+    pub fn source_path(&self) -> Option<&PathBuf> {
+        self.current_file.as_ref().or(self.recorded_path.as_ref())
+    }
+
+    /// Moves the breakpoints and their conditions with the edits made to the
+    /// code since the last call, so they stay on the same statements.
+    pub fn sync_breakpoints(&mut self) {
+        if self.breakpoints_code == self.code {
+            return;
+        }
+        let moved = shift_lines(
+            self.breakpoints.iter().copied(),
+            &self.breakpoints_code,
+            &self.code,
+        );
+        let new_line = |line: usize| moved.iter().find(|m| m.0 == line).map(|m| m.1);
+        self.breakpoints = moved.iter().map(|m| m.1).collect();
+        self.breakpoint_conditions = std::mem::take(&mut self.breakpoint_conditions)
+            .into_iter()
+            .filter_map(|(line, cond)| Some((new_line(line)?, cond)))
+            .collect();
+        self.breakpoints_code = self.code.clone();
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.code != self.last_saved_code
     }
 
     pub fn display_name(&self) -> String {
-        self.current_file
-            .as_ref()
+        let name = self
+            .source_path()
             .and_then(|p| p.file_name())
             .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "Untitled".to_string())
+            .unwrap_or_else(|| "Untitled".to_string());
+        if self.current_file.is_none() && self.recorded_path.is_some() {
+            format!("{name} (recorded)")
+        } else {
+            name
+        }
     }
 
     pub fn is_pristine_new(&self) -> bool {
-        self.current_file.is_none() && !self.is_dirty()
+        self.source_path().is_none() && !self.is_dirty()
     }
 }
 
+/// Where `lines` of `old` are in `new`, as `(old, new)` pairs, for the edit
+/// that turned one into the other: lines starting after the edit move with it
+/// and those starting before it stay, as does the line the edit starts at. A
+/// line that started inside the edit keeps its number if the edit added or
+/// removed no lines, and is dropped otherwise.
+fn shift_lines(
+    lines: impl IntoIterator<Item = usize>,
+    old: &str,
+    new: &str,
+) -> Vec<(usize, usize)> {
+    let (prefix, old_end, delta) = edit_span(old, new);
+    let starts = line_starts(old);
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let start = *starts.get(line.checked_sub(1)?)?;
+            let moved = if start < prefix {
+                line
+            } else if start >= old_end {
+                line.checked_add_signed(delta)?
+            } else if start == prefix || delta == 0 {
+                line
+            } else {
+                return None;
+            };
+            Some((line, moved))
+        })
+        .collect()
+}
+
 pub enum TabBarAction {
     None,
     Activate(usize),
@@ -233,3 +331,70 @@ pub fn show_tab_bar(
 
     action
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "!start\n!func f() -> :void {\n    print(\"a\");\n    print(\"b\");\n}\nprint(\"c\");\n!end\n";
+
+    fn moved(lines: &[usize], old: &str, new: &str) -> Vec<(usize, usize)> {
+        shift_lines(lines.iter().copied(), old, new)
+    }
+
+    #[test]
+    fn lines_move_with_lines_added_or_removed_above() {
+        let new = CODE.replacen("!start\n", "!start\n# note\n", 1);
+        assert_eq!(moved(&[3, 6], CODE, &new), [(3, 4), (6, 7)]);
+        assert_eq!(moved(&[4, 6], &new, CODE), [(4, 3), (6, 5)]);
+    }
+
+    #[test]
+    fn lines_stay_for_edits_below_or_on_the_same_line() {
+        let new = CODE.replace("print(\"c\");", "print(\"c\");\nprint(\"d\");");
+        assert_eq!(moved(&[3, 6], CODE, &new), [(3, 3), (6, 6)]);
+        let new = CODE.replace("print(\"a\")", "print(\"aaa\")");
+        assert_eq!(moved(&[3], CODE, &new), [(3, 3)]);
+    }
+
+    #[test]
+    fn a_line_break_typed_at_the_start_of_a_line_moves_it_down() {
+        let new = CODE.replace("    print(\"b\")", "\n    print(\"b\")");
+        assert_eq!(moved(&[4], CODE, &new), [(4, 5)]);
+    }
+
+    #[test]
+    fn lines_inside_an_edit_that_removes_lines_are_dropped() {
+        let new = CODE.replace("a\");\n    print(\"b", "");
+        assert_eq!(moved(&[3, 4, 5], CODE, &new), [(3, 3), (5, 4)]);
+    }
+
+    #[test]
+    fn reindenting_keeps_every_line() {
+        let new = CODE.replace("    ", "        ");
+        assert_eq!(moved(&[3, 4], CODE, &new), [(3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn breakpoints_and_conditions_follow_edits_above_them() {
+        let code = "!start\n:int x = 1;\nprint(\"{}\", x);\n!end\n".to_string();
+        let mut tab = Tab::from_file(PathBuf::from("a.fr"), code, Theme::dark());
+        tab.breakpoints.extend([2, 3]);
+        tab.breakpoint_conditions.insert(3, "x > 0".to_string());
+
+        tab.code = tab
+            .code
+            .replacen("!start\n", "!start\n# first\n# second\n", 1);
+        tab.sync_breakpoints();
+        assert_eq!(tab.breakpoints, BTreeSet::from([4, 5]));
+        assert_eq!(
+            tab.breakpoint_conditions,
+            BTreeMap::from([(5, "x > 0".to_string())])
+        );
+
+        tab.code = tab.code.replacen("# first\n", "", 1);
+        tab.sync_breakpoints();
+        assert_eq!(tab.breakpoints, BTreeSet::from([3, 4]));
+        assert_eq!(tab.breakpoint_conditions.keys().collect::<Vec<_>>(), [&4]);
+    }
+}
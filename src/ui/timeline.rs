@@ -0,0 +1,116 @@
+use crate::ui::debugger::DebugFrame;
+use crate::ui::theme::Theme;
+use eframe::egui;
+
+#[derive(Default)]
+pub struct TimelineBar {
+    pub watch_var: String,
+}
+
+pub enum TimelineAction {
+    None,
+    StepBack,
+    StepForward,
+    JumpTo(usize),
+    ReturnToLive,
+    PreviousWrite(String),
+}
+
+impl TimelineBar {
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        frame: &DebugFrame,
+        position: usize,
+        live: usize,
+        rewound: bool,
+        t: &Theme,
+    ) -> TimelineAction {
+        let mut action = TimelineAction::None;
+
+        let mut var_names: Vec<&str> = Vec::new();
+        for v in frame.scopes.iter().flat_map(|sc| sc.vars.iter()) {
+            if !var_names.contains(&v.name.as_str()) {
+                var_names.push(&v.name);
+            }
+        }
+
+        egui::TopBottomPanel::bottom("debug_timeline_panel")
+            .frame(
+                egui::Frame::new()
+                    .fill(t.panel_bg)
+                    .inner_margin(egui::Margin {
+                        left: 12,
+                        right: 12,
+                        top: 6,
+                        bottom: 6,
+                    }),
+            )
+            .exact_height(40.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 6.0;
+
+                    if ui
+                        .add_enabled(position > 0, egui::Button::new("⏮  Back  F7"))
+                        .clicked()
+                    {
+                        action = TimelineAction::StepBack;
+                    }
+                    if ui
+                        .add_enabled(rewound, egui::Button::new("⏭"))
+                        .on_hover_text("Forward through recorded steps")
+                        .clicked()
+                    {
+                        action = TimelineAction::StepForward;
+                    }
+
+                    let mut pos = position;
+                    ui.spacing_mut().slider_width = (ui.available_width() - 420.0).max(120.0);
+                    let slider = ui.add_enabled(
+                        live > 0,
+                        egui::Slider::new(&mut pos, 0..=live).show_value(false),
+                    );
+                    if slider.changed() {
+                        action = TimelineAction::JumpTo(pos);
+                    }
+
+                    ui.label(
+                        egui::RichText::new(format!("step {}/{}", position + 1, live + 1))
+                            .size(11.5)
+                            .color(if rewound { t.accent } else { t.status_bar_fg }),
+                    );
+                    if rewound && ui.button("Live").clicked() {
+                        action = TimelineAction::ReturnToLive;
+                    }
+
+                    ui.separator();
+
+                    egui::ComboBox::from_id_salt("timeline_watch_var")
+                        .width(110.0)
+                        .selected_text(if self.watch_var.is_empty() {
+                            "variable…"
+                        } else {
+                            self.watch_var.as_str()
+                        })
+                        .show_ui(ui, |ui| {
+                            for name in &var_names {
+                                ui.selectable_value(&mut self.watch_var, name.to_string(), *name);
+                            }
+                        });
+                    if ui
+                        .add_enabled(
+                            !self.watch_var.is_empty(),
+                            egui::Button::new("⟲  Last write"),
+                        )
+                        .on_hover_text("Run back to the previous write of this variable")
+                        .clicked()
+                    {
+                        action = TimelineAction::PreviousWrite(self.watch_var.clone());
+                    }
+                });
+            });
+
+        action
+    }
+}
@@ -5,10 +5,12 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UserProfile {
     pub theme: ThemeVariant,
     pub font_size: f32,
     pub show_line_numbers: bool,
+    pub format_on_save: bool,
 }
 
 impl Default for UserProfile {
@@ -17,6 +19,7 @@ impl Default for UserProfile {
             theme: ThemeVariant::Dark,
             font_size: 14.0,
             show_line_numbers: true,
+            format_on_save: true,
         }
     }
 }
@@ -90,6 +93,7 @@ impl SettingsPanel {
             + 32.0
             + 46.0
             + 46.0
+            + 34.0
             + 24.0;
         let panel_rect = egui::Rect::from_center_size(screen.center(), egui::vec2(w, h));
 
@@ -333,6 +337,24 @@ impl SettingsPanel {
                                 },
                             );
                         });
+
+                        ui.add_space(8.0);
+
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Format on save")
+                                    .size(13.5)
+                                    .color(t.tab_active_fg),
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if ui.checkbox(&mut profile.format_on_save, "").changed() {
+                                        changed = true;
+                                    }
+                                },
+                            );
+                        });
                     });
             });
 
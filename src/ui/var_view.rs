@@ -1,6 +1,19 @@
-use super::debugger::DebugFrame;
+use super::debugger::{DebugFrame, FractalValue, VarRow};
 use super::theme::Theme;
+use super::watch;
+use crate::compiler::parser::ParseNode;
 use eframe::egui;
+use std::collections::{HashMap, HashSet};
+
+// An expanded list shows at most this many elements.
+const CHILD_LIMIT: usize = 100;
+// Collections longer than this show their length before the elements.
+const SUMMARY_ITEMS: usize = 8;
+
+struct Watch {
+    text: String,
+    expr: ParseNode,
+}
 
 pub struct VarViewWindow {
     pub open: bool,
@@ -12,6 +25,13 @@ pub struct VarViewWindow {
     prev_stack_depth: usize,
 
     stack_expanded: bool,
+
+    watches: Vec<Watch>,
+    new_watch: String,
+    watch_error: Option<String>,
+
+    // Paths of the expanded rows, such as `q` or `q/[3]/x`.
+    expanded: HashSet<String>,
 }
 
 impl VarViewWindow {
@@ -23,6 +43,10 @@ impl VarViewWindow {
             selected_scope: None,
             prev_stack_depth: 0,
             stack_expanded: true,
+            watches: Vec::new(),
+            new_watch: String::new(),
+            watch_error: None,
+            expanded: HashSet::new(),
         }
     }
 
@@ -38,7 +62,15 @@ impl VarViewWindow {
         self.prev_stack_depth = 0;
     }
 
-    pub fn show(&mut self, ctx: &egui::Context, frame: &DebugFrame, theme: &Theme) {
+    /// `check_watch` parses and type-checks a new watch expression at the
+    /// current stop.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        frame: &DebugFrame,
+        theme: &Theme,
+        check_watch: &dyn Fn(&str) -> Result<ParseNode, String>,
+    ) {
         if !self.open {
             return;
         }
@@ -81,6 +113,15 @@ impl VarViewWindow {
 
         let mut new_selected = self.selected_scope;
         let mut new_stack_expanded = self.stack_expanded;
+        let mut watches = std::mem::take(&mut self.watches);
+        let mut new_watch = std::mem::take(&mut self.new_watch);
+        let mut watch_error = self.watch_error.take();
+        let mut expanded = std::mem::take(&mut self.expanded);
+        let watch_vars = frame
+            .scopes
+            .get(effective_scope)
+            .map(|s| s.vars.as_slice())
+            .unwrap_or_default();
 
         egui::Window::new("Variable State")
             .id(egui::Id::new("fractal_var_view"))
@@ -370,6 +411,8 @@ impl VarViewWindow {
                                     draw_var_table(
                                         ui,
                                         scope.vars.as_slice(),
+                                        &frame.previous_values,
+                                        &mut expanded,
                                         text_col,
                                         muted_text,
                                         value_col,
@@ -384,6 +427,88 @@ impl VarViewWindow {
                             }
                         }
 
+                        ui.add_space(10.0);
+                        let (w_sep, _) = ui.allocate_exact_size(
+                            egui::vec2(ui.available_width(), 1.0),
+                            egui::Sense::hover(),
+                        );
+                        ui.painter()
+                            .rect_filled(w_sep, egui::CornerRadius::ZERO, t.border);
+                        ui.add_space(8.0);
+                        ui.horizontal(|ui| {
+                            ui.add_space(10.0);
+                            ui.label(
+                                egui::RichText::new("watch")
+                                    .size(12.0)
+                                    .color(muted_text)
+                                    .strong(),
+                            );
+                        });
+                        ui.add_space(4.0);
+
+                        let mut remove = None;
+                        for (idx, w) in watches.iter().enumerate() {
+                            let (text, col) = match watch::eval(&w.expr, watch_vars) {
+                                Ok(v) => (watch::display(&v), value_col),
+                                Err(e) => (e, t.terminal_error),
+                            };
+                            ui.horizontal(|ui| {
+                                ui.add_space(12.0);
+                                if ui.small_button("✕").on_hover_text("Remove watch").clicked() {
+                                    remove = Some(idx);
+                                }
+                                ui.label(
+                                    egui::RichText::new(format!("{} =", w.text))
+                                        .size(12.5)
+                                        .color(t.type_name)
+                                        .monospace(),
+                                );
+                                ui.label(
+                                    egui::RichText::new(text).size(12.5).color(col).monospace(),
+                                );
+                            });
+                        }
+                        if let Some(idx) = remove {
+                            watches.remove(idx);
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0);
+                            let edit = ui.add(
+                                egui::TextEdit::singleline(&mut new_watch)
+                                    .font(egui::TextStyle::Monospace)
+                                    .hint_text("add watch expression…")
+                                    .desired_width(ui.available_width() - 12.0),
+                            );
+                            if edit.lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter))
+                                && !new_watch.trim().is_empty()
+                            {
+                                match check_watch(new_watch.trim()) {
+                                    Ok(expr) => {
+                                        watches.push(Watch {
+                                            text: new_watch.trim().to_string(),
+                                            expr,
+                                        });
+                                        new_watch.clear();
+                                        watch_error = None;
+                                    }
+                                    Err(e) => watch_error = Some(e),
+                                }
+                            }
+                        });
+                        if let Some(ref e) = watch_error {
+                            ui.horizontal(|ui| {
+                                ui.add_space(12.0);
+                                ui.label(
+                                    egui::RichText::new(e)
+                                        .size(11.5)
+                                        .color(t.terminal_error)
+                                        .italics(),
+                                );
+                            });
+                        }
+
                         if let Some(err) = &frame.error {
                             ui.add_space(10.0);
                             egui::Frame::new()
@@ -448,16 +573,133 @@ impl VarViewWindow {
 
         self.selected_scope = new_selected;
         self.stack_expanded = new_stack_expanded;
+        self.watches = watches;
+        self.new_watch = new_watch;
+        self.watch_error = watch_error;
+        self.expanded = expanded;
         self.open = open;
     }
 }
 
+// One row of the variable table: a variable or, once expanded, one of its
+// elements or fields. `value` is `None` for the row that stands in for the
+// elements past `CHILD_LIMIT`.
+struct VarLine<'a> {
+    depth: usize,
+    path: String,
+    name: String,
+    type_label: String,
+    value: Option<&'a FractalValue>,
+    changed: bool,
+    open: bool,
+}
+
+// Flattens the expanded part of each variable's value into rows. With `diff`,
+// a changed variable is compared element by element with `previous`.
+fn var_lines<'a>(
+    vars: &'a [VarRow],
+    previous: &'a HashMap<String, FractalValue>,
+    expanded: &HashSet<String>,
+    diff: bool,
+) -> Vec<VarLine<'a>> {
+    let mut lines = Vec::new();
+    for row in vars {
+        let changed = diff && row.changed;
+        let before = previous.get(&row.name).filter(|_| changed);
+        push_lines(
+            &mut lines,
+            expanded,
+            VarLine {
+                depth: 0,
+                path: row.name.clone(),
+                name: row.name.clone(),
+                type_label: row.type_label.clone(),
+                value: Some(&row.value),
+                changed,
+                open: false,
+            },
+            before,
+        );
+    }
+    lines
+}
+
+fn push_lines<'a>(
+    lines: &mut Vec<VarLine<'a>>,
+    expanded: &HashSet<String>,
+    mut line: VarLine<'a>,
+    before: Option<&'a FractalValue>,
+) {
+    let children = line.value.map(|v| v.children()).unwrap_or_default();
+    line.open = !children.is_empty() && expanded.contains(&line.path);
+    if !line.open {
+        lines.push(line);
+        return;
+    }
+    let (depth, path) = (line.depth + 1, line.path.clone());
+    lines.push(line);
+
+    let before_children = before.map(|b| b.children()).unwrap_or_default();
+    for (i, (label, child)) in children.iter().enumerate().take(CHILD_LIMIT) {
+        let child_before = before_children
+            .get(i)
+            .filter(|(l, _)| l == label)
+            .map(|(_, v)| *v);
+        push_lines(
+            lines,
+            expanded,
+            VarLine {
+                depth,
+                path: format!("{path}/{label}"),
+                name: label.clone(),
+                type_label: child.type_label().to_string(),
+                value: Some(child),
+                changed: before.is_some() && child_before != Some(*child),
+                open: false,
+            },
+            child_before,
+        );
+    }
+    if children.len() > CHILD_LIMIT {
+        lines.push(VarLine {
+            depth,
+            path: format!("{path}/…"),
+            name: format!("… {} more", children.len() - CHILD_LIMIT),
+            type_label: String::new(),
+            value: None,
+            changed: false,
+            open: false,
+        });
+    }
+}
+
+// The value column of a row. Strings read as strings, and long collections
+// lead with their length and show only their first elements.
+fn value_summary(value: &FractalValue) -> String {
+    match value {
+        FractalValue::List(items) | FractalValue::Array(items)
+            if items.len() > SUMMARY_ITEMS
+                && !items.iter().all(|v| matches!(v, FractalValue::Char(_))) =>
+        {
+            let head: Vec<String> = items
+                .iter()
+                .take(SUMMARY_ITEMS)
+                .map(watch::display)
+                .collect();
+            format!("len {}  [{}, …]", items.len(), head.join(", "))
+        }
+        other => watch::display(other),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn draw_var_table(
     ui: &mut egui::Ui,
-    vars: &[super::debugger::VarRow],
+    vars: &[VarRow],
+    previous: &HashMap<String, FractalValue>,
+    expanded: &mut HashSet<String>,
     text_col: egui::Color32,
-    _muted: egui::Color32,
+    muted: egui::Color32,
     value_col: egui::Color32,
     changed_bg: egui::Color32,
     changed_fg: egui::Color32,
@@ -495,14 +737,28 @@ fn draw_var_table(
     }
 
     let font = egui::FontId::monospace(12.0);
+    let mut toggled = None;
+
+    for (idx, line) in var_lines(vars, previous, expanded, !is_paused_frame)
+        .iter()
+        .enumerate()
+    {
+        let expandable = line.value.is_some_and(|v| !v.children().is_empty());
+        let sense = if expandable {
+            egui::Sense::click()
+        } else {
+            egui::Sense::hover()
+        };
+        let (row_rect, row_resp) = ui.allocate_exact_size(egui::vec2(available_w, row_h), sense);
+        if row_resp.clicked() {
+            toggled = Some(line.path.clone());
+        }
 
-    for (idx, row) in vars.iter().enumerate() {
-        let (row_rect, _) =
-            ui.allocate_exact_size(egui::vec2(available_w, row_h), egui::Sense::hover());
-
-    let show_changed = row.changed && !is_paused_frame;
-    let text_col = if show_changed {
+        let show_changed = line.changed;
+        let text_col = if show_changed {
             egui::Color32::from_rgb(30, 20, 0)
+        } else if line.value.is_none() {
+            muted
         } else {
             text_col
         };
@@ -526,12 +782,21 @@ fn draw_var_table(
                 .rect_filled(row_rect, egui::CornerRadius::ZERO, alt_row);
         }
 
-        let display_name = row.name.strip_prefix("fractal_").unwrap_or(&row.name);
+        let indent = line.depth as f32 * 12.0;
+        if expandable {
+            ui.painter().text(
+                egui::pos2(row_rect.left() + 4.0 + indent, row_rect.center().y),
+                egui::Align2::LEFT_CENTER,
+                if line.open { "▾" } else { "▸" },
+                egui::FontId::proportional(11.0),
+                text_col,
+            );
+        }
 
         ui.painter().text(
-            egui::pos2(row_rect.left() + 8.0, row_rect.center().y),
+            egui::pos2(row_rect.left() + 16.0 + indent, row_rect.center().y),
             egui::Align2::LEFT_CENTER,
-            clip_str(display_name, col_name_w - 12.0, &font, ui),
+            clip_str(&line.name, col_name_w - 20.0 - indent, &font, ui),
             font.clone(),
             text_col,
         );
@@ -539,23 +804,23 @@ fn draw_var_table(
         ui.painter().text(
             egui::pos2(row_rect.left() + col_name_w + 8.0, row_rect.center().y),
             egui::Align2::LEFT_CENTER,
-            clip_str(&row.type_label, col_type_w - 12.0, &font, ui),
+            clip_str(&line.type_label, col_type_w - 12.0, &font, ui),
             font.clone(),
             type_col_final,
         );
 
-
-
-        ui.painter().text(
-            egui::pos2(
-                row_rect.left() + col_name_w + col_type_w + 8.0,
-                row_rect.center().y,
-            ),
-            egui::Align2::LEFT_CENTER,
-            clip_str(&row.value, col_val_w - 16.0, &font, ui),
-            font.clone(),
-            value_col_final,
-        );
+        if let Some(value) = line.value {
+            ui.painter().text(
+                egui::pos2(
+                    row_rect.left() + col_name_w + col_type_w + 8.0,
+                    row_rect.center().y,
+                ),
+                egui::Align2::LEFT_CENTER,
+                clip_str(&value_summary(value), col_val_w - 16.0, &font, ui),
+                font.clone(),
+                value_col_final,
+            );
+        }
 
         if show_changed {
             ui.painter().circle_filled(
@@ -566,6 +831,11 @@ fn draw_var_table(
         }
     }
 
+    if let Some(path) = toggled {
+        if !expanded.remove(&path) {
+            expanded.insert(path);
+        }
+    }
 }
 
 fn clip_str(s: &str, max_px: f32, font: &egui::FontId, ui: &egui::Ui) -> String {
@@ -0,0 +1,275 @@
+use crate::compiler::parser::{AccessStep, AddOp, CmpOp, MulOp, ParseNode, ShiftOp, UnOp};
+use crate::ui::debugger::{FractalValue, VarRow};
+use std::cmp::Ordering;
+
+/// Evaluates a watch expression against the variables of one scope of a
+/// debug snapshot. Only pure expressions can be evaluated: calls are limited
+/// to the side-effect free builtins, since the program itself is paused.
+pub fn eval(expr: &ParseNode, vars: &[VarRow]) -> Result<FractalValue, String> {
+    use FractalValue as V;
+    match expr {
+        ParseNode::IntLit(n, _) => Ok(V::Int(*n)),
+        ParseNode::FloatLit(f, _) => Ok(V::Float(*f)),
+        ParseNode::CharLit(c, _) => Ok(V::Char(*c)),
+        ParseNode::BoolLit(b, _) => Ok(V::Bool(*b)),
+        ParseNode::StringLit(s, _) => Ok(V::List(s.chars().map(V::Char).collect())),
+        ParseNode::Null(_) => Ok(V::Null),
+        ParseNode::Identifier(name, _) => lookup(name, vars),
+        ParseNode::ArrayLit(elems, _) => Ok(V::List(
+            elems
+                .iter()
+                .map(|e| eval(e, vars))
+                .collect::<Result<_, _>>()?,
+        )),
+        ParseNode::AccessChain { base, steps, .. } => {
+            let (mut value, rest) = match steps.first() {
+                Some(AccessStep::Call(args)) => {
+                    let args: Vec<FractalValue> = args
+                        .iter()
+                        .map(|a| eval(a, vars))
+                        .collect::<Result<_, _>>()?;
+                    (call_builtin(base, &args)?, &steps[1..])
+                }
+                _ => (lookup(base, vars)?, &steps[..]),
+            };
+            for step in rest {
+                value = match (step, value) {
+                    (AccessStep::Field(f, _), V::Struct(_, fields)) => fields
+                        .into_iter()
+                        .find(|(name, _)| name == f)
+                        .map(|(_, v)| v)
+                        .ok_or_else(|| format!("no field `{f}`"))?,
+                    (AccessStep::Field(f, _), V::Null) => {
+                        return Err(format!("cannot read `{f}` of a null struct"))
+                    }
+                    (AccessStep::Index(i), V::List(items) | V::Array(items)) => {
+                        let index = as_int(&eval(i, vars)?)?;
+                        let len = items.len();
+                        usize::try_from(index)
+                            .ok()
+                            .and_then(|i| items.into_iter().nth(i))
+                            .ok_or_else(|| format!("index {index} out of bounds (len {len})"))?
+                    }
+                    (AccessStep::Call(_), _) => {
+                        return Err("function values cannot be called from a watch".into())
+                    }
+                    (_, other) => {
+                        return Err(format!("cannot access into a {} value", other.type_label()))
+                    }
+                };
+            }
+            Ok(value)
+        }
+        ParseNode::LogOr { left, right, .. } => Ok(V::Bool(
+            as_bool(&eval(left, vars)?)? || as_bool(&eval(right, vars)?)?,
+        )),
+        ParseNode::LogAnd { left, right, .. } => Ok(V::Bool(
+            as_bool(&eval(left, vars)?)? && as_bool(&eval(right, vars)?)?,
+        )),
+        ParseNode::LogNot { operand, .. } => Ok(V::Bool(!as_bool(&eval(operand, vars)?)?)),
+        ParseNode::Cmp {
+            left, op, right, ..
+        } => {
+            let (l, r) = (eval(left, vars)?, eval(right, vars)?);
+            let ord = compare(&l, &r)?;
+            Ok(V::Bool(match op {
+                CmpOp::EqEq => ord == Some(Ordering::Equal),
+                CmpOp::Ne => ord != Some(Ordering::Equal),
+                CmpOp::Lt => ord == Some(Ordering::Less),
+                CmpOp::Gt => ord == Some(Ordering::Greater),
+                CmpOp::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                CmpOp::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+            }))
+        }
+        ParseNode::BitOr { left, right, .. } => int_op(left, right, vars, |a, b| Some(a | b)),
+        ParseNode::BitXor { left, right, .. } => int_op(left, right, vars, |a, b| Some(a ^ b)),
+        ParseNode::BitAnd { left, right, .. } => int_op(left, right, vars, |a, b| Some(a & b)),
+        ParseNode::BitShift {
+            left, op, right, ..
+        } => match op {
+            ShiftOp::Left => int_op(left, right, vars, |a, b| a.checked_shl(b as u32)),
+            ShiftOp::Right => int_op(left, right, vars, |a, b| a.checked_shr(b as u32)),
+        },
+        ParseNode::Add {
+            left, op, right, ..
+        } => match (eval(left, vars)?, op, eval(right, vars)?) {
+            (V::Int(a), AddOp::Add, V::Int(b)) => a.checked_add(b).map(V::Int).ok_or(overflow()),
+            (V::Int(a), AddOp::Sub, V::Int(b)) => a.checked_sub(b).map(V::Int).ok_or(overflow()),
+            (V::Float(a), AddOp::Add, V::Float(b)) => Ok(V::Float(a + b)),
+            (V::Float(a), AddOp::Sub, V::Float(b)) => Ok(V::Float(a - b)),
+            (V::List(mut a), AddOp::Add, V::List(b)) => {
+                a.extend(b);
+                Ok(V::List(a))
+            }
+            (l, _, r) => Err(mismatch(&l, &r)),
+        },
+        ParseNode::Mul {
+            left, op, right, ..
+        } => match (eval(left, vars)?, op, eval(right, vars)?) {
+            (V::Int(_), MulOp::Div | MulOp::Mod, V::Int(0)) => Err("division by zero".into()),
+            (V::Int(a), MulOp::Mul, V::Int(b)) => a.checked_mul(b).map(V::Int).ok_or(overflow()),
+            (V::Int(a), MulOp::Div, V::Int(b)) => a.checked_div(b).map(V::Int).ok_or(overflow()),
+            (V::Int(a), MulOp::Mod, V::Int(b)) => a.checked_rem(b).map(V::Int).ok_or(overflow()),
+            (V::Float(a), MulOp::Mul, V::Float(b)) => Ok(V::Float(a * b)),
+            (V::Float(a), MulOp::Div, V::Float(b)) => Ok(V::Float(a / b)),
+            (V::Float(a), MulOp::Mod, V::Float(b)) => Ok(V::Float(a % b)),
+            (l, _, r) => Err(mismatch(&l, &r)),
+        },
+        ParseNode::Unary { op, operand, .. } => match (op, eval(operand, vars)?) {
+            (UnOp::Neg, V::Int(n)) => n.checked_neg().map(V::Int).ok_or(overflow()),
+            (UnOp::Neg, V::Float(f)) => Ok(V::Float(-f)),
+            (UnOp::BitNot, V::Int(n)) => Ok(V::Int(!n)),
+            (_, v) => Err(format!(
+                "cannot apply that operator to a {}",
+                v.type_label()
+            )),
+        },
+        ParseNode::Cast {
+            target_type, expr, ..
+        } => cast(eval(expr, vars)?, target_type),
+        _ => Err("this kind of expression cannot be evaluated in a watch".into()),
+    }
+}
+
+/// Formats a watch result; `:list<:char>` values read better as strings.
+pub fn display(value: &FractalValue) -> String {
+    match value {
+        FractalValue::List(items)
+            if !items.is_empty() && items.iter().all(|v| matches!(v, FractalValue::Char(_))) =>
+        {
+            let s: String = items
+                .iter()
+                .filter_map(|v| match v {
+                    FractalValue::Char(c) => Some(*c),
+                    _ => None,
+                })
+                .collect();
+            format!("{s:?}")
+        }
+        other => other.display(),
+    }
+}
+
+fn lookup(name: &str, vars: &[VarRow]) -> Result<FractalValue, String> {
+    let row = vars
+        .iter()
+        .find(|v| v.name == name)
+        .ok_or_else(|| format!("`{name}` is not in scope here"))?;
+    Ok(row.value.clone())
+}
+
+fn call_builtin(name: &str, args: &[FractalValue]) -> Result<FractalValue, String> {
+    use FractalValue as V;
+    let float = |v: &FractalValue| match v {
+        V::Int(n) => Ok(*n as f64),
+        V::Float(f) => Ok(*f),
+        other => Err(format!("expected a number, got {}", other.type_label())),
+    };
+    match (name, args) {
+        ("len", [V::List(items) | V::Array(items)]) => Ok(V::Int(items.len() as i64)),
+        ("abs", [V::Int(n)]) => n.checked_abs().map(V::Int).ok_or(overflow()),
+        ("abs", [V::Float(f)]) => Ok(V::Float(f.abs())),
+        ("sqrt", [x]) => Ok(V::Float(float(x)?.sqrt())),
+        ("pow", [x, y]) => Ok(V::Float(float(x)?.powf(float(y)?))),
+        ("floor", [x]) => Ok(V::Int(float(x)?.floor() as i64)),
+        ("ceil", [x]) => Ok(V::Int(float(x)?.ceil() as i64)),
+        ("min" | "max", [a, b]) => {
+            let ord = compare(a, b)?.ok_or_else(|| mismatch(a, b))?;
+            let pick_a = (name == "min") == (ord != Ordering::Greater);
+            Ok(if pick_a { a.clone() } else { b.clone() })
+        }
+        ("to_int", [x]) => cast(x.clone(), &ParseNode::TypeInt(0)),
+        ("to_float", [x]) => cast(x.clone(), &ParseNode::TypeFloat(0)),
+        ("find", [V::List(items) | V::Array(items), needle]) => Ok(V::Int(
+            items
+                .iter()
+                .position(|v| v == needle)
+                .map(|i| i as i64)
+                .unwrap_or(-1),
+        )),
+        (
+            "len" | "abs" | "sqrt" | "pow" | "floor" | "ceil" | "min" | "max" | "to_int"
+            | "to_float" | "find",
+            _,
+        ) => Err(format!("wrong arguments to `{name}`")),
+        _ => Err(format!("`{name}` cannot be called from a watch")),
+    }
+}
+
+fn cast(value: FractalValue, target: &ParseNode) -> Result<FractalValue, String> {
+    use FractalValue as V;
+    match (target, value) {
+        (ParseNode::TypeInt(_), V::Int(n)) => Ok(V::Int(n)),
+        (ParseNode::TypeInt(_), V::Float(f)) => Ok(V::Int(f as i64)),
+        (ParseNode::TypeInt(_), V::Char(c)) => Ok(V::Int(c as i64)),
+        (ParseNode::TypeInt(_), V::Bool(b)) => Ok(V::Int(b as i64)),
+        (ParseNode::TypeFloat(_), V::Int(n)) => Ok(V::Float(n as f64)),
+        (ParseNode::TypeFloat(_), V::Float(f)) => Ok(V::Float(f)),
+        (ParseNode::TypeChar(_), V::Int(n)) => u8::try_from(n)
+            .map(|b| V::Char(b as char))
+            .map_err(|_| format!("{n} is not a valid :char")),
+        (ParseNode::TypeChar(_), V::Char(c)) => Ok(V::Char(c)),
+        (ParseNode::TypeBoolean(_), V::Bool(b)) => Ok(V::Bool(b)),
+        (ParseNode::TypeBoolean(_), V::Int(n)) => Ok(V::Bool(n != 0)),
+        (_, v) => Err(format!("cannot cast a {} value", v.type_label())),
+    }
+}
+
+fn compare(l: &FractalValue, r: &FractalValue) -> Result<Option<Ordering>, String> {
+    use FractalValue as V;
+    match (l, r) {
+        (V::Int(a), V::Int(b)) => Ok(Some(a.cmp(b))),
+        (V::Float(a), V::Float(b)) => Ok(a.partial_cmp(b)),
+        (V::Char(a), V::Char(b)) => Ok(Some(a.cmp(b))),
+        (V::Bool(a), V::Bool(b)) => Ok(Some(a.cmp(b))),
+        (V::Null, V::Null) => Ok(Some(Ordering::Equal)),
+        (V::Null, V::Struct(..)) | (V::Struct(..), V::Null) => Ok(None),
+        (V::List(a) | V::Array(a), V::List(b) | V::Array(b)) => {
+            for (x, y) in a.iter().zip(b) {
+                match compare(x, y)? {
+                    Some(Ordering::Equal) => {}
+                    other => return Ok(other),
+                }
+            }
+            Ok(Some(a.len().cmp(&b.len())))
+        }
+        (V::Struct(..), V::Struct(..)) => Ok((l == r).then_some(Ordering::Equal)),
+        _ => Err(mismatch(l, r)),
+    }
+}
+
+fn int_op(
+    left: &ParseNode,
+    right: &ParseNode,
+    vars: &[VarRow],
+    op: impl Fn(i64, i64) -> Option<i64>,
+) -> Result<FractalValue, String> {
+    let (a, b) = (as_int(&eval(left, vars)?)?, as_int(&eval(right, vars)?)?);
+    op(a, b).map(FractalValue::Int).ok_or(overflow())
+}
+
+fn as_int(v: &FractalValue) -> Result<i64, String> {
+    match v {
+        FractalValue::Int(n) => Ok(*n),
+        other => Err(format!("expected an :int, got {}", other.type_label())),
+    }
+}
+
+fn as_bool(v: &FractalValue) -> Result<bool, String> {
+    match v {
+        FractalValue::Bool(b) => Ok(*b),
+        other => Err(format!("expected a :bool, got {}", other.type_label())),
+    }
+}
+
+fn mismatch(l: &FractalValue, r: &FractalValue) -> String {
+    format!(
+        "mismatched operands {} and {}",
+        l.type_label(),
+        r.type_label()
+    )
+}
+
+fn overflow() -> String {
+    "integer overflow".into()
+}
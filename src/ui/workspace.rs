@@ -0,0 +1,702 @@
+use super::icons as ic;
+use super::theme::Theme;
+use eframe::egui;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// How often the tree is read from disk again, to pick up changes made
+// outside the editor.
+const RESCAN: Duration = Duration::from_secs(2);
+
+// What a new `.fr` file starts with, the same as a new tab.
+const NEW_FILE_CODE: &str = "!start\n# code here\n!end\n";
+
+/// An `!import` statement.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub line: usize,
+    /// The name its members are reached through, as in `module::f`.
+    pub module: String,
+    /// What follows `!import`, quotes included.
+    pub spec: String,
+    /// The file a quoted path names, resolved against the importing file the
+    /// way the preprocessor does. `None` for standard library modules.
+    pub path: Option<PathBuf>,
+}
+
+/// The `!import` statements of `code`, the text of `file` when it has one.
+pub fn imports(code: &str, file: Option<&Path>) -> Vec<Import> {
+    code.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let rest = line.trim().strip_prefix("!import")?;
+            let spec = rest.split(';').next()?.trim();
+            if spec.is_empty() {
+                return None;
+            }
+            let (module, path) = if spec.starts_with('"') {
+                let mut path = PathBuf::from(spec.trim_matches('"'));
+                if path.extension().is_none() {
+                    path.set_extension("fr");
+                }
+                let module = path.file_stem()?.to_string_lossy().to_string();
+                let dir = file.and_then(Path::parent).unwrap_or(Path::new(""));
+                (module, Some(dir.join(path)))
+            } else {
+                (spec.to_string(), None)
+            };
+            Some(Import {
+                line: i + 1,
+                module,
+                spec: spec.to_string(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Whether `path` is a Fractal source file.
+pub fn is_source(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "fr")
+}
+
+/// Every `.fr` file under `root`, sorted, skipping hidden directories.
+pub fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for (path, is_dir) in read_dir(&dir) {
+            if is_dir {
+                dirs.push(path);
+            } else if is_source(&path) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+// The entries of `dir` with whether each is a directory: directories first,
+// then by name, without hidden ones.
+fn read_dir(dir: &Path) -> Vec<(PathBuf, bool)> {
+    let Ok(rd) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<(PathBuf, bool)> = rd
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .map(|e| {
+            let path = e.path();
+            let is_dir = path.is_dir();
+            (path, is_dir)
+        })
+        .collect();
+    entries.sort_by(|(a, a_dir), (b, b_dir)| {
+        b_dir.cmp(a_dir).then_with(|| {
+            let name = |p: &PathBuf| p.file_name().map(|n| n.to_string_lossy().to_lowercase());
+            name(a).cmp(&name(b))
+        })
+    });
+    entries
+}
+
+// A row of the tree.
+enum Row {
+    Dir { path: PathBuf, depth: usize },
+    File { path: PathBuf, depth: usize },
+    // A module imported by the file above it.
+    Import { import: Import, depth: usize },
+}
+
+// What the name prompt is for.
+#[derive(Clone)]
+enum Prompt {
+    NewFile(PathBuf),
+    NewFolder(PathBuf),
+    Rename(PathBuf),
+    Delete(PathBuf),
+}
+
+pub enum ExplorerAction {
+    None,
+    Open(PathBuf),
+    /// A file or folder was renamed from the first path to the second.
+    Renamed(PathBuf, PathBuf),
+    Deleted(PathBuf),
+    ToggleGraph,
+    Close,
+}
+
+/// An opened folder, shown as a file tree in a side panel.
+pub struct Workspace {
+    pub root: PathBuf,
+    /// Whether the side panel is shown.
+    pub open: bool,
+    // Folders, and `.fr` files whose imports are listed, that are expanded.
+    expanded: BTreeSet<PathBuf>,
+    rows: Vec<Row>,
+    scanned: Option<Instant>,
+    prompt: Option<Prompt>,
+    text: String,
+    error: Option<String>,
+    focus: bool,
+}
+
+impl Workspace {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            open: true,
+            expanded: BTreeSet::new(),
+            rows: Vec::new(),
+            scanned: None,
+            prompt: None,
+            text: String::new(),
+            error: None,
+            focus: false,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.to_string_lossy().to_string())
+    }
+
+    /// Whether `path` is inside the workspace.
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Reads the tree from disk again on the next frame.
+    pub fn refresh(&mut self) {
+        self.scanned = None;
+    }
+
+    fn rescan(&mut self) {
+        self.rows.clear();
+        self.push_dir(&self.root.clone(), 0);
+        self.scanned = Some(Instant::now());
+    }
+
+    fn push_dir(&mut self, dir: &Path, depth: usize) {
+        for (path, is_dir) in read_dir(dir) {
+            let expanded = self.expanded.contains(&path);
+            if is_dir {
+                self.rows.push(Row::Dir {
+                    path: path.clone(),
+                    depth,
+                });
+                if expanded {
+                    self.push_dir(&path, depth + 1);
+                }
+            } else {
+                self.rows.push(Row::File {
+                    path: path.clone(),
+                    depth,
+                });
+                if expanded && is_source(&path) {
+                    let code = fs::read_to_string(&path).unwrap_or_default();
+                    for import in imports(&code, Some(&path)) {
+                        self.rows.push(Row::Import {
+                            import,
+                            depth: depth + 1,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn ask(&mut self, prompt: Prompt) {
+        self.text = match &prompt {
+            Prompt::Rename(path) => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        self.prompt = Some(prompt);
+        self.error = None;
+        self.focus = true;
+    }
+
+    /// Shows the tree in a side panel, with `active` highlighted, and the
+    /// dialogs of its context menu.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        active: Option<&Path>,
+        graph_open: bool,
+        theme: &Theme,
+    ) -> ExplorerAction {
+        if !self.open {
+            return ExplorerAction::None;
+        }
+        if self.scanned.is_none_or(|t| t.elapsed() >= RESCAN) {
+            self.rescan();
+        }
+        ctx.request_repaint_after(RESCAN);
+
+        let t = *theme;
+        let mut action = ExplorerAction::None;
+        let mut ask = None;
+        let mut toggle = None;
+
+        egui::SidePanel::left("fractal_explorer")
+            .resizable(true)
+            .default_width(240.0)
+            .min_width(160.0)
+            .frame(
+                egui::Frame::new()
+                    .fill(t.panel_bg)
+                    .stroke(egui::Stroke::new(1.0, t.border)),
+            )
+            .show(ctx, |ui| {
+                egui::Frame::new()
+                    .fill(t.tab_bar_bg)
+                    .inner_margin(egui::Margin::symmetric(12, 8))
+                    .show(ui, |ui| {
+                        ui.set_min_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new(self.name())
+                                    .size(14.0)
+                                    .color(t.tab_active_fg)
+                                    .strong(),
+                            )
+                            .on_hover_text(self.root.to_string_lossy());
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.spacing_mut().item_spacing.x = 2.0;
+                                    if header_button(ui, ic::WINDOW_CLOSE, "Close Folder", &t) {
+                                        action = ExplorerAction::Close;
+                                    }
+                                    let graph = if graph_open {
+                                        "Hide Import Graph"
+                                    } else {
+                                        "Show Import Graph"
+                                    };
+                                    if header_button(ui, ic::IMPORT_GRAPH, graph, &t) {
+                                        action = ExplorerAction::ToggleGraph;
+                                    }
+                                    if header_button(ui, ic::ARROWS_CLOCKWISE, "Refresh", &t) {
+                                        self.refresh();
+                                    }
+                                    if header_button(ui, ic::NEW_FOLDER, "New Folder…", &t) {
+                                        ask = Some(Prompt::NewFolder(self.root.clone()));
+                                    }
+                                    if header_button(ui, ic::NEW_FILE, "New File…", &t) {
+                                        ask = Some(Prompt::NewFile(self.root.clone()));
+                                    }
+                                },
+                            );
+                        });
+                    });
+
+                let (sep, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 1.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter()
+                    .rect_filled(sep, egui::CornerRadius::ZERO, t.border);
+
+                let rows = egui::ScrollArea::vertical()
+                    .id_salt("explorer_scroll")
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Frame::new()
+                            .inner_margin(egui::Margin::symmetric(6, 6))
+                            .show(ui, |ui| {
+                                ui.spacing_mut().item_spacing.y = 0.0;
+                                for row in &self.rows {
+                                    let resp = tree_row(ui, row, &self.expanded, active, &t);
+                                    let (path, is_dir) = match row {
+                                        Row::Dir { path, .. } => (path, true),
+                                        Row::File { path, .. } => (path, false),
+                                        Row::Import { import, .. } => {
+                                            if resp.clicked() {
+                                                if let Some(path) = &import.path {
+                                                    action = ExplorerAction::Open(path.clone());
+                                                }
+                                            }
+                                            continue;
+                                        }
+                                    };
+                                    // The caret of a file toggles the list
+                                    // of its imports.
+                                    let on_caret = resp.interact_pointer_pos().is_some_and(|p| {
+                                        p.x < resp.rect.left() + row_indent(row) + 16.0
+                                    });
+                                    if resp.clicked() {
+                                        if is_dir || (on_caret && is_source(path)) {
+                                            toggle = Some(path.clone());
+                                        } else {
+                                            action = ExplorerAction::Open(path.clone());
+                                        }
+                                    }
+                                    let dir = if is_dir {
+                                        path.clone()
+                                    } else {
+                                        path.parent().unwrap_or(&self.root).to_path_buf()
+                                    };
+                                    resp.context_menu(|ui| {
+                                        if ui
+                                            .button(format!("{}  New File…", ic::NEW_FILE))
+                                            .clicked()
+                                        {
+                                            ask = Some(Prompt::NewFile(dir.clone()));
+                                            ui.close();
+                                        }
+                                        if ui
+                                            .button(format!("{}  New Folder…", ic::NEW_FOLDER))
+                                            .clicked()
+                                        {
+                                            ask = Some(Prompt::NewFolder(dir.clone()));
+                                            ui.close();
+                                        }
+                                        ui.separator();
+                                        if ui.button(format!("{}  Rename…", ic::RENAME)).clicked()
+                                        {
+                                            ask = Some(Prompt::Rename(path.clone()));
+                                            ui.close();
+                                        }
+                                        if ui.button(format!("{}  Delete…", ic::DISCARD)).clicked()
+                                        {
+                                            ask = Some(Prompt::Delete(path.clone()));
+                                            ui.close();
+                                        }
+                                    });
+                                }
+                                if self.rows.is_empty() {
+                                    ui.add_space(10.0);
+                                    ui.label(
+                                        egui::RichText::new("This folder is empty")
+                                            .size(12.0)
+                                            .color(t.tab_inactive_fg),
+                                    );
+                                }
+                            });
+                    });
+                // Right-clicking below the rows creates things at the root.
+                ui.interact(
+                    rows.inner_rect,
+                    egui::Id::new("explorer_background"),
+                    egui::Sense::click(),
+                )
+                .context_menu(|ui| {
+                    if ui.button(format!("{}  New File…", ic::NEW_FILE)).clicked() {
+                        ask = Some(Prompt::NewFile(self.root.clone()));
+                        ui.close();
+                    }
+                    if ui
+                        .button(format!("{}  New Folder…", ic::NEW_FOLDER))
+                        .clicked()
+                    {
+                        ask = Some(Prompt::NewFolder(self.root.clone()));
+                        ui.close();
+                    }
+                });
+            });
+
+        if let Some(path) = toggle {
+            if !self.expanded.remove(&path) {
+                self.expanded.insert(path);
+            }
+            self.refresh();
+        }
+        if let Some(prompt) = ask {
+            self.ask(prompt);
+        }
+        if let Some(done) = self.show_prompt(ctx, &t) {
+            action = done;
+        }
+        action
+    }
+
+    // The dialog asking for a name, or to confirm a delete.
+    fn show_prompt(&mut self, ctx: &egui::Context, t: &Theme) -> Option<ExplorerAction> {
+        let prompt = self.prompt.clone()?;
+        let (title, label, button) = match &prompt {
+            Prompt::NewFile(_) => ("New File", "Name of the new file:", "Create"),
+            Prompt::NewFolder(_) => ("New Folder", "Name of the new folder:", "Create"),
+            Prompt::Rename(_) => ("Rename", "New name:", "Rename"),
+            Prompt::Delete(_) => ("Delete", "", "Delete"),
+        };
+        let mut open = true;
+        let mut confirmed = false;
+
+        egui::Window::new(title)
+            .id(egui::Id::new("fractal_explorer_prompt"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(340.0)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .frame(egui::Frame::window(&ctx.style()).fill(t.panel_bg))
+            .show(ctx, |ui| {
+                ui.add_space(4.0);
+                if let Prompt::Delete(path) = &prompt {
+                    let what = if path.is_dir() {
+                        "the folder and everything in it"
+                    } else {
+                        "the file"
+                    };
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Delete {what} `{}`? This cannot be undone.",
+                            self.relative(path)
+                        ))
+                        .size(13.0)
+                        .color(t.tab_active_fg),
+                    );
+                } else {
+                    ui.label(egui::RichText::new(label).size(13.0).color(t.tab_active_fg));
+                    ui.add_space(6.0);
+                    let edit = ui.add(
+                        egui::TextEdit::singleline(&mut self.text)
+                            .hint_text(match &prompt {
+                                Prompt::NewFile(_) => "module.fr",
+                                _ => "",
+                            })
+                            .desired_width(f32::INFINITY),
+                    );
+                    if std::mem::take(&mut self.focus) {
+                        edit.request_focus();
+                    }
+                    confirmed = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                }
+
+                if let Some(ref err) = self.error {
+                    ui.add_space(4.0);
+                    ui.label(
+                        egui::RichText::new(format!("{}  {}", ic::ERROR, err))
+                            .size(12.0)
+                            .color(t.terminal_error),
+                    );
+                }
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    if ui.button(button).clicked() {
+                        confirmed = true;
+                    }
+                });
+            });
+
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.prompt = None;
+            return None;
+        }
+        if !confirmed {
+            return None;
+        }
+        let done = match prompt {
+            Prompt::NewFile(dir) => create_file(&dir, &self.text).map(|path| {
+                self.expanded.insert(dir);
+                ExplorerAction::Open(path)
+            }),
+            Prompt::NewFolder(dir) => create_folder(&dir, &self.text).map(|_| {
+                self.expanded.insert(dir);
+                ExplorerAction::None
+            }),
+            Prompt::Rename(path) => {
+                rename(&path, &self.text).map(|to| ExplorerAction::Renamed(path, to))
+            }
+            Prompt::Delete(path) => fs::metadata(&path)
+                .and_then(|m| {
+                    if m.is_dir() {
+                        fs::remove_dir_all(&path)
+                    } else {
+                        fs::remove_file(&path)
+                    }
+                })
+                .map(|_| ExplorerAction::Deleted(path))
+                .map_err(|e| format!("Cannot delete it: {e}")),
+        };
+        match done {
+            Ok(action) => {
+                self.prompt = None;
+                self.refresh();
+                Some(action)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                self.focus = true;
+                None
+            }
+        }
+    }
+
+    /// `path` relative to the root, for display.
+    pub fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn check_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        Err("Enter a name.".into())
+    } else if name.contains(['/', '\\']) || name == "." || name == ".." {
+        Err(format!("`{name}` is not a valid name."))
+    } else {
+        Ok(name)
+    }
+}
+
+// Creates the file `name` in `dir`, a `.fr` file unless it has another
+// extension.
+fn create_file(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let mut path = dir.join(check_name(name)?);
+    if path.extension().is_none() {
+        path.set_extension("fr");
+    }
+    if path.exists() {
+        return Err(format!("`{}` already exists.", file_name(&path)));
+    }
+    let code = if is_source(&path) { NEW_FILE_CODE } else { "" };
+    fs::write(&path, code).map_err(|e| format!("Cannot create it: {e}"))?;
+    Ok(path)
+}
+
+fn create_folder(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = dir.join(check_name(name)?);
+    if path.exists() {
+        return Err(format!("`{}` already exists.", file_name(&path)));
+    }
+    fs::create_dir(&path).map_err(|e| format!("Cannot create it: {e}"))?;
+    Ok(path)
+}
+
+fn rename(from: &Path, name: &str) -> Result<PathBuf, String> {
+    let to = from.with_file_name(check_name(name)?);
+    if to == from {
+        return Ok(to);
+    }
+    if to.exists() {
+        return Err(format!("`{}` already exists.", file_name(&to)));
+    }
+    fs::rename(from, &to).map_err(|e| format!("Cannot rename it: {e}"))?;
+    Ok(to)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn row_indent(row: &Row) -> f32 {
+    let depth = match row {
+        Row::Dir { depth, .. } | Row::File { depth, .. } | Row::Import { depth, .. } => *depth,
+    };
+    depth as f32 * 14.0
+}
+
+fn header_button(ui: &mut egui::Ui, icon: &str, tip: &str, t: &Theme) -> bool {
+    ui.add(
+        egui::Button::new(
+            egui::RichText::new(icon)
+                .size(14.0)
+                .color(t.tab_inactive_fg),
+        )
+        .frame(false),
+    )
+    .on_hover_text(tip)
+    .clicked()
+}
+
+// One row of the tree: a caret for what expands, an icon and a name.
+fn tree_row(
+    ui: &mut egui::Ui,
+    row: &Row,
+    expanded: &BTreeSet<PathBuf>,
+    active: Option<&Path>,
+    t: &Theme,
+) -> egui::Response {
+    let (rect, resp) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 22.0), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    let x = rect.left() + row_indent(row);
+    let y = rect.center().y;
+    let is_active = matches!(row, Row::File { path, .. } if Some(path.as_path()) == active);
+    if is_active {
+        painter.rect_filled(rect, egui::CornerRadius::same(3), t.selection);
+    } else if resp.hovered() {
+        painter.rect_filled(rect, egui::CornerRadius::same(3), t.menu_hover_bg);
+    }
+    let caret = |open: bool| {
+        if open {
+            ic::CARET_DOWN
+        } else {
+            ic::CARET_RIGHT
+        }
+    };
+    let (caret, icon, icon_color, name, name_color) = match row {
+        Row::Dir { path, .. } => (
+            Some(caret(expanded.contains(path))),
+            ic::FOLDER,
+            t.struct_name,
+            file_name(path),
+            t.tab_active_fg,
+        ),
+        Row::File { path, .. } => (
+            is_source(path).then(|| caret(expanded.contains(path))),
+            ic::FILE,
+            if is_source(path) {
+                t.accent
+            } else {
+                t.tab_inactive_fg
+            },
+            file_name(path),
+            t.tab_active_fg,
+        ),
+        Row::Import { import, .. } => (
+            None,
+            ic::IMPORT,
+            t.keyword,
+            import.spec.clone(),
+            if import.path.as_ref().is_some_and(|p| p.is_file()) {
+                t.tab_inactive_fg
+            } else {
+                t.terminal_error
+            },
+        ),
+    };
+    if let Some(caret) = caret {
+        painter.text(
+            egui::pos2(x + 6.0, y),
+            egui::Align2::CENTER_CENTER,
+            caret,
+            egui::FontId::proportional(11.0),
+            t.tab_inactive_fg,
+        );
+    }
+    painter.text(
+        egui::pos2(x + 16.0, y),
+        egui::Align2::LEFT_CENTER,
+        icon,
+        egui::FontId::proportional(13.0),
+        icon_color,
+    );
+    painter.text(
+        egui::pos2(x + 34.0, y),
+        egui::Align2::LEFT_CENTER,
+        name,
+        egui::FontId::proportional(13.0),
+        name_color,
+    );
+    if resp.hovered() && matches!(row, Row::Import { .. }) {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    resp
+}
@@ -0,0 +1,283 @@
+// Drives fractal-dap the way an editor does: Content-Length framed requests on
+// its stdin, responses and events read back from its stdout.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+// Long enough for fractal-compiler and rustc to build the debuggee.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+const PROGRAM: &str = "\
+!start
+!func add(:int a, :int b) -> :int {
+    :int s = a + b;
+    !return s;
+}
+:int x = 1;
+:int y = add(x, 2);
+print(\"{}\\n\", y);
+!end
+";
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    seq: u64,
+    // Events that arrived while waiting for something else.
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fractal-dap"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("start fractal-dap");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (tx, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut out = BufReader::new(stdout);
+            while let Some(message) = read_message(&mut out) {
+                if tx.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+        Client {
+            child,
+            stdin,
+            messages,
+            seq: 0,
+            events: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, command: &str, arguments: Value) -> u64 {
+        self.seq += 1;
+        let body = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+        self.seq
+    }
+
+    fn next_message(&mut self) -> Value {
+        self.messages
+            .recv_timeout(TIMEOUT)
+            .unwrap_or_else(|_| panic!("fractal-dap went quiet; events so far: {:?}", self.events))
+    }
+
+    fn response(&mut self, seq: u64) -> Value {
+        loop {
+            let message = self.next_message();
+            match message["type"].as_str() {
+                Some("response") if message["request_seq"] == seq => return message,
+                Some("event") => self.events.push(message),
+                _ => panic!("unexpected message {message}"),
+            }
+        }
+    }
+
+    // Sends a request and returns its successful response.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.send(command, arguments);
+        let response = self.response(seq);
+        assert_eq!(response["command"], command);
+        assert_eq!(response["success"], true, "{command} failed: {response}");
+        response
+    }
+
+    fn event(&mut self, name: &str) -> Value {
+        if let Some(i) = self.events.iter().position(|e| e["event"] == name) {
+            return self.events.remove(i);
+        }
+        loop {
+            let message = self.next_message();
+            if message["event"] == name {
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+
+    fn stopped(&mut self) -> String {
+        let event = self.event("stopped");
+        assert_eq!(event["body"]["threadId"], 1);
+        event["body"]["reason"].as_str().unwrap().to_string()
+    }
+
+    // The innermost frame's function name and line.
+    fn top_frame(&mut self) -> (String, u64) {
+        let trace = self.request("stackTrace", json!({ "threadId": 1 }));
+        let frame = &trace["body"]["stackFrames"][0];
+        (
+            frame["name"].as_str().unwrap().to_string(),
+            frame["line"].as_u64().unwrap(),
+        )
+    }
+
+    fn locals(&mut self) -> Vec<(String, String)> {
+        let scopes = self.request("scopes", json!({ "frameId": 0 }));
+        let reference = scopes["body"]["scopes"][0]["variablesReference"].clone();
+        assert_ne!(reference, 0);
+        let vars = self.request("variables", json!({ "variablesReference": reference }));
+        vars["body"]["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                (
+                    v["name"].as_str().unwrap().to_string(),
+                    v["value"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_message(out: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if out.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(n) = header.strip_prefix("Content-Length:") {
+            length = n.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0u8; length?];
+    out.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+// A directory of its own for the program and the files its build leaves.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fractal-dap-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn launch(client: &mut Client, program: &Path, breakpoints: &[u64]) {
+    let init = client.request("initialize", json!({ "adapterID": "fractal" }));
+    assert_eq!(init["body"]["supportsConfigurationDoneRequest"], true);
+    client.event("initialized");
+
+    // The launch is answered once configuration is done.
+    let launch = client.send("launch", json!({ "program": program }));
+    let lines: Vec<Value> = breakpoints.iter().map(|l| json!({ "line": l })).collect();
+    let set = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": program }, "breakpoints": lines }),
+    );
+    let verified = set["body"]["breakpoints"].as_array().unwrap();
+    assert_eq!(verified.len(), breakpoints.len());
+    assert!(verified.iter().all(|b| b["verified"] == true));
+    client.request("configurationDone", json!({}));
+    let response = client.response(launch);
+    assert_eq!(response["success"], true, "launch failed: {response}");
+}
+
+#[test]
+fn steps_through_a_program() {
+    let dir = scratch_dir("steps");
+    let program = dir.join("steps.fr");
+    fs::write(&program, PROGRAM).unwrap();
+    let mut client = Client::start();
+
+    launch(&mut client, &program, &[6, 8]);
+    assert_eq!(client.stopped(), "breakpoint");
+
+    let threads = client.request("threads", json!({}));
+    assert_eq!(threads["body"]["threads"][0]["id"], 1);
+    // A snapshot is taken once its line has run.
+    assert_eq!(client.top_frame(), ("<main>".to_string(), 6));
+    assert!(client
+        .locals()
+        .contains(&("x".to_string(), "1".to_string())));
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    let (name, line) = client.top_frame();
+    assert!(name.starts_with("add"), "stopped in {name}");
+    assert_eq!(line, 3);
+    let locals = client.locals();
+    assert!(
+        locals.contains(&("s".to_string(), "3".to_string())),
+        "{locals:?}"
+    );
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.top_frame().1, 4);
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.top_frame(), ("<main>".to_string(), 7));
+    assert!(client
+        .locals()
+        .contains(&("y".to_string(), "3".to_string())));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(client.top_frame().1, 8);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.event("terminated");
+    let output: String = client
+        .events
+        .iter()
+        .filter(|e| e["event"] == "output" && e["body"]["category"] == "stdout")
+        .map(|e| e["body"]["output"].as_str().unwrap().to_string())
+        .collect();
+    assert!(output.contains('3'), "program output: {output:?}");
+
+    client.request("disconnect", json!({}));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_program_that_does_not_compile_fails_the_launch() {
+    let dir = scratch_dir("broken");
+    let program = dir.join("broken.fr");
+    fs::write(&program, "!start\n:int x = ;\n!end\n").unwrap();
+    let mut client = Client::start();
+
+    client.request("initialize", json!({}));
+    let launch = client.send("launch", json!({ "program": program }));
+    client.request("configurationDone", json!({}));
+    let response = client.response(launch);
+    assert_eq!(response["success"], false);
+    assert!(!response["message"].as_str().unwrap().is_empty());
+    client.event("terminated");
+
+    client.request("disconnect", json!({}));
+    let _ = fs::remove_dir_all(&dir);
+}
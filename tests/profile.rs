@@ -0,0 +1,73 @@
+// Builds a program with `fractal-compiler --profile`, runs it and reads the
+// profile it wrote back.
+
+use fractal::compiler::profile;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const PROGRAM: &str = "\
+!start
+!func sum(:int n) -> :int {
+    :int s = 0;
+    !for (:int i, 0, n, 1) {
+        s += i;
+    }
+    !return s;
+}
+:int t = 0;
+!for (:int k, 0, 4, 1) {
+    t += sum(3);
+}
+print(\"{}\\n\", t);
+!end
+";
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fractal-profile-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn loop_lines_are_counted_once_per_pass() {
+    let dir = scratch_dir("loops");
+    let source = dir.join("loops.fr");
+    fs::write(&source, PROGRAM).unwrap();
+
+    let built = Command::new(env!("CARGO_BIN_EXE_fractal-compiler"))
+        .arg("--profile")
+        .arg("loops.fr")
+        .current_dir(&dir)
+        .output()
+        .expect("run fractal-compiler");
+    assert!(
+        built.status.success(),
+        "{}",
+        String::from_utf8_lossy(&built.stderr)
+    );
+    let ran = Command::new(dir.join(format!("loops{}", std::env::consts::EXE_SUFFIX)))
+        .current_dir(&dir)
+        .output()
+        .expect("run the profiled program");
+    assert_eq!(String::from_utf8_lossy(&ran.stdout), "12\n");
+
+    let profile = profile::read(&profile::path_for(&source)).unwrap();
+    let hits = |line: usize| {
+        profile
+            .lines
+            .iter()
+            .find(|l| l.file.is_empty() && l.line == line)
+            .map_or(0, |l| l.hits)
+    };
+    // The outer loop runs once and the inner one once per call to `sum`.
+    assert_eq!(hits(10), 1);
+    assert_eq!(hits(11), 4);
+    assert_eq!(hits(4), 4);
+    assert_eq!(hits(5), 12);
+    assert_eq!(hits(7), 4);
+    let sum = profile.functions.iter().find(|f| f.name == "sum").unwrap();
+    assert_eq!(sum.calls, 4);
+    let _ = fs::remove_dir_all(&dir);
+}